@@ -0,0 +1,189 @@
+use anyhow::{Context, Result};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// An entry of a tar archive, with its path relative to the root of the archive
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    pub permissions: u16,
+    pub user: String,
+    pub group: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum EntryKind {
+    Dir,
+    File(Vec<u8>),
+    Symlink(String),
+}
+
+/// Add the contents of a tarball to the package below `prefix`, keeping the modes, ownership and
+/// symlinks recorded in the archive. Gzip and zstd compressed archives are decompressed on the
+/// fly.
+///
+/// The destination of each entry is appended to `destinations`.
+pub fn add_tar(
+    archive_path: &str,
+    prefix: &str,
+    builder: rpm::PackageBuilder,
+    destinations: &mut Vec<String>,
+) -> Result<rpm::PackageBuilder> {
+    let file = fs::File::open(archive_path)
+        .with_context(|| format!("unable to open archive {}", archive_path))?;
+    let entries = read_tar(file, &BTreeMap::new())
+        .with_context(|| format!("unable to read archive {}", archive_path))?;
+    add_entries(builder, entries, Path::new(prefix), destinations)
+}
+
+/// Read every entry of a (possibly compressed) tar archive. rpm-rs can't create hard links, so
+/// they're turned into copies of the file they link to, which is looked up earlier in the
+/// archive and then in `lower`.
+pub fn read_tar(reader: impl Read, lower: &BTreeMap<PathBuf, Entry>) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut positions: HashMap<PathBuf, usize> = HashMap::new();
+
+    let mut archive = tar::Archive::new(decompress(reader)?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        let Some(path) = relative_path(&entry_path)? else {
+            // the root of the archive
+            continue;
+        };
+
+        let header = entry.header();
+        let permissions = (header.mode()? & 0o7777) as u16;
+        let user = owner_name(header.username(), header.uid().unwrap_or(0))
+            .with_context(|| format!("invalid user of {:?}", entry_path))?;
+        let group = owner_name(header.groupname(), header.gid().unwrap_or(0))
+            .with_context(|| format!("invalid group of {:?}", entry_path))?;
+
+        let entry_type = header.entry_type();
+        let kind = if entry_type.is_dir() {
+            EntryKind::Dir
+        } else if entry_type.is_symlink() {
+            let target = entry
+                .link_name()?
+                .with_context(|| format!("symlink {:?} has no target", entry_path))?;
+            EntryKind::Symlink(target.to_string_lossy().into_owned())
+        } else if entry_type.is_hard_link() {
+            let target = entry
+                .link_name()?
+                .with_context(|| format!("hard link {:?} has no target", entry_path))?;
+            let target_entry = relative_path(&target)?.and_then(|target| {
+                positions
+                    .get(&target)
+                    .map(|&position| &entries[position])
+                    .or_else(|| lower.get(&target))
+            });
+            match target_entry {
+                Some(Entry {
+                    kind: EntryKind::File(content),
+                    ..
+                }) => EntryKind::File(content.clone()),
+                _ => anyhow::bail!(
+                    "hard link {:?} points to {:?}, which isn't earlier in the archive",
+                    entry_path,
+                    target
+                ),
+            }
+        } else if entry_type.is_file() || entry_type.is_contiguous() {
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            EntryKind::File(content)
+        } else {
+            anyhow::bail!(
+                "{:?} is of an unsupported type ({:?})",
+                entry_path,
+                entry_type
+            );
+        };
+
+        positions.insert(path.clone(), entries.len());
+        entries.push(Entry {
+            path,
+            kind,
+            permissions,
+            user,
+            group,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Add archive entries to the package, below `prefix`
+pub fn add_entries(
+    mut builder: rpm::PackageBuilder,
+    entries: impl IntoIterator<Item = Entry>,
+    prefix: &Path,
+    destinations: &mut Vec<String>,
+) -> Result<rpm::PackageBuilder> {
+    let prefix = Path::new("/").join(prefix);
+    for entry in entries {
+        let dest = prefix.join(&entry.path).to_string_lossy().into_owned();
+        let options = rpm::FileOptions::new(&dest)
+            .user(entry.user)
+            .group(entry.group);
+        builder = match entry.kind {
+            EntryKind::Dir => builder.with_file_contents(
+                Vec::new(),
+                options.mode(rpm::FileMode::dir(entry.permissions)),
+            ),
+            EntryKind::File(content) => builder.with_file_contents(
+                content,
+                options.mode(rpm::FileMode::regular(entry.permissions)),
+            ),
+            EntryKind::Symlink(target) => {
+                let options = options
+                    .symlink(&target)
+                    .mode(rpm::FileMode::symbolic_link(0o777));
+                builder.with_file_contents(target, options)
+            }
+        }
+        .with_context(|| format!("error adding {}", dest))?;
+        destinations.push(dest);
+    }
+    Ok(builder)
+}
+
+/// The user or group name of an entry, checked like the owners of the file options. rpm only
+/// records names, so an entry which only has a numeric id is recorded with the id as its name.
+fn owner_name(name: Result<Option<&str>, std::str::Utf8Error>, id: u64) -> Result<String> {
+    match name.ok().flatten() {
+        Some(name) if !name.is_empty() => crate::build::parse_owner(name),
+        _ => crate::build::parse_owner(&id.to_string()),
+    }
+}
+
+/// Decompress gzip and zstd streams, which are recognized by their magic numbers
+fn decompress<'a>(reader: impl Read + 'a) -> Result<Box<dyn Read + 'a>> {
+    let mut reader = io::BufReader::new(reader);
+    let magic = reader.fill_buf()?;
+    Ok(if magic.starts_with(&[0x1f, 0x8b]) {
+        Box::new(flate2::bufread::GzDecoder::new(reader))
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Box::new(zstd::Decoder::with_buffer(reader)?)
+    } else {
+        Box::new(reader)
+    })
+}
+
+/// The path of an archive entry relative to the root of the archive, or `None` for the root
+/// itself. Paths escaping from the root are rejected.
+pub fn relative_path(path: &Path) -> Result<Option<PathBuf>> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir | Component::RootDir => {}
+            _ => anyhow::bail!("archive entry {:?} points outside of the archive", path),
+        }
+    }
+    Ok(Some(relative).filter(|r| !r.as_os_str().is_empty()))
+}
@@ -0,0 +1,4649 @@
+use anyhow::{Context, Result};
+use clap::{Args as _, FromArgMatches as _};
+use clap_derive::{Args, ValueEnum};
+use regex::Regex;
+use rpm::signature::{AlgorithmType, Signing as _};
+use sha2::{Digest, Sha512};
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::time::Instant;
+
+use crate::modulemd::ModuleDocument;
+use crate::selinux::FileContext;
+use crate::sysusers::SysUser;
+use crate::template::{self, Expand};
+use crate::{
+    archive, changelog, depgen, filetrigger, header, inspect, lint, man, modulemd, oci, publish,
+    repo, selinux, sign, spec, systemd, timings,
+};
+
+#[derive(Args, Clone, Debug)]
+pub struct BuildArgs {
+    #[arg(short = 'o', long, value_name = "OUT", help = "Specify an out file")]
+    pub out: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "The file name of the package when --out isn't a file, with %{name}, %{epoch} (0 if there is none), %{version}, %{release} and %{arch} replaced, e.g. '%{name}-%{epoch}:%{version}-%{release}.%{arch}.rpm'. Defaults to '%{name}-%{version}-%{release}.%{arch}.rpm'"
+    )]
+    pub filename_format: Option<String>,
+
+    #[arg(
+        long,
+        help = "Create the directory the package is written to if it doesn't exist, e.g. for -o out/"
+    )]
+    pub create_out_dir: bool,
+
+    #[arg(
+        required_unless_present_any = ["manifest", "from_cargo", "from_spec"],
+        help = "Specify the name of your package"
+    )]
+    pub name: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "MANIFEST",
+        help = "Read the package definition from a TOML or YAML manifest. Options passed on the command line take precedence over the manifest"
+    )]
+    pub manifest: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "CARGO_TOML",
+        num_args = 0..=1,
+        default_missing_value = "Cargo.toml",
+        help = "Take the name, version, description, license and homepage from a Cargo.toml (./Cargo.toml by default). The manifest and options passed on the command line take precedence"
+    )]
+    pub from_cargo: Option<PathBuf>,
+
+    #[arg(
+        long,
+        requires = "from_cargo",
+        help = "Add the release builds of the binaries of the cargo package (target/release/<bin>) as executables in /usr/bin, and build the package for the host architecture"
+    )]
+    pub cargo_bins: bool,
+
+    #[arg(
+        long,
+        value_name = "SPEC_FILE",
+        help = "Take the preamble, descriptions, files, scriptlets and changelog of the packages of an rpmbuild spec file. The build sections aren't run, the files are taken from the --buildroot. The manifest and options passed on the command line take precedence"
+    )]
+    pub from_spec: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "BUILDROOT",
+        help = "Package everything in a staging directory, e.g. the DESTDIR of make install, with the paths in it as the destinations. Directories other than the standard ones such as /usr/bin are packaged as well, and symlinks are kept. With --from-spec, only the files listed in the %files sections are taken from it"
+    )]
+    pub buildroot: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATTERN:ATTRIBUTES",
+        requires = "buildroot",
+        help = "Set the attributes of the files and directories of the --buildroot whose destination matches a glob pattern, e.g. '/etc/awesome/*:config,noreplace' or '/**/*.la:exclude'. Besides the attributes of --file, the flags config, doc, license and readme are accepted, and exclude leaves the paths out. Later patterns take precedence"
+    )]
+    pub buildroot_attr: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        help = "Define a variable, which is substituted for ${KEY} or %{KEY} in the paths and metadata of the package. The variables name, epoch, version, release and arch are always defined"
+    )]
+    pub define: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Substitute environment variables referenced as $NAME or ${NAME} in the paths and metadata of the package ($$ for a literal $)"
+    )]
+    pub expand_env: bool,
+
+    #[arg(
+        long,
+        value_name = "EPOCH",
+        default_value = "0",
+        help = "Specify an epoch"
+    )]
+    pub epoch: u32,
+
+    #[arg(
+        long,
+        value_name = "VERSION",
+        default_value = "1.0.0",
+        help = "Specify a version"
+    )]
+    pub version: String,
+
+    #[arg(
+        long,
+        value_name = "RELEASE",
+        default_value = "1",
+        help = "Specify release number of the package"
+    )]
+    pub release: String,
+
+    #[arg(
+        long,
+        help = "Turn a version which isn't valid for rpm into one that is, e.g. 1.0.0-beta.1 (a SemVer pre-release) into 1.0.0~beta.1, which sorts before 1.0.0, and v1.2 into 1.2"
+    )]
+    pub sanitize_version: bool,
+
+    #[arg(
+        long,
+        value_name = "SOURCE",
+        value_enum,
+        help = "Append an automatically increasing suffix to the release: the build time (timestamp, e.g. 1.20240601120000), or the number of commits of the git repository in the current directory (git-count, e.g. 1.42.20240601git1a2b3c4) or since its latest tag (git-describe, e.g. 1.5.20240601git1a2b3c4), with the date and hash of the last commit"
+    )]
+    pub auto_release: Option<AutoRelease>,
+
+    #[arg(
+        long,
+        value_name = "ARCH",
+        default_value = "noarch",
+        help = "Specify the target architecture"
+    )]
+    pub arch: String,
+
+    #[arg(
+        long,
+        value_name = "OS",
+        help = "Specify the operating system the package is built for (defaults to linux)"
+    )]
+    pub os: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PLATFORM",
+        help = "Specify the platform the package is built for, e.g. x86_64-redhat-linux-gnu"
+    )]
+    pub platform: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        help = "Make the package relocatable with `rpm --prefix`, by listing a directory which every file is installed below. Can be repeated"
+    )]
+    pub prefix: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "MODULARITY_LABEL",
+        help = "Mark the package as part of a module stream with a label of the form <name>:<stream>:<version>:<context>"
+    )]
+    pub modularity_label: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "MODULEMD",
+        help = "Add the package to the artifacts of a modulemd document, which is written next to the package as <nvra>.modulemd.yaml. The modularity label defaults to the one of the module"
+    )]
+    pub modulemd: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "LICENSE",
+        default_value = "MIT",
+        help = "Specify a license"
+    )]
+    pub license: String,
+
+    #[arg(
+        long,
+        value_name = "SUMMARY",
+        default_value = "",
+        help = "Give a simple description of the package"
+    )]
+    pub summary: String,
+
+    #[arg(
+        long,
+        value_name = "DESCRIPTION",
+        help = "Give a full description of the package. Defaults to the summary"
+    )]
+    pub description: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "DESCRIPTION_FILE",
+        conflicts_with = "description",
+        help = "Path to a file that contains the full description of the package"
+    )]
+    pub description_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SUMMARY_LANG",
+        help = "Add a translation of the summary. Use the format <lang>:<text>, e.g. de:Ein tolles Paket. Can be repeated"
+    )]
+    pub summary_lang: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "DESCRIPTION_LANG",
+        help = "Add a translation of the description read from a file. Use the format <lang>:<path>. Can be repeated"
+    )]
+    pub description_lang: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Specify the URL of the project's homepage"
+    )]
+    pub url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "VENDOR",
+        help = "Specify the organization distributing the package"
+    )]
+    pub vendor: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PACKAGER",
+        help = "Specify the person or team which built the package, e.g. 'Jane Doe <jane@example.com>'"
+    )]
+    pub packager: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "GROUP",
+        help = "Specify the group of the package, e.g. 'Applications/System'. Defaults to 'Unspecified'"
+    )]
+    pub group: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "DISTRIBUTION",
+        help = "Specify the distribution the package is part of"
+    )]
+    pub distribution: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Add a regular file to the rpm. Use the format <source-path>:<dest-path>[:<attributes>], where the optional attributes are a comma-separated list such as mode=0644,user=nginx,group=nginx,caps=cap_net_raw+ep. The source path may be a glob pattern, in which case (or if it ends with a '/') the destination is treated as a directory. rpm resolves the user and group by name when the package is installed, a numeric id is recorded as the name"
+    )]
+    pub file: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Don't fail if a glob pattern passed as a file source doesn't match any files"
+    )]
+    pub allow_empty_glob: bool,
+
+    #[arg(
+        long,
+        value_name = "POLICY",
+        value_enum,
+        default_value = "error",
+        help = "What to do when the file and directory options add different files with the same destination: fail the build (error), keep the one given first (first-wins) or the one given last (last-wins). The other options can't add a file with the destination of another one"
+    )]
+    pub on_conflict: ConflictPolicy,
+
+    #[arg(
+        long,
+        value_name = "SEPARATOR",
+        default_value = ":",
+        value_parser = clap::builder::NonEmptyStringValueParser::new(),
+        help = "The separator between the source, destination and attributes of the file and directory options, and between the parts of --file-from-stdin, --symlink, --create-dir, --from-tar, --from-oci-layer, --buildroot-attr, --systemd-unit and --man-page. The source is split off at the last separator before the destination, so Windows paths such as C:\\build\\foo.exe:/usr/bin/foo work with the default, and a separator can be escaped with a backslash"
+    )]
+    pub file_separator: String,
+
+    #[arg(
+        long,
+        value_name = "MODE",
+        help = "The octal permissions of plain files without a mode attribute, e.g. 0600, instead of the ones of their source (0644 for generated files). Executable sources get --default-exec-mode"
+    )]
+    pub default_file_mode: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "MODE",
+        help = "The octal permissions of directories without a mode attribute [default: 0755]"
+    )]
+    pub default_dir_mode: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "MODE",
+        help = "The octal permissions of --exec-file files, and of executable sources with --default-file-mode, without a mode attribute [default: 0755]"
+    )]
+    pub default_exec_mode: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "USER",
+        help = "The user owning the files without a user attribute [default: root]. rpm resolves owners by name when the package is installed, so a numeric id is recorded as the name of the user"
+    )]
+    pub default_user: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "GROUP",
+        help = "The group owning the files without a group attribute [default: root]. rpm resolves owners by name when the package is installed, so a numeric id is recorded as the name of the group"
+    )]
+    pub default_group: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "EXEC_FILE",
+        help = "Add an executable file to the rpm. Accepts the same format and attributes as --file"
+    )]
+    pub exec_file: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "DOC_FILE",
+        help = "Add a documentation file to the rpm. Accepts the same format and attributes as --file"
+    )]
+    pub doc_file: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "CONFIG_FILE",
+        help = "Add a config file to the rpm. Accepts the same format and attributes as --file"
+    )]
+    pub config_file: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "LICENSE_FILE",
+        help = "Add a license file to the rpm, as listed by `rpm -qL`. Accepts the same format and attributes as --file"
+    )]
+    pub license_file: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "README_FILE",
+        help = "Add a readme file to the rpm. Accepts the same format and attributes as --file"
+    )]
+    pub readme_file: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "ARTIFACT_FILE",
+        help = "Add a build artifact to the rpm, like %artifact, such as the build-id links of debug info. Accepts the same format and attributes as --file"
+    )]
+    pub artifact_file: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE_FROM_STDIN",
+        help = "Add a regular file to the rpm with the content read from stdin. Use the format <dest-path>[:<attributes>], which accepts the same attributes as --file"
+    )]
+    pub file_from_stdin: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Add the files of a list of NUL-separated <source-path> and <dest-path> pairs, e.g. generated with find -print0, read from a file or from stdin with -. Directories become directories of the package and symlinks are kept"
+    )]
+    pub files_from: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SYMLINK",
+        help = "Add a symbolic link to the rpm. Use the format <link-path>:<target>. The link doesn't need to exist on the build host"
+    )]
+    pub symlink: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Add a directory and all its files to the rpm, <source>:<dest>[:<attributes>], the attributes (such as user=app,mode=0640,flags=config(noreplace)) apply to every file"
+    )]
+    pub dir: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "DOC_DIR",
+        help = "Add a documentation directory and all its files to the rpm, <source>:<dest>[:<attributes>]"
+    )]
+    pub doc_dir: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "CONFIG_DIR",
+        help = "Add a config directory and all its files to the rpm, <source>:<dest>[:<attributes>]"
+    )]
+    pub config_dir: Vec<String>,
+
+    #[arg(
+        long,
+        overrides_with = "dir_follow_symlinks",
+        help = "Package symlinks found inside directories added with --dir, --doc-dir and --config-dir as symlinks, instead of packaging the files they point to"
+    )]
+    pub dir_preserve_symlinks: bool,
+
+    #[arg(
+        long,
+        overrides_with = "dir_preserve_symlinks",
+        help = "Package the files which symlinks inside directories point to (the default), e.g. to undo a --dir-preserve-symlinks of the config"
+    )]
+    pub dir_follow_symlinks: bool,
+
+    #[arg(
+        long,
+        value_name = "DEPTH",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Only descend this many levels into directories added with --dir, --doc-dir and --config-dir, 1 adds only the files directly inside them"
+    )]
+    pub dir_max_depth: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Only add the files directly inside directories added with --dir, --doc-dir and --config-dir, same as --dir-max-depth 1"
+    )]
+    pub dir_no_recurse: bool,
+
+    #[arg(
+        long,
+        help = "Add the license files (LICENSE, COPYING, ...) found in directories added with --dir, --doc-dir and --config-dir as license files below /usr/share/licenses/<name>"
+    )]
+    pub auto_license_files: bool,
+
+    #[arg(
+        long,
+        value_name = "CREATE_DIR",
+        help = "Add an empty directory to the rpm, so that the package owns it. Use the format <dest-path>[:<attributes>], which accepts the same attributes as --file"
+    )]
+    pub create_dir: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FROM_TAR",
+        help = "Add the contents of a tarball to the rpm, keeping the modes, ownership and symlinks recorded in it. Use the format <archive>[:<dest-prefix>]. Gzip and zstd compressed archives are decompressed"
+    )]
+    pub from_tar: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FROM_OCI_LAYER",
+        help = "Add the filesystem of a container image to the rpm. Use the format <image>[:<dest-prefix>], where the image is an OCI image layout (a directory or a tarball), a tarball written by docker save, or a single layer or exported root filesystem. The layers are stacked and their whiteouts applied"
+    )]
+    pub from_oci_layer: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "SYSTEMD_UNIT",
+        help = "Install a systemd unit into /usr/lib/systemd/system, and add the scriptlet snippets which enable it on installation, stop and disable it on removal and restart it on upgrades, along with the dependencies on systemd. Use the format <src>:<unit-name>, e.g. foo.service:foo.service"
+    )]
+    pub systemd_unit: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "MAN_PAGE",
+        help = "Install a man page into /usr/share/man/man<section> as a doc file, gzipped and named after the section like man expects. Use the format <src>:<section>, e.g. docs/foo.1:1 for /usr/share/man/man1/foo.1.gz"
+    )]
+    pub man_page: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "SYSUSER",
+        help = "Create a system user and a group of the same name when the package is installed. A sysusers.d file is added to /usr/lib/sysusers.d, along with a pre-installation snippet which applies it (or falls back to useradd) and user()/group() provides. Use the format <name>[:uid=<uid>,home=<path>,shell=<path>], where the paths are absolute and only contain letters, digits and . _ / + -"
+    )]
+    pub sysuser: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "SELINUX_CONTEXT",
+        help = "Label a path of the package and everything below it with an SELinux context. The context is registered with semanage and applied with restorecon when the package is installed, and removed with it. Use the format <dest-path>:<context>, where the context is a type or user:role:type[:range]"
+    )]
+    pub selinux_context: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "SELINUX_MODULE",
+        help = "Install an SELinux policy module (.pp, .pp.bz2 or .cil) into /usr/share/selinux/packages, and load it into the targeted policy when the package is installed"
+    )]
+    pub selinux_module: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "COMPRESSION",
+        value_enum,
+        help = "Specify the compression algorithm."
+    )]
+    pub compression: Option<Compression>,
+
+    #[arg(
+        long,
+        value_name = "COMPRESSION_LEVEL",
+        allow_negative_numbers = true,
+        help = "Specify the compression level. Valid levels are 1 to 9 for gzip, xz and bzip2 and -7 to 22 for zstd"
+    )]
+    pub compression_level: Option<i32>,
+
+    #[arg(
+        long,
+        help = "Store the payload uncompressed when its files, such as compressed tarballs or container images, are already compressed, rather than spending time compressing them again"
+    )]
+    pub skip_compression_if_incompressible: bool,
+
+    #[arg(
+        long,
+        value_name = "MB",
+        value_parser = clap::value_parser!(u64).range(..=u64::MAX / MIB),
+        help = "Keep the memory used to assemble the package under this many megabytes where possible: the compression level is lowered until the compressor fits in half of it, and content read from stdin beyond a quarter of it is spilled to a temporary file"
+    )]
+    pub max_memory: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "CHANGELOG_ENTRY",
+        help = "Add a changelog entry to the rpm. The entry has the form <author>:<content>:<yyyy-mm-dd> (time is in UTC). The content may contain ':', and '\\:' is a literal ':' in the author"
+    )]
+    pub changelog: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "CHANGELOG_FILE",
+        help = "Add the entries of a changelog file in the format of a spec file %changelog section, with entries like '* Mon Jan 01 2024 Name <email> - 1.0-1' followed by their content"
+    )]
+    pub changelog_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "REPO",
+        num_args = 0..=1,
+        default_missing_value = ".",
+        help = "Add changelog entries for the most recent commits of a git repository (the current directory by default)"
+    )]
+    pub changelog_from_git: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "LIMIT",
+        default_value = "10",
+        requires = "changelog_from_git",
+        help = "The number of commits to add to the changelog with --changelog-from-git"
+    )]
+    pub changelog_limit: usize,
+
+    #[arg(
+        long,
+        value_name = "REQUIRES",
+        help = "Indicates that the rpm requires another package. Use the format '<name> [>|>=|=|<=|< version] [[<qualifier>,...]]', where the qualifiers are the scriptlets it's needed for (pre, post, preun, postun, pretrans, posttrans, preuntrans, postuntrans, verify), like Requires(post), or interp, meta or prereq"
+    )]
+    pub requires: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "DEPENDENCY",
+        help = "Install the rpm after another package when both are in the same transaction, without requiring it, like OrderWithRequires. Use the format '<name> [>|>=|=|<=|< version]'"
+    )]
+    pub order_with_requires: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "PROVIDES",
+        help = "Indicates that the rpm provides another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    )]
+    pub provides: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "OBSOLETES",
+        help = "Indicates that the rpm obsoletes another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    )]
+    pub obsoletes: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "CONFLICTS",
+        help = "Indicates that the rpm conflicts with another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    )]
+    pub conflicts: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "SUGGESTS",
+        help = "Indicates that the rpm suggests another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    )]
+    pub suggests: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "ENHANCES",
+        help = "Indicates that the rpm enhances another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    )]
+    pub enhances: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "RECOMMENDS",
+        help = "Indicates that the rpm recommends another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    )]
+    pub recommends: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "SUPPLEMENTS",
+        help = "Indicates that the rpm supplements another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    )]
+    pub supplements: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Add the requirements listed in a file, one per line in the same format as --requires. Empty lines and comments starting with '#' are skipped"
+    )]
+    pub requires_from: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Add the provides listed in a file, one per line in the same format as --provides. Empty lines and comments starting with '#' are skipped"
+    )]
+    pub provides_from: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Add the obsoletes listed in a file, one per line in the same format as --obsoletes. Empty lines and comments starting with '#' are skipped"
+    )]
+    pub obsoletes_from: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "OLD_NAME",
+        help = "Mark the package as a rename of another package, adding 'Obsoletes: <old-name> < <max-version>' and 'Provides: <old-name> = <max-version>'. Use the format <old-name>[=<max-version>], the version defaults to the [epoch:]version-release of this package"
+    )]
+    pub renames_from: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Add the conflicts listed in a file, one per line in the same format as --conflicts. Empty lines and comments starting with '#' are skipped"
+    )]
+    pub conflicts_from: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Add the suggestions listed in a file, one per line in the same format as --suggests. Empty lines and comments starting with '#' are skipped"
+    )]
+    pub suggests_from: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Add the enhancements listed in a file, one per line in the same format as --enhances. Empty lines and comments starting with '#' are skipped"
+    )]
+    pub enhances_from: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Add the recommendations listed in a file, one per line in the same format as --recommends. Empty lines and comments starting with '#' are skipped"
+    )]
+    pub recommends_from: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Add the supplements listed in a file, one per line in the same format as --supplements. Empty lines and comments starting with '#' are skipped"
+    )]
+    pub supplements_from: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PRE_INSTALL_SCRIPT",
+        help = "Path to a file that contains the pre-installation script"
+    )]
+    pub pre_install_script: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "INTERPRETER",
+        help = "Interpreter (and arguments) to run the pre-installation script with, e.g. /usr/bin/lua. Can be used without a script to run a program such as /sbin/ldconfig"
+    )]
+    pub pre_install_interpreter: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FLAGS",
+        value_enum,
+        value_delimiter = ',',
+        help = "Comma-separated flags for the pre-installation script"
+    )]
+    pub pre_install_flags: Vec<ScriptletFlag>,
+
+    #[arg(
+        long,
+        value_name = "POST_INSTALL_SCRIPT",
+        help = "Path to a file that contains the post-installation script"
+    )]
+    pub post_install_script: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "INTERPRETER",
+        help = "Interpreter (and arguments) to run the post-installation script with, e.g. /usr/bin/lua. Can be used without a script to run a program such as /sbin/ldconfig"
+    )]
+    pub post_install_interpreter: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FLAGS",
+        value_enum,
+        value_delimiter = ',',
+        help = "Comma-separated flags for the post-installation script"
+    )]
+    pub post_install_flags: Vec<ScriptletFlag>,
+
+    #[arg(
+        long,
+        value_name = "PRE_UNINSTALL_SCRIPT",
+        help = "Path to a file that contains a pre-uninstall script"
+    )]
+    pub pre_uninstall_script: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "INTERPRETER",
+        help = "Interpreter (and arguments) to run the pre-uninstall script with, e.g. /usr/bin/lua. Can be used without a script to run a program such as /sbin/ldconfig"
+    )]
+    pub pre_uninstall_interpreter: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FLAGS",
+        value_enum,
+        value_delimiter = ',',
+        help = "Comma-separated flags for the pre-uninstall script"
+    )]
+    pub pre_uninstall_flags: Vec<ScriptletFlag>,
+
+    #[arg(
+        long,
+        value_name = "POST_UNINSTALL_SCRIPT",
+        help = "Path to a file that contains a post-uninstall script"
+    )]
+    pub post_uninstall_script: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "INTERPRETER",
+        help = "Interpreter (and arguments) to run the post-uninstall script with, e.g. /usr/bin/lua. Can be used without a script to run a program such as /sbin/ldconfig"
+    )]
+    pub post_uninstall_interpreter: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FLAGS",
+        value_enum,
+        value_delimiter = ',',
+        help = "Comma-separated flags for the post-uninstall script"
+    )]
+    pub post_uninstall_flags: Vec<ScriptletFlag>,
+
+    #[arg(
+        long,
+        value_name = "PRE_TRANS_SCRIPT",
+        help = "Path to a file that contains a script to run before the transaction (%pretrans)"
+    )]
+    pub pre_trans_script: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "INTERPRETER",
+        help = "Interpreter (and arguments) to run the pre-transaction script with, e.g. /usr/bin/lua. Can be used without a script to run a program such as /sbin/ldconfig"
+    )]
+    pub pre_trans_interpreter: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FLAGS",
+        value_enum,
+        value_delimiter = ',',
+        help = "Comma-separated flags for the pre-transaction script"
+    )]
+    pub pre_trans_flags: Vec<ScriptletFlag>,
+
+    #[arg(
+        long,
+        value_name = "POST_TRANS_SCRIPT",
+        help = "Path to a file that contains a script to run after the transaction (%posttrans)"
+    )]
+    pub post_trans_script: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "INTERPRETER",
+        help = "Interpreter (and arguments) to run the post-transaction script with, e.g. /usr/bin/lua. Can be used without a script to run a program such as /sbin/ldconfig"
+    )]
+    pub post_trans_interpreter: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FLAGS",
+        value_enum,
+        value_delimiter = ',',
+        help = "Comma-separated flags for the post-transaction script"
+    )]
+    pub post_trans_flags: Vec<ScriptletFlag>,
+
+    #[arg(
+        long,
+        value_name = "VERIFY_SCRIPT",
+        help = "Path to a file that contains a script to run when the package is checked with rpm -V (%verifyscript)"
+    )]
+    pub verify_script: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "INTERPRETER",
+        help = "Interpreter (and arguments) to run the verify script with, e.g. /usr/bin/lua. Can be used without a script to run a program"
+    )]
+    pub verify_interpreter: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FLAGS",
+        value_enum,
+        value_delimiter = ',',
+        help = "Comma-separated flags for the verify script"
+    )]
+    pub verify_flags: Vec<ScriptletFlag>,
+
+    #[arg(
+        long,
+        value_name = "FILE_TRIGGER_IN",
+        help = "Run a script whenever packages install files below a path prefix, <path-prefix>:<script-path> (%filetriggerin). The script is run with /bin/sh and gets the paths of the files on its standard input"
+    )]
+    pub file_trigger_in: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE_TRIGGER_UN",
+        help = "Run a script before packages remove files below a path prefix, <path-prefix>:<script-path> (%filetriggerun)"
+    )]
+    pub file_trigger_un: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE_TRIGGER_POSTUN",
+        help = "Run a script after packages removed files below a path prefix, <path-prefix>:<script-path> (%filetriggerpostun)"
+    )]
+    pub file_trigger_postun: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "TRANS_FILE_TRIGGER_IN",
+        help = "Run a script once at the end of a transaction which installs files below a path prefix, <path-prefix>:<script-path> (%transfiletriggerin)"
+    )]
+    pub trans_file_trigger_in: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "TRANS_FILE_TRIGGER_UN",
+        help = "Run a script once at the start of a transaction which removes files below a path prefix, <path-prefix>:<script-path> (%transfiletriggerun)"
+    )]
+    pub trans_file_trigger_un: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "TRANS_FILE_TRIGGER_POSTUN",
+        help = "Run a script once at the end of a transaction which removed files below a path prefix, <path-prefix>:<script-path> (%transfiletriggerpostun)"
+    )]
+    pub trans_file_trigger_postun: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "RPM_FORMAT",
+        value_enum,
+        help = "Specify the RPM spec format to use when building the package."
+    )]
+    pub rpm_format: Option<RpmVersion>,
+
+    #[arg(
+        long,
+        value_name = "PAYLOAD_FORMAT",
+        value_enum,
+        help = "Require a cpio payload format: cpio or newc (the same SVR4 format, which rpm calls cpio), or stripped, the cpio variant without file metadata which rpm >= 4.12 reads. Stripped payloads are written for --rpm-format v6, which it selects by default"
+    )]
+    pub payload_format: Option<PayloadFormat>,
+
+    #[arg(
+        long,
+        value_name = "DISTRIBUTION",
+        value_enum,
+        help = "Only use what the rpm of a distribution can install: el7 has no zstd compression (xz is the default instead), rich dependencies, file triggers or ECDSA and EdDSA signatures, el8 has no ECDSA and EdDSA signatures, and none of them supports the v6 format. Incompatible options are an error"
+    )]
+    pub compat: Option<Compat>,
+
+    #[arg(
+        long,
+        value_name = "ALGORITHM",
+        value_enum,
+        help = "The algorithm of the file digests recorded in the package, sha256 by default"
+    )]
+    pub file_digest_algo: Option<FileDigestAlgorithm>,
+
+    #[arg(
+        long,
+        value_name = "ALGORITHM",
+        value_enum,
+        help = "The digests of the header stored in the signature header, instead of the usual sha256 and sha3-256, e.g. to add the legacy sha1 one for older rpm versions or to leave out all but sha256. Can be repeated. With --compat el7 it defaults to sha1 and sha256"
+    )]
+    pub header_digest: Vec<HeaderDigest>,
+
+    #[arg(
+        long,
+        help = "Make the build reproducible. File modification times, the build time and the signature time are clamped to SOURCE_DATE_EPOCH (or to 0 if it isn't set) and changelog entries are sorted newest first"
+    )]
+    pub reproducible: bool,
+
+    #[arg(
+        long,
+        value_name = "BUILD_TIME",
+        help = "Set the build time of the package, as a Unix timestamp or an ISO 8601 date such as 2024-05-01T12:00:00Z. File modification times and the signature time are clamped to it, and it takes precedence over SOURCE_DATE_EPOCH"
+    )]
+    pub build_time: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "POLICY",
+        help = "The modification time recorded for the files: preserve takes the one of the source file (the default), now the time of the build, and clamp=<time> the one of the source file unless it is later than the Unix timestamp or ISO 8601 date. The source date of --reproducible and --build-time still applies"
+    )]
+    pub file_mtime: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "BUILD_HOST",
+        help = "Set the build host recorded in the package"
+    )]
+    pub build_host: Option<String>,
+
+    #[arg(
+        long,
+        help = "Check the package for common mistakes before writing it, and fail the build if errors are found"
+    )]
+    pub lint: bool,
+
+    #[arg(
+        long,
+        help = "Fail the build if a destination path isn't absolute, contains '..', or is in a directory which is neither in the package nor a standard one such as /usr/bin"
+    )]
+    pub strict_paths: bool,
+
+    #[arg(
+        long,
+        help = "Add the parent directories of the packaged files to the package, e.g. /opt/myapp and /opt/myapp/bin, unless they are standard ones such as /usr/bin, so that they are removed along with the package"
+    )]
+    pub own_parent_dirs: bool,
+
+    #[arg(
+        long,
+        help = "Build the package without writing it, and print its metadata, dependencies, files and scriptlets along with the path it would be written to"
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        long,
+        help = "Report the wall time spent in each phase of the build (file walking, digests and compression, header assembly, signing, writing) at the end"
+    )]
+    pub timings: bool,
+
+    #[arg(
+        long,
+        help = "Print the absolute path of the rpm as the last line of the output, so that scripts don't need to work out the file name"
+    )]
+    pub print_output_path: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write a spec file describing the package and its subpackages, e.g. to review it or to hand it to rpmbuild later. If the path is a directory, the spec file is written into it as <name>.spec. This is done with --dry-run as well"
+    )]
+    pub emit_spec: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Add the interpreters of the executable scripts in the package (e.g. /usr/bin/python3 for #!/usr/bin/python3) as requirements, like rpmbuild does. The same as --dep-generator interpreter"
+    )]
+    pub auto_interpreter_requires: bool,
+
+    #[arg(
+        long,
+        help = "Generate python3dist() provides and requires from the metadata of the Python distributions in the package (.dist-info and .egg-info), like Fedora's pythondistdeps does. The same as --dep-generator python"
+    )]
+    pub python_auto_deps: bool,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Generate provides and requires from the files of the package with a dependency generator: interpreter (shebangs), node (package.json), perl (modules and scripts), pkgconfig (.pc files) or python (.dist-info and .egg-info)"
+    )]
+    pub dep_generator: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "REGEX",
+        help = "Drop the requirements (given with --requires or generated) matching a regular expression, like %__requires_exclude does"
+    )]
+    pub filter_requires: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "REGEX",
+        help = "Drop the provides (given with --provides or generated) matching a regular expression, like %__provides_exclude does"
+    )]
+    pub filter_provides: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "SIGN_WITH_PGP_ASC",
+        help = "Sign this package with the specified PGP secret key, can be given multiple times to add a signature per key"
+    )]
+    pub sign_with_pgp_asc: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "MODE",
+        default_value = "header-only",
+        help = "Which signatures to add when signing: only over the header, or also (legacy) over the header and payload"
+    )]
+    pub signature_mode: sign::SignatureMode,
+
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        help = "Run a shell command after the package was written, with {} replaced by its path, e.g. 'rpmlint {}'. The build fails if the command does. Can be given multiple times"
+    )]
+    pub post_build_exec: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Upload the package once it was written (and the post-build commands succeeded) to an http(s)://, s3:// or scp:// URL, the latter with scp -O (OpenSSH 8.9 or later). URLs ending with / are directories. Can be given multiple times"
+    )]
+    pub publish: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Download (or checksum) the published package again and check that it matches"
+    )]
+    pub publish_verify: bool,
+
+    #[arg(
+        long,
+        value_name = "REPOSITORY",
+        help = "Fail the build unless the package is newer than the packages of the same name in a repository, a directory with a repodata/ directory or an http(s) URL, so that it upgrades them. Can be given multiple times"
+    )]
+    pub check_upgrades_from: Vec<String>,
+
+    /// Additional packages defined in the manifest, which are built after this one
+    #[arg(skip)]
+    pub subpackages: Vec<BuildArgs>,
+
+    /// The scriptlets of the spec file, used unless a script or interpreter is passed for them
+    #[arg(skip)]
+    pub spec_scriptlets: Vec<spec::Scriptlet>,
+
+    /// The changelog of the spec file, added after the entries passed on the command line
+    #[arg(skip)]
+    pub spec_changelog: Vec<changelog::Entry>,
+}
+
+impl BuildArgs {
+    /// Create the arguments for a package with the given name, with every other option set to
+    /// its default value, the same as running `rpm-builder build <name>`
+    pub fn new(name: &str) -> BuildArgs {
+        let command = BuildArgs::augment_args(clap::Command::new("build"));
+        let matches = command.get_matches_from(["build", name]);
+        BuildArgs::from_arg_matches(&matches).expect("the default arguments are valid")
+    }
+
+    /// Substitute environment variables (with `--expand-env`), then the variables defined with
+    /// `--define` and the built-in ones (`name`, `epoch`, `version`, `release` and `arch`) in
+    /// the paths and metadata
+    pub fn expand_variables(&mut self) -> Result<()> {
+        if self.expand_env {
+            self.expand_values(&template::expand_env)?;
+        }
+
+        let mut variables = self
+            .define
+            .iter()
+            .map(|raw| template::parse_define(raw))
+            .collect::<Result<template::Variables>>()?;
+
+        // the built-in variables may themselves use the defined ones
+        let expander = |value: &str| Ok(template::expand(value, &variables));
+        self.name.expand(&expander)?;
+        self.version.expand(&expander)?;
+        self.release.expand(&expander)?;
+        self.arch.expand(&expander)?;
+        variables.insert("name".to_owned(), self.name.clone().unwrap_or_default());
+        variables.insert("epoch".to_owned(), self.epoch.to_string());
+        variables.insert("version".to_owned(), self.version.clone());
+        variables.insert("release".to_owned(), self.release.clone());
+        variables.insert("arch".to_owned(), self.arch.clone());
+
+        self.expand_values(&|value| Ok(template::expand(value, &variables)))
+    }
+
+    fn expand_values(&mut self, expander: &dyn Fn(&str) -> Result<String>) -> Result<()> {
+        macro_rules! expand {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    self.$field
+                        .expand(expander)
+                        .with_context(|| format!("invalid {}", stringify!($field)))?;
+                )*
+            };
+        }
+        expand!(
+            out,
+            name,
+            version,
+            release,
+            arch,
+            os,
+            platform,
+            prefix,
+            modularity_label,
+            modulemd,
+            license,
+            summary,
+            description,
+            description_file,
+            summary_lang,
+            description_lang,
+            url,
+            vendor,
+            packager,
+            group,
+            distribution,
+            file,
+            exec_file,
+            doc_file,
+            config_file,
+            license_file,
+            readme_file,
+            artifact_file,
+            file_from_stdin,
+            files_from,
+            symlink,
+            dir,
+            doc_dir,
+            config_dir,
+            create_dir,
+            from_tar,
+            from_oci_layer,
+            systemd_unit,
+            man_page,
+            sysuser,
+            selinux_context,
+            selinux_module,
+            changelog,
+            changelog_file,
+            changelog_from_git,
+            requires,
+            order_with_requires,
+            provides,
+            obsoletes,
+            conflicts,
+            suggests,
+            recommends,
+            enhances,
+            supplements,
+            requires_from,
+            provides_from,
+            obsoletes_from,
+            renames_from,
+            conflicts_from,
+            suggests_from,
+            enhances_from,
+            recommends_from,
+            supplements_from,
+            pre_install_script,
+            pre_install_interpreter,
+            post_install_script,
+            post_install_interpreter,
+            pre_uninstall_script,
+            pre_uninstall_interpreter,
+            post_uninstall_script,
+            post_uninstall_interpreter,
+            pre_trans_script,
+            pre_trans_interpreter,
+            post_trans_script,
+            post_trans_interpreter,
+            verify_script,
+            verify_interpreter,
+            file_trigger_in,
+            file_trigger_un,
+            file_trigger_postun,
+            trans_file_trigger_in,
+            trans_file_trigger_un,
+            trans_file_trigger_postun,
+            build_time,
+            build_host,
+            sign_with_pgp_asc,
+            post_build_exec,
+            publish,
+        );
+        Ok(())
+    }
+
+    /// Take the options a subpackage shares with its parent package (everything but the
+    /// summary, description, files, dependencies and scriptlets) from `parent`, except for the
+    /// ones for which `is_overridden` returns true
+    pub fn inherit_from(&mut self, parent: &BuildArgs, is_overridden: impl Fn(&str) -> bool) {
+        macro_rules! inherit {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if !is_overridden(stringify!($field)) {
+                        self.$field = parent.$field.clone();
+                    }
+                )*
+            };
+        }
+        inherit!(
+            define,
+            expand_env,
+            out,
+            filename_format,
+            create_out_dir,
+            epoch,
+            version,
+            release,
+            auto_release,
+            sanitize_version,
+            arch,
+            os,
+            platform,
+            modularity_label,
+            license,
+            url,
+            vendor,
+            packager,
+            group,
+            distribution,
+            allow_empty_glob,
+            on_conflict,
+            file_separator,
+            default_file_mode,
+            default_dir_mode,
+            default_exec_mode,
+            default_user,
+            default_group,
+            dir_max_depth,
+            dir_no_recurse,
+            auto_license_files,
+            compression,
+            compression_level,
+            skip_compression_if_incompressible,
+            max_memory,
+            changelog,
+            changelog_file,
+            changelog_from_git,
+            changelog_limit,
+            rpm_format,
+            payload_format,
+            compat,
+            file_digest_algo,
+            header_digest,
+            reproducible,
+            build_time,
+            file_mtime,
+            build_host,
+            lint,
+            strict_paths,
+            own_parent_dirs,
+            dry_run,
+            print_output_path,
+            auto_interpreter_requires,
+            python_auto_deps,
+            dep_generator,
+            filter_requires,
+            filter_provides,
+            sign_with_pgp_asc,
+            signature_mode,
+            post_build_exec,
+            publish,
+            publish_verify,
+            check_upgrades_from,
+            spec_changelog,
+        );
+        // the symlink flags override each other, so setting either of them overrides both
+        if !is_overridden("dir_preserve_symlinks") && !is_overridden("dir_follow_symlinks") {
+            self.dir_preserve_symlinks = parent.dir_preserve_symlinks;
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+    None,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum ScriptletFlag {
+    /// Expand macros in the script
+    Expand,
+    /// Expand header query formats in the script
+    Qformat,
+    /// Fail the transaction if the script fails
+    Critical,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum ConflictPolicy {
+    /// Fail the build
+    Error,
+    /// Keep the file given first
+    FirstWins,
+    /// Keep the file given last
+    LastWins,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum RpmVersion {
+    V4,
+    V6,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum FileDigestAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+pub enum PayloadFormat {
+    Cpio,
+    /// An alias of cpio, by the name of the format
+    Newc,
+    /// The cpio variant whose entries only hold the index of the file in the header, needed for
+    /// payloads over 4 GiB
+    Stripped,
+}
+
+impl PayloadFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            PayloadFormat::Cpio => "cpio",
+            PayloadFormat::Newc => "newc",
+            PayloadFormat::Stripped => "stripped",
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+pub enum HeaderDigest {
+    /// The legacy digest, the only one rpm before 4.14 checks
+    Sha1,
+    Sha256,
+    #[value(name = "sha3-256")]
+    Sha3_256,
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+pub enum AutoRelease {
+    Timestamp,
+    GitCount,
+    GitDescribe,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum Compat {
+    /// RHEL / CentOS 7, with rpm 4.11
+    El7,
+    /// RHEL / CentOS Stream 8, with rpm 4.14
+    El8,
+    /// Fedora 41, with rpm 4.20
+    Fc41,
+}
+
+impl Compat {
+    fn name(&self) -> &'static str {
+        match self {
+            Compat::El7 => "el7",
+            Compat::El8 => "el8",
+            Compat::Fc41 => "fc41",
+        }
+    }
+
+    fn supports_zstd(&self) -> bool {
+        !matches!(self, Compat::El7)
+    }
+
+    fn supports_rich_dependencies(&self) -> bool {
+        !matches!(self, Compat::El7)
+    }
+
+    fn supports_file_triggers(&self) -> bool {
+        !matches!(self, Compat::El7)
+    }
+
+    /// Whether rpm can check signatures made with keys of the algorithm, older versions only
+    /// know about RSA (and DSA) keys
+    fn supports_signature_algorithm(&self, algorithm: AlgorithmType) -> bool {
+        matches!(self, Compat::Fc41) || matches!(algorithm, AlgorithmType::RSA)
+    }
+}
+
+pub fn build(mut args: BuildArgs) -> Result<()> {
+    let start = Instant::now();
+    let report_timings = args.timings;
+    if report_timings {
+        timings::enable();
+    }
+    let mut subpackages = std::mem::take(&mut args.subpackages);
+    let has_subpackages = !subpackages.is_empty();
+
+    // computed once, so that the subpackages get the same release
+    let mut release_suffixes: Vec<(AutoRelease, String)> = Vec::new();
+    for package_args in std::iter::once(&mut args).chain(subpackages.iter_mut()) {
+        let Some(auto_release) = &package_args.auto_release else {
+            continue;
+        };
+        let cached = release_suffixes
+            .iter()
+            .find(|(source, _)| source == auto_release);
+        let suffix = match cached {
+            Some((_, suffix)) => suffix.clone(),
+            None => {
+                let source_date = match &package_args.build_time {
+                    Some(build_time) => Some(parse_build_time(build_time)?),
+                    None => source_date_epoch(package_args.reproducible)?,
+                };
+                let suffix = release_suffix(auto_release, source_date)?;
+                release_suffixes.push((auto_release.clone(), suffix.clone()));
+                suffix
+            }
+        };
+        package_args.release = format!("{}.{}", package_args.release, suffix);
+    }
+
+    let emit_spec = args.emit_spec.clone();
+    // the spec file only needs the metadata of the packages, their payloads are dropped once
+    // they are written rather than all kept in memory
+    let mut packages = vec![build_package(args, has_subpackages)?];
+    packages[0].content = Vec::new();
+    for subpackage in subpackages {
+        let name = subpackage.name.clone().unwrap_or_default();
+        let mut pkg = build_package(subpackage, true)
+            .with_context(|| format!("error building subpackage {}", name))?;
+        pkg.content = Vec::new();
+        packages.push(pkg);
+    }
+
+    if let Some(emit_spec) = emit_spec {
+        let path = spec::write(&emit_spec, &packages)?;
+        tracing::info!(path = %path.display(), "wrote spec file");
+    }
+    if report_timings {
+        timings::report(start.elapsed());
+    }
+    Ok(())
+}
+
+/// Build a package and write it. With subpackages, all the packages are written to the
+/// `--out` directory, which is created like any other output directory.
+fn build_package(mut args: BuildArgs, has_subpackages: bool) -> Result<rpm::Package> {
+    args.expand_variables()?;
+    let mut out = args.out.clone();
+    let filename_format = args.filename_format.clone();
+    let create_out_dir = args.create_out_dir;
+    if let Some(dir) = out.as_ref().filter(|_| has_subpackages) {
+        if !dir.is_dir() && !create_out_dir && !ends_with_separator(dir) {
+            anyhow::bail!(
+                "the output path {:?} must be a directory when building subpackages, use --create-out-dir to create it",
+                dir
+            );
+        }
+        // the trailing separator makes it a directory for output_path
+        out = Some(dir.join(""));
+    }
+    let dry_run = args.dry_run;
+    let print_output_path = args.print_output_path;
+    let modulemd = args.modulemd.clone();
+    let post_build_exec = args.post_build_exec.clone();
+    let publish = args.publish.clone();
+    let publish_verify = args.publish_verify;
+    let check_upgrades_from = args.check_upgrades_from.clone();
+    let max_memory = args.max_memory;
+    let max_memory_bytes = max_memory_bytes(max_memory)?;
+
+    let pkg = package(args)?;
+    match (max_memory, max_memory_bytes) {
+        (Some(max_memory), Some(bytes)) if pkg.content.len() as u64 > bytes => tracing::warn!(
+            payload_mb = pkg.content.len() as u64 / MIB,
+            max_memory,
+            "the compressed payload, which is assembled in memory, exceeds --max-memory"
+        ),
+        _ => {}
+    }
+    for repository in &check_upgrades_from {
+        check_upgrade(&pkg, repository)?;
+    }
+    let output_path = output_path(&pkg, out.as_deref(), filename_format.as_deref())?;
+    let modulemd_path = output_path.with_extension("modulemd.yaml");
+
+    if dry_run {
+        let info = inspect::PackageInfo::from_package(&pkg)?;
+        inspect::print_text(&info);
+        println!("Output       : {}", output_path.display());
+    } else {
+        let out_dir = output_path.parent().filter(|p| !p.as_os_str().is_empty());
+        if let Some(out_dir) = out_dir.filter(|dir| !dir.exists()) {
+            if !create_out_dir {
+                anyhow::bail!(
+                    "the output directory {:?} doesn't exist, use --create-out-dir to create it",
+                    out_dir
+                );
+            }
+            fs::create_dir_all(out_dir)
+                .with_context(|| format!("unable to create output directory {:?}", out_dir))?;
+        }
+        let mut out_file = fs::File::create(&output_path)
+            .with_context(|| format!("unable to create output file {:?}", &output_path))?;
+
+        let writing = timings::phase("writing");
+        pkg.write(&mut out_file)
+            .with_context(|| format!("unable to write package to path {:?}", &output_path))?;
+        drop(writing);
+        tracing::info!(path = %output_path.display(), "wrote package");
+
+        if let Some(modulemd) = modulemd {
+            let mut module = ModuleDocument::load(&modulemd)?;
+            module.add_artifact(&pkg.metadata.get_nevra()?.to_string())?;
+            module.write(&modulemd_path)?;
+            tracing::info!(path = %modulemd_path.display(), "wrote modulemd document");
+        }
+
+        for command in &post_build_exec {
+            run_post_build_hook(command, &output_path)?;
+        }
+        for url in &publish {
+            publish::upload(&output_path, url, publish_verify)?;
+        }
+    }
+
+    if print_output_path {
+        let absolute_path = std::path::absolute(&output_path)
+            .with_context(|| format!("unable to resolve the output path {:?}", &output_path))?;
+        println!("{}", absolute_path.display());
+    }
+
+    Ok(pkg)
+}
+
+/// Add the dependencies listed in the files of `--requires-from` and the like to the ones given
+/// directly
+fn read_dependency_files(args: &mut BuildArgs) -> Result<()> {
+    // requirements can have qualifiers, unlike the other kinds of dependencies
+    let parse_requirement: fn(&str) -> Result<rpm::Dependency> = parse_requirement;
+    for (paths, dependencies, parse) in [
+        (&args.requires_from, &mut args.requires, parse_requirement),
+        (&args.provides_from, &mut args.provides, parse_dependency),
+        (&args.obsoletes_from, &mut args.obsoletes, parse_dependency),
+        (&args.conflicts_from, &mut args.conflicts, parse_dependency),
+        (&args.suggests_from, &mut args.suggests, parse_dependency),
+        (&args.enhances_from, &mut args.enhances, parse_dependency),
+        (
+            &args.recommends_from,
+            &mut args.recommends,
+            parse_dependency,
+        ),
+        (
+            &args.supplements_from,
+            &mut args.supplements,
+            parse_dependency,
+        ),
+    ] {
+        for path in paths {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("error reading dependency file {:?}", path))?;
+            for (index, line) in content.lines().enumerate() {
+                let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+                if line.is_empty() {
+                    continue;
+                }
+                parse(line).with_context(|| {
+                    format!("invalid dependency on line {} of {:?}", index + 1, path)
+                })?;
+                dependencies.push(line.to_owned());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Add the obsoletes and provides of the renames given with `--renames-from`
+fn add_renames(args: &mut BuildArgs) -> Result<()> {
+    for rename in &args.renames_from {
+        let (name, version) = match rename.split_once('=') {
+            Some((name, version)) => (name.trim(), version.trim().to_owned()),
+            None if args.epoch > 0 => (
+                rename.trim(),
+                format!("{}:{}-{}", args.epoch, args.version, args.release),
+            ),
+            None => (rename.trim(), format!("{}-{}", args.version, args.release)),
+        };
+        if args.name.as_deref() == Some(name) {
+            anyhow::bail!(
+                "invalid rename {:?}: the package can't be renamed from itself",
+                rename
+            );
+        }
+        let obsoletes = format!("{} < {}", name, version);
+        let provides = format!("{} = {}", name, version);
+        for dependency in [&obsoletes, &provides] {
+            if !parse_dependency(dependency).is_ok_and(|dependency| dependency.name == name) {
+                anyhow::bail!(
+                    "invalid rename {:?}, use the format <old-name>[=<max-version>]",
+                    rename
+                );
+            }
+        }
+        tracing::info!(name, version, "adding rename");
+        args.obsoletes.push(obsoletes);
+        args.provides.push(provides);
+    }
+    Ok(())
+}
+
+/// Read the NUL-separated `<source-path>` and `<dest-path>` pairs of `--files-from`, from stdin if
+/// the path is `-`
+fn read_file_list(path: &Path) -> Result<Vec<(String, String)>> {
+    let mut content = Vec::new();
+    if path == Path::new("-") {
+        std::io::stdin()
+            .read_to_end(&mut content)
+            .context("error reading the file list from stdin")?;
+    } else {
+        content = fs::read(path).with_context(|| format!("error reading file list {:?}", path))?;
+    }
+
+    // the list may end with a NUL, like the output of find -print0
+    let content = content.strip_suffix(&[0]).unwrap_or(&content);
+    if content.is_empty() {
+        return Ok(Vec::new());
+    }
+    let paths = content
+        .split(|b| *b == 0)
+        .map(|path| std::str::from_utf8(path).map(str::to_owned))
+        .collect::<Result<Vec<_>, _>>()
+        .context("the file list contains a path which isn't UTF-8")?;
+    if paths.iter().any(String::is_empty) {
+        anyhow::bail!("the file list contains an empty path");
+    }
+    if paths.len() % 2 != 0 {
+        anyhow::bail!(
+            "the file list has no destination for {}",
+            paths[paths.len() - 1]
+        );
+    }
+    Ok(paths
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect())
+}
+
+/// Check that a package is newer than the packages of the same name in a repository (for
+/// `--check-upgrades-from`), so that installing it upgrades them
+fn check_upgrade(pkg: &rpm::Package, repository: &str) -> Result<()> {
+    let name = pkg.metadata.get_name()?;
+    let epoch = match pkg.metadata.get_epoch().unwrap_or(0) {
+        0 => String::new(),
+        epoch => epoch.to_string(),
+    };
+    let version = pkg.metadata.get_version()?;
+    let release = pkg.metadata.get_release()?;
+    let evr = rpm::Evr::new(epoch.as_str(), version, release);
+
+    let packages = repo::published_packages(repository).with_context(|| {
+        format!(
+            "unable to read the packages of the repository {}",
+            repository
+        )
+    })?;
+    let newest = packages
+        .iter()
+        .filter(|published| published.name == name)
+        .max_by(|a, b| a.evr().cmp(&b.evr()));
+    match newest {
+        Some(newest) if newest.evr() >= evr => anyhow::bail!(
+            "{} {} doesn't upgrade {} {}.{} of the repository {}",
+            name,
+            evr,
+            newest.name,
+            newest.evr(),
+            newest.arch,
+            repository
+        ),
+        Some(newest) => {
+            tracing::info!(%repository, published = %newest.evr(), "checked the upgrade path");
+        }
+        None => tracing::info!(%repository, "the package isn't in the repository yet"),
+    }
+    Ok(())
+}
+
+/// Run a post-build hook with `sh -c`, with every `{}` in the command replaced by the (quoted)
+/// path of the package. Its output goes to stderr, to keep stdout for `--print-output-path`.
+fn run_post_build_hook(command: &str, package_path: &Path) -> Result<()> {
+    let quoted_path = format!(
+        "'{}'",
+        package_path.to_string_lossy().replace('\'', r"'\''")
+    );
+    let command = command.replace("{}", &quoted_path);
+    tracing::info!(%command, "running post-build hook");
+    let status = process::Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .stdout(std::io::stderr())
+        .status()
+        .with_context(|| format!("unable to run post-build hook {:?}", command))?;
+    if !status.success() {
+        anyhow::bail!("post-build hook {:?} failed ({})", command, status);
+    }
+    Ok(())
+}
+
+/// Build (and sign, if a key was provided) the package described by the arguments, without
+/// writing it anywhere. Subpackages are ignored, variables are only substituted if
+/// [`BuildArgs::expand_variables`] was called, and the lint checks only run if `args.lint` is
+/// set.
+pub fn package(mut args: BuildArgs) -> Result<rpm::Package> {
+    if args.sanitize_version {
+        let version = sanitize_version(&args.version);
+        if version != args.version {
+            tracing::info!(from = args.version, to = version, "sanitized the version");
+            args.version = version;
+        }
+    }
+    if let Some(name) = &args.name {
+        check_nevr_part("name", name)?;
+    }
+    check_nevr_part("version", &args.version)?;
+    check_nevr_part("release", &args.release)?;
+    read_dependency_files(&mut args)?;
+    add_renames(&mut args)?;
+    if let Some(compat) = &args.compat {
+        check_compat(&args, compat)?;
+    }
+
+    // read up front, since the package can be assembled twice
+    let reads_file_list_from_stdin = args.files_from.as_deref() == Some(Path::new("-"));
+    if args.file_from_stdin.is_some() && reads_file_list_from_stdin {
+        anyhow::bail!("--file-from-stdin and --files-from - can't both read stdin");
+    }
+    let stdin_content = match &args.file_from_stdin {
+        Some(_) => Some(
+            read_stdin(max_memory_bytes(args.max_memory)?.map(|bytes| bytes / 4))
+                .context("error reading file content from stdin")?,
+        ),
+        None => None,
+    };
+    let file_list = match &args.files_from {
+        Some(path) => read_file_list(path)?,
+        None => Vec::new(),
+    };
+
+    let mut generators = args.dep_generator.clone();
+    if args.auto_interpreter_requires {
+        generators.push("interpreter".to_owned());
+    }
+    if args.python_auto_deps {
+        generators.push("python".to_owned());
+    }
+    if !generators.is_empty() {
+        let dependency_generation = timings::phase("dependency generation");
+        let generators = generators
+            .iter()
+            .map(|name| depgen::find(name))
+            .collect::<Result<Vec<_>>>()?;
+
+        // like rpmbuild generates dependencies from the files in the buildroot, the dependencies
+        // are generated from the files of a first, uncompressed and unsigned, build
+        let mut scan_args = args.clone();
+        scan_args.compression = Some(Compression::None);
+        scan_args.compression_level = None;
+        scan_args.sign_with_pgp_asc.clear();
+        scan_args.lint = false;
+        // the files would otherwise be logged twice
+        let scan = tracing::dispatcher::with_default(&tracing::Dispatch::none(), || {
+            assemble(scan_args, stdin_content.as_ref(), &file_list)
+        })?;
+        let files = scan.files()?.collect::<Result<Vec<_>, _>>()?;
+
+        for generator in generators {
+            let dependencies = generator.generate(&files).with_context(|| {
+                format!(
+                    "error running the {} dependency generator",
+                    generator.name()
+                )
+            })?;
+            tracing::debug!(
+                generator = generator.name(),
+                requires = ?dependencies.requires,
+                provides = ?dependencies.provides,
+                "generated dependencies"
+            );
+            for requirement in dependencies.requires {
+                if !args.requires.contains(&requirement) {
+                    args.requires.push(requirement);
+                }
+            }
+            for provide in dependencies.provides {
+                if !args.provides.contains(&provide) {
+                    args.provides.push(provide);
+                }
+            }
+        }
+        drop(dependency_generation);
+    }
+
+    assemble(args, stdin_content.as_ref(), &file_list)
+}
+
+fn assemble(
+    mut args: BuildArgs,
+    stdin_content: Option<&Spooled>,
+    file_list: &[(String, String)],
+) -> Result<rpm::Package> {
+    let name = args
+        .name
+        .as_deref()
+        .context("no package name was provided on the command line or in the manifest")?;
+    let defaults = FileDefaults::from_args(&args)?;
+
+    let compression = match args.compression {
+        None if args.compat.as_ref().is_some_and(|c| !c.supports_zstd()) => {
+            rpm::CompressionType::Xz
+        }
+        Some(Compression::Gzip) => rpm::CompressionType::Gzip,
+        Some(Compression::Zstd) => rpm::CompressionType::Zstd,
+        Some(Compression::Xz) => rpm::CompressionType::Xz,
+        Some(Compression::Bzip2) => rpm::CompressionType::Bzip2,
+        Some(Compression::None) => rpm::CompressionType::None,
+        _ => rpm::CompressionType::default(),
+    };
+    let compression = match args.compression_level {
+        Some(level) => compression_with_level(compression, level)?,
+        None => compression.into(),
+    };
+    let compression = match args.max_memory {
+        Some(max_memory) => {
+            let limited = limit_compression(compression, max_memory / 2);
+            if limited != compression {
+                tracing::info!(from = %compression, to = %limited, "lowered the compression level to fit --max-memory");
+            }
+            limited
+        }
+        None => compression,
+    };
+
+    // the rpm crate writes a stripped payload for v6 packages and when the files exceed 4 GiB,
+    // it can't be chosen otherwise
+    let config = match (&args.rpm_format, &args.payload_format) {
+        (Some(RpmVersion::V4), Some(PayloadFormat::Stripped)) => anyhow::bail!(
+            "--payload-format stripped can't be used with --rpm-format v4, stripped payloads are only written for v6 packages"
+        ),
+        (Some(RpmVersion::V6), Some(PayloadFormat::Cpio | PayloadFormat::Newc)) => anyhow::bail!(
+            "--payload-format {} can't be used with --rpm-format v6, v6 packages have a stripped payload",
+            args.payload_format.as_ref().unwrap().name()
+        ),
+        (Some(RpmVersion::V4), _) => rpm::BuildConfig::v4(),
+        (Some(RpmVersion::V6), _) | (None, Some(PayloadFormat::Stripped)) => {
+            rpm::BuildConfig::v6()
+        }
+        (None, _) => rpm::BuildConfig::default(),
+    }
+    .compression(compression);
+
+    let mut builder = rpm::PackageBuilder::new(
+        name,
+        &args.version,
+        &args.license,
+        &args.arch,
+        &args.summary,
+    )
+    .using_config(config)
+    .release(args.release)
+    .epoch(args.epoch);
+
+    let source_date = match &args.build_time {
+        Some(build_time) => Some(parse_build_time(build_time)?),
+        None => source_date_epoch(args.reproducible)?,
+    };
+    if let Some(source_date) = source_date {
+        builder = builder.source_date(source_date);
+    }
+    if let Some(build_host) = &args.build_host {
+        builder = builder.build_host(build_host);
+    }
+
+    if let Some(url) = args.url {
+        builder = builder.url(url);
+    }
+    if let Some(vendor) = args.vendor {
+        builder = builder.vendor(vendor);
+    }
+
+    if let Some(description) = args.description {
+        builder = builder.description(description);
+    } else if let Some(description_path) = args.description_file {
+        let content = fs::read_to_string(&description_path)
+            .with_context(|| format!("error reading description-file {:?}", description_path))?;
+        builder = builder.description(content.trim_end());
+    }
+
+    // every destination is recorded with the flag which added it, as rpm quietly drops all
+    // but the first file for a path
+    let mut destinations = Vec::new();
+
+    // the files from the filesystem are collected first, so that the conflicts between their
+    // destinations can be resolved before they are added
+    // with the flags which FileOptionsBuilder can't set, which are set in the header
+    let file_flags: [(&str, &str, &[String], FileOptionsModifier, rpm::FileFlags); 7] = [
+        (
+            "--file",
+            "regular file",
+            &args.file,
+            |o, _| o,
+            rpm::FileFlags::empty(),
+        ),
+        (
+            "--exec-file",
+            "executable file",
+            &args.exec_file,
+            |o, defaults| o.mode(rpm::FileMode::regular(defaults.exec)),
+            rpm::FileFlags::empty(),
+        ),
+        (
+            "--config-file",
+            "config file",
+            &args.config_file,
+            |o, _| o.is_config(),
+            rpm::FileFlags::empty(),
+        ),
+        (
+            "--doc-file",
+            "doc file",
+            &args.doc_file,
+            |o, _| o.is_doc(),
+            rpm::FileFlags::empty(),
+        ),
+        (
+            "--license-file",
+            "license file",
+            &args.license_file,
+            |o, _| o.is_license(),
+            rpm::FileFlags::empty(),
+        ),
+        (
+            "--readme-file",
+            "readme file",
+            &args.readme_file,
+            |o, _| o.is_readme(),
+            rpm::FileFlags::empty(),
+        ),
+        (
+            "--artifact-file",
+            "artifact file",
+            &args.artifact_file,
+            |o, _| o,
+            rpm::FileFlags::ARTIFACT,
+        ),
+    ];
+    let file_walking = timings::phase("file walking");
+    let mut files = Vec::new();
+    for (flag, kind, raw_files, modifier, header_flags) in file_flags {
+        for (source, dest, options, file_flags) in parse_file_options(
+            raw_files,
+            &args.file_separator,
+            args.allow_empty_glob,
+            |o, source| modifier(defaults.file(o, source), &defaults),
+        )? {
+            files.push(SourceFile {
+                source,
+                is_symlink: false,
+                dest,
+                flag,
+                kind,
+                options,
+                file_flags: file_flags | header_flags,
+            });
+        }
+    }
+    // directories with their destination, the flag they were given with and their options
+    let mut dirs = Vec::new();
+    for (source, dest) in file_list {
+        let metadata = fs::symlink_metadata(source)
+            .with_context(|| format!("error reading {} from --files-from", source))?;
+        let options = defaults.options(dest);
+        if metadata.is_dir() {
+            let dest = dest.trim_end_matches('/').to_owned();
+            let options = defaults.dir(&dest);
+            dirs.push((dest, "--files-from", options));
+        } else if metadata.is_symlink() {
+            files.push(SourceFile {
+                source: fs::read_link(source)
+                    .with_context(|| format!("error reading symlink {}", source))?,
+                is_symlink: true,
+                dest: dest.clone(),
+                flag: "--files-from",
+                kind: "symlink",
+                options,
+                file_flags: rpm::FileFlags::empty(),
+            });
+        } else {
+            files.push(SourceFile {
+                source: PathBuf::from(source),
+                is_symlink: false,
+                dest: dest.clone(),
+                flag: "--files-from",
+                kind: "regular file",
+                options: defaults.file(rpm::FileOptions::new(dest), Some(Path::new(source))),
+                file_flags: rpm::FileFlags::empty(),
+            });
+        }
+    }
+    // with a spec file, the buildroot is only where the files of %files are taken from
+    if let (Some(buildroot), None) = (&args.buildroot, &args.from_spec) {
+        process_buildroot(
+            buildroot,
+            &args.buildroot_attr,
+            &args.file_separator,
+            &defaults,
+            &mut files,
+            &mut dirs,
+        )
+        .with_context(|| format!("error adding buildroot {:?}", buildroot))?;
+    }
+    let preserve_symlinks = args.dir_preserve_symlinks;
+    let max_depth = if args.dir_no_recurse {
+        Some(1)
+    } else {
+        args.dir_max_depth
+    };
+    let separator = &args.file_separator;
+    process_dir(
+        &args.dir,
+        "--dir",
+        separator,
+        preserve_symlinks,
+        max_depth,
+        &mut files,
+        |o, source| defaults.file(o, source),
+    )?;
+    process_dir(
+        &args.doc_dir,
+        "--doc-dir",
+        separator,
+        preserve_symlinks,
+        max_depth,
+        &mut files,
+        |o, source| defaults.file(o, source).is_doc(),
+    )?;
+    process_dir(
+        &args.config_dir,
+        "--config-dir",
+        separator,
+        preserve_symlinks,
+        max_depth,
+        &mut files,
+        |o, source| defaults.file(o, source).is_config(),
+    )?;
+    if args.auto_license_files {
+        let dirs = [&args.dir, &args.doc_dir, &args.config_dir];
+        let roots: Vec<String> = dirs
+            .into_iter()
+            .flatten()
+            .filter_map(|dir| split_file_argument(dir, separator).1)
+            .collect();
+        let license_dir = format!("/usr/share/licenses/{}", name);
+        move_license_files(&mut files, &roots, &license_dir, &defaults);
+    }
+
+    // the files are added by destination, so that neither the order of the flags nor the one of
+    // the directory listings shows in the package. Sorting after resolving the conflicts keeps
+    // the order of the flags for --on-conflict.
+    let mut files = resolve_conflicts(files, &args.on_conflict)?;
+    files.sort_by(|a, b| a.dest.cmp(&b.dest));
+    let mut sources = Vec::new();
+    // the flags of the files which are set in the header after the build, by destination
+    let mut file_flags = HashMap::new();
+    for file in files {
+        destinations.push(Destination::new(file.flag, &file.dest));
+        if !file.file_flags.is_empty() {
+            file_flags.insert(PathBuf::from(&file.dest), file.file_flags);
+        }
+        if file.is_symlink {
+            let target = file.source.to_string_lossy();
+            tracing::info!(dest = file.dest, target = %target, "adding symlink");
+            builder = add_symlink(builder, file.options, &target)
+                .with_context(|| format!("error adding symlink {}", file.dest))?;
+        } else {
+            let source = file.source.display();
+            tracing::info!(source = %source, dest = file.dest, "adding {}", file.kind);
+            builder = builder
+                .with_file(&file.source, file.options)
+                .with_context(|| format!("error adding {} {}", file.kind, source))?;
+            sources.push(file.source);
+        }
+    }
+
+    drop(file_walking);
+
+    if args.skip_compression_if_incompressible
+        && !matches!(args.compression, Some(Compression::None))
+        && is_incompressible(&sources)?
+    {
+        tracing::info!("the files are already compressed, storing the payload uncompressed");
+        builder = builder.using_config(config.compression(rpm::CompressionType::None));
+    }
+
+    dirs.sort_by(|a, b| a.0.cmp(&b.0));
+    for (dest, flag, options) in dirs {
+        tracing::info!(dest, "adding directory");
+        destinations.push(Destination::new(flag, &dest));
+        builder = builder
+            .with_file_contents(Vec::new(), options)
+            .with_context(|| format!("error adding directory {}", dest))?;
+    }
+
+    if let Some(raw_file) = &args.file_from_stdin {
+        let (dest, attributes) = split_dest_argument(raw_file, &args.file_separator);
+        let dest = dest.as_str();
+        let attributes = attributes.as_deref().unwrap_or_default();
+        let options =
+            apply_file_attributes(defaults.generated(dest), attributes, rpm::FileMode::regular)
+                .with_context(|| format!("invalid file argument {}", raw_file))?;
+        if !header_file_flags(attributes).is_empty() {
+            file_flags.insert(PathBuf::from(dest), header_file_flags(attributes));
+        }
+        tracing::info!(source = "stdin", dest, "adding regular file");
+        destinations.push(Destination::new("--file-from-stdin", dest));
+        builder = match stdin_content {
+            Some(Spooled::File(path)) => builder.with_file(path, options),
+            Some(Spooled::Memory(content)) => builder.with_file_contents(content.clone(), options),
+            None => builder.with_file_contents(Vec::new(), options),
+        }
+        .with_context(|| format!("error adding file {}", dest))?;
+    }
+
+    for raw_symlink in &args.symlink {
+        let (link, target) = parse_symlink(raw_symlink, &args.file_separator)?;
+        let link = link.as_str();
+        tracing::info!(dest = link, target, "adding symlink");
+        destinations.push(Destination::new("--symlink", link));
+        builder = add_symlink(builder, defaults.options(link), &target)
+            .with_context(|| format!("error adding symlink {}", link))?;
+    }
+
+    for raw_dir in &args.create_dir {
+        let (dest, attributes) = split_dest_argument(raw_dir, &args.file_separator);
+        let dest = dest.as_str();
+        let attributes = attributes.as_deref().unwrap_or_default();
+        tracing::info!(dest, "adding directory");
+        destinations.push(Destination::new("--create-dir", dest.trim_end_matches('/')));
+        let options = defaults.dir(dest.trim_end_matches('/'));
+        let options = apply_file_attributes(options, attributes, rpm::FileMode::dir)
+            .with_context(|| format!("invalid directory argument {}", raw_dir))?;
+        if !header_file_flags(attributes).is_empty() {
+            file_flags.insert(
+                PathBuf::from(dest.trim_end_matches('/')),
+                header_file_flags(attributes),
+            );
+        }
+        builder = builder
+            .with_file_contents(Vec::new(), options)
+            .with_context(|| format!("error adding directory {}", dest))?;
+    }
+
+    for raw_tar in &args.from_tar {
+        tracing::info!(source = raw_tar, "adding the contents of an archive");
+        let mut paths = Vec::new();
+        let (archive_path, prefix) = split_prefix_argument(raw_tar, &args.file_separator);
+        builder = archive::add_tar(&archive_path, &prefix, builder, &mut paths)
+            .with_context(|| format!("error adding archive {}", raw_tar))?;
+        Destination::extend(&mut destinations, "--from-tar", paths);
+    }
+
+    for raw_image in &args.from_oci_layer {
+        tracing::info!(source = raw_image, "adding the filesystem of an image");
+        let mut paths = Vec::new();
+        let (source, prefix) = split_prefix_argument(raw_image, &args.file_separator);
+        builder = oci::add_image(&source, &prefix, &args.arch, builder, &mut paths)
+            .with_context(|| format!("error adding image {}", raw_image))?;
+        Destination::extend(&mut destinations, "--from-oci-layer", paths);
+    }
+
+    let mut units = Vec::new();
+    for raw_unit in &args.systemd_unit {
+        let (source, unit) = systemd::parse_unit(raw_unit, &args.file_separator)?;
+        let source = source.as_str();
+        let dest = format!("{}/{}", systemd::UNIT_DIR, unit);
+        tracing::info!(source, dest, "adding systemd unit");
+        let options = defaults.generated(&dest);
+        builder = builder
+            .with_file(source, options)
+            .with_context(|| format!("error adding systemd unit {}", source))?;
+        destinations.push(Destination::new("--systemd-unit", dest));
+        units.push(unit);
+    }
+
+    for raw_man_page in &args.man_page {
+        let (source, section) = man::parse_man_page(raw_man_page, &args.file_separator)?;
+        let source = source.as_str();
+        let dest = man::dest(source, &section)?;
+        tracing::info!(source, dest, "adding man page");
+        let content = man::read_compressed(source)?;
+        builder = builder
+            .with_file_contents(content, defaults.generated(&dest).is_doc())
+            .with_context(|| format!("error adding man page {}", source))?;
+        destinations.push(Destination::new("--man-page", dest));
+    }
+
+    let mut pre_install_snippets = Vec::new();
+    for raw_user in &args.sysuser {
+        let user = SysUser::parse(raw_user)?;
+        let dest = user.config_path();
+        tracing::info!(user = user.name, dest, "adding system user");
+        let options = defaults.generated(&dest);
+        builder = builder
+            .with_file_contents(user.config(), options)
+            .with_context(|| format!("error adding {}", dest))?;
+        destinations.push(Destination::new("--sysuser", dest));
+        pre_install_snippets.push(user.pre_install());
+        builder = builder
+            .provides(rpm::Dependency::any(format!("user({})", user.name)))
+            .provides(rpm::Dependency::any(format!("group({})", user.name)));
+    }
+
+    let mut post_install_snippets = Vec::new();
+    let mut pre_uninstall_snippets = Vec::new();
+    let mut post_uninstall_snippets = Vec::new();
+
+    // modules are loaded first, since they can define the types of the contexts
+    for module in &args.selinux_module {
+        let name = selinux::module_name(module)?;
+        let file_name = module.file_name().unwrap_or_default().to_string_lossy();
+        let dest = format!("{}/{}", selinux::MODULE_DIR, file_name);
+        tracing::info!(source = %module.display(), dest, "adding SELinux policy module");
+        let options = defaults.generated(&dest);
+        builder = builder
+            .with_file(module, options)
+            .with_context(|| format!("error adding policy module {:?}", module))?;
+        post_install_snippets.push(selinux::module_post_install(&dest));
+        post_uninstall_snippets.push(selinux::module_post_uninstall(&name));
+        destinations.push(Destination::new("--selinux-module", dest));
+    }
+    for raw_context in &args.selinux_context {
+        let context = FileContext::parse(raw_context)?;
+        tracing::info!(context = raw_context, "adding SELinux file context");
+        post_install_snippets.push(context.post_install());
+        post_uninstall_snippets.push(context.post_uninstall());
+    }
+
+    if !units.is_empty() {
+        post_install_snippets.push(systemd::post_install(&units));
+        pre_uninstall_snippets.push(systemd::pre_uninstall(&units));
+        post_uninstall_snippets.push(systemd::post_uninstall(&units));
+        builder = builder
+            .requires(rpm::Dependency::script_post("systemd"))
+            .requires(rpm::Dependency::script_preun("systemd"))
+            .requires(rpm::Dependency::script_postun("systemd"));
+    }
+
+    // --lint reports them along with its other errors
+    if !args.lint {
+        check_duplicate_destinations(&destinations)?;
+    }
+
+    if args.own_parent_dirs {
+        for dest in unowned_parent_dirs(&destinations) {
+            tracing::info!(dest, "adding parent directory");
+            let options = defaults.dir(&dest);
+            builder = builder
+                .with_file_contents(Vec::new(), options)
+                .with_context(|| format!("error adding directory {}", dest))?;
+            destinations.push(Destination::new("--own-parent-dirs", dest));
+        }
+    }
+
+    if args.strict_paths {
+        check_strict_paths(&destinations)?;
+    }
+
+    if let Some(scriptlet) = load_scriptlet(
+        "pre-install-script",
+        args.pre_install_script,
+        args.pre_install_interpreter,
+        spec::Package::scriptlet(&args.spec_scriptlets, "pre-install-script"),
+        &args.pre_install_flags,
+        &pre_install_snippets,
+    )? {
+        builder = builder.pre_install_script(scriptlet);
+    }
+
+    if let Some(scriptlet) = load_scriptlet(
+        "post-install-script",
+        args.post_install_script,
+        args.post_install_interpreter,
+        spec::Package::scriptlet(&args.spec_scriptlets, "post-install-script"),
+        &args.post_install_flags,
+        &post_install_snippets,
+    )? {
+        builder = builder.post_install_script(scriptlet);
+    }
+
+    if let Some(scriptlet) = load_scriptlet(
+        "pre-uninstall-script",
+        args.pre_uninstall_script,
+        args.pre_uninstall_interpreter,
+        spec::Package::scriptlet(&args.spec_scriptlets, "pre-uninstall-script"),
+        &args.pre_uninstall_flags,
+        &pre_uninstall_snippets,
+    )? {
+        builder = builder.pre_uninstall_script(scriptlet);
+    }
+
+    if let Some(scriptlet) = load_scriptlet(
+        "post-uninstall-script",
+        args.post_uninstall_script,
+        args.post_uninstall_interpreter,
+        spec::Package::scriptlet(&args.spec_scriptlets, "post-uninstall-script"),
+        &args.post_uninstall_flags,
+        &post_uninstall_snippets,
+    )? {
+        builder = builder.post_uninstall_script(scriptlet);
+    }
+
+    if let Some(scriptlet) = load_scriptlet(
+        "pre-trans-script",
+        args.pre_trans_script,
+        args.pre_trans_interpreter,
+        spec::Package::scriptlet(&args.spec_scriptlets, "pre-trans-script"),
+        &args.pre_trans_flags,
+        &[],
+    )? {
+        builder = builder.pre_trans_script(scriptlet);
+    }
+
+    if let Some(scriptlet) = load_scriptlet(
+        "post-trans-script",
+        args.post_trans_script,
+        args.post_trans_interpreter,
+        spec::Package::scriptlet(&args.spec_scriptlets, "post-trans-script"),
+        &args.post_trans_flags,
+        &[],
+    )? {
+        builder = builder.post_trans_script(scriptlet);
+    }
+
+    // the builder doesn't write the verify script, so it's added to the header below
+    let verify_script = load_scriptlet(
+        "verify-script",
+        args.verify_script,
+        args.verify_interpreter,
+        spec::Package::scriptlet(&args.spec_scriptlets, "verify-script"),
+        &args.verify_flags,
+        &[],
+    )?;
+
+    // the builder doesn't write file triggers either
+    let trigger_flags = [
+        (
+            &args.file_trigger_in,
+            rpm::DependencyFlags::TRIGGERIN,
+            false,
+        ),
+        (
+            &args.file_trigger_un,
+            rpm::DependencyFlags::TRIGGERUN,
+            false,
+        ),
+        (
+            &args.file_trigger_postun,
+            rpm::DependencyFlags::TRIGGERPOSTUN,
+            false,
+        ),
+        (
+            &args.trans_file_trigger_in,
+            rpm::DependencyFlags::TRIGGERIN,
+            true,
+        ),
+        (
+            &args.trans_file_trigger_un,
+            rpm::DependencyFlags::TRIGGERUN,
+            true,
+        ),
+        (
+            &args.trans_file_trigger_postun,
+            rpm::DependencyFlags::TRIGGERPOSTUN,
+            true,
+        ),
+    ];
+    let mut file_triggers = Vec::new();
+    let mut trans_file_triggers = Vec::new();
+    for (raw_triggers, kind, transaction) in trigger_flags {
+        for raw_trigger in raw_triggers {
+            let trigger = filetrigger::parse(raw_trigger, &args.file_separator, kind)?;
+            tracing::info!(prefix = trigger.prefix, "adding file trigger");
+            if transaction {
+                trans_file_triggers.push(trigger);
+            } else {
+                file_triggers.push(trigger);
+            }
+        }
+    }
+    if !file_triggers.is_empty() || !trans_file_triggers.is_empty() {
+        // like rpmbuild, so that versions of rpm which would ignore the triggers refuse the package
+        builder = builder.requires(rpm::Dependency {
+            name: "rpmlib(FileTriggers)".to_owned(),
+            flags: rpm::DependencyFlags::RPMLIB | rpm::DependencyFlags::LE,
+            version: "4.12.0-1".to_owned(),
+        });
+    }
+
+    let mut changelog = args
+        .changelog
+        .iter()
+        .map(|raw_entry| changelog::parse_entry(raw_entry))
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(changelog_path) = &args.changelog_file {
+        changelog.extend(changelog::parse_file(changelog_path)?);
+    }
+    changelog.extend(args.spec_changelog);
+    if let Some(repo) = &args.changelog_from_git {
+        changelog.extend(changelog::from_git(repo, args.changelog_limit)?);
+    }
+    if args.reproducible {
+        // stable sort, so entries from the same day keep the order they were given in
+        changelog.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp));
+    }
+    for entry in changelog {
+        builder = builder.add_changelog_entry(
+            entry.name,
+            entry.content,
+            rpm::Timestamp::from(entry.timestamp as u32),
+        );
+    }
+
+    let requires_filters = compile_filters(&args.filter_requires)?;
+    args.requires
+        .retain(|item| !requires_filters.iter().any(|filter| filter.is_match(item)));
+    let provides_filters = compile_filters(&args.filter_provides)?;
+    args.provides
+        .retain(|item| !provides_filters.iter().any(|filter| filter.is_match(item)));
+
+    let uses_rich_dependencies = [
+        &args.requires,
+        &args.conflicts,
+        &args.suggests,
+        &args.enhances,
+        &args.recommends,
+        &args.supplements,
+    ]
+    .into_iter()
+    .flatten()
+    .any(|item| is_rich_dependency(item));
+    if uses_rich_dependencies {
+        // rpmbuild adds the same requirement, so that older versions of rpm refuse the package
+        builder = builder.requires(rpm::Dependency {
+            name: "rpmlib(RichDependencies)".to_owned(),
+            flags: rpm::DependencyFlags::RPMLIB | rpm::DependencyFlags::LE,
+            version: "4.12.0-1".to_owned(),
+        });
+    }
+
+    for item in args.requires {
+        let dependency = parse_requirement(&item)?;
+        tracing::info!(kind = "requires", dependency = item, "adding dependency");
+        builder = builder.requires(dependency);
+    }
+
+    for item in args.obsoletes {
+        if is_rich_dependency(&item) {
+            anyhow::bail!("rich dependencies can't be used for obsoletes: {}", item);
+        }
+        let dependency = parse_dependency(&item)?;
+        tracing::info!(kind = "obsoletes", dependency = item, "adding dependency");
+        builder = builder.obsoletes(dependency);
+    }
+
+    for item in args.conflicts {
+        let dependency = parse_dependency(&item)?;
+        tracing::info!(kind = "conflicts", dependency = item, "adding dependency");
+        builder = builder.conflicts(dependency);
+    }
+
+    for item in args.provides {
+        if is_rich_dependency(&item) {
+            anyhow::bail!("rich dependencies can't be used for provides: {}", item);
+        }
+        let dependency = parse_dependency(&item)?;
+        tracing::info!(kind = "provides", dependency = item, "adding dependency");
+        builder = builder.provides(dependency);
+    }
+
+    for item in args.suggests {
+        let dependency = parse_dependency(&item)?;
+        tracing::info!(kind = "suggests", dependency = item, "adding dependency");
+        builder = builder.suggests(dependency);
+    }
+
+    for item in args.enhances {
+        let dependency = parse_dependency(&item)?;
+        tracing::info!(kind = "enhances", dependency = item, "adding dependency");
+        builder = builder.enhances(dependency);
+    }
+
+    for item in args.recommends {
+        let dependency = parse_dependency(&item)?;
+        tracing::info!(kind = "recommends", dependency = item, "adding dependency");
+        builder = builder.recommends(dependency);
+    }
+
+    for item in args.supplements {
+        let dependency = parse_dependency(&item)?;
+        tracing::info!(kind = "supplements", dependency = item, "adding dependency");
+        builder = builder.supplements(dependency);
+    }
+
+    // tags the builder doesn't write are set in the built header, which is signed afterwards
+    let mut header_tags = Vec::new();
+    if let Some(os) = args.os {
+        header_tags.push((rpm::IndexTag::RPMTAG_OS, header::Value::String(os)));
+    }
+    if let Some(platform) = args.platform {
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_PLATFORM,
+            header::Value::String(platform),
+        ));
+    }
+    if let Some(scriptlet) = verify_script {
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_VERIFYSCRIPT,
+            header::Value::String(scriptlet.script),
+        ));
+        if let Some(flags) = scriptlet.flags {
+            header_tags.push((
+                rpm::IndexTag::RPMTAG_VERIFYSCRIPTFLAGS,
+                header::Value::Int32(vec![flags.bits()]),
+            ));
+        }
+        if let Some(program) = scriptlet.program {
+            header_tags.push((
+                rpm::IndexTag::RPMTAG_VERIFYSCRIPTPROG,
+                header::Value::StringArray(program),
+            ));
+        }
+    }
+    header_tags.extend(filetrigger::header_tags(&file_triggers, false));
+    header_tags.extend(filetrigger::header_tags(&trans_file_triggers, true));
+    if let Some(packager) = args.packager {
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_PACKAGER,
+            header::Value::String(packager),
+        ));
+    }
+    if let Some(group) = args.group {
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_GROUP,
+            header::Value::I18nString(vec![group]),
+        ));
+    }
+    if let Some(distribution) = args.distribution {
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_DISTRIBUTION,
+            header::Value::String(distribution),
+        ));
+    }
+    if !args.order_with_requires.is_empty() {
+        let mut dependencies = Vec::new();
+        for item in &args.order_with_requires {
+            if is_rich_dependency(item) {
+                anyhow::bail!(
+                    "rich dependencies can't be used for order-with-requires: {}",
+                    item
+                );
+            }
+            tracing::info!(
+                kind = "order-with-requires",
+                dependency = item,
+                "adding dependency"
+            );
+            dependencies.push(parse_dependency(item)?);
+        }
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_ORDERNAME,
+            header::Value::StringArray(dependencies.iter().map(|d| d.name.clone()).collect()),
+        ));
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_ORDERVERSION,
+            header::Value::StringArray(dependencies.iter().map(|d| d.version.clone()).collect()),
+        ));
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_ORDERFLAGS,
+            header::Value::Int32(dependencies.iter().map(|d| d.flags.bits()).collect()),
+        ));
+    }
+    let module = args
+        .modulemd
+        .as_deref()
+        .map(ModuleDocument::load)
+        .transpose()?;
+    let modularity_label = match (args.modularity_label, &module) {
+        (Some(label), _) => Some(label),
+        (None, Some(module)) => Some(module.label()?),
+        (None, None) => None,
+    };
+    if let Some(label) = modularity_label {
+        modulemd::check_label(&label)?;
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_MODULARITYLABEL,
+            header::Value::String(label),
+        ));
+    }
+
+    // the rpm crate digests and compresses the files in a single pass
+    let payload = timings::phase("digests and compression");
+    let pkg = builder.build()?;
+    drop(payload);
+    let header_assembly = timings::phase("header assembly");
+    // rpm records the size of the files in 64 bits exactly when the payload is stripped
+    let stripped = pkg
+        .metadata
+        .header
+        .entry_is_present(rpm::IndexTag::RPMTAG_LONGSIZE);
+    match &args.payload_format {
+        Some(format @ (PayloadFormat::Cpio | PayloadFormat::Newc)) if stripped => anyhow::bail!(
+            "the files of the package exceed 4 GiB, which requires a stripped payload instead of --payload-format {}",
+            format.name()
+        ),
+        _ => {}
+    }
+    if !args.summary_lang.is_empty() || !args.description_lang.is_empty() {
+        header_tags.extend(translations(
+            &pkg,
+            &args.summary_lang,
+            &args.description_lang,
+        )?);
+    }
+    if !args.prefix.is_empty() {
+        let prefixes = parse_prefixes(&args.prefix, &pkg)?;
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_PREFIXES,
+            header::Value::StringArray(prefixes),
+        ));
+    }
+    let mtimes = args
+        .file_mtime
+        .as_deref()
+        .map(|policy| file_mtimes(&pkg, policy, source_date))
+        .transpose()?;
+    if let Some(mtimes) = mtimes.flatten() {
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_FILEMTIMES,
+            header::Value::Int32(mtimes),
+        ));
+    }
+    // rpm always records SHA-256 digests
+    let digests = match args.file_digest_algo {
+        Some(FileDigestAlgorithm::Sha512) => sha512_file_digests(&pkg)?,
+        _ => None,
+    };
+    if let Some(digests) = digests {
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_FILEDIGESTS,
+            header::Value::StringArray(digests),
+        ));
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_FILEDIGESTALGO,
+            header::Value::Int32(vec![rpm::DigestAlgorithm::Sha2_512 as u32]),
+        ));
+    }
+    if !file_flags.is_empty() {
+        header_tags.push((
+            rpm::IndexTag::RPMTAG_FILEFLAGS,
+            header::Value::Int32(with_header_file_flags(&pkg, &file_flags)?),
+        ));
+    }
+    let mut pkg = header::set_tags(pkg, &header_tags)?;
+    drop(header_assembly);
+
+    let signing = timings::phase("signing");
+    if !args.sign_with_pgp_asc.is_empty() {
+        let signers = sign::load_signers(&args.sign_with_pgp_asc)?;
+        if let Some(compat) = &args.compat {
+            for (signer, path) in signers.iter().zip(&args.sign_with_pgp_asc) {
+                let algorithm = signer.algorithm();
+                if !compat.supports_signature_algorithm(algorithm) {
+                    anyhow::bail!(
+                        "the {:?} key {:?} can't be used with --compat {}, its rpm can only check RSA signatures",
+                        algorithm,
+                        path,
+                        compat.name()
+                    );
+                }
+            }
+        }
+        // like the build time, the signature time is clamped to the source date
+        let now = rpm::Timestamp::now();
+        let signature_time = source_date
+            .map(rpm::Timestamp::from)
+            .filter(|time| *time < now)
+            .unwrap_or(now);
+        pkg = sign::sign_package(pkg, &signers, signature_time, &args.signature_mode)?;
+    }
+    let header_digests = match (&args.header_digest[..], &args.compat) {
+        ([], Some(Compat::El7)) => vec![HeaderDigest::Sha1, HeaderDigest::Sha256],
+        (digests, _) => digests.to_vec(),
+    };
+    if !header_digests.is_empty() {
+        pkg = set_header_digests(pkg, &header_digests)?;
+    }
+    drop(signing);
+
+    // the mode and flags of a file are only settled once rpm has added it
+    for entry in pkg.metadata.get_file_entries()? {
+        tracing::debug!(
+            path = %entry.path.display(),
+            mode = format!("{:o}", u16::from(entry.mode)),
+            flags = ?entry.flags,
+            user = entry.ownership.user,
+            group = entry.ownership.group,
+            "packaged file"
+        );
+    }
+
+    if args.lint {
+        let paths = destinations.into_iter().map(|d| d.path).collect::<Vec<_>>();
+        let mut findings = lint::check_destinations(&paths);
+        findings.extend(lint::check_package(&pkg)?);
+        findings.sort_by(|a, b| a.severity.cmp(&b.severity));
+        lint::report(&findings)?;
+    }
+
+    Ok(pkg)
+}
+
+/// The directories which are expected to exist on every system, so files can be added to them
+/// without the package owning them
+const STANDARD_DIRS: &[&str] = &[
+    "/",
+    "/boot",
+    "/etc",
+    "/etc/cron.d",
+    "/etc/default",
+    "/etc/logrotate.d",
+    "/etc/pki",
+    "/etc/profile.d",
+    "/etc/sysconfig",
+    "/etc/sysctl.d",
+    "/etc/systemd/system",
+    "/etc/xdg",
+    "/home",
+    "/opt",
+    "/srv",
+    "/usr",
+    "/usr/bin",
+    "/usr/include",
+    "/usr/lib",
+    "/usr/lib/systemd/system",
+    "/usr/lib/systemd/user",
+    "/usr/lib/sysusers.d",
+    "/usr/lib/tmpfiles.d",
+    "/usr/lib/udev/rules.d",
+    "/usr/lib64",
+    "/usr/libexec",
+    "/usr/local",
+    "/usr/local/bin",
+    "/usr/local/lib",
+    "/usr/local/share",
+    "/usr/sbin",
+    "/usr/share",
+    "/usr/share/applications",
+    "/usr/share/doc",
+    "/usr/share/icons",
+    "/usr/share/info",
+    "/usr/share/licenses",
+    "/usr/share/man",
+    "/usr/share/man/man1",
+    "/usr/share/man/man5",
+    "/usr/share/man/man8",
+    "/usr/share/pixmaps",
+    "/usr/share/selinux/packages",
+    "/var",
+    "/var/cache",
+    "/var/lib",
+    "/var/log",
+    "/var/opt",
+    "/var/spool",
+    "/var/tmp",
+];
+
+/// A file from the filesystem, which is added to the package once the conflicts between the
+/// files with the same destination are resolved
+pub struct SourceFile {
+    /// The file, or the target of a symlink kept with `--dir-preserve-symlinks`
+    pub source: PathBuf,
+    pub is_symlink: bool,
+    pub dest: String,
+    /// The flag the file was given with
+    pub flag: &'static str,
+    /// The kind of file, as logged when it's added
+    pub kind: &'static str,
+    pub options: rpm::FileOptionsBuilder,
+    /// Flags which the options can't carry, such as missingok, set in the header after the build
+    pub file_flags: rpm::FileFlags,
+}
+
+type FileOptionsModifier = fn(rpm::FileOptionsBuilder, &FileDefaults) -> rpm::FileOptionsBuilder;
+
+/// The modes and owners of the files and directories which don't get them from their attributes,
+/// for `--default-file-mode`, `--default-dir-mode`, `--default-exec-mode`, `--default-user` and
+/// `--default-group`
+pub struct FileDefaults {
+    /// The permissions of plain files, which otherwise keep the ones of their source
+    pub file: Option<u16>,
+    pub dir: u16,
+    pub exec: u16,
+    /// The owners, which rpm otherwise sets to root
+    pub user: Option<String>,
+    pub group: Option<String>,
+}
+
+impl FileDefaults {
+    pub fn from_args(args: &BuildArgs) -> Result<FileDefaults> {
+        let parse = |value: &Option<String>, flag: &str| {
+            value
+                .as_deref()
+                .map(|value| parse_permissions(value).with_context(|| format!("invalid {}", flag)))
+                .transpose()
+        };
+        Ok(FileDefaults {
+            file: parse(&args.default_file_mode, "--default-file-mode")?,
+            dir: parse(&args.default_dir_mode, "--default-dir-mode")?.unwrap_or(0o755),
+            exec: parse(&args.default_exec_mode, "--default-exec-mode")?.unwrap_or(0o755),
+            user: args
+                .default_user
+                .as_deref()
+                .map(parse_owner)
+                .transpose()
+                .context("invalid --default-user")?,
+            group: args
+                .default_group
+                .as_deref()
+                .map(parse_owner)
+                .transpose()
+                .context("invalid --default-group")?,
+        })
+    }
+
+    /// The options of a file or directory with the default owners
+    pub fn options(&self, dest: &str) -> rpm::FileOptionsBuilder {
+        self.owned(rpm::FileOptions::new(dest))
+    }
+
+    fn owned(&self, mut options: rpm::FileOptionsBuilder) -> rpm::FileOptionsBuilder {
+        if let Some(user) = &self.user {
+            options = options.user(user);
+        }
+        if let Some(group) = &self.group {
+            options = options.group(group);
+        }
+        options
+    }
+
+    /// The options of a file from the filesystem, `None` for symlinks
+    pub fn file(
+        &self,
+        options: rpm::FileOptionsBuilder,
+        source: Option<&Path>,
+    ) -> rpm::FileOptionsBuilder {
+        let options = self.owned(options);
+        match (self.file, source) {
+            (Some(_), Some(source)) if is_executable(source) => {
+                options.mode(rpm::FileMode::regular(self.exec))
+            }
+            (Some(permissions), Some(_)) => options.mode(rpm::FileMode::regular(permissions)),
+            _ => options,
+        }
+    }
+
+    /// The options of a file generated for the package, such as a systemd unit
+    pub fn generated(&self, dest: &str) -> rpm::FileOptionsBuilder {
+        self.options(dest)
+            .mode(rpm::FileMode::regular(self.file.unwrap_or(0o644)))
+    }
+
+    pub fn dir(&self, dest: &str) -> rpm::FileOptionsBuilder {
+        self.options(dest).mode(rpm::FileMode::dir(self.dir))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    false
+}
+
+/// The destination path of a file added to the package, with the flag which added it
+struct Destination {
+    path: String,
+    flag: &'static str,
+}
+
+impl Destination {
+    fn new(flag: &'static str, path: impl Into<String>) -> Destination {
+        Destination {
+            path: path.into(),
+            flag,
+        }
+    }
+
+    fn extend(destinations: &mut Vec<Destination>, flag: &'static str, paths: Vec<String>) {
+        destinations.extend(paths.into_iter().map(|path| Destination::new(flag, path)));
+    }
+}
+
+/// The parent directories of the destinations which aren't in the package themselves, leaving
+/// out the standard ones
+fn unowned_parent_dirs(destinations: &[Destination]) -> BTreeSet<String> {
+    let owned = destinations
+        .iter()
+        .map(|d| Path::new(&d.path))
+        .collect::<BTreeSet<_>>();
+    destinations
+        .iter()
+        .flat_map(|d| Path::new(&d.path).ancestors().skip(1))
+        .filter(|dir| dir.is_absolute() && !owned.contains(dir))
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .filter(|dir| !STANDARD_DIRS.contains(&dir.as_str()))
+        .collect()
+}
+
+/// Fail on a destination which was added more than once, as rpm would quietly keep the first
+/// file. The file and directory flags have gone through `--on-conflict` already, so a duplicate
+/// involves one of the other flags, whose files are added as they are read.
+fn check_duplicate_destinations(destinations: &[Destination]) -> Result<()> {
+    let mut flags = HashMap::new();
+    for destination in destinations {
+        if let Some(flag) = flags.insert(Path::new(&destination.path), destination.flag) {
+            anyhow::bail!(
+                "{}: {} was already added by {}, --on-conflict only applies to the file and directory flags",
+                destination.flag,
+                destination.path,
+                flag
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The checks of `--strict-paths`, which fail on the first destination with a problem
+fn check_strict_paths(destinations: &[Destination]) -> Result<()> {
+    let mut flags = HashMap::new();
+    for destination in destinations {
+        let path = Path::new(&destination.path);
+        if !path.is_absolute() {
+            anyhow::bail!(
+                "{}: the destination path {} is not absolute",
+                destination.flag,
+                destination.path
+            );
+        }
+        if path.components().any(|c| c == Component::ParentDir) {
+            anyhow::bail!(
+                "{}: the destination path {} contains '..'",
+                destination.flag,
+                destination.path
+            );
+        }
+        flags.insert(path, destination.flag);
+    }
+    for destination in destinations {
+        let Some(parent) = Path::new(&destination.path).parent() else {
+            continue;
+        };
+        if !flags.contains_key(parent) && !STANDARD_DIRS.iter().any(|d| Path::new(d) == parent) {
+            anyhow::bail!(
+                "{}: {} is in {}, which is neither in the package nor a standard directory (it can be added with --create-dir)",
+                destination.flag,
+                destination.path,
+                parent.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Check that the options only use what the rpm of the `--compat` distribution can install. The
+/// algorithm of the signing keys is checked once they are loaded.
+fn check_compat(args: &BuildArgs, compat: &Compat) -> Result<()> {
+    let fail = |reason: &str| -> Result<()> {
+        anyhow::bail!(
+            "{} can't be used with --compat {}, its rpm doesn't support it",
+            reason,
+            compat.name()
+        )
+    };
+    if matches!(args.rpm_format, Some(RpmVersion::V6)) {
+        fail("--rpm-format v6")?;
+    }
+    if matches!(args.payload_format, Some(PayloadFormat::Stripped)) {
+        fail("--payload-format stripped")?;
+    }
+    if matches!(args.compression, Some(Compression::Zstd)) && !compat.supports_zstd() {
+        fail("--compression zstd")?;
+    }
+    let checks_sha1_only = matches!(compat, Compat::El7);
+    if checks_sha1_only
+        && !args.header_digest.is_empty()
+        && !args.header_digest.contains(&HeaderDigest::Sha1)
+    {
+        fail("--header-digest without sha1")?;
+    }
+    if !compat.supports_rich_dependencies() {
+        let dependencies = [
+            &args.requires,
+            &args.provides,
+            &args.obsoletes,
+            &args.conflicts,
+            &args.suggests,
+            &args.enhances,
+            &args.recommends,
+            &args.supplements,
+        ];
+        let rich = dependencies
+            .into_iter()
+            .flatten()
+            .find(|dependency| is_rich_dependency(dependency));
+        if let Some(dependency) = rich {
+            fail(&format!("the rich dependency {}", dependency))?;
+        }
+    }
+    if !compat.supports_file_triggers() {
+        let triggers = [
+            ("--file-trigger-in", &args.file_trigger_in),
+            ("--file-trigger-un", &args.file_trigger_un),
+            ("--file-trigger-postun", &args.file_trigger_postun),
+            ("--trans-file-trigger-in", &args.trans_file_trigger_in),
+            ("--trans-file-trigger-un", &args.trans_file_trigger_un),
+            (
+                "--trans-file-trigger-postun",
+                &args.trans_file_trigger_postun,
+            ),
+        ];
+        if let Some((flag, _)) = triggers.iter().find(|(_, raw)| !raw.is_empty()) {
+            fail(flag)?;
+        }
+    }
+    Ok(())
+}
+
+/// The path a package is written to: `<out>/<filename>` if `out` is a directory (or ends with a
+/// separator), `out` (with the `.rpm` extension) if it isn't, and `<filename>` in the current directory
+/// without an `out`. The file name is `<nvra>.rpm` unless there is a `--filename-format`.
+pub fn output_path(
+    pkg: &rpm::Package,
+    out: Option<&Path>,
+    filename_format: Option<&str>,
+) -> Result<PathBuf> {
+    let nevra = pkg.metadata.get_nevra()?;
+    let filename = match filename_format {
+        Some(format) => {
+            let epoch = match nevra.epoch() {
+                "" => "0",
+                epoch => epoch,
+            };
+            let variables = template::Variables::from([
+                ("name".to_owned(), nevra.name().to_owned()),
+                ("epoch".to_owned(), epoch.to_owned()),
+                ("version".to_owned(), nevra.version().to_owned()),
+                ("release".to_owned(), nevra.release().to_owned()),
+                ("arch".to_owned(), nevra.arch().to_owned()),
+            ]);
+            let filename = template::expand(format, &variables);
+            if filename.contains("%{") || filename.contains("${") {
+                anyhow::bail!(
+                    "invalid filename-format {}, only name, epoch, version, release and arch can be used",
+                    format
+                );
+            }
+            if filename.is_empty() || filename.contains('/') {
+                anyhow::bail!(
+                    "invalid filename-format {}, it needs to produce a file name",
+                    format
+                );
+            }
+            filename
+        }
+        None => format!("{}.rpm", nevra.nvra()),
+    };
+    Ok(match out {
+        Some(path) if fs::metadata(path).is_ok_and(|m| m.is_dir()) || ends_with_separator(path) => {
+            path.join(filename)
+        }
+        Some(path) => path.with_extension("rpm"),
+        None => PathBuf::from(filename),
+    })
+}
+
+/// Whether a path ends with a separator, which makes it a directory even if it doesn't exist
+fn ends_with_separator(path: &Path) -> bool {
+    path.as_os_str()
+        .to_string_lossy()
+        .ends_with(std::path::is_separator)
+}
+
+fn load_scriptlet(
+    kind: &str,
+    path: Option<PathBuf>,
+    interpreter: Option<String>,
+    spec_scriptlet: Option<spec::Scriptlet>,
+    flags: &[ScriptletFlag],
+    snippets: &[String],
+) -> Result<Option<rpm::Scriptlet>> {
+    // the scriptlet of a spec file is only used if it isn't replaced on the command line
+    let (interpreter, spec_content) = match spec_scriptlet {
+        Some(scriptlet) if path.is_none() && interpreter.is_none() => {
+            (scriptlet.interpreter, Some(scriptlet.content))
+        }
+        _ => (interpreter, None),
+    };
+    let mut content = match (path, &interpreter, spec_content) {
+        (Some(path), _, _) => fs::read_to_string(&path)
+            .with_context(|| format!("error reading {} {:?}", kind, path))?,
+        (None, _, Some(content)) => content,
+        // e.g. `%post -p /sbin/ldconfig`, where the interpreter is run without a script
+        (None, Some(_), None) => String::new(),
+        (None, None, None) if snippets.is_empty() && flags.is_empty() => return Ok(None),
+        (None, None, None) if snippets.is_empty() => {
+            anyhow::bail!("flags were provided for {} without a script", kind)
+        }
+        (None, None, None) => String::new(),
+    };
+
+    // shell snippets generated by other options, e.g. --systemd-unit or --sysuser
+    if !snippets.is_empty() {
+        let program = interpreter
+            .as_deref()
+            .and_then(|i| i.split_whitespace().next());
+        if let Some(program) = program.filter(|p| !matches!(*p, "/bin/sh" | "/bin/bash")) {
+            anyhow::bail!(
+                "{} is run with {}, so the generated shell snippets can't be added to it",
+                kind,
+                program
+            );
+        }
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&snippets.concat());
+    }
+    let mut scriptlet = rpm::Scriptlet::new(content);
+
+    if let Some(interpreter) = interpreter {
+        let program: Vec<&str> = interpreter.split_whitespace().collect();
+        if program.is_empty() {
+            anyhow::bail!("empty interpreter provided for {}", kind);
+        }
+        scriptlet = scriptlet.prog(program);
+    }
+
+    if !flags.is_empty() {
+        let flags = flags
+            .iter()
+            .fold(rpm::ScriptletFlags::empty(), |acc, flag| {
+                acc | match flag {
+                    ScriptletFlag::Expand => rpm::ScriptletFlags::EXPAND,
+                    ScriptletFlag::Qformat => rpm::ScriptletFlags::QFORMAT,
+                    ScriptletFlag::Critical => rpm::ScriptletFlags::CRITICAL,
+                }
+            });
+        scriptlet = scriptlet.flags(flags);
+    }
+
+    Ok(Some(scriptlet))
+}
+
+fn compression_with_level(
+    compression: rpm::CompressionType,
+    level: i32,
+) -> Result<rpm::CompressionWithLevel> {
+    let (compression_with_level, valid_levels) = match compression {
+        rpm::CompressionType::Gzip => (rpm::CompressionWithLevel::Gzip(level as u32), 1..=9),
+        rpm::CompressionType::Zstd => (rpm::CompressionWithLevel::Zstd(level), -7..=22),
+        rpm::CompressionType::Xz => (rpm::CompressionWithLevel::Xz(level as u32), 1..=9),
+        rpm::CompressionType::Bzip2 => (rpm::CompressionWithLevel::Bzip2(level as u32), 1..=9),
+        rpm::CompressionType::None => {
+            anyhow::bail!("a compression level can't be used without compression")
+        }
+    };
+    if !valid_levels.contains(&level) {
+        anyhow::bail!(
+            "invalid compression level {} for {}, it needs to be between {} and {}",
+            level,
+            compression,
+            valid_levels.start(),
+            valid_levels.end()
+        );
+    }
+    Ok(compression_with_level)
+}
+
+/// The timestamp to clamp all times in the package to, if any.
+///
+/// SOURCE_DATE_EPOCH is always honored when it is set; `--reproducible` falls back to 0 without it.
+fn source_date_epoch(reproducible: bool) -> Result<Option<u32>> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) => {
+            let timestamp = value.trim().parse().with_context(|| {
+                format!(
+                    "invalid SOURCE_DATE_EPOCH {:?}, expected a Unix timestamp",
+                    value
+                )
+            })?;
+            Ok(Some(timestamp))
+        }
+        Err(_) if reproducible => Ok(Some(0)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// The header tags of the translated summaries and descriptions: the locales are listed in
+/// `RPMTAG_HEADERI18NTABLE`, after the default "C" one, and the summary and description hold one
+/// text per locale, which is the untranslated one for locales without a translation
+fn translations(
+    pkg: &rpm::Package,
+    summary_lang: &[String],
+    description_lang: &[String],
+) -> Result<Vec<(rpm::IndexTag, header::Value)>> {
+    let mut locales = vec!["C".to_owned()];
+    let mut summaries = vec![pkg.metadata.get_summary()?.to_owned()];
+    let mut descriptions = vec![pkg.metadata.get_description()?.to_owned()];
+
+    let mut translate = |locale: &str, texts: &mut Vec<String>, text: String| {
+        let position = match locales.iter().position(|l| l == locale) {
+            Some(position) => position,
+            None => {
+                locales.push(locale.to_owned());
+                locales.len() - 1
+            }
+        };
+        texts.resize(locales.len(), texts[0].clone());
+        texts[position] = text;
+    };
+    for raw in summary_lang {
+        let (locale, text) = parse_translation(raw)?;
+        translate(locale, &mut summaries, text.to_owned());
+    }
+    for raw in description_lang {
+        let (locale, path) = parse_translation(raw)?;
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("error reading description translation {:?}", path))?;
+        translate(locale, &mut descriptions, text.trim_end().to_owned());
+    }
+    summaries.resize(locales.len(), summaries[0].clone());
+    descriptions.resize(locales.len(), descriptions[0].clone());
+
+    Ok(vec![
+        (
+            rpm::IndexTag::RPMTAG_HEADERI18NTABLE,
+            header::Value::StringArray(locales),
+        ),
+        (
+            rpm::IndexTag::RPMTAG_SUMMARY,
+            header::Value::I18nString(summaries),
+        ),
+        (
+            rpm::IndexTag::RPMTAG_DESCRIPTION,
+            header::Value::I18nString(descriptions),
+        ),
+    ])
+}
+
+/// Split a `<lang>:<value>` translation
+fn parse_translation(raw: &str) -> Result<(&str, &str)> {
+    let locale_regex = Regex::new(r"^[a-z]{2,3}(_[A-Z]{2})?(\.[A-Za-z0-9-]+)?(@[a-z]+)?$").unwrap();
+    match raw.split_once(':') {
+        Some((locale, value)) if locale_regex.is_match(locale) => Ok((locale, value)),
+        _ => anyhow::bail!(
+            "invalid translation {}, it needs to be of the form <lang>:<value>, with a locale such as de or pt_BR",
+            raw
+        ),
+    }
+}
+
+/// Normalize the `--prefix` directories and check that every file of the package is below one of
+/// them, as rpm can only relocate those
+fn parse_prefixes(raw_prefixes: &[String], pkg: &rpm::Package) -> Result<Vec<String>> {
+    let mut prefixes = Vec::new();
+    for raw_prefix in raw_prefixes {
+        let prefix = raw_prefix.trim_end_matches('/');
+        if !prefix.starts_with('/') {
+            anyhow::bail!(
+                "invalid prefix {:?}, it needs to be an absolute path",
+                raw_prefix
+            );
+        }
+        prefixes.push(prefix.to_owned());
+    }
+    for entry in pkg.metadata.get_file_entries()? {
+        if !prefixes.iter().any(|prefix| entry.path.starts_with(prefix)) {
+            anyhow::bail!(
+                "{} is not below any of the prefixes {}",
+                entry.path.display(),
+                prefixes.join(", ")
+            );
+        }
+    }
+    Ok(prefixes)
+}
+
+/// The suffix `--auto-release` appends to the release
+fn release_suffix(source: &AutoRelease, source_date: Option<u32>) -> Result<String> {
+    let git = |args: &[&str]| -> Result<String> {
+        let output = process::Command::new("git")
+            .args(args)
+            .output()
+            .context("unable to run git to derive the release")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "unable to derive the release from git: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let stdout = String::from_utf8(output.stdout).context("git output is not valid UTF-8")?;
+        Ok(stdout.trim().to_owned())
+    };
+    let date = |timestamp: i64, format: &str| -> Result<String> {
+        let date_time = chrono::DateTime::from_timestamp(timestamp, 0)
+            .with_context(|| format!("invalid timestamp {}", timestamp))?;
+        Ok(date_time.format(format).to_string())
+    };
+
+    let count = match source {
+        AutoRelease::Timestamp => {
+            let timestamp = source_date.map_or_else(|| chrono::Utc::now().timestamp(), i64::from);
+            return date(timestamp, "%Y%m%d%H%M%S");
+        }
+        AutoRelease::GitCount => git(&["rev-list", "--count", "HEAD"])?,
+        AutoRelease::GitDescribe => {
+            let description = git(&["describe", "--tags", "--long"])?;
+            // <tag>-<count>-g<hash>, tags can contain '-'
+            let mut parts = description.rsplitn(3, '-');
+            match (parts.next(), parts.next()) {
+                (Some(_), Some(count)) => count.to_owned(),
+                _ => anyhow::bail!("unexpected git describe output: {}", description),
+            }
+        }
+    };
+    let last_commit = git(&["log", "-1", "--format=%ct %h"])?;
+    let (timestamp, hash) = last_commit
+        .split_once(' ')
+        .with_context(|| format!("unexpected git log output: {}", last_commit))?;
+    let timestamp = timestamp
+        .parse()
+        .with_context(|| format!("invalid commit time in git log output: {}", last_commit))?;
+    Ok(format!(
+        "{}.{}git{}",
+        count,
+        date(timestamp, "%Y%m%d")?,
+        hash
+    ))
+}
+
+/// Parse a `--build-time`, which is either a Unix timestamp or an ISO 8601 date, optionally with
+/// a time and an offset (UTC if there is none)
+fn parse_build_time(value: &str) -> Result<u32> {
+    let timestamp = if let Ok(timestamp) = value.parse::<i64>() {
+        timestamp
+    } else if let Ok(date_time) = chrono::DateTime::parse_from_rfc3339(value) {
+        date_time.timestamp()
+    } else if let Ok(date_time) = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+    {
+        date_time.and_utc().timestamp()
+    } else if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        date.and_time(chrono::NaiveTime::MIN).and_utc().timestamp()
+    } else {
+        anyhow::bail!(
+            "invalid build time {:?}, expected a Unix timestamp or an ISO 8601 date",
+            value
+        );
+    };
+    // rpm would silently replace a build time in the future with the current time
+    if timestamp > chrono::Utc::now().timestamp() {
+        anyhow::bail!("the build time {:?} is in the future", value);
+    }
+    u32::try_from(timestamp).with_context(|| format!("the build time {:?} is before 1970", value))
+}
+
+/// Store exactly the given digests of the header in the signature header
+fn set_header_digests(pkg: rpm::Package, digests: &[HeaderDigest]) -> Result<rpm::Package> {
+    let header = header::header_bytes(&pkg)?;
+    let mut tags = Vec::new();
+    let mut removed = Vec::new();
+    for digest in [
+        HeaderDigest::Sha1,
+        HeaderDigest::Sha256,
+        HeaderDigest::Sha3_256,
+    ] {
+        let (tag, value) = match digest {
+            HeaderDigest::Sha1 => (
+                rpm::IndexSignatureTag::RPMSIGTAG_SHA1,
+                format!("{:x}", sha1::Sha1::digest(&header)),
+            ),
+            HeaderDigest::Sha256 => (
+                rpm::IndexSignatureTag::RPMSIGTAG_SHA256,
+                format!("{:x}", sha2::Sha256::digest(&header)),
+            ),
+            HeaderDigest::Sha3_256 => (
+                rpm::IndexSignatureTag::RPMSIGTAG_SHA3_256,
+                format!("{:x}", sha3::Sha3_256::digest(&header)),
+            ),
+        };
+        if digests.contains(&digest) {
+            tags.push((tag, header::Value::String(value)));
+        } else {
+            removed.push(tag);
+        }
+    }
+    let pkg = header::remove_signature_tags(pkg, &removed)?;
+    header::set_signature_tags(pkg, &tags)
+}
+
+/// The share of its size a sample of the payload has to keep when compressed for the payload to
+/// be considered incompressible (for `--skip-compression-if-incompressible`)
+const INCOMPRESSIBLE_RATIO: f64 = 0.95;
+
+/// The size of the samples taken at the start and in the middle of each file
+const COMPRESSION_SAMPLE_SIZE: u64 = 128 * 1024;
+
+/// Estimate whether files are already compressed, by compressing samples of them with a fast
+/// compressor and scaling the result by the size of the files
+fn is_incompressible(sources: &[PathBuf]) -> Result<bool> {
+    let mut size = 0.0;
+    let mut compressed_size = 0.0;
+    for source in sources {
+        let mut file =
+            fs::File::open(source).with_context(|| format!("unable to read file {:?}", source))?;
+        let file_size = file.metadata()?.len();
+        let mut sample = Vec::new();
+        for offset in [0, file_size / 2] {
+            file.seek(io::SeekFrom::Start(offset))?;
+            file.by_ref()
+                .take(COMPRESSION_SAMPLE_SIZE)
+                .read_to_end(&mut sample)
+                .with_context(|| format!("unable to read file {:?}", source))?;
+            if file_size <= COMPRESSION_SAMPLE_SIZE {
+                break;
+            }
+        }
+        if sample.is_empty() {
+            continue;
+        }
+        let compressed = zstd::bulk::compress(&sample, 1)?;
+        size += file_size as f64;
+        compressed_size += file_size as f64 * compressed.len() as f64 / sample.len() as f64;
+    }
+    Ok(size > 0.0 && compressed_size >= size * INCOMPRESSIBLE_RATIO)
+}
+
+const MIB: u64 = 1024 * 1024;
+
+/// The `--max-memory` in bytes
+fn max_memory_bytes(max_memory: Option<u64>) -> Result<Option<u64>> {
+    max_memory
+        .map(|max_memory| {
+            max_memory.checked_mul(MIB).with_context(|| {
+                format!(
+                    "invalid --max-memory {}: it's more memory than can be addressed",
+                    max_memory
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Content read up front, kept in memory or spilled to a temporary file, which is removed when
+/// it's dropped
+enum Spooled {
+    Memory(Vec<u8>),
+    File(PathBuf),
+}
+
+impl Drop for Spooled {
+    fn drop(&mut self) {
+        if let Spooled::File(path) = self {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Read stdin, spilling it to a temporary file once it exceeds `limit` bytes
+fn read_stdin(limit: Option<u64>) -> Result<Spooled> {
+    let mut stdin = std::io::stdin().lock();
+    let mut content = Vec::new();
+    let Some(limit) = limit else {
+        stdin.read_to_end(&mut content)?;
+        return Ok(Spooled::Memory(content));
+    };
+    stdin.by_ref().take(limit + 1).read_to_end(&mut content)?;
+    if content.len() as u64 <= limit {
+        return Ok(Spooled::Memory(content));
+    }
+
+    let (path, mut file) = create_temp_file("rpm-builder-stdin")?;
+    // from here on the file is removed when reading fails
+    let spooled = Spooled::File(path.clone());
+    io::Write::write_all(&mut file, &content)?;
+    drop(content);
+    io::copy(&mut stdin, &mut file)
+        .with_context(|| format!("unable to write temporary file {:?}", path))?;
+    tracing::info!(path = %path.display(), "spilled the content read from stdin to a temporary file");
+    Ok(spooled)
+}
+
+/// Create a new temporary file only the current user can read, with a random name so that other
+/// users can't prepare the path, e.g. as a symlink to a file of the user
+fn create_temp_file(prefix: &str) -> Result<(PathBuf, fs::File)> {
+    let mut attempt = 0;
+    loop {
+        // RandomState is seeded randomly for every instance
+        let random = std::hash::BuildHasher::hash_one(
+            &std::collections::hash_map::RandomState::new(),
+            (process::id(), attempt),
+        );
+        let path = std::env::temp_dir().join(format!("{}-{:016x}", prefix, random));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists && attempt < 16 => {
+                attempt += 1;
+            }
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("unable to create temporary file {:?}", path));
+            }
+        }
+    }
+}
+
+/// The window, chain table and hash table sizes (as logarithms) zstd uses for each compression
+/// level, 0 being the default level 3
+const ZSTD_PARAMETERS: [(u32, u32, u32); 23] = [
+    (21, 16, 17),
+    (19, 13, 14),
+    (20, 15, 16),
+    (21, 16, 17),
+    (21, 18, 18),
+    (21, 18, 19),
+    (21, 18, 19),
+    (21, 19, 20),
+    (21, 19, 20),
+    (22, 20, 21),
+    (22, 21, 22),
+    (22, 21, 22),
+    (22, 22, 23),
+    (22, 22, 22),
+    (22, 22, 23),
+    (22, 23, 23),
+    (22, 22, 22),
+    (23, 23, 22),
+    (23, 23, 22),
+    (23, 24, 22),
+    (25, 25, 23),
+    (26, 26, 24),
+    (27, 27, 25),
+];
+
+/// The memory in MiB xz needs to compress with each preset, as documented by xz(1)
+const XZ_MEMORY: [u64; 10] = [3, 9, 17, 32, 48, 94, 94, 186, 370, 674];
+
+/// The approximate memory in MiB a compressor needs at a compression level
+fn compressor_memory(compression: rpm::CompressionWithLevel) -> u64 {
+    match compression {
+        rpm::CompressionWithLevel::None | rpm::CompressionWithLevel::Gzip(_) => 1,
+        // 400 KiB and 8 times the block size of 100 KiB per level
+        rpm::CompressionWithLevel::Bzip2(level) => (400 + 800 * level as u64).div_ceil(1024),
+        rpm::CompressionWithLevel::Xz(level) => XZ_MEMORY[level.min(9) as usize],
+        rpm::CompressionWithLevel::Zstd(level) => {
+            // the negative levels need less than level 1
+            let level = if level < 0 { 1 } else { level.min(22) };
+            let (window, chain, hash) = ZSTD_PARAMETERS[level as usize];
+            ((1 << window) + (4 << chain) + (4 << hash)) / MIB
+        }
+    }
+}
+
+/// Lower the compression level until the compressor needs at most `budget` MiB
+fn limit_compression(
+    mut compression: rpm::CompressionWithLevel,
+    budget: u64,
+) -> rpm::CompressionWithLevel {
+    while compressor_memory(compression) > budget {
+        compression = match compression {
+            rpm::CompressionWithLevel::Xz(level) if level > 0 => {
+                rpm::CompressionWithLevel::Xz(level - 1)
+            }
+            rpm::CompressionWithLevel::Zstd(level) if level > 1 => {
+                rpm::CompressionWithLevel::Zstd(level - 1)
+            }
+            rpm::CompressionWithLevel::Bzip2(level) if level > 1 => {
+                rpm::CompressionWithLevel::Bzip2(level - 1)
+            }
+            _ => break,
+        };
+    }
+    compression
+}
+
+/// The SHA-512 digests of the files of a package, in place of the SHA-256 ones which rpm records.
+/// There are none to set if the package has no files.
+fn sha512_file_digests(pkg: &rpm::Package) -> Result<Option<Vec<String>>> {
+    let mut digests = Vec::new();
+    for file in pkg.files()? {
+        let file = file?;
+        let digest = match file.metadata.digest {
+            Some(_) => format!("{:x}", Sha512::digest(&file.content)),
+            None => String::new(),
+        };
+        digests.push(digest);
+    }
+    Ok(Some(digests).filter(|digests| !digests.is_empty()))
+}
+
+/// The flags of the files of a package, with the ones which their options can't carry added
+fn with_header_file_flags(
+    pkg: &rpm::Package,
+    file_flags: &HashMap<PathBuf, rpm::FileFlags>,
+) -> Result<Vec<u32>> {
+    let entries = pkg.metadata.get_file_entries()?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            let extra = file_flags.get(&entry.path).copied();
+            (entry.flags | extra.unwrap_or(rpm::FileFlags::empty())).bits()
+        })
+        .collect())
+}
+
+/// The modification times of the files of a package according to a `--file-mtime` policy, still
+/// clamped to the source date. There are none to set if the package has no files.
+fn file_mtimes(
+    pkg: &rpm::Package,
+    policy: &str,
+    source_date: Option<u32>,
+) -> Result<Option<Vec<u32>>> {
+    let header = &pkg.metadata.header;
+    let mtimes = if header.entry_is_present(rpm::IndexTag::RPMTAG_FILEMTIMES) {
+        header.get_entry_data_as_u32_array(rpm::IndexTag::RPMTAG_FILEMTIMES)?
+    } else {
+        Vec::new()
+    };
+    let mtimes = match policy.split_once('=') {
+        None if policy == "preserve" => mtimes,
+        None if policy == "now" => {
+            let now = u32::try_from(chrono::Utc::now().timestamp())?;
+            vec![now; mtimes.len()]
+        }
+        Some(("clamp", time)) => {
+            let time =
+                parse_build_time(time).with_context(|| format!("invalid file-mtime {}", policy))?;
+            mtimes.into_iter().map(|mtime| mtime.min(time)).collect()
+        }
+        _ => anyhow::bail!(
+            "invalid file-mtime {}, expected preserve, now or clamp=<time>",
+            policy
+        ),
+    };
+    let clamp = source_date.unwrap_or(u32::MAX);
+    Ok(Some(
+        mtimes.into_iter().map(|mtime| mtime.min(clamp)).collect(),
+    ))
+}
+
+/// Collect the files of every `<source-dir>:<dest-dir>[:<attributes>]` directory (recursively)
+/// given with `flag`, to be added to the package
+pub fn process_dir<F>(
+    dirs: &[String],
+    flag: &'static str,
+    separator: &str,
+    preserve_symlinks: bool,
+    max_depth: Option<u32>,
+    files: &mut Vec<SourceFile>,
+    options_modifier: F,
+) -> Result<()>
+where
+    F: Fn(rpm::FileOptionsBuilder, Option<&Path>) -> rpm::FileOptionsBuilder,
+{
+    for dir in dirs {
+        let (source, dest, attributes) = split_file_argument(dir, separator);
+        let Some(dest) = dest else {
+            anyhow::bail!(
+                "invalid file argument:{} it needs to be of the form <source-path>{}<dest-path>[{}<attributes>]",
+                dir,
+                separator,
+                separator
+            );
+        };
+        let dir = source.as_str();
+        let target = PathBuf::from(dest);
+        let first_file = files.len();
+        add_dir(
+            dir,
+            &target,
+            flag,
+            preserve_symlinks,
+            max_depth,
+            files,
+            &options_modifier,
+        )
+        .with_context(|| format!("error adding dir {}", dir))?;
+        // the attributes apply to every file in the tree
+        let Some(attributes) = attributes else {
+            continue;
+        };
+        for file in &mut files[first_file..] {
+            let file_mode = if file.is_symlink {
+                rpm::FileMode::symbolic_link
+            } else {
+                rpm::FileMode::regular
+            };
+            let options = std::mem::replace(&mut file.options, rpm::FileOptions::new(""));
+            file.options = apply_file_attributes(options, &attributes, file_mode)
+                .with_context(|| format!("invalid attributes for dir {}", dir))?;
+            file.file_flags |= header_file_flags(&attributes);
+        }
+    }
+    Ok(())
+}
+
+/// The names of license files, which can be followed by `-<suffix>`, e.g. `LICENSE-MIT`
+const LICENSE_FILE_NAMES: [&str; 5] = ["LICENSE", "LICENCE", "COPYING", "COPYRIGHT", "NOTICE"];
+
+/// Whether a file is named like a license file, e.g. `LICENSE`, `LICENSE-MIT`, `COPYING.LIB` or
+/// `license.txt`
+fn is_license_file(file_name: &str) -> bool {
+    let name = file_name.to_ascii_uppercase();
+    let name = [".TXT", ".MD", ".RST"]
+        .iter()
+        .find_map(|extension| name.strip_suffix(extension))
+        .unwrap_or(&name);
+    LICENSE_FILE_NAMES
+        .iter()
+        .any(|license| match name.strip_prefix(license) {
+            Some(rest) => {
+                rest.is_empty()
+                    || rest.starts_with('-')
+                    || (*license == "COPYING" && rest.starts_with('.'))
+            }
+            None => false,
+        })
+}
+
+/// Turn the license files in the directories with the destinations `roots` into license files
+/// below `license_dir`, keeping their path inside the directory, like `--license-file` adds them.
+/// Files which already are below `/usr/share/licenses` keep their destination.
+fn move_license_files(
+    files: &mut [SourceFile],
+    roots: &[String],
+    license_dir: &str,
+    defaults: &FileDefaults,
+) {
+    for file in files.iter_mut() {
+        if file.is_symlink
+            || !matches!(file.flag, "--dir" | "--doc-dir" | "--config-dir")
+            || !is_license_file(file.dest.rsplit('/').next().unwrap_or_default())
+        {
+            continue;
+        }
+        let dest = if file.dest.starts_with("/usr/share/licenses/") {
+            file.dest.clone()
+        } else {
+            // the innermost directory the file is in
+            let relative = roots
+                .iter()
+                .map(|root| root.trim_end_matches('/'))
+                .filter_map(|root| file.dest.strip_prefix(root)?.strip_prefix('/'))
+                .min_by_key(|relative| relative.len())
+                .unwrap_or(file.dest.trim_start_matches('/'));
+            format!("{}/{}", license_dir, relative)
+        };
+        tracing::info!(source = %file.source.display(), dest, "detected license file");
+        let options = defaults.file(rpm::FileOptions::new(&dest), Some(&file.source));
+        file.options = options.is_license();
+        file.kind = "license file";
+        file.dest = dest;
+    }
+}
+
+fn add_dir<P: AsRef<Path>, F>(
+    full_path: P,
+    target_path: &Path,
+    flag: &'static str,
+    preserve_symlinks: bool,
+    max_depth: Option<u32>,
+    files: &mut Vec<SourceFile>,
+    options_modifier: &F,
+) -> Result<()>
+where
+    F: Fn(rpm::FileOptionsBuilder, Option<&Path>) -> rpm::FileOptionsBuilder,
+{
+    // sort the entries so that the result doesn't depend on the order of the directory listing
+    let mut entries = std::fs::read_dir(full_path)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let metadata = entry.metadata()?;
+        let mut new_target = target_path.to_path_buf();
+
+        if preserve_symlinks && metadata.file_type().is_symlink() {
+            new_target.push(entry.file_name());
+            let dest = new_target.to_string_lossy().into_owned();
+            files.push(SourceFile {
+                source: std::fs::read_link(entry.path())?,
+                is_symlink: true,
+                options: options_modifier(rpm::FileOptions::new(&dest), None),
+                dest,
+                flag,
+                kind: "symlink",
+                file_flags: rpm::FileFlags::empty(),
+            });
+            continue;
+        }
+
+        // symlinks are followed, but keep their own name
+        let source = entry.path();
+        let metadata = std::fs::metadata(&source)?;
+        new_target.push(entry.file_name());
+
+        if metadata.is_dir() {
+            if max_depth == Some(1) {
+                tracing::debug!(
+                    source = %source.display(),
+                    "skipping directory below the maximum depth"
+                );
+                continue;
+            }
+            add_dir(
+                &source,
+                &new_target,
+                flag,
+                preserve_symlinks,
+                max_depth.map(|depth| depth - 1),
+                files,
+                options_modifier,
+            )?;
+        } else {
+            let dest = new_target.to_string_lossy().into_owned();
+            files.push(SourceFile {
+                options: options_modifier(rpm::FileOptions::new(&dest), Some(&source)),
+                source,
+                is_symlink: false,
+                dest,
+                flag,
+                kind: "file",
+                file_flags: rpm::FileFlags::empty(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Collect everything in a buildroot, with the paths in it as destinations. Directories are
+/// collected unless they are standard ones, and symlinks are kept.
+fn process_buildroot(
+    root: &Path,
+    raw_overrides: &[String],
+    separator: &str,
+    defaults: &FileDefaults,
+    files: &mut Vec<SourceFile>,
+    dirs: &mut Vec<(String, &'static str, rpm::FileOptionsBuilder)>,
+) -> Result<()> {
+    let overrides = raw_overrides
+        .iter()
+        .map(|raw| {
+            let (pattern, attributes) = split_last(raw, separator).with_context(|| {
+                format!(
+                    "invalid buildroot-attr {}, expected <pattern>:<attributes>",
+                    raw
+                )
+            })?;
+            let pattern = glob::Pattern::new(&pattern)
+                .with_context(|| format!("invalid pattern in buildroot-attr {}", raw))?;
+            Ok((pattern, attributes))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // like in paths, `*` doesn't match `/` (but `**` does)
+    let match_options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(&dir)?.collect::<Result<Vec<_>, _>>()?;
+        // sort the entries so that the result doesn't depend on the order of the directory listing
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let path = entry.path();
+            let relative = path.strip_prefix(root)?;
+            let dest = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .fold(String::new(), |dest, name| dest + "/" + &name);
+            let mut attributes = Vec::new();
+            let mut flags = Vec::new();
+            for (pattern, raw_attributes) in &overrides {
+                if !pattern.matches_with(&dest, match_options) {
+                    continue;
+                }
+                for attribute in raw_attributes.split(',') {
+                    match attribute {
+                        "config" | "doc" | "license" | "readme" | "exclude" => {
+                            flags.push(attribute)
+                        }
+                        _ => attributes.push(attribute),
+                    }
+                }
+            }
+            if flags.contains(&"exclude") {
+                continue;
+            }
+            let apply = |options, file_mode| {
+                let mut options = apply_file_attributes(options, &attributes.join(","), file_mode)
+                    .with_context(|| format!("invalid buildroot-attr for {}", dest))?;
+                for flag in &flags {
+                    options = match *flag {
+                        "config" => options.is_config(),
+                        "doc" => options.is_doc(),
+                        "license" => options.is_license(),
+                        _ => options.is_readme(),
+                    };
+                }
+                anyhow::Ok(options)
+            };
+
+            // only files get the flags which are set in the header, like %config(missingok)
+            let file_flags = header_file_flags(&attributes.join(","));
+
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                if !STANDARD_DIRS.contains(&dest.as_str()) {
+                    let options = apply(defaults.dir(&dest), rpm::FileMode::dir)?;
+                    dirs.push((dest, "--buildroot", options));
+                }
+                pending.push(path);
+            } else if file_type.is_symlink() {
+                let options = apply(defaults.options(&dest), rpm::FileMode::symbolic_link)?;
+                files.push(SourceFile {
+                    source: fs::read_link(&path)?,
+                    is_symlink: true,
+                    dest,
+                    flag: "--buildroot",
+                    kind: "symlink",
+                    options,
+                    file_flags,
+                });
+            } else {
+                let options = defaults.file(rpm::FileOptions::new(&dest), Some(&path));
+                let options = apply(options, rpm::FileMode::regular)?;
+                files.push(SourceFile {
+                    source: path,
+                    is_symlink: false,
+                    dest,
+                    flag: "--buildroot",
+                    kind: "file",
+                    options,
+                    file_flags,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Keep one of the files from the filesystem with the same destination, as chosen by the
+/// policy. Adding the same source more than once isn't a conflict.
+fn resolve_conflicts(files: Vec<SourceFile>, policy: &ConflictPolicy) -> Result<Vec<SourceFile>> {
+    let mut resolved: Vec<SourceFile> = Vec::with_capacity(files.len());
+    let mut positions = HashMap::new();
+    for file in files {
+        let Some(&index) = positions.get(Path::new(&file.dest)) else {
+            positions.insert(PathBuf::from(&file.dest), resolved.len());
+            resolved.push(file);
+            continue;
+        };
+        let existing = &resolved[index];
+        if existing.source == file.source && existing.is_symlink == file.is_symlink {
+            continue;
+        }
+        let describe = |f: &SourceFile| format!("{} {}", f.flag, f.source.display());
+        match policy {
+            ConflictPolicy::Error => anyhow::bail!(
+                "{} and {} have the same destination {}, use --on-conflict first-wins or last-wins to keep one of them",
+                describe(existing),
+                describe(&file),
+                file.dest
+            ),
+            ConflictPolicy::FirstWins => {
+                tracing::warn!(
+                    dest = file.dest,
+                    kept = describe(existing),
+                    skipped = describe(&file),
+                    "conflicting destination"
+                );
+            }
+            ConflictPolicy::LastWins => {
+                tracing::warn!(
+                    dest = file.dest,
+                    kept = describe(&file),
+                    skipped = describe(existing),
+                    "conflicting destination"
+                );
+                resolved[index] = file;
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+fn add_symlink(
+    builder: rpm::PackageBuilder,
+    options: rpm::FileOptionsBuilder,
+    target: &str,
+) -> Result<rpm::PackageBuilder> {
+    let options = options
+        .symlink(target)
+        .mode(rpm::FileMode::symbolic_link(0o777));
+    // like in a cpio archive created by rpmbuild, the content of a symlink is its target
+    Ok(builder.with_file_contents(target, options)?)
+}
+
+/// Parse `<source-path>:<dest-path>[:<attributes>]` file arguments, expanding globs, into the
+/// source, destination, options and header-only flags of every file
+pub fn parse_file_options<F>(
+    raw_files: &[String],
+    separator: &str,
+    allow_empty_glob: bool,
+    options_modifier: F,
+) -> Result<Vec<(PathBuf, String, rpm::FileOptionsBuilder, rpm::FileFlags)>>
+where
+    F: Fn(rpm::FileOptionsBuilder, Option<&Path>) -> rpm::FileOptionsBuilder,
+{
+    let mut files = Vec::new();
+    for input in raw_files {
+        let (source, dest, attributes) = split_file_argument(input, separator);
+        let Some(dest) = dest else {
+            anyhow::bail!(
+                "invalid file argument:{} it needs to be of the form <source-path>{sep}<dest-path>[{sep}<attributes>]",
+                input,
+                sep = separator
+            );
+        };
+        let (source, dest) = (source.as_str(), dest.as_str());
+
+        let is_glob = is_glob(source);
+        let sources = if is_glob {
+            let matches =
+                expand_glob(source).with_context(|| format!("invalid file argument {}", input))?;
+            if matches.is_empty() && !allow_empty_glob {
+                anyhow::bail!(
+                    "glob pattern {} in file argument {} did not match any files",
+                    source,
+                    input
+                );
+            }
+            matches
+        } else {
+            vec![PathBuf::from(source)]
+        };
+
+        for source in sources {
+            let dest = if is_glob || dest.ends_with('/') {
+                let file_name = source.file_name().context("path does not have filename")?;
+                format!(
+                    "{}/{}",
+                    dest.trim_end_matches('/'),
+                    file_name.to_string_lossy()
+                )
+            } else {
+                dest.to_owned()
+            };
+            let options = options_modifier(rpm::FileOptions::new(&dest), Some(&source));
+            let options = match &attributes {
+                Some(attributes) => {
+                    apply_file_attributes(options, attributes, rpm::FileMode::regular)
+                        .with_context(|| format!("invalid file argument {}", input))?
+                }
+                None => options,
+            };
+            let file_flags = header_file_flags(attributes.as_deref().unwrap_or_default());
+            files.push((source, dest, options, file_flags));
+        }
+    }
+    Ok(files)
+}
+
+/// Split a `<source-path>:<dest-path>[:<attributes>]` argument on the separator, returning the
+/// source, the destination and the attributes. With more than two parts, the last one is the
+/// attributes unless it starts with `/` or `.` like a destination, and the source is everything
+/// before the destination, so it may contain the separator as in `C:\build\foo.exe`. A
+/// separator preceded by a backslash is a literal one.
+fn split_file_argument(input: &str, separator: &str) -> (String, Option<String>, Option<String>) {
+    let mut parts = split_escaped(input, separator);
+    let attributes = match parts.len() {
+        0..=2 => None,
+        _ if is_dest(parts.last().unwrap()) => None,
+        _ => parts.pop(),
+    };
+    let dest = match parts.len() {
+        0 | 1 => None,
+        _ => parts.pop(),
+    };
+    (parts.join(separator), dest, attributes)
+}
+
+/// Split a `<dest-path>[:<attributes>]` argument on the separator like [`split_file_argument`]:
+/// the attributes are after the last separator, unless that part starts with `/` or `.`
+fn split_dest_argument(input: &str, separator: &str) -> (String, Option<String>) {
+    let mut parts = split_escaped(input, separator);
+    let attributes = match parts.last() {
+        Some(last) if parts.len() > 1 && !is_dest(last) => parts.pop(),
+        _ => None,
+    };
+    (parts.join(separator), attributes)
+}
+
+/// Split a `<source>[:<dest-prefix>]` argument on the separator like [`split_file_argument`]:
+/// the prefix is after the last separator if it starts with `/` or `.`, otherwise the separator
+/// is part of the source as in `C:\build\root.tar`. The prefix defaults to `/`.
+fn split_prefix_argument(input: &str, separator: &str) -> (String, String) {
+    let mut parts = split_escaped(input, separator);
+    let prefix = match parts.last() {
+        Some(last) if parts.len() > 1 && is_dest(last) => parts.pop(),
+        _ => None,
+    };
+    (
+        parts.join(separator),
+        prefix.unwrap_or_else(|| "/".to_owned()),
+    )
+}
+
+/// Split an argument into its parts on the separator, keeping a separator preceded by a
+/// backslash as a literal one
+pub fn split_escaped(input: &str, separator: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut rest = input;
+    while !rest.is_empty() {
+        if let Some(tail) = rest
+            .strip_prefix('\\')
+            .and_then(|r| r.strip_prefix(separator))
+        {
+            parts.last_mut().unwrap().push_str(separator);
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix(separator) {
+            parts.push(String::new());
+            rest = tail;
+        } else {
+            let c = rest.chars().next().unwrap();
+            parts.last_mut().unwrap().push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    parts
+}
+
+/// Whether a part of an argument is a path of the package rather than attributes
+fn is_dest(part: &str) -> bool {
+    part.starts_with('/') || part.starts_with('.')
+}
+
+/// Split an argument of two parts at the last separator, such as `<src>:<unit-name>`, where a
+/// separator preceded by a backslash is a literal one
+pub fn split_last(input: &str, separator: &str) -> Option<(String, String)> {
+    let mut parts = split_escaped(input, separator);
+    let last = parts.pop().filter(|_| !parts.is_empty())?;
+    Some((parts.join(separator), last))
+}
+
+/// Split a `<link-path>:<target>` argument at the last separator
+fn parse_symlink(input: &str, separator: &str) -> Result<(String, String)> {
+    match split_last(input, separator) {
+        Some((link, target)) if !link.is_empty() && !target.is_empty() => Ok((link, target)),
+        _ => anyhow::bail!(
+            "invalid symlink argument:{} it needs to be of the form <link-path>:<target>",
+            input
+        ),
+    }
+}
+
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Expand a glob pattern into the (sorted) list of regular files it matches
+fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut matches = Vec::new();
+    for entry in glob::glob(pattern)? {
+        let path = entry?;
+        if !path.is_dir() {
+            matches.push(path);
+        }
+    }
+    Ok(matches)
+}
+
+/// Check the name of a user or group owning files. rpm records owners by name, so a numeric id is
+/// kept as it is, except for 0, which is always root.
+pub fn parse_owner(value: &str) -> Result<String> {
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        let id: u32 = value
+            .parse()
+            .with_context(|| format!("invalid owner id {}", value))?;
+        if id == 0 {
+            return Ok("root".to_owned());
+        }
+        tracing::warn!(
+            owner = value,
+            "rpm looks file owners up by name, the numeric owner only resolves on systems with a user or group of that name"
+        );
+        return Ok(id.to_string());
+    }
+    let valid = value.chars().enumerate().all(|(i, c)| {
+        c.is_ascii_alphanumeric() || c == '_' || (i > 0 && matches!(c, '.' | '-' | '$'))
+    });
+    if value.is_empty() || !valid {
+        anyhow::bail!(
+            "invalid owner {:?}, expected a user or group name or a numeric id",
+            value
+        );
+    }
+    Ok(value.to_owned())
+}
+
+/// Parse octal permissions such as 0644
+fn parse_permissions(value: &str) -> Result<u16> {
+    u16::from_str_radix(value, 8)
+        .ok()
+        .filter(|p| *p <= 0o7777)
+        .with_context(|| {
+            format!(
+                "invalid file mode '{}', expected octal permissions such as 0644",
+                value
+            )
+        })
+}
+
+/// Apply a comma-separated list of file attributes, e.g. `mode=0640,user=nginx,group=nginx` or
+/// `noreplace`
+///
+/// `file_mode` creates the mode of the right file type from the permissions given by `mode`.
+fn apply_file_attributes(
+    mut options: rpm::FileOptionsBuilder,
+    attributes: &str,
+    file_mode: fn(u16) -> rpm::FileMode,
+) -> Result<rpm::FileOptionsBuilder> {
+    let mut parsed: Vec<(&str, String)> = Vec::new();
+    for attribute in attributes.split(',').filter(|a| !a.is_empty()) {
+        match parsed.last_mut() {
+            // capabilities are comma-separated too, e.g. caps=cap_net_raw,cap_net_admin+ep
+            Some(("caps", caps)) if attribute.starts_with("cap_") => {
+                caps.push(',');
+                caps.push_str(attribute);
+            }
+            // and so are the checks of verify=not(md5,size,mtime)
+            Some(("verify", checks)) if checks.contains('(') && !checks.contains(')') => {
+                checks.push(',');
+                checks.push_str(attribute);
+            }
+            _ => {
+                let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+                parsed.push((key, value.to_owned()));
+            }
+        }
+    }
+
+    for (key, value) in parsed {
+        // attributes which are flags rather than key=value pairs
+        if key == "noreplace" && value.is_empty() {
+            options = options.is_config_noreplace();
+            continue;
+        }
+        // the missingok flag itself is set in the header, see header_file_flags
+        if key == "missingok" && value.is_empty() {
+            options = options.is_config();
+            continue;
+        }
+        if value.is_empty() {
+            anyhow::bail!("file attribute '{}' requires a value", key);
+        }
+        options = match key {
+            "mode" => options.mode(file_mode(parse_permissions(&value)?)),
+            "user" => options.user(parse_owner(&value)?),
+            "group" => options.group(parse_owner(&value)?),
+            "caps" => options
+                .caps(value.as_str())
+                .with_context(|| format!("invalid file capabilities '{}'", value))?,
+            "verify" => options.verify(parse_verify_flags(&value)?),
+            "flags" => match value.as_str() {
+                "config" => options.is_config(),
+                "config(noreplace)" => options.is_config_noreplace(),
+                "config(missingok)" => options.is_config(),
+                "doc" => options.is_doc(),
+                "license" => options.is_license(),
+                "readme" => options.is_readme(),
+                "ghost" => options.is_ghost(),
+                _ => anyhow::bail!(
+                    "unknown file flag '{}', expected config, config(noreplace), config(missingok), doc, license, readme or ghost",
+                    value
+                ),
+            },
+            _ => anyhow::bail!("unknown file attribute '{}'", key),
+        };
+    }
+    Ok(options)
+}
+
+/// The flags of the file attributes which `rpm::FileOptionsBuilder` can't set, so that they are
+/// set in the header once the package is built: `missingok` and `flags=config(missingok)` mark
+/// a config file which may be missing, like `%config(missingok)`
+fn header_file_flags(attributes: &str) -> rpm::FileFlags {
+    let missingok = attributes
+        .split(',')
+        .any(|attribute| attribute == "missingok" || attribute == "flags=config(missingok)");
+    if missingok {
+        rpm::FileFlags::MISSINGOK
+    } else {
+        rpm::FileFlags::empty()
+    }
+}
+
+/// The checks of `rpm -V` which the verify attribute can select, with their `%verify` names
+const VERIFY_CHECKS: [(&str, rpm::FileVerifyFlags); 12] = [
+    ("md5", rpm::FileVerifyFlags::MD5),
+    ("filedigest", rpm::FileVerifyFlags::FILEDIGEST),
+    ("size", rpm::FileVerifyFlags::FILESIZE),
+    ("link", rpm::FileVerifyFlags::LINKTO),
+    ("linkto", rpm::FileVerifyFlags::LINKTO),
+    ("user", rpm::FileVerifyFlags::USER),
+    ("owner", rpm::FileVerifyFlags::USER),
+    ("group", rpm::FileVerifyFlags::GROUP),
+    ("mtime", rpm::FileVerifyFlags::MTIME),
+    ("mode", rpm::FileVerifyFlags::MODE),
+    ("rdev", rpm::FileVerifyFlags::RDEV),
+    ("caps", rpm::FileVerifyFlags::CAPS),
+];
+
+/// Parse the value of the verify attribute: `not(md5,size,mtime)` checks everything but the
+/// listed properties, like `%verify(not md5 size mtime)`, and `(mode,user)` or `mode` only the
+/// listed ones
+fn parse_verify_flags(value: &str) -> Result<rpm::FileVerifyFlags> {
+    let (negated, list) = match value.strip_prefix("not") {
+        Some(list) if list.starts_with('(') => (true, list),
+        _ => (false, value),
+    };
+    let list = match list.strip_prefix('(') {
+        Some(list) => list
+            .strip_suffix(')')
+            .with_context(|| format!("unterminated verify attribute '{}'", value))?,
+        None => list,
+    };
+    let mut flags = rpm::FileVerifyFlags::empty();
+    for check in list.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+        let (_, flag) = VERIFY_CHECKS
+            .iter()
+            .find(|(name, _)| *name == check)
+            .with_context(|| {
+                format!(
+                    "unknown verify check '{}', expected md5, size, link, user, group, mtime, mode, rdev or caps",
+                    check
+                )
+            })?;
+        flags |= *flag;
+    }
+    Ok(if negated {
+        rpm::FileVerifyFlags::all() - flags
+    } else {
+        flags
+    })
+}
+
+/// The qualifiers of requirements, e.g. `post` in `Requires(post)`
+const REQUIREMENT_QUALIFIERS: [(&str, rpm::DependencyFlags); 12] = [
+    ("pre", rpm::DependencyFlags::SCRIPT_PRE),
+    ("post", rpm::DependencyFlags::SCRIPT_POST),
+    ("preun", rpm::DependencyFlags::SCRIPT_PREUN),
+    ("postun", rpm::DependencyFlags::SCRIPT_POSTUN),
+    ("pretrans", rpm::DependencyFlags::PRETRANS),
+    ("posttrans", rpm::DependencyFlags::POSTTRANS),
+    ("preuntrans", rpm::DependencyFlags::PREUNTRANS),
+    ("postuntrans", rpm::DependencyFlags::POSTUNTRANS),
+    ("verify", rpm::DependencyFlags::SCRIPT_VERIFY),
+    ("interp", rpm::DependencyFlags::INTERP),
+    ("meta", rpm::DependencyFlags::META),
+    ("prereq", rpm::DependencyFlags::PREREQ),
+];
+
+/// Parse a requirement, a dependency which can be followed by qualifiers in brackets, e.g.
+/// `systemd [post,preun]` for `Requires(post,preun): systemd`
+pub fn parse_requirement(line: &str) -> Result<rpm::Dependency> {
+    let qualified = line
+        .trim_end()
+        .strip_suffix(']')
+        .and_then(|line| line.rsplit_once('['));
+    let Some((dependency, qualifiers)) = qualified else {
+        return parse_dependency(line);
+    };
+    let mut dependency = parse_dependency(dependency.trim_end())?;
+    for qualifier in qualifiers.split(',').map(str::trim) {
+        let (_, flag) = REQUIREMENT_QUALIFIERS
+            .iter()
+            .find(|(name, _)| *name == qualifier)
+            .with_context(|| {
+                format!("unknown qualifier {:?} in requirement {}", qualifier, line)
+            })?;
+        dependency.flags |= *flag;
+    }
+    Ok(dependency)
+}
+
+/// Whether a dependency uses the rich (boolean) syntax, e.g. `(foo >= 1.0 or bar)`
+pub fn is_rich_dependency(line: &str) -> bool {
+    line.trim_start().starts_with('(')
+}
+
+/// Check that the parentheses of a rich dependency are balanced, and that the whole expression is
+/// enclosed by them
+fn parse_rich_dependency(line: &str) -> Result<rpm::Dependency> {
+    let line = line.trim();
+    let mut depth = 0;
+    for (index, c) in line.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => anyhow::bail!("unbalanced parentheses in dependency {}", line),
+            ')' => depth -= 1,
+            _ if depth == 0 => anyhow::bail!(
+                "unexpected '{}' after the end of rich dependency {}",
+                &line[index..],
+                line
+            ),
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        anyhow::bail!("unbalanced parentheses in dependency {}", line);
+    }
+    if line[1..line.len() - 1].trim().is_empty() {
+        anyhow::bail!("empty rich dependency {}", line);
+    }
+    Ok(rpm::Dependency::any(line))
+}
+
+fn compile_filters(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).with_context(|| format!("invalid dependency filter {}", pattern))
+        })
+        .collect()
+}
+
+/// Check a name, version or release of the package against the rules of rpmbuild: they may only
+/// contain letters, digits and a few special characters, and start with a letter, a digit, `_`
+/// or `%`
+fn check_nevr_part(part: &str, value: &str) -> Result<()> {
+    let special = match part {
+        "name" => "._+-%{}",
+        _ => "._+%{}~^",
+    };
+    let invalid = value
+        .char_indices()
+        .find(|(_, c)| !c.is_ascii_alphanumeric() && !special.contains(*c));
+    if value.is_empty() {
+        anyhow::bail!("the {} of the package is empty", part);
+    } else if !value.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '%') {
+        anyhow::bail!(
+            "invalid {} {:?}: it has to start with a letter, a digit, '_' or '%'",
+            part,
+            value
+        );
+    } else if let Some((index, c)) = invalid {
+        let hint = match (part, c) {
+            ("version", '-') => ", use '~' for a pre-release (or --sanitize-version)",
+            ("release", '-') => ", the release is separated from the version by rpm",
+            _ => "",
+        };
+        anyhow::bail!(
+            "invalid {} {:?}: {:?} at position {} isn't allowed{}",
+            part,
+            value,
+            c,
+            index + 1,
+            hint
+        );
+    } else if value.contains("..") {
+        anyhow::bail!("invalid {} {:?}: it contains \"..\"", part, value);
+    }
+    Ok(())
+}
+
+/// Turn a version into a valid one for rpm for `--sanitize-version`: a leading `v` is dropped, a
+/// SemVer pre-release introduced by `-` becomes a `~` one, which sorts before the release
+/// itself, and any further `-` is replaced by `.`
+fn sanitize_version(version: &str) -> String {
+    let version = match version.strip_prefix(['v', 'V']) {
+        Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => rest,
+        _ => version,
+    };
+    match version.split_once('-') {
+        Some((release, pre_release)) => format!("{}~{}", release, pre_release.replace('-', ".")),
+        None => version.to_owned(),
+    }
+}
+
+/// Check that a version is a valid `[epoch:]version[-release]` string
+pub fn validate_evr(evr: &str) -> Result<()> {
+    if evr.contains(char::is_whitespace) {
+        anyhow::bail!("'{}' contains whitespace", evr);
+    }
+    let (epoch, version, release) = rpm::Evr::parse_values(evr);
+    if !epoch.chars().all(|c| c.is_ascii_digit()) || (evr.contains(':') && epoch.is_empty()) {
+        anyhow::bail!("the epoch of '{}' needs to be a number", evr);
+    }
+    if version.is_empty() || version.contains(':') {
+        anyhow::bail!("'{}' has an invalid version", evr);
+    }
+    if (evr.contains('-') && release.is_empty()) || release.contains(['-', ':']) {
+        anyhow::bail!("'{}' has an invalid release", evr);
+    }
+    Ok(())
+}
+
+/// Parse a dependency of the form `<name> [>|>=|=|<=|< version]`, or a rich dependency enclosed
+/// in parentheses. Names can have (nested) qualifiers in parentheses, which may contain anything
+/// but unbalanced parentheses, e.g. `perl(Foo::Bar)`, `libc.so.6()(64bit)` or
+/// `bundled(golang(github.com/foo/bar))`, and file names can contain spaces
+pub fn parse_dependency(line: &str) -> Result<rpm::Dependency> {
+    if is_rich_dependency(line) {
+        return parse_rich_dependency(line);
+    }
+
+    // the name ends at the first comparison operator outside of parentheses
+    let line = line.trim();
+    let mut depth = 0;
+    let mut name_end = line.len();
+    let mut first_space = None;
+    for (index, c) in line.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => anyhow::bail!("unbalanced parentheses in dependency {}", line),
+            ')' => depth -= 1,
+            '<' | '>' | '=' if depth == 0 => {
+                name_end = index;
+                break;
+            }
+            c if c.is_whitespace() && depth == 0 => {
+                first_space.get_or_insert(index);
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        anyhow::bail!("unbalanced parentheses in dependency {}", line);
+    }
+    let name = line[..name_end].trim_end();
+    if !name.starts_with(|c: char| c.is_alphanumeric() || c == '_' || c == '/') {
+        anyhow::bail!(
+            "invalid dependency {}, the name has to start with a letter, a digit, '_' or '/'",
+            line
+        );
+    }
+    if !name.starts_with('/') && first_space.is_some_and(|index| index < name.len()) {
+        anyhow::bail!(
+            "invalid dependency {}, only file names can contain spaces outside of parentheses",
+            line
+        );
+    }
+
+    let condition = &line[name_end..];
+    if condition.is_empty() {
+        return Ok(rpm::Dependency::any(name));
+    }
+    let operator = if condition.starts_with(">=") || condition.starts_with("<=") {
+        &condition[..2]
+    } else {
+        &condition[..1]
+    };
+    let version = condition[operator.len()..].trim();
+    validate_evr(version).with_context(|| format!("invalid version in dependency {}", line))?;
+    let dependency = match operator {
+        "=" => rpm::Dependency::eq(name, version),
+        "<" => rpm::Dependency::less(name, version),
+        "<=" => rpm::Dependency::less_eq(name, version),
+        ">=" => rpm::Dependency::greater_eq(name, version),
+        _ => rpm::Dependency::greater(name, version),
+    };
+    Ok(dependency)
+}
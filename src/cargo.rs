@@ -0,0 +1,135 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The parts of a `Cargo.toml` which are used to describe an rpm
+#[derive(Debug)]
+pub struct CargoPackage {
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+    pub license: Option<String>,
+    pub url: Option<String>,
+    /// The names of the binaries built by the package
+    pub bins: Vec<String>,
+    /// The directory containing the `Cargo.toml`
+    pub root: PathBuf,
+}
+
+#[derive(Deserialize, Debug)]
+struct CargoToml {
+    package: Option<PackageSection>,
+    #[serde(default)]
+    bin: Vec<BinSection>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PackageSection {
+    name: String,
+    version: Option<toml::Value>,
+    description: Option<toml::Value>,
+    license: Option<toml::Value>,
+    homepage: Option<toml::Value>,
+    repository: Option<toml::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BinSection {
+    name: Option<String>,
+}
+
+impl CargoPackage {
+    /// Load the package from a `Cargo.toml`, or from the `Cargo.toml` inside of a directory
+    pub fn load(path: &Path) -> Result<CargoPackage> {
+        let path = if path.is_dir() {
+            path.join("Cargo.toml")
+        } else {
+            path.to_path_buf()
+        };
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("error reading cargo manifest {:?}", path))?;
+        let cargo_toml: CargoToml = toml::from_str(&content)
+            .with_context(|| format!("error parsing cargo manifest {:?}", path))?;
+        let package = cargo_toml
+            .package
+            .with_context(|| format!("cargo manifest {:?} has no [package] section", path))?;
+
+        let field = |key: &str, value: Option<toml::Value>| -> Result<Option<String>> {
+            match value {
+                Some(toml::Value::String(s)) => Ok(Some(s)),
+                // e.g. `version.workspace = true`
+                Some(_) => anyhow::bail!(
+                    "the '{}' of cargo manifest {:?} is inherited from the workspace, which isn't supported",
+                    key,
+                    path
+                ),
+                None => Ok(None),
+            }
+        };
+
+        let mut bins: Vec<String> = cargo_toml.bin.into_iter().filter_map(|b| b.name).collect();
+        if bins.is_empty() {
+            bins.push(package.name.clone());
+        }
+
+        Ok(CargoPackage {
+            version: field("version", package.version)?.unwrap_or_else(|| "0.0.0".to_owned()),
+            description: field("description", package.description)?,
+            license: field("license", package.license)?,
+            url: match field("homepage", package.homepage)? {
+                Some(homepage) => Some(homepage),
+                None => field("repository", package.repository)?,
+            },
+            name: package.name,
+            bins,
+            root: path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| PathBuf::from(".")),
+        })
+    }
+
+    /// Translate the package into the equivalent command line arguments (without the name). With
+    /// `with_bins`, the release builds of the binaries are added as executables in `/usr/bin`.
+    pub fn to_args(&self, with_bins: bool) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![format!("--version={}", self.version).into()];
+        if let Some(description) = &self.description {
+            // cargo descriptions are usually a single sentence, which fits the summary better
+            args.push(format!("--summary={}", description.trim()).into());
+        }
+        if let Some(license) = &self.license {
+            args.push(format!("--license={}", license).into());
+        }
+        if let Some(url) = &self.url {
+            args.push(format!("--url={}", url).into());
+        }
+
+        if with_bins {
+            let target_dir = std::env::var_os("CARGO_TARGET_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| self.root.join("target"));
+            for bin in &self.bins {
+                let source = target_dir.join("release").join(bin);
+                args.push(format!("--exec-file={}:/usr/bin/{}", source.display(), bin).into());
+            }
+            args.push(format!("--arch={}", host_arch()).into());
+        }
+
+        args
+    }
+}
+
+/// The rpm name of the architecture rpm-builder was built for, which binaries built by cargo
+/// on the same host are for as well
+fn host_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86" => "i686",
+        "arm" => "armv7hl",
+        "powerpc64" if cfg!(target_endian = "little") => "ppc64le",
+        "powerpc64" => "ppc64",
+        arch => arch,
+    }
+}
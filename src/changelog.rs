@@ -0,0 +1,155 @@
+use anyhow::{Context, Result};
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// A single changelog entry. Unless it comes from git, the timestamp is midnight (UTC) of the day
+/// of the entry.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub name: String,
+    pub content: String,
+    pub timestamp: i64,
+}
+
+/// Parse a changelog entry of the form `<author>:<content>:<yyyy-mm-dd>`. The date is split off
+/// at the last `:` and the author at the first one, so the content may contain `:` (e.g. in URLs
+/// or times). `\:` is a literal `:`, for authors which contain one.
+pub fn parse_entry(raw_entry: &str) -> Result<Entry> {
+    let form_error = || {
+        format!(
+            "invalid changelog entry {} it needs to be of the form <author>:<content>:<yyyy-mm-dd>",
+            raw_entry
+        )
+    };
+    let (rest, raw_date) = raw_entry.rsplit_once(':').with_context(form_error)?;
+    let (name, content) = split_author(rest).with_context(form_error)?;
+    let date = chrono::NaiveDate::parse_from_str(raw_date, "%Y-%m-%d").with_context(|| {
+        format!(
+            "invalid date {:?} in changelog entry {}",
+            raw_date, raw_entry
+        )
+    })?;
+
+    Ok(Entry {
+        name,
+        content: content.replace("\\:", ":"),
+        timestamp: date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp(),
+    })
+}
+
+/// Split the author off at the first `:` which isn't escaped
+fn split_author(raw: &str) -> Option<(String, &str)> {
+    let mut author = String::new();
+    let mut chars = raw.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|(_, next)| *next == ':') => {
+                author.push(':');
+                chars.next();
+            }
+            ':' => return Some((author, &raw[index + 1..])),
+            _ => author.push(c),
+        }
+    }
+    None
+}
+
+/// Parse a changelog file in the format used by the `%changelog` section of spec files:
+///
+/// ```text
+/// * Mon Jan 01 2024 Jane Doe <jane@example.com> - 1.0-1
+/// - Fixed a bug
+/// - Added a feature
+/// ```
+pub fn parse_file(path: &Path) -> Result<Vec<Entry>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("error reading changelog file {:?}", path))?;
+    parse(&content).with_context(|| format!("invalid changelog file {:?}", path))
+}
+
+/// Parse the content of a changelog file, or of the `%changelog` section of a spec file
+pub fn parse(content: &str) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if let Some(header) = line.strip_prefix('*') {
+            let entry = parse_header(header).with_context(|| format!("line {}", index + 1))?;
+            entries.push(entry);
+        } else if let Some(entry) = entries.last_mut() {
+            if !entry.content.is_empty() || !line.trim().is_empty() {
+                entry.content.push_str(line);
+                entry.content.push('\n');
+            }
+        } else if !line.trim().is_empty() {
+            anyhow::bail!("line {}: expected an entry starting with '*'", index + 1);
+        }
+    }
+
+    for entry in &mut entries {
+        entry.content.truncate(entry.content.trim_end().len());
+    }
+    Ok(entries)
+}
+
+/// Parse the header line of an entry, e.g. `Mon Jan 01 2024 Jane Doe <jane@example.com> - 1.0-1`
+fn parse_header(header: &str) -> Result<Entry> {
+    // the day of the week is redundant, so like rpmbuild, don't insist on it being correct
+    let mut parts = header.split_whitespace();
+    let (_, month, day, year) = (parts.next(), parts.next(), parts.next(), parts.next());
+    let (Some(month), Some(day), Some(year)) = (month, day, year) else {
+        anyhow::bail!("expected '* <Day> <Month> <DD> <YYYY> <author>'");
+    };
+    let raw_date = format!("{} {} {}", month, day, year);
+    let date = chrono::NaiveDate::parse_from_str(&raw_date, "%b %d %Y")
+        .with_context(|| format!("invalid date '{}'", raw_date))?;
+
+    let name = parts.collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        anyhow::bail!("missing the author of the entry");
+    }
+
+    Ok(Entry {
+        name,
+        content: String::new(),
+        timestamp: date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp(),
+    })
+}
+
+/// Create changelog entries from the `limit` most recent commits of a git repository, using the
+/// committer, the commit time and the subject of the commit
+pub fn from_git(repo: &Path, limit: usize) -> Result<Vec<Entry>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .arg("log")
+        .arg(format!("--max-count={}", limit))
+        .arg("--format=%cn <%ce>%x1f%ct%x1f%s")
+        .output()
+        .with_context(|| format!("unable to run git to read the history of {:?}", repo))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "unable to read the git history of {:?}: {}",
+            repo,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let stdout = String::from_utf8(output.stdout).context("git log output is not valid UTF-8")?;
+    stdout
+        .lines()
+        .map(|line| {
+            let fields: Vec<&str> = line.split('\x1f').collect();
+            let [name, timestamp, subject] = fields[..] else {
+                anyhow::bail!("unexpected git log output: {}", line);
+            };
+            Ok(Entry {
+                name: name.to_owned(),
+                content: format!("- {}", subject),
+                timestamp: timestamp
+                    .parse()
+                    .with_context(|| format!("invalid commit time in git log output: {}", line))?,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use clap_derive::Args;
+
+use std::io::Write;
+
+#[derive(Args, Debug)]
+pub struct CompletionsArgs {
+    #[arg(
+        value_name = "SHELL",
+        help = "The shell to generate the completions for"
+    )]
+    pub shell: clap_complete::Shell,
+}
+
+/// Print the completion script of the command line of `command` for a shell to stdout, e.g. for
+/// packagers to install into /usr/share/bash-completion/completions
+pub fn completions(args: CompletionsArgs, mut command: clap::Command) -> Result<()> {
+    let name = command.get_name().to_owned();
+    // generate() panics on write errors, e.g. when the output is piped into head
+    let mut script = Vec::new();
+    clap_complete::generate(args.shell, &mut command, name, &mut script);
+    std::io::stdout().write_all(&script)?;
+    Ok(())
+}
@@ -0,0 +1,67 @@
+//! Dependency generators, which derive provides and requires from the files of a package the way
+//! rpmbuild's file attribute generators do
+
+use anyhow::Result;
+
+use std::collections::BTreeSet;
+
+mod interpreter;
+mod node;
+mod perl;
+mod pkgconfig;
+mod python;
+
+pub use interpreter::InterpreterGenerator;
+pub use node::NodeGenerator;
+pub use perl::PerlGenerator;
+pub use pkgconfig::PkgConfigGenerator;
+pub use python::PythonGenerator;
+
+/// The generated dependencies, in the format accepted by `--provides` and `--requires`
+#[derive(Debug, Default)]
+pub struct Dependencies {
+    pub provides: BTreeSet<String>,
+    pub requires: BTreeSet<String>,
+}
+
+pub trait DependencyGenerator {
+    /// The name the generator is selected with
+    fn name(&self) -> &'static str;
+
+    /// Generate the dependencies of the files of a package
+    fn generate(&self, files: &[rpm::RpmFile]) -> Result<Dependencies>;
+}
+
+/// Every built-in generator
+pub fn generators() -> Vec<Box<dyn DependencyGenerator>> {
+    vec![
+        Box::new(InterpreterGenerator),
+        Box::new(NodeGenerator),
+        Box::new(PerlGenerator),
+        Box::new(PkgConfigGenerator),
+        Box::new(PythonGenerator),
+    ]
+}
+
+/// Look up a built-in generator by its name
+pub fn find(name: &str) -> Result<Box<dyn DependencyGenerator>> {
+    let mut generators = generators();
+    match generators.iter().position(|g| g.name() == name) {
+        Some(position) => Ok(generators.swap_remove(position)),
+        None => anyhow::bail!(
+            "unknown dependency generator {}, the available ones are: {}",
+            name,
+            generators
+                .iter()
+                .map(|g| g.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Whether the file is a regular file with one of its executable bits set
+fn is_executable(file: &rpm::RpmFile) -> bool {
+    matches!(file.metadata.mode, rpm::FileMode::Regular { .. })
+        && file.metadata.mode.permissions() & 0o111 != 0
+}
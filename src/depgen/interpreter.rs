@@ -0,0 +1,33 @@
+use anyhow::Result;
+
+use super::{Dependencies, DependencyGenerator};
+
+/// Require the interpreters of the executable scripts in the package, like rpmbuild's script
+/// dependency generator, e.g. `/usr/bin/python3` for a script starting with
+/// `#!/usr/bin/python3 -s`
+pub struct InterpreterGenerator;
+
+impl DependencyGenerator for InterpreterGenerator {
+    fn name(&self) -> &'static str {
+        "interpreter"
+    }
+
+    fn generate(&self, files: &[rpm::RpmFile]) -> Result<Dependencies> {
+        let mut dependencies = Dependencies::default();
+        for file in files.iter().filter(|file| super::is_executable(file)) {
+            if let Some(interpreter) = interpreter(&file.content) {
+                dependencies.requires.insert(interpreter);
+            }
+        }
+        Ok(dependencies)
+    }
+}
+
+/// The interpreter in the shebang line of a script, if it has one with an absolute path
+pub fn interpreter(content: &[u8]) -> Option<String> {
+    let line = content.strip_prefix(b"#!")?;
+    let line = line.split(|&b| b == b'\n').next().unwrap_or_default();
+    let line = String::from_utf8_lossy(line);
+    let interpreter = line.split_whitespace().next()?;
+    Some(interpreter.to_owned()).filter(|i| i.starts_with('/'))
+}
@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+
+use std::ffi::OsStr;
+use std::path::Component;
+
+use super::{Dependencies, DependencyGenerator};
+
+/// Generate `npm(...)` provides and requires from the `package.json` of the Node.js modules in
+/// the package, like Fedora's nodejs dependency generators do. Modules bundled inside of another
+/// module's `node_modules` are provided as `bundled(nodejs-...)` instead.
+pub struct NodeGenerator;
+
+impl DependencyGenerator for NodeGenerator {
+    fn name(&self) -> &'static str {
+        "node"
+    }
+
+    fn generate(&self, files: &[rpm::RpmFile]) -> Result<Dependencies> {
+        let mut dependencies = Dependencies::default();
+        for file in files {
+            let path = &file.metadata.path;
+            let components: Vec<&OsStr> = path
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(part) => Some(part),
+                    _ => None,
+                })
+                .collect();
+            // `node_modules/<name>/package.json` or `node_modules/@<scope>/<name>/package.json`
+            let is_module = match components.as_slice() {
+                [.., modules, scope, _, file_name]
+                    if *modules == "node_modules" && scope.to_string_lossy().starts_with('@') =>
+                {
+                    *file_name == "package.json"
+                }
+                [.., modules, _, file_name] => {
+                    *modules == "node_modules" && *file_name == "package.json"
+                }
+                _ => false,
+            };
+            if !is_module {
+                continue;
+            }
+            let is_bundled = components.iter().filter(|c| **c == "node_modules").count() > 1;
+
+            let manifest: serde_json::Value = serde_json::from_slice(&file.content)
+                .with_context(|| format!("unable to parse {}", path.display()))?;
+            let Some(name) = manifest.get("name").and_then(|name| name.as_str()) else {
+                continue;
+            };
+            let version = manifest
+                .get("version")
+                .and_then(|version| version.as_str())
+                .map(rpm_version);
+
+            let provide = if is_bundled {
+                format!("bundled(nodejs-{})", name)
+            } else {
+                format!("npm({})", name)
+            };
+            dependencies.provides.insert(match version {
+                Some(version) => format!("{} = {}", provide, version),
+                None => provide,
+            });
+
+            // bundled modules come with their dependencies
+            if is_bundled {
+                continue;
+            }
+            let requirements = manifest
+                .get("dependencies")
+                .and_then(|dependencies| dependencies.as_object());
+            for (dependency, range) in requirements.into_iter().flatten() {
+                let name = format!("npm({})", dependency);
+                let constraints = range.as_str().map(constraints).unwrap_or_default();
+                if constraints.is_empty() {
+                    dependencies.requires.insert(name);
+                    continue;
+                }
+                for constraint in constraints {
+                    dependencies
+                        .requires
+                        .insert(format!("{} {}", name, constraint));
+                }
+            }
+        }
+        Ok(dependencies)
+    }
+}
+
+/// Convert a semver version, whose pre-releases sort before the release, e.g. `1.0.0-beta.1`
+/// becomes `1.0.0~beta.1`
+fn rpm_version(version: &str) -> String {
+    let version = version.trim().trim_start_matches(['v', '=']);
+    // build metadata isn't used for comparisons
+    let version = version.split('+').next().unwrap_or_default();
+    version.replacen('-', "~", 1)
+}
+
+/// Translate a semver range into rpm version constraints. Ranges with alternatives (`||`),
+/// hyphen ranges and non-registry sources such as git URLs don't constrain the version.
+fn constraints(range: &str) -> Vec<String> {
+    let range = range.trim();
+    if range.contains("||") || range.contains(" - ") || range.contains(['/', ':']) {
+        return Vec::new();
+    }
+    range
+        .split_whitespace()
+        .flat_map(|comparator| {
+            for operator in [">=", "<=", ">", "<"] {
+                if let Some(version) = comparator.strip_prefix(operator) {
+                    return vec![format!("{} {}", operator, rpm_version(version))];
+                }
+            }
+            if let Some(version) = comparator.strip_prefix('^') {
+                caret(version)
+            } else if let Some(version) = comparator.strip_prefix('~') {
+                tilde(version)
+            } else {
+                partial(comparator.trim_start_matches(['v', '=']))
+            }
+        })
+        .collect()
+}
+
+/// The numeric parts of a version, up to the first wildcard
+fn parts(version: &str) -> Vec<u64> {
+    let release = version.split(['-', '+']).next().unwrap_or_default();
+    release
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+fn upper_bound(mut parts: Vec<u64>, position: usize) -> String {
+    parts.truncate(position + 1);
+    parts[position] += 1;
+    parts
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// `^1.2.3` allows changes which don't modify the left-most non-zero part
+fn caret(version: &str) -> Vec<String> {
+    let parts = parts(version);
+    if parts.is_empty() {
+        return Vec::new();
+    }
+    let position = parts
+        .iter()
+        .take(parts.len() - 1)
+        .position(|&part| part != 0)
+        .unwrap_or(parts.len() - 1);
+    vec![
+        format!(">= {}", rpm_version(version)),
+        format!("< {}", upper_bound(parts, position)),
+    ]
+}
+
+/// `~1.2.3` allows patch level changes, `~1` minor ones
+fn tilde(version: &str) -> Vec<String> {
+    let parts = parts(version);
+    if parts.is_empty() {
+        return Vec::new();
+    }
+    let position = if parts.len() > 1 { 1 } else { 0 };
+    vec![
+        format!(">= {}", rpm_version(version)),
+        format!("< {}", upper_bound(parts, position)),
+    ]
+}
+
+/// `1.2.3` is an exact version, `1.2`, `1.2.x` and `1.2.*` any version starting with `1.2`, and
+/// `*`, `x` or an empty range any version at all
+fn partial(version: &str) -> Vec<String> {
+    let parts = parts(version);
+    if parts.is_empty() {
+        return Vec::new();
+    }
+    if parts.len() == 3 {
+        return vec![format!("= {}", rpm_version(version))];
+    }
+    let lower = parts
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(".");
+    let position = parts.len() - 1;
+    vec![
+        format!(">= {}", lower),
+        format!("< {}", upper_bound(parts, position)),
+    ]
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+
+use super::{Dependencies, DependencyGenerator};
+
+/// Generate `perl(...)` provides from the packages declared in `.pm` files, and requires from the
+/// modules used by Perl modules and scripts, like rpm's perl.prov and perl.req do
+pub struct PerlGenerator;
+
+impl DependencyGenerator for PerlGenerator {
+    fn name(&self) -> &'static str {
+        "perl"
+    }
+
+    fn generate(&self, files: &[rpm::RpmFile]) -> Result<Dependencies> {
+        let mut dependencies = Dependencies::default();
+        let mut provided_modules = Vec::new();
+        let mut required = Vec::new();
+        for file in files {
+            let extension = file.metadata.path.extension().and_then(|e| e.to_str());
+            let is_module = extension == Some("pm");
+            let is_script = extension == Some("pl")
+                || (super::is_executable(file)
+                    && super::interpreter::interpreter(&file.content)
+                        .is_some_and(|interpreter| interpreter.ends_with("perl")));
+            if !is_module && !is_script {
+                continue;
+            }
+
+            let content = String::from_utf8_lossy(&file.content);
+            for statement in code_lines(&content) {
+                if is_module && let Some((module, version)) = parse_package(statement) {
+                    provided_modules.push(module.clone());
+                    let version = version.or_else(|| find_version(&content));
+                    dependencies.provides.insert(match version {
+                        Some(version) => format!("perl({}) = {}", module, version),
+                        None => format!("perl({})", module),
+                    });
+                } else if let Some(dependency) = parse_use(statement) {
+                    required.push(dependency);
+                }
+            }
+        }
+
+        // modules of the package itself don't need to be required
+        for (module, version) in required {
+            if provided_modules.contains(&module) {
+                continue;
+            }
+            dependencies.requires.insert(match version {
+                Some(version) => format!("perl({}) >= {}", module, version),
+                None => format!("perl({})", module),
+            });
+        }
+        Ok(dependencies)
+    }
+}
+
+/// The lines of code, without POD documentation and without what follows `__END__` or
+/// `__DATA__`
+fn code_lines(content: &str) -> impl Iterator<Item = &str> {
+    let mut in_pod = false;
+    content
+        .lines()
+        .take_while(|line| !matches!(line.trim_end(), "__END__" | "__DATA__"))
+        .filter(move |line| {
+            if line.starts_with("=cut") {
+                in_pod = false;
+                false
+            } else if line.starts_with('=') && line[1..].starts_with(char::is_alphabetic) {
+                in_pod = true;
+                false
+            } else {
+                !in_pod
+            }
+        })
+        .map(str::trim)
+}
+
+fn is_module_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+/// A perl version, e.g. `1.002_003` becomes `1.002003`
+fn parse_version(token: &str) -> Option<String> {
+    let version = token.trim_matches(['\'', '"']).trim_start_matches('v');
+    let is_version = version.starts_with(|c: char| c.is_ascii_digit())
+        && version
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.' || c == '_');
+    is_version.then(|| version.replace('_', ""))
+}
+
+/// `package Foo::Bar;`, `package Foo::Bar 1.2;` or `package Foo::Bar {`
+fn parse_package(statement: &str) -> Option<(String, Option<String>)> {
+    let rest = statement.strip_prefix("package")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut tokens = rest
+        .split(|c: char| c.is_whitespace() || c == ';' || c == '{')
+        .filter(|token| !token.is_empty());
+    let module = tokens.next()?;
+    if !is_module_name(module) || module == "main" {
+        return None;
+    }
+    Some((module.to_owned(), tokens.next().and_then(parse_version)))
+}
+
+/// The first `$VERSION = '1.2';` assignment of a module
+fn find_version(content: &str) -> Option<String> {
+    code_lines(content).find_map(|line| {
+        let (left, right) = line.split_once('=')?;
+        let left = left.trim().trim_start_matches("our").trim();
+        if left != "$VERSION" && !left.ends_with("::VERSION") {
+            return None;
+        }
+        parse_version(right.trim().trim_end_matches(';').trim())
+    })
+}
+
+/// `use Foo::Bar;`, `use Foo::Bar 1.2 qw(...);` or `require Foo::Bar;`, but not
+/// `use 5.010;` or `require "file.pl";`
+fn parse_use(statement: &str) -> Option<(String, Option<String>)> {
+    let rest = statement
+        .strip_prefix("use")
+        .or_else(|| statement.strip_prefix("require"))?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut tokens = rest
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .filter(|token| !token.is_empty());
+    let module = tokens.next()?;
+    if !is_module_name(module) {
+        return None;
+    }
+    Some((module.to_owned(), tokens.next().and_then(parse_version)))
+}
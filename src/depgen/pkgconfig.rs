@@ -0,0 +1,116 @@
+use anyhow::Result;
+
+use std::collections::HashMap;
+
+use super::{Dependencies, DependencyGenerator};
+
+/// Generate `pkgconfig(...)` provides and requires from the `.pc` files in the package, like
+/// rpm's pkgconfigdeps does
+pub struct PkgConfigGenerator;
+
+impl DependencyGenerator for PkgConfigGenerator {
+    fn name(&self) -> &'static str {
+        "pkgconfig"
+    }
+
+    fn generate(&self, files: &[rpm::RpmFile]) -> Result<Dependencies> {
+        let mut dependencies = Dependencies::default();
+        for file in files {
+            let path = &file.metadata.path;
+            let in_pkgconfig_dir = path
+                .parent()
+                .and_then(|parent| parent.file_name())
+                .is_some_and(|name| name == "pkgconfig");
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if !in_pkgconfig_dir || path.extension().is_none_or(|e| e != "pc") {
+                continue;
+            }
+
+            let fields = parse(&String::from_utf8_lossy(&file.content));
+            match fields.get("version").filter(|v| !v.is_empty()) {
+                Some(version) => dependencies
+                    .provides
+                    .insert(format!("pkgconfig({}) = {}", name, version)),
+                None => dependencies.provides.insert(format!("pkgconfig({})", name)),
+            };
+            for key in ["requires", "requires.private"] {
+                if let Some(requires) = fields.get(key) {
+                    dependencies.requires.extend(parse_requires(requires));
+                }
+            }
+            // the .pc files are only useful with pkg-config
+            dependencies
+                .requires
+                .insert("/usr/bin/pkg-config".to_owned());
+        }
+        Ok(dependencies)
+    }
+}
+
+/// The fields of a `.pc` file (with lowercase keys), with the variables in them substituted
+fn parse(content: &str) -> HashMap<String, String> {
+    let mut variables: HashMap<String, String> = HashMap::new();
+    let mut fields = HashMap::new();
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('#') {
+            continue;
+        }
+        let separator = line.find([':', '=']);
+        match separator.map(|position| (position, &line[position..position + 1])) {
+            Some((position, "=")) => {
+                let value = substitute(line[position + 1..].trim(), &variables);
+                variables.insert(line[..position].trim().to_owned(), value);
+            }
+            Some((position, _)) => {
+                let value = substitute(line[position + 1..].trim(), &variables);
+                fields.insert(line[..position].trim().to_ascii_lowercase(), value);
+            }
+            None => {}
+        }
+    }
+    fields
+}
+
+/// Replace every `${name}` with the value of the variable
+fn substitute(value: &str, variables: &HashMap<String, String>) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+        match rest[start + 2..].split_once('}') {
+            Some((name, remainder)) => {
+                output.push_str(variables.get(name).map(String::as_str).unwrap_or(""));
+                rest = remainder;
+            }
+            None => {
+                output.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Parse a list of modules such as `glib-2.0 >= 2.50, zlib`
+fn parse_requires(requires: &str) -> Vec<String> {
+    let mut tokens = requires
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|token| !token.is_empty())
+        .peekable();
+    let mut dependencies = Vec::new();
+    while let Some(name) = tokens.next() {
+        let operator =
+            tokens.next_if(|token| matches!(*token, "=" | "<" | ">" | "<=" | ">=" | "!="));
+        let version = operator.and_then(|_| tokens.next());
+        match (operator, version) {
+            (Some(operator), Some(version)) if operator != "!=" => {
+                dependencies.push(format!("pkgconfig({}) {} {}", name, operator, version))
+            }
+            _ => dependencies.push(format!("pkgconfig({})", name)),
+        }
+    }
+    dependencies
+}
@@ -0,0 +1,343 @@
+use anyhow::Result;
+
+use std::path::{Component, Path};
+
+use super::{Dependencies, DependencyGenerator};
+
+/// Generate `python3dist(...)` provides and requires from the metadata of the Python
+/// distributions in the package, like Fedora's pythondistdeps does with `.dist-info/METADATA`,
+/// `.egg-info/PKG-INFO` and `.egg-info/requires.txt`
+pub struct PythonGenerator;
+
+impl DependencyGenerator for PythonGenerator {
+    fn name(&self) -> &'static str {
+        "python"
+    }
+
+    fn generate(&self, files: &[rpm::RpmFile]) -> Result<Dependencies> {
+        let mut dependencies = Dependencies::default();
+        for file in files {
+            let path = &file.metadata.path;
+            let content = String::from_utf8_lossy(&file.content);
+            let parent = path.parent().unwrap_or(Path::new("/"));
+            let parent_extension = parent.extension().and_then(|e| e.to_str());
+            let python_version = python_version(path);
+
+            let is_metadata = match path.file_name().and_then(|n| n.to_str()) {
+                Some("METADATA") => parent_extension == Some("dist-info"),
+                Some("PKG-INFO") => parent_extension == Some("egg-info"),
+                // egg-info can also be a single file with the content of PKG-INFO
+                _ => {
+                    path.extension().and_then(|e| e.to_str()) == Some("egg-info")
+                        && matches!(file.metadata.mode, rpm::FileMode::Regular { .. })
+                }
+            };
+            if is_metadata {
+                dependencies.add_metadata(&content, python_version.as_deref());
+            } else if path.file_name().and_then(|n| n.to_str()) == Some("requires.txt")
+                && parent_extension == Some("egg-info")
+            {
+                dependencies.add_requires_txt(&content, python_version.as_deref());
+            }
+        }
+        Ok(dependencies)
+    }
+}
+
+impl Dependencies {
+    fn add_metadata(&mut self, content: &str, python_version: Option<&str>) {
+        let mut name = None;
+        let mut version = None;
+        // the headers end with the first empty line, the description follows
+        for line in content.lines().take_while(|line| !line.is_empty()) {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.to_ascii_lowercase().as_str() {
+                "name" => name = Some(value.to_owned()),
+                "version" => version = Some(value.to_owned()),
+                "requires-dist" => self.add_requirement(value, python_version),
+                _ => {}
+            }
+        }
+
+        if let (Some(name), Some(version)) = (name, version) {
+            let name = normalize_name(&name);
+            let version = rpm_version(&version);
+            self.provides
+                .insert(format!("python3dist({}) = {}", name, version));
+            if let Some(python_version) = python_version {
+                self.provides.insert(format!(
+                    "python{}dist({}) = {}",
+                    python_version, name, version
+                ));
+                self.requires
+                    .insert(format!("python(abi) = {}", python_version));
+            }
+        }
+    }
+
+    fn add_requires_txt(&mut self, content: &str, python_version: Option<&str>) {
+        // requirements before the first section are unconditional, `[:<marker>]` sections apply
+        // if the marker does, and `[<extra>]` sections are for extras
+        let mut applies = true;
+        for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                applies = match section.split_once(':') {
+                    Some(("", marker)) => evaluate_marker(marker, python_version),
+                    _ => false,
+                };
+            } else if applies {
+                self.add_requirement(line, python_version);
+            }
+        }
+    }
+
+    /// Add a requirement of the form `name[extras] (specifiers); marker`
+    fn add_requirement(&mut self, requirement: &str, python_version: Option<&str>) {
+        let (requirement, marker) = requirement.split_once(';').unwrap_or((requirement, ""));
+        if !marker.trim().is_empty() && !evaluate_marker(marker, python_version) {
+            return;
+        }
+
+        let end = requirement
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "-_.".contains(c)))
+            .unwrap_or(requirement.len());
+        let (name, rest) = requirement.split_at(end);
+        if name.is_empty() {
+            return;
+        }
+        let name = format!("python3dist({})", normalize_name(name));
+        let specifiers = match rest.find(']') {
+            Some(end) => &rest[end + 1..],
+            None => rest,
+        };
+        let specifiers = specifiers
+            .trim()
+            .trim_start_matches('(')
+            .trim_end_matches(')');
+
+        let mut constraints = Vec::new();
+        for specifier in specifiers
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+        {
+            let operator_end = specifier
+                .find(|c: char| !"<>=!~".contains(c))
+                .unwrap_or(specifier.len());
+            let (operator, version) = specifier.split_at(operator_end);
+            let version = version.trim();
+            match operator {
+                "==" if version.ends_with(".*") => {
+                    let prefix = version.trim_end_matches(".*");
+                    constraints.push(format!(">= {}", rpm_version(prefix)));
+                    constraints.push(format!("< {}", next_version(prefix)));
+                }
+                "==" | "===" => constraints.push(format!("= {}", rpm_version(version))),
+                "~=" => {
+                    constraints.push(format!(">= {}", rpm_version(version)));
+                    let release: Vec<&str> = version.split('.').collect();
+                    if release.len() > 1 {
+                        let prefix = release[..release.len() - 1].join(".");
+                        constraints.push(format!("< {}", next_version(&prefix)));
+                    }
+                }
+                ">=" | "<=" | ">" | "<" => {
+                    constraints.push(format!("{} {}", operator, rpm_version(version)))
+                }
+                // "!=" excludes a version, which needs a rich dependency to express
+                _ => {}
+            }
+        }
+
+        if constraints.is_empty() {
+            self.requires.insert(name);
+        } else {
+            for constraint in constraints {
+                self.requires.insert(format!("{} {}", name, constraint));
+            }
+        }
+    }
+}
+
+/// The `X.Y` of the `pythonX.Y` directory the file is installed in, e.g.
+/// `/usr/lib/python3.12/site-packages`
+fn python_version(path: &Path) -> Option<String> {
+    path.components().find_map(|component| match component {
+        Component::Normal(part) => {
+            let version = part.to_str()?.strip_prefix("python")?;
+            let (major, minor) = version.split_once('.')?;
+            let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+            (is_number(major) && is_number(minor)).then(|| version.to_owned())
+        }
+        _ => None,
+    })
+}
+
+/// Normalize a distribution name as described by PEP 503, e.g. `Foo_Bar.baz` becomes
+/// `foo-bar-baz`
+fn normalize_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    for c in name.chars() {
+        if "-_.".contains(c) {
+            if !normalized.ends_with('-') {
+                normalized.push('-');
+            }
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+        }
+    }
+    normalized
+}
+
+/// Convert a PEP 440 version to one which compares the same way in rpm, e.g. `1.0rc1` becomes
+/// `1.0~rc1`, `1.0.dev2` becomes `1.0~~dev2` and `1.0.post1` becomes `1.0^post1`
+fn rpm_version(version: &str) -> String {
+    let version = version.trim().to_ascii_lowercase();
+    // local versions aren't used for comparisons
+    let version = version.split('+').next().unwrap_or_default();
+    let (epoch, version) = match version.split_once('!') {
+        Some((epoch, version)) => (Some(epoch), version),
+        None => (None, version),
+    };
+
+    let release_end = version
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(version.len());
+    let (release, mut rest) = version.split_at(release_end);
+    let mut converted = release.trim_end_matches('.').to_owned();
+
+    let mut take = |labels: &[&str]| -> Option<String> {
+        let trimmed = rest.trim_start_matches(['.', '-', '_']);
+        let label = labels.iter().find(|label| trimmed.starts_with(*label))?;
+        let after = trimmed[label.len()..].trim_start_matches(['.', '-', '_']);
+        let number_end = after
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(after.len());
+        let number = &after[..number_end];
+        rest = &after[number_end..];
+        Some(if number.is_empty() {
+            "0".to_owned()
+        } else {
+            number.to_owned()
+        })
+    };
+
+    let pre_labels: [(&[&str], &str); 3] = [
+        (&["rc", "c", "preview", "pre"], "rc"),
+        (&["alpha", "a"], "a"),
+        (&["beta", "b"], "b"),
+    ];
+    for (labels, normalized) in pre_labels {
+        if let Some(number) = take(labels) {
+            converted.push_str(&format!("~{}{}", normalized, number));
+            break;
+        }
+    }
+    if let Some(number) = take(&["post", "rev", "r"]) {
+        converted.push_str(&format!("^post{}", number));
+    }
+    if let Some(number) = take(&["dev"]) {
+        converted.push_str(&format!("~~dev{}", number));
+    }
+
+    match epoch {
+        Some(epoch) => format!("{}:{}", epoch, converted),
+        None => converted,
+    }
+}
+
+/// The version right after every version starting with `prefix`, e.g. `1.5` for `1.4`
+fn next_version(prefix: &str) -> String {
+    let mut parts: Vec<String> = prefix.split('.').map(str::to_owned).collect();
+    if let Some(last) = parts.last_mut() {
+        *last = (last.parse::<u64>().unwrap_or(0) + 1).to_string();
+    }
+    rpm_version(&parts.join("."))
+}
+
+/// Evaluate a PEP 508 environment marker for a Linux system running CPython `python_version`.
+/// Requirements of extras and markers which can't be evaluated are left out.
+fn evaluate_marker(marker: &str, python_version: Option<&str>) -> bool {
+    marker.split(" or ").any(|alternative| {
+        alternative
+            .split(" and ")
+            .all(|condition| evaluate_condition(condition, python_version).unwrap_or(false))
+    })
+}
+
+fn evaluate_condition(condition: &str, python_version: Option<&str>) -> Option<bool> {
+    let condition = condition
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')');
+    let operators = [
+        "===", "==", "!=", "<=", ">=", "~=", "<", ">", " not in ", " in ",
+    ];
+    let (position, operator) = operators
+        .iter()
+        .filter_map(|operator| Some((condition.find(operator)?, *operator)))
+        .min_by_key(|(position, operator)| (*position, std::cmp::Reverse(operator.len())))?;
+    let left = condition[..position].trim();
+    let right = condition[position + operator.len()..].trim();
+    let unquote = |s: &str| -> Option<String> {
+        let s = s.strip_prefix(['"', '\''])?.strip_suffix(['"', '\''])?;
+        Some(s.to_owned())
+    };
+    let value = |name: &str| -> Option<String> {
+        Some(match name {
+            "python_version" => python_version?.to_owned(),
+            "python_full_version" => python_version?.to_owned(),
+            "sys_platform" => "linux".to_owned(),
+            "platform_system" => "Linux".to_owned(),
+            "os_name" => "posix".to_owned(),
+            "platform_python_implementation" => "CPython".to_owned(),
+            "implementation_name" => "cpython".to_owned(),
+            // the requirements of extras aren't needed by the package itself
+            "extra" => String::new(),
+            _ => return None,
+        })
+    };
+    let (left, right, is_version) = match (unquote(left), unquote(right)) {
+        (None, Some(literal)) => (value(left)?, literal, left.contains("version")),
+        (Some(literal), None) => (literal, value(right)?, right.contains("version")),
+        _ => return None,
+    };
+
+    let operator = operator.trim();
+    if matches!(operator, "in" | "not in") {
+        return Some(right.contains(&left) == (operator == "in"));
+    }
+    let ordering = if is_version {
+        compare_versions(&left, &right)
+    } else {
+        left.cmp(&right)
+    };
+    Some(match operator {
+        "==" | "===" => ordering.is_eq(),
+        "!=" => ordering.is_ne(),
+        "<=" => ordering.is_le(),
+        ">=" => ordering.is_ge(),
+        "<" => ordering.is_lt(),
+        ">" => ordering.is_gt(),
+        "~=" => ordering.is_ge(),
+        _ => return None,
+    })
+}
+
+/// Compare dotted numeric versions, e.g. `3.10` is greater than `3.9`
+fn compare_versions(left: &str, right: &str) -> std::cmp::Ordering {
+    let parse = |version: &str| -> Vec<u64> {
+        version
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    let (mut left, mut right) = (parse(left), parse(right));
+    let len = left.len().max(right.len());
+    left.resize(len, 0);
+    right.resize(len, 0);
+    left.cmp(&right)
+}
@@ -0,0 +1,213 @@
+use anyhow::{Context, Result};
+use clap_derive::Args;
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::inspect::{FileInfo, OutputFormat, PackageInfo};
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    #[arg(value_name = "OLD", help = "Path to the old rpm")]
+    pub old: PathBuf,
+
+    #[arg(value_name = "NEW", help = "Path to the new rpm")]
+    pub new: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        default_value = "text",
+        help = "Specify the output format"
+    )]
+    pub format: OutputFormat,
+}
+
+/// The differences between two packages
+#[derive(Serialize, Debug, Default)]
+pub struct PackageDiff {
+    pub metadata: Vec<TagChange>,
+    pub files: FileChanges,
+    /// Added and removed dependencies, by kind of dependency. Kinds without changes are left out.
+    pub dependencies: BTreeMap<&'static str, DependencyChanges>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TagChange {
+    pub tag: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct FileChanges {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    /// Files whose content, mode, ownership, symlink target or capabilities changed
+    pub changed: Vec<PathBuf>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct DependencyChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+pub fn diff(args: DiffArgs) -> Result<()> {
+    let old = load(&args.old)?;
+    let new = load(&args.new)?;
+    let diff = PackageDiff::new(&old, &new);
+
+    match args.format {
+        OutputFormat::Text => print_text(&diff),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+    }
+
+    Ok(())
+}
+
+fn load(path: &Path) -> Result<PackageInfo> {
+    let pkg =
+        rpm::Package::open(path).with_context(|| format!("unable to read package {:?}", path))?;
+    PackageInfo::from_package(&pkg)
+        .with_context(|| format!("unable to read the metadata of {:?}", path))
+}
+
+impl PackageDiff {
+    pub fn new(old: &PackageInfo, new: &PackageInfo) -> PackageDiff {
+        let mut diff = PackageDiff::default();
+
+        // the build time is left out, it differs between any two builds
+        let tags = [
+            ("name", old.name.clone(), new.name.clone()),
+            ("epoch", old.epoch.to_string(), new.epoch.to_string()),
+            ("version", old.version.clone(), new.version.clone()),
+            ("release", old.release.clone(), new.release.clone()),
+            ("arch", old.arch.clone(), new.arch.clone()),
+            ("license", old.license.clone(), new.license.clone()),
+            (
+                "url",
+                old.url.clone().unwrap_or_default(),
+                new.url.clone().unwrap_or_default(),
+            ),
+            (
+                "vendor",
+                old.vendor.clone().unwrap_or_default(),
+                new.vendor.clone().unwrap_or_default(),
+            ),
+            ("summary", old.summary.clone(), new.summary.clone()),
+            (
+                "description",
+                old.description.clone(),
+                new.description.clone(),
+            ),
+            (
+                "compression",
+                old.compression.clone(),
+                new.compression.clone(),
+            ),
+        ];
+        diff.metadata = tags
+            .into_iter()
+            .filter(|(_, old, new)| old != new)
+            .map(|(tag, old, new)| TagChange { tag, old, new })
+            .collect();
+
+        let old_files: BTreeMap<&Path, &FileInfo> =
+            old.files.iter().map(|f| (f.path.as_path(), f)).collect();
+        let new_files: BTreeMap<&Path, &FileInfo> =
+            new.files.iter().map(|f| (f.path.as_path(), f)).collect();
+        for (path, old_file) in &old_files {
+            match new_files.get(path) {
+                Some(new_file) if file_changed(old_file, new_file) => {
+                    diff.files.changed.push(path.to_path_buf())
+                }
+                Some(_) => {}
+                None => diff.files.removed.push(path.to_path_buf()),
+            }
+        }
+        diff.files.added = new_files
+            .keys()
+            .filter(|path| !old_files.contains_key(*path))
+            .map(|path| path.to_path_buf())
+            .collect();
+
+        let dependency_sets = [
+            ("provides", &old.provides, &new.provides),
+            ("requires", &old.requires, &new.requires),
+            ("obsoletes", &old.obsoletes, &new.obsoletes),
+            ("conflicts", &old.conflicts, &new.conflicts),
+            ("suggests", &old.suggests, &new.suggests),
+            ("recommends", &old.recommends, &new.recommends),
+            ("enhances", &old.enhances, &new.enhances),
+            ("supplements", &old.supplements, &new.supplements),
+        ];
+        for (kind, old_dependencies, new_dependencies) in dependency_sets {
+            let old_dependencies: Vec<String> =
+                old_dependencies.iter().map(|d| d.to_string()).collect();
+            let new_dependencies: Vec<String> =
+                new_dependencies.iter().map(|d| d.to_string()).collect();
+            let changes = DependencyChanges {
+                added: new_dependencies
+                    .iter()
+                    .filter(|d| !old_dependencies.contains(d))
+                    .cloned()
+                    .collect(),
+                removed: old_dependencies
+                    .iter()
+                    .filter(|d| !new_dependencies.contains(d))
+                    .cloned()
+                    .collect(),
+            };
+            if !changes.added.is_empty() || !changes.removed.is_empty() {
+                diff.dependencies.insert(kind, changes);
+            }
+        }
+
+        diff
+    }
+}
+
+fn file_changed(old: &FileInfo, new: &FileInfo) -> bool {
+    old.digest != new.digest
+        || old.mode != new.mode
+        || old.user != new.user
+        || old.group != new.group
+        || old.linkto != new.linkto
+        || old.caps != new.caps
+}
+
+fn print_text(diff: &PackageDiff) {
+    if !diff.metadata.is_empty() {
+        println!("Metadata:");
+        for change in &diff.metadata {
+            println!("  {}: {} -> {}", change.tag, change.old, change.new);
+        }
+    }
+
+    let files = &diff.files;
+    if !files.added.is_empty() || !files.removed.is_empty() || !files.changed.is_empty() {
+        println!("Files:");
+        for path in &files.added {
+            println!("  + {}", path.display());
+        }
+        for path in &files.removed {
+            println!("  - {}", path.display());
+        }
+        for path in &files.changed {
+            println!("  ~ {}", path.display());
+        }
+    }
+
+    for (kind, changes) in &diff.dependencies {
+        println!("{}:", kind);
+        for dependency in &changes.added {
+            println!("  + {}", dependency);
+        }
+        for dependency in &changes.removed {
+            println!("  - {}", dependency);
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::{Context, Result};
+use clap_derive::Args;
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::build::{is_rich_dependency, parse_dependency};
+use crate::sign::SignatureMode;
+use crate::{changelog, header, sign};
+
+#[derive(Args, Debug)]
+pub struct EditArgs {
+    #[arg(value_name = "PACKAGE", help = "Path to the rpm to edit")]
+    pub package: PathBuf,
+
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "OUT",
+        help = "Write the edited package to this path instead of replacing the original. A directory gets a file named after the edited package"
+    )]
+    pub out: Option<PathBuf>,
+
+    #[arg(long, value_name = "SUMMARY", help = "Replace the summary")]
+    pub summary: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Replace the description with the content of a file"
+    )]
+    pub description_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "RELEASE",
+        conflicts_with = "bump_release",
+        help = "Replace the release"
+    )]
+    pub release: Option<String>,
+
+    #[arg(
+        long,
+        help = "Increment the number the release starts with, e.g. 1.fc40 becomes 2.fc40"
+    )]
+    pub bump_release: bool,
+
+    #[arg(
+        long,
+        value_name = "OBSOLETES",
+        help = "Add an obsoleted package. Use the format '<name> [>|>=|=|<=|< version]'"
+    )]
+    pub obsoletes: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "CHANGELOG_FILE",
+        help = "Replace the changelog with the entries of a changelog file in the format of a spec file %changelog section"
+    )]
+    pub changelog_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SIGN_WITH_PGP_ASC",
+        help = "Sign the edited package with the specified PGP secret key, can be given multiple times. Without it the package is unsigned, since the old signatures don't match the new header"
+    )]
+    pub sign_with_pgp_asc: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "MODE",
+        default_value = "header-only",
+        help = "Which signatures to add: only over the header, or also (legacy) over the header and payload"
+    )]
+    pub signature_mode: SignatureMode,
+}
+
+pub fn edit(args: EditArgs) -> Result<()> {
+    let pkg = rpm::Package::open(&args.package)
+        .with_context(|| format!("unable to read package {:?}", &args.package))?;
+    let tags = edited_tags(&pkg, &args)?;
+    if tags.is_empty() {
+        anyhow::bail!("nothing to edit, pass at least one of the options changing the package");
+    }
+    let was_signed = pkg
+        .metadata
+        .signature
+        .entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_OPENPGP)
+        || pkg
+            .metadata
+            .signature
+            .entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_RSA);
+
+    // the payload is kept as it is, only the header is encoded again
+    let mut pkg = header::set_tags(pkg, &tags)
+        .with_context(|| format!("unable to edit package {:?}", &args.package))?;
+    if !args.sign_with_pgp_asc.is_empty() {
+        let signers = sign::load_signers(&args.sign_with_pgp_asc)?;
+        pkg = sign::sign_package(pkg, &signers, rpm::Timestamp::now(), &args.signature_mode)
+            .with_context(|| format!("unable to sign package {:?}", &args.package))?;
+    } else if was_signed {
+        tracing::warn!(package = %args.package.display(), "removed the signatures of the package");
+    }
+
+    let output_path = match args.out {
+        Some(path) if path.is_dir() => crate::build::output_path(&pkg, Some(&path), None)?,
+        Some(path) => path,
+        None => args.package,
+    };
+    pkg.write_file(&output_path)
+        .with_context(|| format!("unable to write package to path {:?}", &output_path))?;
+    tracing::info!(path = %output_path.display(), "wrote package");
+
+    Ok(())
+}
+
+/// The header tags to replace for the edits
+fn edited_tags(pkg: &rpm::Package, args: &EditArgs) -> Result<Vec<(rpm::IndexTag, header::Value)>> {
+    let metadata = &pkg.metadata;
+    let translations = metadata
+        .header
+        .get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_HEADERI18NTABLE)
+        .map_or(0, |locales| locales.len());
+    let mut tags = Vec::new();
+    let mut set_text = |tag, text: String| {
+        if translations > 1 {
+            anyhow::bail!("the {} of a package with translations can't be edited", tag);
+        }
+        let tag = match tag {
+            "summary" => rpm::IndexTag::RPMTAG_SUMMARY,
+            _ => rpm::IndexTag::RPMTAG_DESCRIPTION,
+        };
+        tags.push((tag, header::Value::I18nString(vec![text])));
+        Ok(())
+    };
+    if let Some(summary) = &args.summary {
+        set_text("summary", summary.clone())?;
+    }
+    if let Some(path) = &args.description_file {
+        let description = fs::read_to_string(path)
+            .with_context(|| format!("error reading description file {:?}", path))?;
+        set_text("description", description.trim_end().to_owned())?;
+    }
+
+    let old_release = metadata.get_release()?;
+    let release = match (&args.release, args.bump_release) {
+        (Some(release), _) => Some(release.clone()),
+        (None, true) => Some(bump_release(old_release)?),
+        (None, false) => None,
+    };
+    if let Some(release) = release {
+        if release.is_empty()
+            || release.contains(['-', ':'])
+            || release.contains(char::is_whitespace)
+        {
+            anyhow::bail!("invalid release {:?}", release);
+        }
+        tracing::info!(from = old_release, to = release, "changing the release");
+        let name = metadata.get_name()?;
+        let version = metadata.get_version()?;
+        let old_evr = evr(metadata, old_release)?;
+        let new_evr = evr(metadata, &release)?;
+
+        // versioned self-provides, as rpmbuild adds them, follow the release
+        let provides = metadata.get_provides()?;
+        if provides.iter().any(|provide| provide.version == old_evr) {
+            let versions = provides
+                .into_iter()
+                .map(|provide| {
+                    if provide.version == old_evr {
+                        new_evr.clone()
+                    } else {
+                        provide.version
+                    }
+                })
+                .collect();
+            tags.push((
+                rpm::IndexTag::RPMTAG_PROVIDEVERSION,
+                header::Value::StringArray(versions),
+            ));
+        }
+        let old_source_rpm = format!("{}-{}-{}.src.rpm", name, version, old_release);
+        if metadata.get_source_rpm().ok() == Some(old_source_rpm.as_str()) {
+            tags.push((
+                rpm::IndexTag::RPMTAG_SOURCERPM,
+                header::Value::String(format!("{}-{}-{}.src.rpm", name, version, release)),
+            ));
+        }
+        tags.push((
+            rpm::IndexTag::RPMTAG_RELEASE,
+            header::Value::String(release),
+        ));
+    }
+
+    if !args.obsoletes.is_empty() {
+        let mut obsoletes = metadata.get_obsoletes().unwrap_or_default();
+        for item in &args.obsoletes {
+            if is_rich_dependency(item) {
+                anyhow::bail!("rich dependencies can't be used for obsoletes: {}", item);
+            }
+            tracing::info!(kind = "obsoletes", dependency = item, "adding dependency");
+            obsoletes.push(parse_dependency(item)?);
+        }
+        tags.extend(dependency_tags(
+            &obsoletes,
+            [
+                rpm::IndexTag::RPMTAG_OBSOLETENAME,
+                rpm::IndexTag::RPMTAG_OBSOLETEVERSION,
+                rpm::IndexTag::RPMTAG_OBSOLETEFLAGS,
+            ],
+        ));
+    }
+
+    if let Some(path) = &args.changelog_file {
+        let entries = changelog::parse_file(path)?;
+        if entries.is_empty() {
+            anyhow::bail!("the changelog file {:?} has no entries", path);
+        }
+        tags.push((
+            rpm::IndexTag::RPMTAG_CHANGELOGTIME,
+            header::Value::Int32(entries.iter().map(|e| e.timestamp as u32).collect()),
+        ));
+        tags.push((
+            rpm::IndexTag::RPMTAG_CHANGELOGNAME,
+            header::Value::StringArray(entries.iter().map(|e| e.name.clone()).collect()),
+        ));
+        tags.push((
+            rpm::IndexTag::RPMTAG_CHANGELOGTEXT,
+            header::Value::StringArray(entries.into_iter().map(|e| e.content).collect()),
+        ));
+    }
+    Ok(tags)
+}
+
+/// The name, version and flags tags of a list of dependencies
+fn dependency_tags(
+    dependencies: &[rpm::Dependency],
+    [name_tag, version_tag, flags_tag]: [rpm::IndexTag; 3],
+) -> Vec<(rpm::IndexTag, header::Value)> {
+    vec![
+        (
+            name_tag,
+            header::Value::StringArray(dependencies.iter().map(|d| d.name.clone()).collect()),
+        ),
+        (
+            version_tag,
+            header::Value::StringArray(dependencies.iter().map(|d| d.version.clone()).collect()),
+        ),
+        (
+            flags_tag,
+            header::Value::Int32(dependencies.iter().map(|d| d.flags.bits()).collect()),
+        ),
+    ]
+}
+
+/// The `[epoch:]version-release` of the package with another release
+fn evr(metadata: &rpm::PackageMetadata, release: &str) -> Result<String> {
+    let version = metadata.get_version()?;
+    Ok(match metadata.get_epoch() {
+        Ok(epoch) if epoch > 0 => format!("{}:{}-{}", epoch, version, release),
+        _ => format!("{}-{}", version, release),
+    })
+}
+
+/// Increment the number a release starts with, keeping the rest, e.g. the dist tag
+fn bump_release(release: &str) -> Result<String> {
+    let digits = release
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(release.len());
+    let number: u64 = release[..digits]
+        .parse()
+        .with_context(|| format!("the release {:?} doesn't start with a number", release))?;
+    Ok(format!("{}{}", number + 1, &release[digits..]))
+}
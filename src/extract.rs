@@ -0,0 +1,148 @@
+use anyhow::{Context, Result};
+use clap_derive::Args;
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Args, Debug)]
+pub struct ExtractArgs {
+    #[arg(value_name = "PACKAGE", help = "Path to the rpm to extract")]
+    pub package: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Directory to extract the files into. It must not exist yet"
+    )]
+    pub into: PathBuf,
+}
+
+pub fn extract(args: ExtractArgs) -> Result<()> {
+    let pkg = rpm::Package::open(&args.package)
+        .with_context(|| format!("unable to read package {:?}", &args.package))?;
+
+    if args.into.symlink_metadata().is_ok() {
+        anyhow::bail!("unable to extract into {:?}: it already exists", &args.into);
+    }
+
+    let files = pkg
+        .metadata
+        .get_file_entries()
+        .with_context(|| format!("unable to read the files of {:?}", &args.package))?;
+    check_paths(&files).with_context(|| format!("refusing to extract {:?}", &args.package))?;
+
+    fs::create_dir(&args.into)
+        .with_context(|| format!("unable to create the directory {:?}", &args.into))?;
+    extract_files(&pkg, &args.into).with_context(|| {
+        format!(
+            "unable to extract package {:?} into {:?}",
+            &args.package, &args.into
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Check that every file of the package stays below the directory it's extracted into: the paths
+/// are joined to it as they are, so they must not contain `..` or lead through a symlink of the
+/// package, which could point anywhere. Only regular files, directories and symlinks can be
+/// extracted.
+fn check_paths(files: &[rpm::FileEntry]) -> Result<()> {
+    let symlinks: HashSet<&Path> = files
+        .iter()
+        .filter(|file| matches!(file.mode, rpm::FileMode::SymbolicLink { .. }))
+        .map(|file| file.path.as_path())
+        .collect();
+    for file in files {
+        if file.path.components().any(|c| c == Component::ParentDir) {
+            anyhow::bail!(
+                "the path {:?} points outside of the package root",
+                file.path
+            );
+        }
+        if let Some(symlink) = file.path.ancestors().skip(1).find(|a| symlinks.contains(a)) {
+            anyhow::bail!(
+                "the path {:?} is inside of the symlink {:?}",
+                file.path,
+                symlink
+            );
+        }
+        match file.mode {
+            rpm::FileMode::Regular { .. } | rpm::FileMode::Dir { .. }
+                if symlinks.contains(file.path.as_path()) =>
+            {
+                anyhow::bail!("the path {:?} is both a symlink and a file", file.path)
+            }
+            rpm::FileMode::Regular { .. }
+            | rpm::FileMode::Dir { .. }
+            | rpm::FileMode::SymbolicLink { .. } => {}
+            _ => anyhow::bail!(
+                "{:?} can't be extracted, its mode {:o} is neither a regular file, a directory nor a symlink",
+                file.path,
+                file.mode.raw_mode()
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Write the files of a package below `dest`. Files are created anew rather than opened, so that
+/// nothing outside of `dest` is written through an existing path.
+fn extract_files(pkg: &rpm::Package, dest: &Path) -> Result<()> {
+    // the permissions of the directories are set at the end, so that their files can be created
+    // even if they aren't writable
+    let mut dirs = Vec::new();
+    for file in pkg.files()? {
+        let file = file?;
+        let entry = &file.metadata;
+        let path = dest.join(entry.path.strip_prefix("/").unwrap_or(&entry.path));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("unable to create the directory {:?}", parent))?;
+        }
+        match entry.mode {
+            rpm::FileMode::Dir { permissions } => {
+                if !path.is_dir() {
+                    fs::create_dir(&path)
+                        .with_context(|| format!("unable to create the directory {:?}", path))?;
+                }
+                dirs.push((path, permissions));
+            }
+            rpm::FileMode::Regular { permissions } => {
+                let mut options = fs::OpenOptions::new();
+                options.write(true).create_new(true);
+                #[cfg(unix)]
+                options.mode(permissions.into());
+                let mut output = options
+                    .open(&path)
+                    .with_context(|| format!("unable to create the file {:?}", path))?;
+                output.write_all(&file.content)?;
+                // the mode given when creating the file is reduced by the umask
+                #[cfg(unix)]
+                output.set_permissions(fs::Permissions::from_mode(permissions.into()))?;
+            }
+            rpm::FileMode::SymbolicLink { .. } => {
+                #[cfg(unix)]
+                std::os::unix::fs::symlink(&entry.linkto, &path)
+                    .with_context(|| format!("unable to create the symlink {:?}", path))?;
+                #[cfg(not(unix))]
+                anyhow::bail!("symlinks like {:?} can only be extracted on unix", path);
+            }
+            _ => anyhow::bail!(
+                "{:?} can't be extracted, its mode {:o} isn't supported",
+                entry.path,
+                entry.mode.raw_mode()
+            ),
+        }
+    }
+    #[cfg(unix)]
+    for (path, permissions) in dirs.into_iter().rev() {
+        fs::set_permissions(&path, fs::Permissions::from_mode(permissions.into()))
+            .with_context(|| format!("unable to set the permissions of {:?}", path))?;
+    }
+    Ok(())
+}
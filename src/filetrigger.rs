@@ -0,0 +1,101 @@
+use anyhow::{Context, Result};
+
+use crate::header::Value;
+
+/// The priority rpmbuild gives to file triggers without `-P`. Triggers with a higher priority run
+/// first.
+const DEFAULT_PRIORITY: u32 = 1_000_000;
+
+/// The interpreter of the trigger scripts, rpm only records a program without arguments for them
+const INTERPRETER: &str = "/bin/sh";
+
+/// A script which rpm runs when any package installs or removes files below a path prefix, with
+/// the paths of those files on its standard input
+pub struct FileTrigger {
+    /// `TRIGGERIN`, `TRIGGERUN` or `TRIGGERPOSTUN`, for `%filetriggerin`, `%filetriggerun` and
+    /// `%filetriggerpostun`
+    pub kind: rpm::DependencyFlags,
+    pub prefix: String,
+    pub script: String,
+}
+
+/// Parse a file trigger given as `<path-prefix>:<script-path>` and read its script
+pub fn parse(raw: &str, separator: &str, kind: rpm::DependencyFlags) -> Result<FileTrigger> {
+    let mut parts = crate::build::split_escaped(raw, separator);
+    let prefix = parts.remove(0);
+    let script_path = parts.join(separator);
+    if script_path.is_empty() {
+        anyhow::bail!(
+            "invalid file trigger {}: it needs to be of the form <path-prefix>{sep}<script-path>",
+            raw,
+            sep = separator
+        );
+    }
+    if !prefix.starts_with('/') {
+        anyhow::bail!(
+            "invalid file trigger {}: the path prefix {} needs to be absolute",
+            raw,
+            prefix
+        );
+    }
+    if crate::build::is_glob(&prefix) {
+        anyhow::bail!(
+            "invalid file trigger {}: rpm matches the files by the path prefix {}, it can't be a glob pattern",
+            raw,
+            prefix
+        );
+    }
+    let script = std::fs::read_to_string(&script_path)
+        .with_context(|| format!("error reading file trigger script {}", script_path))?;
+    Ok(FileTrigger {
+        kind,
+        prefix,
+        script,
+    })
+}
+
+/// The header tags of file triggers, or with `transaction` of the transaction file triggers,
+/// which run once per transaction rather than once per package. Every trigger has a script of
+/// its own.
+pub fn header_tags(triggers: &[FileTrigger], transaction: bool) -> Vec<(rpm::IndexTag, Value)> {
+    if triggers.is_empty() {
+        return Vec::new();
+    }
+    let tags = if transaction {
+        [
+            rpm::IndexTag::RPMTAG_TRANSFILETRIGGERSCRIPTS,
+            rpm::IndexTag::RPMTAG_TRANSFILETRIGGERSCRIPTPROG,
+            rpm::IndexTag::RPMTAG_TRANSFILETRIGGERSCRIPTFLAGS,
+            rpm::IndexTag::RPMTAG_TRANSFILETRIGGERPRIORITIES,
+            rpm::IndexTag::RPMTAG_TRANSFILETRIGGERNAME,
+            rpm::IndexTag::RPMTAG_TRANSFILETRIGGERINDEX,
+            rpm::IndexTag::RPMTAG_TRANSFILETRIGGERFLAGS,
+            rpm::IndexTag::RPMTAG_TRANSFILETRIGGERVERSION,
+        ]
+    } else {
+        [
+            rpm::IndexTag::RPMTAG_FILETRIGGERSCRIPTS,
+            rpm::IndexTag::RPMTAG_FILETRIGGERSCRIPTPROG,
+            rpm::IndexTag::RPMTAG_FILETRIGGERSCRIPTFLAGS,
+            rpm::IndexTag::RPMTAG_FILETRIGGERPRIORITIES,
+            rpm::IndexTag::RPMTAG_FILETRIGGERNAME,
+            rpm::IndexTag::RPMTAG_FILETRIGGERINDEX,
+            rpm::IndexTag::RPMTAG_FILETRIGGERFLAGS,
+            rpm::IndexTag::RPMTAG_FILETRIGGERVERSION,
+        ]
+    };
+    let count = triggers.len();
+    let values = [
+        // the scripts
+        Value::StringArray(triggers.iter().map(|t| t.script.clone()).collect()),
+        Value::StringArray(vec![INTERPRETER.to_owned(); count]),
+        Value::Int32(vec![0; count]),
+        Value::Int32(vec![DEFAULT_PRIORITY; count]),
+        // and the conditions, with the index of their script
+        Value::StringArray(triggers.iter().map(|t| t.prefix.clone()).collect()),
+        Value::Int32((0..count as u32).collect()),
+        Value::Int32(triggers.iter().map(|t| t.kind.bits()).collect()),
+        Value::StringArray(vec![String::new(); count]),
+    ];
+    tags.into_iter().zip(values).collect()
+}
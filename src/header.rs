@@ -0,0 +1,327 @@
+//! Setting header tags which rpm's `PackageBuilder` has no option for, such as `RPMTAG_PREFIXES`,
+//! or which its signing doesn't produce, such as the header and payload signature tags.
+//!
+//! The header of a built package is decoded into its entries, the tags are replaced and the
+//! header is encoded again, laid out the way rpm expects: an immutable region covering every
+//! entry, with the data stored in the order of the (sorted) tags.
+
+use anyhow::{Context, Result};
+
+/// The size of the lead in front of the signature header
+const LEAD_SIZE: usize = 96;
+const HEADER_MAGIC: [u8; 8] = [0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0];
+const ENTRY_SIZE: usize = 16;
+
+const TYPE_NULL: u32 = 0;
+const TYPE_CHAR: u32 = 1;
+const TYPE_INT8: u32 = 2;
+const TYPE_INT16: u32 = 3;
+const TYPE_INT32: u32 = 4;
+const TYPE_INT64: u32 = 5;
+const TYPE_STRING: u32 = 6;
+const TYPE_BIN: u32 = 7;
+const TYPE_STRING_ARRAY: u32 = 8;
+const TYPE_I18N_STRING: u32 = 9;
+
+/// The value of a header tag
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int32(Vec<u32>),
+    String(String),
+    Bin(Vec<u8>),
+    StringArray(Vec<String>),
+    /// One string per locale of `RPMTAG_HEADERI18NTABLE`
+    I18nString(Vec<String>),
+}
+
+impl Value {
+    fn encode(&self) -> Result<Entry> {
+        fn strings(values: &[String]) -> Result<Vec<u8>> {
+            let mut data = Vec::new();
+            for value in values {
+                if value.contains('\0') {
+                    anyhow::bail!("header strings can't contain NUL characters: {:?}", value);
+                }
+                data.extend(value.as_bytes());
+                data.push(0);
+            }
+            Ok(data)
+        }
+
+        let (kind, count, data) = match self {
+            Value::Int32(values) => (
+                TYPE_INT32,
+                values.len(),
+                values.iter().flat_map(|v| v.to_be_bytes()).collect(),
+            ),
+            Value::String(value) => (TYPE_STRING, 1, strings(std::slice::from_ref(value))?),
+            Value::Bin(value) => (TYPE_BIN, value.len(), value.clone()),
+            Value::StringArray(values) => (TYPE_STRING_ARRAY, values.len(), strings(values)?),
+            Value::I18nString(values) => (TYPE_I18N_STRING, values.len(), strings(values)?),
+        };
+        if count == 0 {
+            anyhow::bail!("header tags can't be empty");
+        }
+        Ok(Entry {
+            tag: 0,
+            kind,
+            count: count as u32,
+            data,
+        })
+    }
+}
+
+/// An entry of a header, with its data
+#[derive(Debug, Clone)]
+struct Entry {
+    tag: u32,
+    kind: u32,
+    count: u32,
+    data: Vec<u8>,
+}
+
+/// A decoded header, without the entry of its immutable region
+#[derive(Debug)]
+struct Header {
+    region_tag: u32,
+    entries: Vec<Entry>,
+}
+
+fn read_u32(bytes: &[u8], position: usize) -> Result<u32> {
+    let field = bytes
+        .get(position..position + 4)
+        .context("the header is truncated")?;
+    Ok(u32::from_be_bytes(field.try_into()?))
+}
+
+impl Header {
+    /// Decode the header at the start of `bytes`, returning it and its size
+    fn parse(bytes: &[u8]) -> Result<(Header, usize)> {
+        if !bytes.starts_with(&HEADER_MAGIC) {
+            anyhow::bail!("invalid header magic");
+        }
+        let entry_count = read_u32(bytes, 8)? as usize;
+        let data_size = read_u32(bytes, 12)? as usize;
+        let store_start = 16 + entry_count * ENTRY_SIZE;
+        let store = bytes
+            .get(store_start..store_start + data_size)
+            .context("the header is truncated")?;
+
+        let mut region_tag = None;
+        let mut entries = Vec::with_capacity(entry_count);
+        for index in 0..entry_count {
+            let position = 16 + index * ENTRY_SIZE;
+            let tag = read_u32(bytes, position)?;
+            let kind = read_u32(bytes, position + 4)?;
+            let offset = read_u32(bytes, position + 8)? as usize;
+            let count = read_u32(bytes, position + 12)?;
+            // the region is recreated when encoding
+            if index == 0 && (tag == 62 || tag == 63) {
+                region_tag = Some(tag);
+                continue;
+            }
+            let data = store.get(offset..).context("the header is truncated")?;
+            let length = data_length(kind, count, data)
+                .with_context(|| format!("invalid data of header tag {}", tag))?;
+            entries.push(Entry {
+                tag,
+                kind,
+                count,
+                data: data[..length].to_vec(),
+            });
+        }
+        let region_tag = region_tag.context("the header has no immutable region")?;
+        Ok((
+            Header {
+                region_tag,
+                entries,
+            },
+            store_start + data_size,
+        ))
+    }
+
+    fn set(&mut self, tag: u32, value: &Value) -> Result<()> {
+        let entry = Entry {
+            tag,
+            ..value.encode()?
+        };
+        self.entries.retain(|e| e.tag != tag);
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut entries = self.entries.clone();
+        entries.sort_by_key(|entry| entry.tag);
+
+        let mut index = Vec::with_capacity((entries.len() + 1) * ENTRY_SIZE);
+        let mut store = Vec::new();
+        let mut entry_index = Vec::new();
+        for entry in &entries {
+            let alignment = match entry.kind {
+                TYPE_INT16 => 2,
+                TYPE_INT32 => 4,
+                TYPE_INT64 => 8,
+                _ => 1,
+            };
+            store.resize(store.len().next_multiple_of(alignment), 0);
+            write_entry(
+                &mut entry_index,
+                entry.tag,
+                entry.kind,
+                store.len() as i32,
+                entry.count,
+            );
+            store.extend(&entry.data);
+        }
+
+        // the trailer of the region is an entry pointing back at the start of the index
+        let trailer_offset = store.len() as i32;
+        let region_size = ((entries.len() + 1) * ENTRY_SIZE) as i32;
+        let mut trailer = Vec::with_capacity(ENTRY_SIZE);
+        write_entry(&mut trailer, self.region_tag, TYPE_BIN, -region_size, 16);
+        store.extend(trailer);
+        write_entry(&mut index, self.region_tag, TYPE_BIN, trailer_offset, 16);
+        index.extend(entry_index);
+
+        let mut bytes = HEADER_MAGIC.to_vec();
+        bytes.extend(((entries.len() + 1) as u32).to_be_bytes());
+        bytes.extend((store.len() as u32).to_be_bytes());
+        bytes.extend(index);
+        bytes.extend(store);
+        bytes
+    }
+}
+
+fn write_entry(index: &mut Vec<u8>, tag: u32, kind: u32, offset: i32, count: u32) {
+    index.extend(tag.to_be_bytes());
+    index.extend(kind.to_be_bytes());
+    index.extend(offset.to_be_bytes());
+    index.extend(count.to_be_bytes());
+}
+
+/// The size of the data of an entry
+fn data_length(kind: u32, count: u32, data: &[u8]) -> Result<usize> {
+    let count = count as usize;
+    let length = match kind {
+        TYPE_NULL => 0,
+        TYPE_CHAR | TYPE_INT8 | TYPE_BIN => count,
+        TYPE_INT16 => count * 2,
+        TYPE_INT32 => count * 4,
+        TYPE_INT64 => count * 8,
+        TYPE_STRING | TYPE_STRING_ARRAY | TYPE_I18N_STRING => {
+            let strings = if kind == TYPE_STRING { 1 } else { count };
+            let mut length = 0;
+            for _ in 0..strings {
+                let end = data[length..]
+                    .iter()
+                    .position(|&b| b == 0)
+                    .context("unterminated string")?;
+                length += end + 1;
+            }
+            length
+        }
+        _ => anyhow::bail!("unknown data type {}", kind),
+    };
+    if length > data.len() {
+        anyhow::bail!("the data is truncated");
+    }
+    Ok(length)
+}
+
+/// Encode the metadata of a package, returning it with the position of the main header, which
+/// follows the signature header padded to 8 bytes
+fn encode_metadata(pkg: &rpm::Package) -> Result<(Vec<u8>, usize)> {
+    let mut metadata = Vec::new();
+    pkg.metadata.write(&mut metadata)?;
+    let (_, signature_size) =
+        Header::parse(&metadata[LEAD_SIZE..]).context("unable to decode the signature header")?;
+    let header_start = LEAD_SIZE + signature_size.next_multiple_of(8);
+    Ok((metadata, header_start))
+}
+
+/// The encoded main header of a package, which is what header signatures are computed over
+pub fn header_bytes(pkg: &rpm::Package) -> Result<Vec<u8>> {
+    let (metadata, header_start) = encode_metadata(pkg)?;
+    Ok(metadata[header_start..].to_vec())
+}
+
+/// Set tags in the main header of a package, replacing any values they already have. The
+/// signature header is recreated without signatures, since they don't match the new header.
+pub fn set_tags(pkg: rpm::Package, tags: &[(rpm::IndexTag, Value)]) -> Result<rpm::Package> {
+    if tags.is_empty() {
+        return Ok(pkg);
+    }
+    let (mut metadata, header_start) = encode_metadata(&pkg)?;
+    let (mut header, _) =
+        Header::parse(&metadata[header_start..]).context("unable to decode the package header")?;
+    for (tag, value) in tags {
+        header
+            .set(*tag as u32, value)
+            .with_context(|| format!("invalid value for {}", tag))?;
+    }
+    metadata.truncate(header_start);
+    metadata.extend(header.encode());
+
+    let metadata = rpm::PackageMetadata::parse(&mut metadata.as_slice())
+        .context("unable to read back the modified package header")?;
+    let mut pkg = rpm::Package {
+        metadata,
+        content: pkg.content,
+    };
+    pkg.clear_signatures()?;
+    Ok(pkg)
+}
+
+/// Set tags in the signature header of a package, replacing any values they already have
+pub fn set_signature_tags(
+    pkg: rpm::Package,
+    tags: &[(rpm::IndexSignatureTag, Value)],
+) -> Result<rpm::Package> {
+    if tags.is_empty() {
+        return Ok(pkg);
+    }
+    modify_signature_header(pkg, |signature| {
+        for (tag, value) in tags {
+            signature
+                .set(*tag as u32, value)
+                .with_context(|| format!("invalid value for {}", tag))?;
+        }
+        Ok(())
+    })
+}
+
+/// Remove tags from the signature header of a package, if it has them
+pub fn remove_signature_tags(
+    pkg: rpm::Package,
+    tags: &[rpm::IndexSignatureTag],
+) -> Result<rpm::Package> {
+    modify_signature_header(pkg, |signature| {
+        signature
+            .entries
+            .retain(|entry| !tags.iter().any(|tag| *tag as u32 == entry.tag));
+        Ok(())
+    })
+}
+
+fn modify_signature_header(
+    pkg: rpm::Package,
+    modify: impl FnOnce(&mut Header) -> Result<()>,
+) -> Result<rpm::Package> {
+    let (metadata, header_start) = encode_metadata(&pkg)?;
+    let (mut signature, _) =
+        Header::parse(&metadata[LEAD_SIZE..]).context("unable to decode the signature header")?;
+    modify(&mut signature)?;
+    let mut signature = signature.encode();
+    signature.resize(signature.len().next_multiple_of(8), 0);
+
+    let mut encoded = metadata[..LEAD_SIZE].to_vec();
+    encoded.extend(signature);
+    encoded.extend(&metadata[header_start..]);
+    let metadata = rpm::PackageMetadata::parse(&mut encoded.as_slice())
+        .context("unable to read back the modified signature header")?;
+    Ok(rpm::Package {
+        metadata,
+        content: pkg.content,
+    })
+}
@@ -0,0 +1,393 @@
+use anyhow::{Context, Result};
+use clap_derive::{Args, ValueEnum};
+use serde::Serialize;
+
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct InspectArgs {
+    #[arg(value_name = "PACKAGE", help = "Path to the rpm to inspect")]
+    pub package: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        default_value = "text",
+        help = "Specify the output format"
+    )]
+    pub format: OutputFormat,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Everything `inspect` reports about a package
+#[derive(Serialize, Debug)]
+pub struct PackageInfo {
+    pub name: String,
+    pub epoch: u32,
+    pub version: String,
+    pub release: String,
+    pub arch: String,
+    pub nevra: String,
+    pub license: String,
+    pub url: Option<String>,
+    pub vendor: Option<String>,
+    pub summary: String,
+    pub description: String,
+    pub build_time: u64,
+    pub build_host: Option<String>,
+    pub compression: String,
+    pub provides: Vec<DependencyInfo>,
+    pub requires: Vec<DependencyInfo>,
+    pub obsoletes: Vec<DependencyInfo>,
+    pub conflicts: Vec<DependencyInfo>,
+    pub suggests: Vec<DependencyInfo>,
+    pub recommends: Vec<DependencyInfo>,
+    pub enhances: Vec<DependencyInfo>,
+    pub supplements: Vec<DependencyInfo>,
+    pub files: Vec<FileInfo>,
+    pub scriptlets: Vec<ScriptletInfo>,
+    pub changelog: Vec<ChangelogInfo>,
+    pub signature: SignatureInfo,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DependencyInfo {
+    pub name: String,
+    /// The comparison operator, e.g. `>=`, or `None` if any version satisfies the dependency
+    pub operator: Option<&'static str>,
+    pub version: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FileInfo {
+    pub path: PathBuf,
+    pub mode: u16,
+    pub user: String,
+    pub group: String,
+    pub size: usize,
+    pub digest: Option<String>,
+    pub linkto: Option<String>,
+    pub caps: Option<String>,
+    /// The `%verify` argument of the checks `rpm -V` skips, e.g. `not md5 size mtime`
+    pub verify: Option<String>,
+    pub flags: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScriptletInfo {
+    pub kind: &'static str,
+    pub interpreter: Option<Vec<String>>,
+    pub script: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChangelogInfo {
+    pub name: String,
+    pub timestamp: u64,
+    pub description: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SignatureInfo {
+    pub signed: bool,
+    pub key_ids: Vec<String>,
+}
+
+pub fn inspect(args: InspectArgs) -> Result<()> {
+    let pkg = rpm::Package::open(&args.package)
+        .with_context(|| format!("unable to read package {:?}", &args.package))?;
+    let info = PackageInfo::from_package(&pkg)
+        .with_context(|| format!("unable to read the metadata of {:?}", &args.package))?;
+
+    match args.format {
+        OutputFormat::Text => print_text(&info),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&info)?),
+    }
+
+    Ok(())
+}
+
+/// The checks of `rpm -V` with their `%verify` names, where md5 is the file digest
+const VERIFY_CHECKS: [(&str, rpm::FileVerifyFlags); 9] = [
+    ("md5", rpm::FileVerifyFlags::MD5),
+    ("size", rpm::FileVerifyFlags::FILESIZE),
+    ("link", rpm::FileVerifyFlags::LINKTO),
+    ("user", rpm::FileVerifyFlags::USER),
+    ("group", rpm::FileVerifyFlags::GROUP),
+    ("mtime", rpm::FileVerifyFlags::MTIME),
+    ("mode", rpm::FileVerifyFlags::MODE),
+    ("rdev", rpm::FileVerifyFlags::RDEV),
+    ("caps", rpm::FileVerifyFlags::CAPS),
+];
+
+/// The `%verify` argument of the checks which the verify flags of a file skip, if any
+fn skipped_checks(flags: u32) -> Option<String> {
+    let flags = rpm::FileVerifyFlags::from_bits_retain(flags);
+    let skipped: Vec<&str> = VERIFY_CHECKS
+        .iter()
+        .filter(|(_, check)| !flags.contains(*check))
+        .map(|(name, _)| *name)
+        .collect();
+    (!skipped.is_empty()).then(|| format!("not {}", skipped.join(" ")))
+}
+
+impl PackageInfo {
+    pub fn from_package(pkg: &rpm::Package) -> Result<PackageInfo> {
+        let metadata = &pkg.metadata;
+        let dependencies = |dependencies: Vec<rpm::Dependency>| -> Vec<DependencyInfo> {
+            dependencies.iter().map(DependencyInfo::from).collect()
+        };
+
+        let verify_flags = metadata
+            .header
+            .get_entry_data_as_u32_array(rpm::IndexTag::RPMTAG_FILEVERIFYFLAGS)
+            .unwrap_or_default();
+        let files = metadata
+            .get_file_entries()?
+            .into_iter()
+            .enumerate()
+            .map(|(i, file)| FileInfo {
+                mode: file.mode.raw_mode(),
+                user: file.ownership.user,
+                group: file.ownership.group,
+                size: file.size,
+                digest: file.digest.map(|digest| digest.as_hex().to_owned()),
+                linkto: Some(file.linkto).filter(|linkto| !linkto.is_empty()),
+                caps: file.caps,
+                verify: verify_flags.get(i).and_then(|&flags| skipped_checks(flags)),
+                flags: file
+                    .flags
+                    .iter_names()
+                    .map(|(name, _)| name.to_lowercase())
+                    .collect(),
+                path: file.path,
+            })
+            .collect();
+
+        // the getters fail if the package doesn't have the scriptlet
+        let scriptlets = [
+            ("pre-install", metadata.get_pre_install_script()),
+            ("post-install", metadata.get_post_install_script()),
+            ("pre-uninstall", metadata.get_pre_uninstall_script()),
+            ("post-uninstall", metadata.get_post_uninstall_script()),
+            ("pre-trans", metadata.get_pre_trans_script()),
+            ("post-trans", metadata.get_post_trans_script()),
+            ("pre-untrans", metadata.get_pre_untrans_script()),
+            ("post-untrans", metadata.get_post_untrans_script()),
+            ("verify", verify_script(metadata)),
+        ]
+        .into_iter()
+        .filter_map(|(kind, scriptlet)| {
+            let scriptlet = scriptlet.ok()?;
+            Some(ScriptletInfo {
+                kind,
+                interpreter: scriptlet.program,
+                script: scriptlet.script,
+            })
+        })
+        .collect();
+
+        let changelog = metadata
+            .get_changelog_entries()?
+            .into_iter()
+            .map(|entry| ChangelogInfo {
+                name: entry.name,
+                timestamp: entry.timestamp,
+                description: entry.description,
+            })
+            .collect();
+
+        let signed = [
+            rpm::IndexSignatureTag::RPMSIGTAG_OPENPGP,
+            rpm::IndexSignatureTag::RPMSIGTAG_RSA,
+            rpm::IndexSignatureTag::RPMSIGTAG_DSA,
+            rpm::IndexSignatureTag::RPMSIGTAG_PGP,
+            rpm::IndexSignatureTag::RPMSIGTAG_GPG,
+        ]
+        .into_iter()
+        .any(|tag| metadata.signature.entry_is_present(tag));
+        let signature = SignatureInfo {
+            signed,
+            // not every signature records the id of the key it was made with
+            key_ids: pkg.signature_key_ids().unwrap_or_default(),
+        };
+
+        Ok(PackageInfo {
+            name: metadata.get_name()?.to_owned(),
+            epoch: metadata.get_epoch().unwrap_or(0),
+            version: metadata.get_version()?.to_owned(),
+            release: metadata.get_release()?.to_owned(),
+            arch: metadata.get_arch()?.to_owned(),
+            nevra: metadata.get_nevra()?.to_string(),
+            license: metadata.get_license()?.to_owned(),
+            url: metadata.get_url().ok().map(str::to_owned),
+            vendor: metadata.get_vendor().ok().map(str::to_owned),
+            summary: metadata.get_summary()?.to_owned(),
+            description: metadata.get_description().unwrap_or("").to_owned(),
+            build_time: metadata.get_build_time()?,
+            build_host: metadata.get_build_host().ok().map(str::to_owned),
+            compression: metadata.get_payload_compressor()?.to_string(),
+            provides: dependencies(metadata.get_provides()?),
+            requires: dependencies(metadata.get_requires()?),
+            obsoletes: dependencies(metadata.get_obsoletes()?),
+            conflicts: dependencies(metadata.get_conflicts()?),
+            suggests: dependencies(metadata.get_suggests()?),
+            recommends: dependencies(metadata.get_recommends()?),
+            enhances: dependencies(metadata.get_enhances()?),
+            supplements: dependencies(metadata.get_supplements()?),
+            files,
+            scriptlets,
+            changelog,
+            signature,
+        })
+    }
+}
+
+impl From<&rpm::Dependency> for DependencyInfo {
+    fn from(dependency: &rpm::Dependency) -> Self {
+        let flags = dependency.flags;
+        let operator = if flags.contains(rpm::DependencyFlags::GE) {
+            Some(">=")
+        } else if flags.contains(rpm::DependencyFlags::LE) {
+            Some("<=")
+        } else if flags.contains(rpm::DependencyFlags::GREATER) {
+            Some(">")
+        } else if flags.contains(rpm::DependencyFlags::LESS) {
+            Some("<")
+        } else if flags.contains(rpm::DependencyFlags::EQUAL) {
+            Some("=")
+        } else {
+            None
+        };
+        DependencyInfo {
+            name: dependency.name.clone(),
+            operator,
+            version: operator.map(|_| dependency.version.clone()),
+        }
+    }
+}
+
+/// Format a dependency the same way it would be written in a spec file, e.g. `foo >= 1.0`
+impl fmt::Display for DependencyInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.operator, &self.version) {
+            (Some(operator), Some(version)) => write!(f, "{} {} {}", self.name, operator, version),
+            _ => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// The `%verifyscript` of a package, which the rpm crate has no getter for
+fn verify_script(metadata: &rpm::PackageMetadata) -> Result<rpm::Scriptlet, rpm::Error> {
+    let header = &metadata.header;
+    let script = header.get_entry_data_as_string(rpm::IndexTag::RPMTAG_VERIFYSCRIPT)?;
+    let mut scriptlet = rpm::Scriptlet::new(script);
+    scriptlet.flags = header
+        .get_entry_data_as_u32(rpm::IndexTag::RPMTAG_VERIFYSCRIPTFLAGS)
+        .ok()
+        .map(rpm::ScriptletFlags::from_bits_retain);
+    scriptlet.program = header
+        .get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_VERIFYSCRIPTPROG)
+        .ok()
+        .map(|program| program.to_owned());
+    Ok(scriptlet)
+}
+
+/// Print the information in the same format as `inspect`
+pub fn print_text(info: &PackageInfo) {
+    println!("Name         : {}", info.name);
+    println!("Epoch        : {}", info.epoch);
+    println!("Version      : {}", info.version);
+    println!("Release      : {}", info.release);
+    println!("Architecture : {}", info.arch);
+    println!("NEVRA        : {}", info.nevra);
+    println!("License      : {}", info.license);
+    println!("URL          : {}", info.url.as_deref().unwrap_or(""));
+    println!("Vendor       : {}", info.vendor.as_deref().unwrap_or(""));
+    println!("Summary      : {}", info.summary);
+    println!("Description  : {}", info.description);
+    println!("Build Time   : {}", info.build_time);
+    println!(
+        "Build Host   : {}",
+        info.build_host.as_deref().unwrap_or("")
+    );
+    println!("Compression  : {}", info.compression);
+    match (info.signature.signed, info.signature.key_ids.as_slice()) {
+        (false, _) => println!("Signature    : none"),
+        (true, []) => println!("Signature    : signed"),
+        (true, key_ids) => println!("Signature    : signed with {}", key_ids.join(", ")),
+    }
+
+    let dependency_sets = [
+        ("Provides", &info.provides),
+        ("Requires", &info.requires),
+        ("Obsoletes", &info.obsoletes),
+        ("Conflicts", &info.conflicts),
+        ("Suggests", &info.suggests),
+        ("Recommends", &info.recommends),
+        ("Enhances", &info.enhances),
+        ("Supplements", &info.supplements),
+    ];
+    for (kind, dependencies) in dependency_sets {
+        if dependencies.is_empty() {
+            continue;
+        }
+        println!("{}:", kind);
+        for dependency in dependencies {
+            println!("  {}", dependency);
+        }
+    }
+
+    if !info.files.is_empty() {
+        println!("Files:");
+        for file in &info.files {
+            match &file.linkto {
+                Some(linkto) => println!(
+                    "  {:o} {}:{} {} -> {}",
+                    file.mode,
+                    file.user,
+                    file.group,
+                    file.path.display(),
+                    linkto
+                ),
+                None => println!(
+                    "  {:o} {}:{} {}",
+                    file.mode,
+                    file.user,
+                    file.group,
+                    file.path.display()
+                ),
+            }
+        }
+    }
+
+    if !info.scriptlets.is_empty() {
+        println!("Scriptlets:");
+        for scriptlet in &info.scriptlets {
+            match &scriptlet.interpreter {
+                Some(interpreter) => println!("  {} ({}):", scriptlet.kind, interpreter.join(" ")),
+                None => println!("  {}:", scriptlet.kind),
+            }
+            for line in scriptlet.script.lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+
+    if !info.changelog.is_empty() {
+        println!("Changelog:");
+        for entry in &info.changelog {
+            println!(
+                "  {} ({}): {}",
+                entry.name, entry.timestamp, entry.description
+            );
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Build rpms without spec files.
+//!
+//! This is the library behind the `rpm-builder` command line tool. Every subcommand lives in its
+//! own module, with its arguments in a clap `Args` struct and a function that runs it. Packages
+//! can also be built without going through the command line:
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! let mut args = rpm_builder::BuildArgs::new("awesome");
+//! args.version = "1.2.0".to_owned();
+//! args.exec_file.push("target/release/awesome:/usr/bin/awesome".to_owned());
+//! args.requires.push("wget >= 1.0.0".to_owned());
+//!
+//! let pkg = rpm_builder::build::package(args)?;
+//! pkg.write_file("awesome-1.2.0-1.noarch.rpm")?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod archive;
+pub mod build;
+pub mod cargo;
+pub mod changelog;
+pub mod completions;
+pub mod depgen;
+pub mod diff;
+pub mod edit;
+pub mod extract;
+pub mod filetrigger;
+pub mod header;
+pub mod inspect;
+pub mod lint;
+pub mod man;
+pub mod manifest;
+pub mod manpage;
+pub mod modulemd;
+pub mod oci;
+pub mod publish;
+pub mod repo;
+pub mod selinux;
+pub mod sign;
+pub mod spec;
+pub mod systemd;
+pub mod sysusers;
+pub mod template;
+pub mod timings;
+pub mod verify;
+pub mod version;
+
+pub use build::BuildArgs;
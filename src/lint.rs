@@ -0,0 +1,185 @@
+use anyhow::{Context, Result};
+use clap_derive::Args;
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Args, Debug)]
+pub struct LintArgs {
+    #[arg(value_name = "PACKAGE", help = "Path to the rpm to check")]
+    pub package: PathBuf,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message),
+            Severity::Warning => write!(f, "warning: {}", self.message),
+        }
+    }
+}
+
+pub fn lint(args: LintArgs) -> Result<()> {
+    let pkg = rpm::Package::open(&args.package)
+        .with_context(|| format!("unable to read package {:?}", &args.package))?;
+    let findings = check_package(&pkg)
+        .with_context(|| format!("unable to check package {:?}", &args.package))?;
+    report(&findings)
+}
+
+/// Print the findings to stderr, and fail if any of them is an error
+pub fn report(findings: &[Finding]) -> Result<()> {
+    for finding in findings {
+        eprintln!("{}", finding);
+    }
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        anyhow::bail!("the package has {} lint error(s)", errors);
+    }
+    Ok(())
+}
+
+/// Check the destinations of the files added to a package. rpm turns `./` paths into absolute
+/// ones and only keeps the first of several files with the same path, so these mistakes can't be
+/// found in the package itself.
+pub fn check_destinations(destinations: &[String]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut seen = HashSet::new();
+    let mut duplicates = BTreeSet::new();
+    for destination in destinations {
+        if !destination.starts_with('/') {
+            findings.push(Finding {
+                severity: Severity::Error,
+                message: format!("destination path {} is not absolute", destination),
+            });
+        }
+        if !seen.insert(destination.as_str()) {
+            duplicates.insert(destination.as_str());
+        }
+    }
+    for duplicate in duplicates {
+        findings.push(Finding {
+            severity: Severity::Error,
+            message: format!(
+                "{} was added more than once, only the first file is packaged",
+                duplicate
+            ),
+        });
+    }
+    findings
+}
+
+/// Run the checks which only need the package
+pub fn check_package(pkg: &rpm::Package) -> Result<Vec<Finding>> {
+    let metadata = &pkg.metadata;
+    let mut findings = Vec::new();
+    let mut error = |message: String| {
+        findings.push(Finding {
+            severity: Severity::Error,
+            message,
+        })
+    };
+
+    if metadata.get_license()?.trim().is_empty() {
+        error("the package has no license".to_owned());
+    }
+
+    let files = metadata.get_file_entries()?;
+    for file in &files {
+        if file
+            .path
+            .components()
+            .any(|c| c == Component::ParentDir || c == Component::CurDir)
+        {
+            error(format!(
+                "path {} contains '.' or '..' components",
+                file.path.display()
+            ));
+        }
+    }
+
+    // relative symlinks point into the package itself, so their targets are expected to be in it
+    let mut paths: HashSet<PathBuf> = HashSet::new();
+    for file in &files {
+        paths.extend(file.path.ancestors().map(Path::to_path_buf));
+    }
+    for file in &files {
+        if !matches!(file.mode, rpm::FileMode::SymbolicLink { .. }) || file.linkto.starts_with('/')
+        {
+            continue;
+        }
+        let link_dir = file.path.parent().unwrap_or(Path::new("/"));
+        let target = normalize(&link_dir.join(&file.linkto));
+        if !paths.contains(&target) {
+            error(format!(
+                "symlink {} points to {}, which isn't in the package",
+                file.path.display(),
+                target.display()
+            ));
+        }
+    }
+
+    let mut warning = |message: String| {
+        findings.push(Finding {
+            severity: Severity::Warning,
+            message,
+        })
+    };
+
+    let summary = metadata.get_summary()?;
+    if summary.trim().is_empty() {
+        warning("the package has no summary".to_owned());
+    } else if summary.ends_with('.') {
+        warning(format!("the summary ends with a period: {}", summary));
+    }
+
+    for file in pkg.files()? {
+        let file = file?;
+        let is_executable = matches!(file.metadata.mode, rpm::FileMode::Regular { .. })
+            && file.metadata.mode.permissions() & 0o111 != 0;
+        if is_executable
+            && !file.content.is_empty()
+            && !file.content.starts_with(b"#!")
+            && !file.content.starts_with(b"\x7fELF")
+        {
+            warning(format!(
+                "executable {} is neither a script with a shebang nor an ELF binary",
+                file.metadata.path.display()
+            ));
+        }
+    }
+
+    findings.sort_by(|a, b| a.severity.cmp(&b.severity));
+    Ok(findings)
+}
+
+/// Resolve `.` and `..` in an absolute path without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(part) => normalized.push(part),
+            _ => {}
+        }
+    }
+    normalized
+}
@@ -1,524 +1,491 @@
 use anyhow::{Context, Result};
-use clap::Parser;
-use clap_derive::{Parser, ValueEnum};
-use regex::Regex;
+use clap::{Parser, ValueEnum};
+use clap_derive::{Parser, Subcommand};
+use serde::Serialize;
 
-use std::fs;
+use std::ffi::OsString;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use rpm_builder::cargo::CargoPackage;
+use rpm_builder::inspect::OutputFormat;
+use rpm_builder::manifest::{CONFIG_FILE, Config, Manifest};
+use rpm_builder::spec::SpecFile;
+use rpm_builder::{
+    build, completions, diff, edit, extract, inspect, lint, manpage, publish, repo, sign, verify,
+    version,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "rpm-builder", about = "Build RPMs with ease")]
 pub struct Cli {
-    #[arg(short = 'o', long, value_name = "OUT", help = "Specify an out file")]
-    pub out: Option<PathBuf>,
-
-    #[arg(help = "Specify the name of your package")]
-    pub name: String,
-
-    #[arg(
-        long,
-        value_name = "EPOCH",
-        default_value = "0",
-        help = "Specify an epoch"
-    )]
-    pub epoch: u32,
-
-    #[arg(
-        long,
-        value_name = "VERSION",
-        default_value = "1.0.0",
-        help = "Specify a version"
-    )]
-    pub version: String,
+    #[command(subcommand)]
+    pub command: Command,
 
     #[arg(
+        short,
         long,
-        value_name = "RELEASE",
-        default_value = "1",
-        help = "Specify release number of the package"
+        global = true,
+        action = clap::ArgAction::Count,
+        help = "Log the files, dependencies and signatures which go into a package, -vv for more details"
     )]
-    pub release: String,
+    pub verbose: u8,
 
     #[arg(
         long,
-        value_name = "ARCH",
-        default_value = "noarch",
-        help = "Specify the target architecture"
-    )]
-    pub arch: String,
-
-    #[arg(
-        long,
-        value_name = "LICENSE",
-        default_value = "MIT",
-        help = "Specify a license"
-    )]
-    pub license: String,
-
-    #[arg(
-        long,
-        value_name = "SUMMARY",
-        default_value = "",
-        help = "Give a simple description of the package"
-    )]
-    pub summary: String,
-
-    #[arg(long, value_name = "FILE", help = "Add a regular file to the rpm")]
-    pub file: Vec<String>,
-
-    #[arg(
-        long,
-        value_name = "EXEC_FILE",
-        help = "Add an executable file to the rpm"
-    )]
-    pub exec_file: Vec<String>,
-
-    #[arg(
-        long,
-        value_name = "DOC_FILE",
-        help = "Add a documentation file to the rpm"
-    )]
-    pub doc_file: Vec<String>,
-
-    #[arg(
-        long,
-        value_name = "CONFIG_FILE",
-        help = "Add a config file to the rpm"
-    )]
-    pub config_file: Vec<String>,
-
-    #[arg(
-        long,
-        value_name = "DIR",
-        help = "Add a directory and all its files to the rpm"
-    )]
-    pub dir: Vec<String>,
-
-    #[arg(
-        long,
-        value_name = "DOC_DIR",
-        help = "Add a documentation directory and all its files to the rpm"
-    )]
-    pub doc_dir: Vec<String>,
-
-    #[arg(
-        long,
-        value_name = "CONFIG_DIR",
-        help = "Add a config directory and all its files to the rpm"
+        global = true,
+        value_name = "LOG_FORMAT",
+        value_enum,
+        default_value = "text",
+        help = "The format of the log messages written to stderr"
     )]
-    pub config_dir: Vec<String>,
+    pub log_format: OutputFormat,
 
     #[arg(
         long,
-        value_name = "COMPRESSION",
+        global = true,
+        value_name = "ERROR_FORMAT",
         value_enum,
-        help = "Specify the compression algorithm."
+        default_value = "text",
+        help = "The format of the error written to stderr if a command fails, json prints an object with the kind of error, the exit code, the message and its causes"
     )]
-    pub compression: Option<Compression>,
+    pub error_format: OutputFormat,
 
     #[arg(
         long,
-        value_name = "CHANGELOG_ENTRY",
-        help = "Add a changelog entry to the rpm. The entry has the form <author>:<content>:<yyyy-mm-dd> (time is in UTC)"
+        global = true,
+        value_name = "CONFIG",
+        help = "Take the default options of the subcommands from a config file, ./rpm-builder.toml is used if it exists"
     )]
-    pub changelog: Vec<String>,
+    pub config: Option<PathBuf>,
+}
 
-    #[arg(
-        long,
-        value_name = "REQUIRES",
-        help = "Indicates that the rpm requires another package. Use the format '<name> [>|>=|=|<=|< version]'"
-    )]
-    pub requires: Vec<String>,
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(about = "Build a new rpm", args_override_self = true)]
+    Build(Box<build::BuildArgs>),
 
-    #[arg(
-        long,
-        value_name = "PROVIDES",
-        help = "Indicates that the rpm provides another package. Use the format '<name> [>|>=|=|<=|< version]'"
-    )]
-    pub provides: Vec<String>,
+    #[command(about = "Print the shell completions of rpm-builder")]
+    Completions(completions::CompletionsArgs),
 
-    #[arg(
-        long,
-        value_name = "OBSOLETES",
-        help = "Indicates that the rpm obsoletes another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    #[command(
+        about = "Show the differences between two rpms",
+        args_override_self = true
     )]
-    pub obsoletes: Vec<String>,
+    Diff(diff::DiffArgs),
 
-    #[arg(
-        long,
-        value_name = "CONFLICTS",
-        help = "Indicates that the rpm conflicts with another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    #[command(
+        about = "Change the metadata of an existing rpm without rebuilding its payload",
+        args_override_self = true
     )]
-    pub conflicts: Vec<String>,
+    Edit(edit::EditArgs),
 
-    #[arg(
-        long,
-        value_name = "SUGGESTS",
-        help = "Indicates that the rpm suggests another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    #[command(
+        about = "Extract the files of an existing rpm into a directory",
+        args_override_self = true
     )]
-    pub suggests: Vec<String>,
+    Extract(extract::ExtractArgs),
 
-    #[arg(
-        long,
-        value_name = "ENHANCES",
-        help = "Indicates that the rpm enhances another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    #[command(
+        about = "Display the metadata of an existing rpm",
+        args_override_self = true
     )]
-    pub enhances: Vec<String>,
+    Inspect(inspect::InspectArgs),
 
-    #[arg(
-        long,
-        value_name = "RECOMMENDS",
-        help = "Indicates that the rpm recommends another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    #[command(
+        about = "Check an existing rpm for common mistakes",
+        args_override_self = true
     )]
-    pub recommends: Vec<String>,
+    Lint(lint::LintArgs),
 
-    #[arg(
-        long,
-        value_name = "SUPPLEMENTS",
-        help = "Indicates that the rpm supplements another package. Use the format '<name> [>|>=|=|<=|< version]'"
+    #[command(
+        about = "Generate the man pages of rpm-builder",
+        args_override_self = true
     )]
-    pub supplements: Vec<String>,
+    Manpage(manpage::ManpageArgs),
 
-    #[arg(
-        long,
-        value_name = "PRE_INSTALL_SCRIPT",
-        help = "Path to a file that contains the pre-installation script"
+    #[command(
+        about = "Publish rpms to the repository of an artifact store",
+        args_override_self = true
     )]
-    pub pre_install_script: Option<PathBuf>,
+    Publish(publish::PublishArgs),
 
-    #[arg(
-        long,
-        value_name = "POST_INSTALL_SCRIPT",
-        help = "Path to a file that contains the post-installation script"
+    #[command(
+        about = "Generate the repository metadata (repodata/) of a directory of rpms",
+        args_override_self = true
     )]
-    pub post_install_script: Option<PathBuf>,
+    Repo(repo::RepoArgs),
 
-    #[arg(
-        long,
-        value_name = "PRE_UNINSTALL_SCRIPT",
-        help = "Path to a file that contains a pre-uninstall script"
-    )]
-    pub pre_uninstall_script: Option<PathBuf>,
+    #[command(about = "Sign an existing rpm", args_override_self = true)]
+    Sign(sign::SignArgs),
 
-    #[arg(
-        long,
-        value_name = "POST_UNINSTALL_SCRIPT",
-        help = "Path to a file that contains a post-uninstall script"
+    #[command(
+        about = "Check the digests and signatures of an existing rpm",
+        args_override_self = true
     )]
-    pub post_uninstall_script: Option<PathBuf>,
+    Verify(verify::VerifyArgs),
 
-    #[arg(
-        long,
-        value_name = "RPM_FORMAT",
-        value_enum,
-        help = "Specify the RPM spec format to use when building the package."
-    )]
-    pub rpm_format: Option<RpmVersion>,
-
-    #[arg(
-        long,
-        value_name = "SIGN_WITH_PGP_ASC",
-        help = "Sign this package with the specified PGP secret key"
-    )]
-    pub sign_with_pgp_asc: Option<PathBuf>,
-}
-
-#[derive(ValueEnum, Clone, Debug)]
-pub enum Compression {
-    Gzip,
-    Zstd,
-    None,
+    #[command(about = "Compare versions the way rpm does")]
+    Version(version::VersionArgs),
 }
 
-#[derive(ValueEnum, Clone, Debug)]
-pub enum RpmVersion {
-    V4,
-    V6,
+fn main() -> ExitCode {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    // the format is needed for errors in the command line as well, so it can't wait for clap
+    let error_format = option_value(&argv, "--error-format")
+        .and_then(|value| OutputFormat::from_str(&value.to_string_lossy(), false).ok())
+        .unwrap_or(OutputFormat::Text);
+    match run(argv) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => report_error(&error, &error_format),
+    }
 }
 
-fn main() -> Result<()> {
-    let args = Cli::parse();
-
-    let compression = match args.compression {
-        Some(Compression::Gzip) => rpm::CompressionType::Gzip,
-        Some(Compression::Zstd) => rpm::CompressionType::Zstd,
-        Some(Compression::None) => rpm::CompressionType::None,
-        _ => rpm::CompressionType::default(),
-    };
-
-    let config = match args.rpm_format {
-        Some(RpmVersion::V4) => rpm::BuildConfig::v4(),
-        Some(RpmVersion::V6) => rpm::BuildConfig::v6(),
-        None => rpm::BuildConfig::default(),
-    }
-    .compression(compression);
-
-    let mut builder = rpm::PackageBuilder::new(
-        &args.name,
-        &args.version,
-        &args.license,
-        &args.arch,
-        &args.summary,
-    )
-    .using_config(config)
-    .release(args.release)
-    .epoch(args.epoch);
-
-    for (src, options) in parse_file_options(&args.file)? {
-        builder = builder
-            .with_file(src, options)
-            .with_context(|| format!("error adding regular file {}", src))?;
+fn run(argv: Vec<OsString>) -> Result<()> {
+    let cli = parse_args(argv)?;
+    init_logging(cli.verbose, &cli.log_format);
+    match cli.command {
+        Command::Build(args) => build::build(*args),
+        Command::Completions(args) => {
+            completions::completions(args, <Cli as clap::CommandFactory>::command())
+        }
+        Command::Diff(args) => diff::diff(args),
+        Command::Edit(args) => edit::edit(args),
+        Command::Extract(args) => extract::extract(args),
+        Command::Inspect(args) => inspect::inspect(args),
+        Command::Lint(args) => lint::lint(args),
+        Command::Manpage(args) => manpage::manpage(args, <Cli as clap::CommandFactory>::command()),
+        Command::Publish(args) => publish::publish(args),
+        Command::Repo(args) => repo::repo(args),
+        Command::Sign(args) => sign::sign(args),
+        Command::Verify(args) => verify::verify(args),
+        Command::Version(args) => version::version(args),
     }
+}
 
-    for (src, options) in parse_file_options(&args.exec_file)? {
-        builder = builder
-            .with_file(src, options.mode(0o100755))
-            .with_context(|| format!("error adding executable file {}", src))?;
-    }
+/// The cause of a failure, which decides the exit code
+#[derive(Clone, Copy, Debug)]
+enum ErrorKind {
+    /// Building, inspecting, ... failed for any other reason, e.g. an invalid value of an option
+    Build = 1,
+    /// The command line couldn't be parsed
+    Argument = 2,
+    /// A file couldn't be read or written
+    Io = 3,
+    /// A key couldn't be loaded, or signing or checking a signature failed
+    Signing = 4,
+}
 
-    for (src, options) in parse_file_options(&args.config_file)? {
-        builder = builder
-            .with_file(src, options.is_config())
-            .with_context(|| format!("error adding config file {}", src))?;
+impl ErrorKind {
+    /// The kind of an error, found from the first of its causes which is known
+    fn of(error: &anyhow::Error) -> ErrorKind {
+        for cause in error.chain() {
+            if cause.is::<clap::Error>() {
+                return ErrorKind::Argument;
+            }
+            if cause.is::<std::io::Error>() {
+                return ErrorKind::Io;
+            }
+            match cause.downcast_ref::<rpm::Error>() {
+                Some(rpm::Error::Io(_)) => return ErrorKind::Io,
+                Some(
+                    rpm::Error::SignError(_)
+                    | rpm::Error::KeyLoadUtf8Error(..)
+                    | rpm::Error::KeyLoadSecretKeyError(..)
+                    | rpm::Error::KeyNotFoundError { .. }
+                    | rpm::Error::UnsupportedPGPKeyType(_)
+                    | rpm::Error::UnexpectedIssuerCount(_)
+                    | rpm::Error::VerificationError { .. }
+                    | rpm::Error::NoSignatureFound
+                    | rpm::Error::UnknownVersionSignature,
+                ) => return ErrorKind::Signing,
+                _ => {}
+            }
+        }
+        ErrorKind::Build
     }
 
-    for (src, options) in parse_file_options(&args.doc_file)? {
-        builder = builder
-            .with_file(src, options.is_doc())
-            .with_context(|| format!("error adding doc file {}", src))?;
+    fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Build => "build",
+            ErrorKind::Argument => "argument",
+            ErrorKind::Io => "io",
+            ErrorKind::Signing => "signing",
+        }
     }
+}
 
-    builder = process_dir(&args.dir, builder, |o| o)?;
-    builder = process_dir(&args.doc_dir, builder, |o| o.is_doc())?;
-    builder = process_dir(&args.config_dir, builder, |o| o.is_config())?;
-
-    if let Some(scriptlet_path) = args.pre_install_script {
-        let content = fs::read_to_string(&scriptlet_path)
-            .with_context(|| format!("error reading pre-install-script {:?}", scriptlet_path))?;
-        builder = builder.pre_install_script(content);
-    }
+/// The error object printed with `--error-format json`
+#[derive(Serialize, Debug)]
+struct ErrorReport {
+    kind: &'static str,
+    exit_code: u8,
+    message: String,
+    causes: Vec<String>,
+}
 
-    if let Some(scriptlet_path) = args.post_install_script {
-        let content = fs::read_to_string(&scriptlet_path)
-            .with_context(|| format!("error reading post-install-script {:?}", scriptlet_path))?;
-        builder = builder.post_install_script(content);
+/// Print an error to stderr in the requested format, returning the exit code of its kind
+fn report_error(error: &anyhow::Error, format: &OutputFormat) -> ExitCode {
+    if let Some(clap_error) = error.downcast_ref::<clap::Error>() {
+        // clap prints the help and the version this way too, which aren't errors at all
+        if !clap_error.use_stderr() || matches!(format, OutputFormat::Text) {
+            clap_error.exit();
+        }
     }
-
-    if let Some(scriptlet_path) = args.pre_uninstall_script {
-        let content = fs::read_to_string(&scriptlet_path)
-            .with_context(|| format!("error reading pre-uninstall-script {:?}", scriptlet_path))?;
-        builder = builder.pre_uninstall_script(content);
+    let kind = ErrorKind::of(error);
+    match format {
+        OutputFormat::Text => eprintln!("Error: {:?}", error),
+        OutputFormat::Json => {
+            // clap's own messages start with "error: " as well
+            let mut messages = error.chain().map(|cause| {
+                let message = cause.to_string();
+                message.trim().trim_start_matches("error: ").to_owned()
+            });
+            let report = ErrorReport {
+                kind: kind.name(),
+                exit_code: kind as u8,
+                message: messages.next().unwrap_or_default(),
+                causes: messages.collect(),
+            };
+            match serde_json::to_string(&report) {
+                Ok(json) => eprintln!("{}", json),
+                Err(_) => eprintln!("Error: {:?}", error),
+            }
+        }
     }
+    ExitCode::from(kind as u8)
+}
 
-    if let Some(scriptlet_path) = args.post_uninstall_script {
-        let content = fs::read_to_string(&scriptlet_path)
-            .with_context(|| format!("error reading post-uninstall-script {:?}", scriptlet_path))?;
-        builder = builder.post_uninstall_script(content);
+/// Log to stderr, warnings only unless `-v` (info) or `-vv` (debug) is passed
+fn init_logging(verbose: u8, format: &OutputFormat) {
+    let level = match verbose {
+        0 => tracing::Level::WARN,
+        1 => tracing::Level::INFO,
+        2 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    };
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_target(false);
+    match format {
+        OutputFormat::Text => subscriber.with_ansi(std::io::stderr().is_terminal()).init(),
+        OutputFormat::Json => subscriber.json().init(),
     }
+}
 
-    for raw_entry in args.changelog {
-        let parts: Vec<&str> = raw_entry.split(":").collect();
-        if parts.len() != 3 {
-            anyhow::bail!(
-                "invalid file argument:{} it needs to be of the form <author>:<content>:<yyyy-mm-dd>",
-                &raw_entry
-            );
+/// Parse the command line arguments, merging in the config file, and the manifest, the spec file
+/// and the cargo package if they were provided to `build`.
+///
+/// They are translated into the equivalent command line options, which are placed ahead of the
+/// real ones so that anything passed on the command line takes precedence (and the manifest
+/// takes precedence over the spec file, which takes precedence over the cargo package, which
+/// takes precedence over the config). Options which can be repeated are collected from all of
+/// them.
+fn parse_args(argv: Vec<OsString>) -> Result<Cli> {
+    let mut argv = expand_response_files(argv)?;
+    let config = match option_value(&argv, "--config") {
+        Some(path) => Some(Config::load(Path::new(&path))?),
+        None if Path::new(CONFIG_FILE).is_file() => Some(Config::load(Path::new(CONFIG_FILE))?),
+        None => None,
+    };
+    let command = <Cli as clap::CommandFactory>::command();
+    // the extra options belong to the subcommand, so they go directly after it
+    let Some(subcommand_index) = subcommand_position(&argv, &command) else {
+        return Ok(Cli::try_parse_from(argv)?);
+    };
+    let subcommand = argv[subcommand_index].to_string_lossy().into_owned();
+    let mut position = subcommand_index + 1;
+
+    let mut config_name = None;
+    if let Some(config) = &config {
+        for name in config.subcommands() {
+            if command.find_subcommand(name).is_none() {
+                anyhow::bail!("invalid config: there is no {} subcommand", name);
+            }
+        }
+        let config_args = config.to_args(&subcommand)?;
+        let count = config_args.len();
+        argv.splice(position..position, config_args);
+        position += count;
+        if subcommand == "build" {
+            config_name = config.section("build").and_then(|s| s.name.clone());
         }
-        let name = parts[0];
-        let content = parts[1];
-        let raw_time = parts[2];
-        let parse_result = chrono::NaiveDate::parse_from_str(raw_time, "%Y-%m-%d");
-        let date = parse_result
-            .with_context(|| format!("error while parsing date time: {:?}", parse_result.err()))?;
-        let seconds = date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
-        builder = builder.add_changelog_entry(name, content, rpm::Timestamp::from(seconds as u32));
     }
 
-    for item in args.requires {
-        let dependency = parse_dependency(&item)?;
-        builder = builder.requires(dependency);
+    let cli = match Cli::try_parse_from(&argv) {
+        Ok(cli) => cli,
+        // the name from the config is only used when nothing else provides one
+        Err(error) if error.kind() == clap::error::ErrorKind::MissingRequiredArgument => {
+            let Some(name) = &config_name else {
+                return Err(error.into());
+            };
+            argv.insert(position, name.into());
+            position += 1;
+            Cli::try_parse_from(&argv)?
+        }
+        Err(error) => return Err(error.into()),
+    };
+    let Command::Build(args) = &cli.command else {
+        return Ok(cli);
+    };
+    let config_subpackages = config
+        .iter()
+        .filter_map(|c| c.section("build"))
+        .flat_map(|s| &s.subpackages)
+        .collect::<Vec<_>>();
+    if args.manifest.is_none()
+        && args.from_cargo.is_none()
+        && args.from_spec.is_none()
+        && config_subpackages.is_empty()
+    {
+        return Ok(cli);
     }
-
-    for item in args.obsoletes {
-        let dependency = parse_dependency(&item)?;
-        builder = builder.obsoletes(dependency);
+    let manifest = args.manifest.as_deref().map(Manifest::load).transpose()?;
+    let spec_file = args
+        .from_spec
+        .as_deref()
+        .map(|path| SpecFile::load(path, args.buildroot.as_deref(), &args.arch))
+        .transpose()?;
+    let cargo_package = args
+        .from_cargo
+        .as_deref()
+        .map(CargoPackage::load)
+        .transpose()?;
+
+    let mut extra_args = Vec::new();
+    if let Some(cargo_package) = &cargo_package {
+        extra_args.extend(cargo_package.to_args(args.cargo_bins));
     }
-
-    for item in args.conflicts {
-        let dependency = parse_dependency(&item)?;
-        builder = builder.conflicts(dependency);
+    if let Some(spec_file) = &spec_file {
+        extra_args.extend(spec_file.package.to_args());
     }
-
-    for item in args.provides {
-        let dependency = parse_dependency(&item)?;
-        builder = builder.provides(dependency);
+    if let Some(manifest) = &manifest {
+        extra_args.extend(manifest.to_args()?);
     }
-
-    for item in args.suggests {
-        let dependency = parse_dependency(&item)?;
-        builder = builder.suggests(dependency);
+    if args.name.is_none() {
+        let name = manifest
+            .as_ref()
+            .and_then(|m| m.name.clone())
+            .or_else(|| spec_file.as_ref().map(|s| s.package.name.clone()))
+            .or_else(|| cargo_package.map(|c| c.name));
+        extra_args.extend(name.map(OsString::from));
     }
+    argv.splice(position..position, extra_args);
 
-    for item in args.enhances {
-        let dependency = parse_dependency(&item)?;
-        builder = builder.enhances(dependency);
+    let mut cli = Cli::try_parse_from(argv)?;
+    let Command::Build(args) = &mut cli.command else {
+        unreachable!("the manifest only applies to the build subcommand");
+    };
+    if let Some(spec_file) = &spec_file {
+        args.spec_scriptlets = spec_file.package.scriptlets.clone();
+        args.spec_changelog = spec_file.changelog.clone();
     }
-
-    for item in args.recommends {
-        let dependency = parse_dependency(&item)?;
-        builder = builder.recommends(dependency);
+    for subpackage in config_subpackages
+        .into_iter()
+        .chain(manifest.iter().flat_map(|m| &m.subpackages))
+    {
+        let name = subpackage.name.as_deref().unwrap_or_default();
+        let options = subpackage.to_args()?;
+        args.subpackages.push(parse_subpackage(
+            name,
+            options,
+            |option| subpackage.sets(option),
+            args,
+        )?);
     }
-
-    for item in args.supplements {
-        let dependency = parse_dependency(&item)?;
-        builder = builder.supplements(dependency);
+    for subpackage in spec_file.iter().flat_map(|s| &s.subpackages) {
+        let mut subpackage_args = parse_subpackage(
+            &subpackage.name,
+            subpackage.to_args(),
+            |option| subpackage.sets(option),
+            args,
+        )?;
+        subpackage_args.spec_scriptlets = subpackage.scriptlets.clone();
+        args.subpackages.push(subpackage_args);
     }
-
-    let pkg = if let Some(signing_key_path) = args.sign_with_pgp_asc {
-        let raw_key = fs::read(&signing_key_path).with_context(|| {
-            format!(
-                "unable to load private key file from path {:?}",
-                signing_key_path
-            )
-        })?;
-
-        let signer =
-            rpm::signature::pgp::Signer::load_from_asc_bytes(&raw_key).with_context(|| {
-                format!(
-                    "unable to create signer from private key {:?}",
-                    signing_key_path
-                )
-            })?;
-
-        builder.build_and_sign(signer)?
-    } else {
-        builder.build()?
-    };
-
-    let filename = format!("{}.rpm", pkg.metadata.get_nevra().unwrap().nvra());
-
-    let output_path = args
-        .out
-        .map(|path| {
-            if fs::metadata(&path).is_ok_and(|m| m.is_dir()) {
-                Path::new(&path).join(filename)
-            } else {
-                Path::new(&path).with_extension("rpm")
-            }
-        })
-        .unwrap_or_else(|| {
-            PathBuf::from(format!("{}.rpm", pkg.metadata.get_nevra().unwrap().nvra()))
-        });
-
-    let mut out_file = fs::File::create(&output_path)
-        .with_context(|| format!("unable to create output file {:?}", &output_path))?;
-
-    pkg.write(&mut out_file)
-        .with_context(|| format!("unable to write package to path {:?}", &output_path))?;
-
-    Ok(())
+    Ok(cli)
 }
 
-fn process_dir<F>(
-    dirs: &[String],
-    mut builder: rpm::PackageBuilder,
-    options_modifier: F,
-) -> Result<rpm::PackageBuilder>
-where
-    F: Fn(rpm::FileOptionsBuilder) -> rpm::FileOptionsBuilder,
-{
-    for dir in dirs {
-        let parts: Vec<&str> = dir.split(":").collect();
-        if parts.len() != 2 {
-            anyhow::bail!(
-                "invalid file argument:{} it needs to be of the form <source-path>:<dest-path>",
-                dir
-            );
+/// Replace every `@<file>` argument with the arguments in the file, one per line (empty lines
+/// are skipped), so that long command lines don't run into the limits of the OS
+fn expand_response_files(argv: Vec<OsString>) -> Result<Vec<OsString>> {
+    let mut expanded = Vec::with_capacity(argv.len());
+    let mut args = argv.into_iter();
+    expanded.extend(args.next());
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            expanded.push(arg);
+            expanded.extend(args.by_ref());
+            break;
         }
-        let dir = parts[0];
-        let target = PathBuf::from(parts[1]);
-        builder = add_dir(dir, &target, builder, &options_modifier)
-            .with_context(|| format!("error adding dir {}", dir))?;
+        let Some(path) = arg.to_str().and_then(|a| a.strip_prefix('@')) else {
+            expanded.push(arg);
+            continue;
+        };
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("error reading arguments file {:?}", path))?;
+        expanded.extend(
+            content
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(OsString::from),
+        );
     }
-    Ok(builder)
+    Ok(expanded)
 }
 
-fn add_dir<P: AsRef<Path>, F>(
-    full_path: P,
-    target_path: &PathBuf,
-    mut builder: rpm::PackageBuilder,
-    options_modifier: &F,
-) -> Result<rpm::PackageBuilder>
-where
-    F: Fn(rpm::FileOptionsBuilder) -> rpm::FileOptionsBuilder,
-{
-    for entry in std::fs::read_dir(full_path)? {
-        let entry = entry?;
-        let metadata = entry.metadata()?;
-        let mut new_target = target_path.clone();
-
-        let source = if metadata.file_type().is_symlink() {
-            std::fs::read_link(entry.path().as_path())?
-        } else {
-            entry.path()
-        };
-
-        let file_name = source.file_name().context("path does not have filename")?;
-
-        new_target.push(file_name);
-
-        builder = if metadata.file_type().is_dir() {
-            add_dir(&source, &new_target, builder, options_modifier)?
-        } else {
-            let options = options_modifier(rpm::FileOptions::new(new_target.to_string_lossy()));
-            builder.with_file(&source, options)?
+/// The value of a global option such as `--config`. It is needed before the command line can be
+/// parsed, e.g. since the options of the config are part of it.
+fn option_value(argv: &[OsString], name: &str) -> Option<OsString> {
+    let mut args = argv.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        } else if arg == name {
+            return args.next().cloned();
+        } else if let Some(value) = arg
+            .to_str()
+            .and_then(|a| a.strip_prefix(name))
+            .and_then(|a| a.strip_prefix('='))
+        {
+            return Some(value.into());
         }
     }
-    Ok(builder)
+    None
 }
 
-fn parse_file_options(raw_files: &Vec<String>) -> Result<Vec<(&str, rpm::FileOptionsBuilder)>> {
-    raw_files
-        .iter()
-        .map(|input| {
-            let parts: Vec<&str> = input.split(":").collect();
-            if parts.len() != 2 {
-                anyhow::bail!(
-                    "invalid file argument:{} it needs to be of the form <source-path>:<dest-path>",
-                    input
-                );
-            }
-            Ok((parts[0], rpm::FileOptions::new(parts[1])))
-        })
-        .collect()
+/// The position of the subcommand in the command line, skipping the values of global options
+fn subcommand_position(argv: &[OsString], command: &clap::Command) -> Option<usize> {
+    let mut index = 1;
+    while let Some(arg) = argv.get(index) {
+        if arg == "--config" || arg == "--log-format" || arg == "--error-format" {
+            index += 1;
+        } else if command.find_subcommand(arg).is_some() {
+            return Some(index);
+        }
+        index += 1;
+    }
+    None
 }
 
-fn parse_dependency(line: &str) -> Result<rpm::Dependency> {
-    let re = Regex::new(r"^([a-zA-Z0-9\-\._/]+)(\s*(>=|>|=|<=|<)(.+))?$").unwrap();
-
-    let parts = re
-        .captures(line)
-        .with_context(|| format!("invalid pattern in dependency block {}", line))?;
-    let parts: Vec<String> = parts
-        .iter()
-        .filter(|c| c.is_some())
-        .map(|c| String::from(c.unwrap().as_str()))
-        .collect();
-
-    if parts.len() <= 2 {
-        Ok(rpm::Dependency::any(&parts[1]))
-    } else {
-        let version = parts[4].trim();
-        let dep = match parts[3].as_str() {
-            "=" => rpm::Dependency::eq(&parts[1], version),
-            "<" => rpm::Dependency::less(&parts[1], version),
-            "<=" => rpm::Dependency::less_eq(&parts[1], version),
-            ">=" => rpm::Dependency::greater_eq(&parts[1], version),
-            ">" => rpm::Dependency::greater(&parts[1], version),
-            _ => {
-                anyhow::bail!("regex is invalid here, got unknown match {}", &parts[3]);
-            }
-        };
-        Ok(dep)
-    }
+/// Parse the options of a subpackage, which are taken from the main package unless the
+/// subpackage sets them itself
+fn parse_subpackage(
+    name: &str,
+    options: Vec<OsString>,
+    is_overridden: impl Fn(&str) -> bool,
+    parent: &build::BuildArgs,
+) -> Result<build::BuildArgs> {
+    let mut argv: Vec<OsString> = vec!["rpm-builder".into(), "build".into(), name.into()];
+    argv.extend(options);
+
+    let cli = Cli::try_parse_from(argv)
+        .with_context(|| format!("invalid options for subpackage {}", name))?;
+    let Command::Build(mut args) = cli.command else {
+        unreachable!("subpackages are always parsed as build arguments");
+    };
+    args.inherit_from(parent, is_overridden);
+    Ok(*args)
 }
@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// The directory the sections of the man pages are installed into
+pub const MAN_DIR: &str = "/usr/share/man";
+
+/// Parse a man page given as `<src>:<section>`, where the section is a number, optionally
+/// followed by a suffix such as the `p` of `3p`
+pub fn parse_man_page(raw: &str, separator: &str) -> Result<(String, String)> {
+    let Some((source, section)) = crate::build::split_last(raw, separator) else {
+        anyhow::bail!(
+            "invalid man page {}: it needs to be of the form <src>:<section>",
+            raw
+        );
+    };
+    let is_valid = section.starts_with(|c: char| ('1'..='9').contains(&c))
+        && section.chars().all(|c| c.is_ascii_alphanumeric());
+    if !is_valid {
+        anyhow::bail!(
+            "invalid man page section {}: it needs to be a number from 1 to 9, optionally followed by a suffix, e.g. 1 or 3p",
+            section
+        );
+    }
+    Ok((source, section))
+}
+
+/// Where the man page of a section is installed, e.g. `/usr/share/man/man1/foo.1.gz` for
+/// `foo.1`, `foo.1.gz`, `foo.man` or `foo` in section 1. Like man expects, the extension of the
+/// file name is the section, replacing the one of the source if that is a section or `man`.
+pub fn dest(source: &str, section: &str) -> Result<String> {
+    let file_name = Path::new(source)
+        .file_name()
+        .with_context(|| format!("man page {} does not have a file name", source))?
+        .to_string_lossy();
+    let file_name = file_name.strip_suffix(".gz").unwrap_or(&file_name);
+    let name = match file_name.rsplit_once('.') {
+        Some((name, extension))
+            if !name.is_empty()
+                && (extension == "man" || extension.starts_with(|c: char| c.is_ascii_digit())) =>
+        {
+            name
+        }
+        _ => file_name,
+    };
+    let section_dir = &section[..1];
+    Ok(format!(
+        "{}/man{}/{}.{}.gz",
+        MAN_DIR, section_dir, name, section
+    ))
+}
+
+/// Read a man page, compressing it like rpmbuild's brp-compress does with `gzip -9 -n`, i.e.
+/// without the name and time of the source. Man pages which are already gzipped are kept.
+pub fn read_compressed(source: &str) -> Result<Vec<u8>> {
+    let content =
+        std::fs::read(source).with_context(|| format!("error reading man page {}", source))?;
+    if content.starts_with(&[0x1f, 0x8b]) {
+        // make sure it's a complete gzip stream, man would fail on it otherwise
+        flate2::read::GzDecoder::new(content.as_slice())
+            .read_to_end(&mut Vec::new())
+            .with_context(|| format!("invalid gzipped man page {}", source))?;
+        return Ok(content);
+    }
+    let mut encoder = flate2::GzBuilder::new().write(Vec::new(), flate2::Compression::best());
+    encoder.write_all(&content)?;
+    Ok(encoder.finish()?)
+}
@@ -0,0 +1,213 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+/// A package definition loaded from a TOML or YAML file.
+///
+/// Every key corresponds to the long form of a command line option, e.g. `exec_file` (or
+/// `exec-file`) maps to `--exec-file`, with the exception of `name` which supplies the package
+/// name. Options which may be repeated on the command line take a list of values.
+///
+/// Additional packages built alongside the main one are described by a list of `subpackage`
+/// tables, which take the same keys (and must have a `name`).
+#[derive(Debug)]
+pub struct Manifest {
+    pub name: Option<String>,
+    pub subpackages: Vec<Manifest>,
+    options: BTreeMap<String, Value>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Value {
+    Bool(bool),
+    Integer(i64),
+    String(String),
+    List(Vec<Value>),
+    Table(BTreeMap<String, Value>),
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Manifest> {
+        let options = read_table(path, "manifest")?;
+        Manifest::from_table(options).with_context(|| format!("invalid manifest {:?}", path))
+    }
+
+    /// Create a manifest from its table of options, which may list subpackages
+    fn from_table(mut options: BTreeMap<String, Value>) -> Result<Manifest> {
+        let subpackages = match options.remove("subpackage") {
+            Some(Value::List(tables)) => tables
+                .into_iter()
+                .enumerate()
+                .map(|(index, table)| {
+                    let Value::Table(options) = table else {
+                        anyhow::bail!("each subpackage must be a table");
+                    };
+                    let subpackage = Manifest::from_options(options)?;
+                    if subpackage.name.is_none() {
+                        anyhow::bail!("subpackage {} has no 'name'", index + 1);
+                    }
+                    if options_contain(&subpackage.options, "subpackage") {
+                        anyhow::bail!("subpackages can't have subpackages of their own");
+                    }
+                    Ok(subpackage)
+                })
+                .collect::<Result<Vec<_>>>()?,
+            Some(_) => anyhow::bail!("'subpackage' must be a list of tables"),
+            None => Vec::new(),
+        };
+
+        let mut manifest = Manifest::from_options(options)?;
+        manifest.subpackages = subpackages;
+        Ok(manifest)
+    }
+
+    fn from_options(mut options: BTreeMap<String, Value>) -> Result<Manifest> {
+        let name = match options.remove("name") {
+            Some(Value::String(name)) => Some(name),
+            Some(_) => anyhow::bail!("'name' must be a string"),
+            None => None,
+        };
+
+        Ok(Manifest {
+            name,
+            subpackages: Vec::new(),
+            options,
+        })
+    }
+
+    /// Whether the manifest sets the option with the given long name, e.g. `exec_file`
+    pub fn sets(&self, option: &str) -> bool {
+        options_contain(&self.options, option)
+    }
+
+    /// Translate the manifest into the equivalent list of command line arguments
+    pub fn to_args(&self) -> Result<Vec<OsString>> {
+        if self.sets("manifest") {
+            anyhow::bail!("a manifest cannot reference another manifest");
+        }
+        self.options_to_args()
+    }
+
+    fn options_to_args(&self) -> Result<Vec<OsString>> {
+        let mut args = Vec::new();
+
+        for (key, value) in &self.options {
+            let flag = format!("--{}", key.replace('_', "-"));
+            match value {
+                Value::List(items) => {
+                    for item in items {
+                        push_arg(&mut args, &flag, item)
+                            .with_context(|| format!("invalid value in manifest key '{}'", key))?;
+                    }
+                }
+                _ => push_arg(&mut args, &flag, value)
+                    .with_context(|| format!("invalid value for manifest key '{}'", key))?,
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+/// The name of the config file which is used when it is in the current directory
+pub const CONFIG_FILE: &str = "rpm-builder.toml";
+
+/// A project config file, with the default options of the subcommands.
+///
+/// Every subcommand has a table of options in the same format as a manifest, e.g. `[build]` with
+/// the package definition (which may list subpackages as `[[build.subpackage]]`) or `[publish]`
+/// with the artifact store to publish to. The options of the config have the lowest precedence:
+/// manifests, spec files, cargo packages and the command line override them.
+#[derive(Debug)]
+pub struct Config {
+    sections: BTreeMap<String, Manifest>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config> {
+        let table = read_table(path, "config")?;
+        let mut sections = BTreeMap::new();
+        for (name, value) in table {
+            let Value::Table(options) = value else {
+                anyhow::bail!(
+                    "invalid config {:?}: '{}' must be a table with the options of the {} subcommand",
+                    path,
+                    name,
+                    name
+                );
+            };
+            let section = Manifest::from_table(options)
+                .with_context(|| format!("invalid [{}] section in config {:?}", name, path))?;
+            if section.sets("config") {
+                anyhow::bail!(
+                    "invalid config {:?}: a config cannot reference another config",
+                    path
+                );
+            }
+            if name != "build" && (section.name.is_some() || !section.subpackages.is_empty()) {
+                anyhow::bail!(
+                    "invalid config {:?}: only the [build] section can have a 'name' and subpackages",
+                    path
+                );
+            }
+            sections.insert(name, section);
+        }
+        Ok(Config { sections })
+    }
+
+    /// The names of the subcommands the config has options for
+    pub fn subcommands(&self) -> impl Iterator<Item = &str> {
+        self.sections.keys().map(String::as_str)
+    }
+
+    /// The options of a subcommand, if the config has any
+    pub fn section(&self, subcommand: &str) -> Option<&Manifest> {
+        self.sections.get(subcommand)
+    }
+
+    /// The options of a subcommand as command line arguments. Unlike a manifest, the `[build]`
+    /// section may refer to a manifest.
+    pub fn to_args(&self, subcommand: &str) -> Result<Vec<OsString>> {
+        match self.section(subcommand) {
+            Some(section) => section
+                .options_to_args()
+                .with_context(|| format!("invalid [{}] section in the config", subcommand)),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// Read a TOML or YAML file (depending on its extension) into a table
+fn read_table(path: &Path, kind: &str) -> Result<BTreeMap<String, Value>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("error reading {} {:?}", kind, path))?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&content)
+            .with_context(|| format!("error parsing YAML {} {:?}", kind, path)),
+        _ => toml::from_str(&content)
+            .with_context(|| format!("error parsing TOML {} {:?}", kind, path)),
+    }
+}
+
+fn push_arg(args: &mut Vec<OsString>, flag: &str, value: &Value) -> Result<()> {
+    match value {
+        Value::Bool(true) => args.push(flag.into()),
+        Value::Bool(false) => {}
+        Value::Integer(i) => args.push(format!("{}={}", flag, i).into()),
+        // use the --flag=value form so that values starting with '-' are not mistaken for flags
+        Value::String(s) => args.push(format!("{}={}", flag, s).into()),
+        Value::List(_) => anyhow::bail!("nested lists are not supported"),
+        Value::Table(_) => anyhow::bail!("tables are only supported for subpackages"),
+    }
+    Ok(())
+}
+
+/// Keys may use either `_` or `-` between words
+fn options_contain(options: &BTreeMap<String, Value>, option: &str) -> bool {
+    options.keys().any(|key| key.replace('-', "_") == option)
+}
@@ -0,0 +1,35 @@
+use anyhow::{Context, Result};
+use clap_derive::Args;
+
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct ManpageArgs {
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "OUT_DIR",
+        help = "Write the man pages of rpm-builder and of each of its subcommands (e.g. rpm-builder-build.1) into a directory, instead of printing the one of rpm-builder to stdout"
+    )]
+    pub out: Option<PathBuf>,
+}
+
+/// Generate the man pages of the command line of `command`
+pub fn manpage(args: ManpageArgs, command: clap::Command) -> Result<()> {
+    // the version goes into the titles of the pages, without adding a --version flag
+    let command = command
+        .version(env!("CARGO_PKG_VERSION"))
+        .disable_version_flag(true)
+        .propagate_version(true);
+    let Some(out_dir) = args.out else {
+        clap_mangen::Man::new(command).render(&mut std::io::stdout())?;
+        return Ok(());
+    };
+    fs::create_dir_all(&out_dir)
+        .with_context(|| format!("unable to create the directory {:?}", out_dir))?;
+    clap_mangen::generate_to(command, &out_dir)
+        .with_context(|| format!("unable to write the man pages to {:?}", out_dir))?;
+    tracing::info!(path = %out_dir.display(), "wrote man pages");
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use serde_yaml::{Mapping, Value};
+
+use std::fs;
+use std::path::Path;
+
+/// Check a modularity label, which is `<name>:<stream>:<version>:<context>` like the labels
+/// written by the module build service
+pub fn check_label(label: &str) -> Result<()> {
+    let parts: Vec<&str> = label.split(':').collect();
+    let is_valid = match parts.as_slice() {
+        [name, stream, version, context] => {
+            !name.is_empty()
+                && !stream.is_empty()
+                && !version.is_empty()
+                && version.chars().all(|c| c.is_ascii_digit())
+                && !context.is_empty()
+        }
+        _ => false,
+    };
+    if !is_valid {
+        anyhow::bail!(
+            "invalid modularity label {}, expected <name>:<stream>:<version>:<context> with a numeric version",
+            label
+        );
+    }
+    Ok(())
+}
+
+/// A modulemd (version 2) document describing the module a package belongs to
+#[derive(Debug)]
+pub struct ModuleDocument {
+    document: Value,
+}
+
+impl ModuleDocument {
+    pub fn load(path: &Path) -> Result<ModuleDocument> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("unable to read modulemd file {:?}", path))?;
+        let document: Value = serde_yaml::from_str(&content)
+            .with_context(|| format!("unable to parse modulemd file {:?}", path))?;
+        let is_modulemd = document.get("document").and_then(Value::as_str) == Some("modulemd")
+            && document.get("version").and_then(Value::as_u64) == Some(2);
+        if !is_modulemd {
+            anyhow::bail!("{:?} is not a modulemd version 2 document", path);
+        }
+        Ok(ModuleDocument { document })
+    }
+
+    /// The modularity label of the packages of the module
+    pub fn label(&self) -> Result<String> {
+        let data = &self.document["data"];
+        let field = |key: &str| match &data[key] {
+            Value::String(value) => Ok(value.clone()),
+            Value::Number(value) => Ok(value.to_string()),
+            _ => anyhow::bail!(
+                "the modulemd document has no {}, which is needed for the modularity label",
+                key
+            ),
+        };
+        let label = format!(
+            "{}:{}:{}:{}",
+            field("name")?,
+            field("stream")?,
+            field("version")?,
+            field("context")?
+        );
+        check_label(&label)?;
+        Ok(label)
+    }
+
+    /// List a package (given as its NEVRA) as an artifact of the module
+    pub fn add_artifact(&mut self, nevra: &str) -> Result<()> {
+        let data = self.document["data"]
+            .as_mapping_mut()
+            .context("the modulemd document has no data")?;
+        let rpms = data
+            .entry("artifacts".into())
+            .or_insert_with(|| Value::Mapping(Mapping::new()))
+            .as_mapping_mut()
+            .context("the artifacts of the modulemd document aren't a mapping")?
+            .entry("rpms".into())
+            .or_insert_with(|| Value::Sequence(Vec::new()))
+            .as_sequence_mut()
+            .context("the rpm artifacts of the modulemd document aren't a list")?;
+        let nevra = Value::String(nevra.to_owned());
+        if !rpms.contains(&nevra) {
+            rpms.push(nevra);
+        }
+        Ok(())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let content = serde_yaml::to_string(&self.document)?;
+        fs::write(path, format!("---\n{}...\n", content))
+            .with_context(|| format!("unable to write modulemd file {:?}", path))
+    }
+}
@@ -0,0 +1,264 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::archive::{self, Entry, EntryKind};
+
+/// The prefix of the files which delete a path of the layers below
+const WHITEOUT_PREFIX: &str = ".wh.";
+/// The file which hides every path below its directory in the layers below
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+#[derive(Deserialize, Debug)]
+struct Descriptor {
+    digest: String,
+    platform: Option<Platform>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Platform {
+    architecture: String,
+}
+
+/// An image manifest, or an image index if it has `manifests`
+#[derive(Deserialize, Debug)]
+struct Manifest {
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+}
+
+/// An entry of the `manifest.json` written by `docker save`
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct DockerManifest {
+    layers: Vec<String>,
+}
+
+/// Where the blobs of an image are read from
+enum Image {
+    Dir(PathBuf),
+    Archive(BTreeMap<PathBuf, Entry>),
+}
+
+/// Add the filesystem of a container image to the package below `prefix`. The source is either
+/// an OCI image layout (a directory, or a tarball as written by `skopeo copy ... oci-archive:...`),
+/// a tarball written by `docker save`, or a single layer or exported root filesystem. The layers of an image are stacked in order, and their whiteout
+/// files are applied instead of being packaged.
+///
+/// If the image has manifests for several platforms, the one matching `arch` is used. The
+/// destination of each entry is appended to `destinations`.
+pub fn add_image(
+    source: &str,
+    prefix: &str,
+    arch: &str,
+    builder: rpm::PackageBuilder,
+    destinations: &mut Vec<String>,
+) -> Result<rpm::PackageBuilder> {
+    let source = Path::new(source);
+    if !source.exists() {
+        anyhow::bail!(
+            "{:?} doesn't exist. Images can't be pulled from a registry, save them locally first, e.g. with `skopeo copy docker://<image> oci:<dir>`",
+            source
+        );
+    }
+
+    let image = if source.is_dir() {
+        Image::Dir(source.to_path_buf())
+    } else {
+        let file =
+            fs::File::open(source).with_context(|| format!("unable to open image {:?}", source))?;
+        let entries = archive::read_tar(file, &BTreeMap::new())
+            .with_context(|| format!("unable to read image {:?}", source))?;
+        let entries: BTreeMap<PathBuf, Entry> = entries
+            .into_iter()
+            .map(|entry| (entry.path.clone(), entry))
+            .collect();
+        if !entries.contains_key(Path::new("oci-layout"))
+            && !entries.contains_key(Path::new("manifest.json"))
+        {
+            // a single layer, or an exported root filesystem
+            let mut filesystem = BTreeMap::new();
+            apply_layer(&mut filesystem, entries.into_values().collect());
+            return archive::add_entries(
+                builder,
+                filesystem.into_values(),
+                Path::new(prefix),
+                destinations,
+            );
+        }
+        Image::Archive(entries)
+    };
+
+    let mut filesystem = BTreeMap::new();
+    for layer in image.layers(arch)? {
+        let blob = image.read(&layer)?;
+        let entries = archive::read_tar(&blob[..], &filesystem)
+            .with_context(|| format!("unable to read layer {}", layer))?;
+        apply_layer(&mut filesystem, entries);
+    }
+    archive::add_entries(
+        builder,
+        filesystem.into_values(),
+        Path::new(prefix),
+        destinations,
+    )
+}
+
+impl Image {
+    /// Read a file of the image
+    fn read(&self, path: &str) -> Result<Cow<'_, [u8]>> {
+        match self {
+            Image::Dir(dir) => {
+                Ok(Cow::Owned(fs::read(dir.join(path)).with_context(|| {
+                    format!("unable to read {:?}", dir.join(path))
+                })?))
+            }
+            Image::Archive(entries) => {
+                let mut path = PathBuf::from(path);
+                // `docker save` links identical layers to each other
+                for _ in 0..8 {
+                    match entries.get(&path).map(|entry| &entry.kind) {
+                        Some(EntryKind::File(content)) => return Ok(Cow::Borrowed(content)),
+                        Some(EntryKind::Symlink(target)) => {
+                            let link_dir = path.parent().unwrap_or(Path::new(""));
+                            path = archive::relative_path(&normalize(&link_dir.join(target)))?
+                                .unwrap_or_default();
+                        }
+                        _ => break,
+                    }
+                }
+                anyhow::bail!("the image has no file {:?}", path)
+            }
+        }
+    }
+
+    /// The paths of the layers of the image, from the bottom one to the top one
+    fn layers(&self, arch: &str) -> Result<Vec<String>> {
+        if self.read("index.json").is_err() {
+            let manifest: Vec<DockerManifest> =
+                serde_json::from_slice(&self.read("manifest.json")?)
+                    .context("unable to parse manifest.json")?;
+            let [manifest] = <[DockerManifest; 1]>::try_from(manifest).map_err(|manifests| {
+                anyhow::anyhow!(
+                    "the image archive contains {} images instead of exactly one",
+                    manifests.len()
+                )
+            })?;
+            return Ok(manifest.layers);
+        }
+
+        let mut manifest: Manifest = serde_json::from_slice(&self.read("index.json")?)
+            .context("unable to parse index.json")?;
+        // indexes can refer to other indexes, e.g. one for every platform
+        for _ in 0..8 {
+            if manifest.manifests.is_empty() {
+                return manifest
+                    .layers
+                    .iter()
+                    .map(|layer| blob_path(&layer.digest))
+                    .collect();
+            }
+            let descriptor = select_manifest(&manifest.manifests, arch)?;
+            let path = blob_path(&descriptor.digest)?;
+            manifest = serde_json::from_slice(&self.read(&path)?)
+                .with_context(|| format!("unable to parse manifest {}", descriptor.digest))?;
+        }
+        anyhow::bail!("the image indexes are nested too deeply")
+    }
+}
+
+/// Pick the manifest for the architecture of the package, falling back to the first one for
+/// `noarch` packages and images without platforms
+fn select_manifest<'a>(manifests: &'a [Descriptor], arch: &str) -> Result<&'a Descriptor> {
+    let go_arch = match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "i386" | "i486" | "i586" | "i686" => "386",
+        "armv7hl" | "armv7l" | "armhfp" => "arm",
+        other => other,
+    };
+    let platform_matches = |descriptor: &&Descriptor| {
+        descriptor
+            .platform
+            .as_ref()
+            .is_some_and(|platform| platform.architecture == go_arch)
+    };
+    let has_platforms = manifests.iter().any(|m| m.platform.is_some());
+    match manifests.iter().find(platform_matches) {
+        Some(descriptor) => Ok(descriptor),
+        None if arch == "noarch" || !has_platforms => manifests
+            .first()
+            .context("the image index has no manifests"),
+        None => anyhow::bail!("the image has no manifest for the {} architecture", arch),
+    }
+}
+
+/// The path of a blob in an OCI image layout, e.g. `blobs/sha256/<hex>` for `sha256:<hex>`
+fn blob_path(digest: &str) -> Result<String> {
+    match digest.split_once(':') {
+        Some((algorithm, hex))
+            if !algorithm.is_empty()
+                && !hex.is_empty()
+                && algorithm.chars().all(|c| c.is_ascii_alphanumeric())
+                && hex.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            Ok(format!("blobs/{}/{}", algorithm, hex))
+        }
+        _ => anyhow::bail!("invalid digest {}", digest),
+    }
+}
+
+/// Stack a layer onto the filesystem made of the layers below it. The whiteouts of the layer only
+/// apply to the layers below, so they're handled before the entries of the layer are added.
+fn apply_layer(filesystem: &mut BTreeMap<PathBuf, Entry>, entries: Vec<Entry>) {
+    let (whiteouts, entries): (Vec<Entry>, Vec<Entry>) = entries
+        .into_iter()
+        .partition(|entry| file_name(&entry.path).starts_with(WHITEOUT_PREFIX));
+
+    for whiteout in whiteouts {
+        let dir = whiteout.path.parent().unwrap_or(Path::new(""));
+        let name = file_name(&whiteout.path);
+        if name == OPAQUE_WHITEOUT {
+            filesystem.retain(|path, _| path == dir || !path.starts_with(dir));
+        } else {
+            let hidden = dir.join(&name[WHITEOUT_PREFIX.len()..]);
+            filesystem.retain(|path, _| !path.starts_with(&hidden));
+        }
+    }
+
+    for entry in entries {
+        if !matches!(entry.kind, EntryKind::Dir) {
+            // e.g. a file replacing a directory of a lower layer
+            filesystem.retain(|path, _| path == &entry.path || !path.starts_with(&entry.path));
+        }
+        filesystem.insert(entry.path.clone(), entry);
+    }
+}
+
+fn file_name(path: &Path) -> Cow<'_, str> {
+    path.file_name()
+        .map(OsStr::to_string_lossy)
+        .unwrap_or_default()
+}
+
+/// Resolve `.` and `..` in a relative path without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            std::path::Component::Normal(part) => normalized.push(part),
+            _ => {}
+        }
+    }
+    normalized
+}
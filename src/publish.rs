@@ -0,0 +1,447 @@
+//! Publishing packages, either by uploading them to a URL (`build --publish`) or to the
+//! repository of an artifact store (the `publish` subcommand)
+
+use anyhow::{Context, Result};
+use base64::prelude::*;
+use clap_derive::Args;
+use sha2::{Digest, Sha256};
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+mod artifactory;
+mod nexus;
+mod pulp;
+
+pub use artifactory::ArtifactoryPublisher;
+pub use nexus::NexusPublisher;
+pub use pulp::PulpPublisher;
+
+#[derive(Args, Debug)]
+pub struct PublishArgs {
+    #[arg(
+        value_name = "PACKAGE",
+        required = true,
+        help = "Paths to the rpms to publish"
+    )]
+    pub packages: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "BACKEND",
+        help = "The kind of artifact store to publish to: pulp, nexus or artifactory"
+    )]
+    pub to: String,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "The base URL of the server, e.g. https://pulp.example.com or https://example.jfrog.io/artifactory"
+    )]
+    pub url: String,
+
+    #[arg(
+        long,
+        value_name = "REPOSITORY",
+        help = "The name of the repository to publish to"
+    )]
+    pub repository: String,
+
+    #[arg(
+        long,
+        value_name = "DIRECTORY",
+        help = "The directory of the repository to upload to (for nexus and artifactory)"
+    )]
+    pub directory: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "USER",
+        help = "Authenticate as this user, with the password from --password or $RPM_BUILDER_PUBLISH_PASSWORD"
+    )]
+    pub user: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PASSWORD",
+        requires = "user",
+        help = "The password of the user"
+    )]
+    pub password: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        conflicts_with = "user",
+        help = "Authenticate with a bearer token, which can also be given with $RPM_BUILDER_PUBLISH_TOKEN"
+    )]
+    pub token: Option<String>,
+}
+
+/// The server and repository packages are published to
+#[derive(Debug)]
+pub struct Server {
+    /// The base URL, without a trailing `/`
+    pub url: String,
+    pub repository: String,
+    pub directory: Option<String>,
+    pub auth: Auth,
+}
+
+impl Server {
+    /// The URL of a file with the given path relative to the base URL
+    fn file_url(&self, base: &str, file_name: &str) -> String {
+        let mut url = format!("{}/{}", self.url, base.trim_matches('/'));
+        let directory = self.directory.as_deref().unwrap_or("").trim_matches('/');
+        if !directory.is_empty() {
+            url.push('/');
+            url.push_str(directory);
+        }
+        format!("{}/{}", url, file_name)
+    }
+}
+
+pub trait Publisher {
+    /// The name the publisher is selected with
+    fn name(&self) -> &'static str;
+
+    /// Upload a package to the repository of the server
+    fn publish(&self, server: &Server, path: &Path) -> Result<()>;
+}
+
+/// Every built-in publisher
+pub fn publishers() -> Vec<Box<dyn Publisher>> {
+    vec![
+        Box::new(ArtifactoryPublisher),
+        Box::new(NexusPublisher),
+        Box::new(PulpPublisher),
+    ]
+}
+
+/// Look up a built-in publisher by its name
+pub fn find(name: &str) -> Result<Box<dyn Publisher>> {
+    let mut publishers = publishers();
+    match publishers.iter().position(|p| p.name() == name) {
+        Some(position) => Ok(publishers.swap_remove(position)),
+        None => anyhow::bail!(
+            "unknown publisher {}, the available ones are: {}",
+            name,
+            publishers
+                .iter()
+                .map(|p| p.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+pub fn publish(args: PublishArgs) -> Result<()> {
+    let publisher = find(&args.to)?;
+    let auth = match (args.user, args.token) {
+        (Some(user), _) => Auth::Basic {
+            user,
+            password: match args.password {
+                Some(password) => password,
+                None => std::env::var("RPM_BUILDER_PUBLISH_PASSWORD").unwrap_or_default(),
+            },
+        },
+        (None, Some(token)) => Auth::Token(token),
+        (None, None) => match std::env::var("RPM_BUILDER_PUBLISH_TOKEN") {
+            Ok(token) => Auth::Token(token),
+            Err(_) => Auth::None,
+        },
+    };
+    let server = Server {
+        url: args.url.trim_end_matches('/').to_owned(),
+        repository: args.repository,
+        directory: args.directory,
+        auth,
+    };
+
+    for package in &args.packages {
+        tracing::info!(
+            path = %package.display(),
+            publisher = publisher.name(),
+            url = %server.url,
+            repository = %server.repository,
+            "publishing package"
+        );
+        publisher.publish(&server, package).with_context(|| {
+            format!(
+                "unable to publish {:?} to the {} repository {}",
+                package,
+                publisher.name(),
+                server.repository
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// The file name of a package, which it keeps in the artifact store
+fn file_name(path: &Path) -> Result<String> {
+    let file_name = path
+        .file_name()
+        .context("the package path has no file name")?;
+    Ok(file_name.to_string_lossy().into_owned())
+}
+
+/// Where a package is published to, given as a URL. URLs ending with a `/` are directories,
+/// which the package is uploaded into under its own file name.
+#[derive(Debug, PartialEq)]
+pub enum Destination {
+    /// `http(s)://[user:password@]host/path`, the package is uploaded with a PUT request
+    Http { url: String, auth: Auth },
+    /// `s3://bucket/key`, the package is uploaded with the AWS CLI
+    S3 { url: String },
+    /// `scp://[user@]host[:port]/path` (or `ssh://`), the package is uploaded with scp, which needs
+    /// to support `-O` (OpenSSH 8.9 or later)
+    Scp {
+        host: String,
+        port: Option<u16>,
+        path: String,
+    },
+}
+
+impl Destination {
+    pub fn parse(url: &str, file_name: &str) -> Result<Destination> {
+        let Some((scheme, rest)) = url.split_once("://") else {
+            anyhow::bail!(
+                "invalid publish URL {}, it needs to start with a scheme",
+                url
+            );
+        };
+        let rest = match rest.ends_with('/') {
+            true => format!("{}{}", rest, file_name),
+            false => rest.to_owned(),
+        };
+        let (authority, path) = rest.split_once('/').unwrap_or((&rest, ""));
+        if authority.is_empty() || path.is_empty() {
+            anyhow::bail!("invalid publish URL {}, it needs a host and a path", url);
+        }
+
+        match scheme {
+            "http" | "https" => {
+                let (auth, host) = match authority.rsplit_once('@') {
+                    Some((user_info, host)) => {
+                        let (user, password) = user_info.split_once(':').unwrap_or((user_info, ""));
+                        let auth = Auth::Basic {
+                            user: user.to_owned(),
+                            password: password.to_owned(),
+                        };
+                        (auth, host)
+                    }
+                    None => (Auth::None, authority),
+                };
+                Ok(Destination::Http {
+                    url: format!("{}://{}/{}", scheme, host, path),
+                    auth,
+                })
+            }
+            "s3" => Ok(Destination::S3 {
+                url: format!("s3://{}", rest),
+            }),
+            "scp" | "ssh" => {
+                let (host, port) = match authority.rsplit_once(':') {
+                    Some((host, port)) => {
+                        let port = port
+                            .parse()
+                            .with_context(|| format!("invalid port in publish URL {}", url))?;
+                        (host, Some(port))
+                    }
+                    None => (authority, None),
+                };
+                // ssh and scp would take a host starting with - as an option
+                if host.starts_with('-') || host.contains(char::is_whitespace) {
+                    anyhow::bail!(
+                        "invalid host {:?} in publish URL {}, it can't start with - or contain whitespace",
+                        host,
+                        url
+                    );
+                }
+                Ok(Destination::Scp {
+                    host: host.to_owned(),
+                    port,
+                    path: format!("/{}", path),
+                })
+            }
+            _ => anyhow::bail!(
+                "unsupported publish URL {}, only http(s)://, s3:// and scp:// (or ssh://) URLs are supported",
+                url
+            ),
+        }
+    }
+
+    /// Upload a file
+    pub fn upload(&self, path: &Path) -> Result<()> {
+        match self {
+            Destination::Http { url, auth } => {
+                let content =
+                    fs::read(path).with_context(|| format!("unable to read {:?}", path))?;
+                auth.apply(ureq::put(url))
+                    .header("Content-Type", "application/x-rpm")
+                    .send(&content[..])
+                    .with_context(|| format!("unable to upload to {}", self))?;
+            }
+            Destination::S3 { url } => {
+                run(Command::new("aws").arg("s3").arg("cp").arg(path).arg(url))?;
+            }
+            Destination::Scp {
+                host,
+                port,
+                path: remote_path,
+            } => {
+                // -O makes scp use the legacy protocol, which passes the remote path through a
+                // shell on the remote host like ssh does. With the SFTP protocol, the default
+                // since OpenSSH 9.0, the quotes would be taken as part of the path.
+                let mut command = Command::new("scp");
+                command.arg("-O");
+                if let Some(port) = port {
+                    command.arg("-P").arg(port.to_string());
+                }
+                command
+                    .arg("--")
+                    .arg(path)
+                    .arg(format!("{}:{}", host, shell_quote(remote_path)));
+                run(&mut command)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The sha256 checksum of the uploaded file, as a hexadecimal string
+    pub fn checksum(&self) -> Result<String> {
+        match self {
+            Destination::Http { url, auth } => {
+                let content = auth
+                    .apply(ureq::get(url))
+                    .call()
+                    .and_then(|mut response| {
+                        response
+                            .body_mut()
+                            .with_config()
+                            .limit(u64::MAX)
+                            .read_to_vec()
+                    })
+                    .with_context(|| format!("unable to download {}", self))?;
+                Ok(format!("{:x}", Sha256::digest(content)))
+            }
+            Destination::S3 { url } => {
+                let content = run(Command::new("aws").arg("s3").arg("cp").arg(url).arg("-"))?;
+                Ok(format!("{:x}", Sha256::digest(content)))
+            }
+            Destination::Scp { host, port, path } => {
+                let mut command = Command::new("ssh");
+                if let Some(port) = port {
+                    command.arg("-p").arg(port.to_string());
+                }
+                // the remote command goes through a shell, so the path is quoted
+                command
+                    .arg("--")
+                    .arg(host)
+                    .arg(format!("sha256sum -- {}", shell_quote(path)));
+                let output = String::from_utf8(run(&mut command)?)?;
+                let checksum = output.split_whitespace().next().unwrap_or_default();
+                Ok(checksum.to_owned())
+            }
+        }
+    }
+}
+
+/// The URL without the credentials, for messages
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Destination::Http { url, .. } | Destination::S3 { url } => write!(f, "{}", url),
+            Destination::Scp {
+                host,
+                port: Some(port),
+                path,
+            } => write!(f, "scp://{}:{}{}", host, port, path),
+            Destination::Scp {
+                host,
+                port: None,
+                path,
+            } => write!(f, "scp://{}{}", host, path),
+        }
+    }
+}
+
+/// How to authenticate HTTP requests
+#[derive(Debug, PartialEq)]
+pub enum Auth {
+    None,
+    Basic {
+        user: String,
+        password: String,
+    },
+    /// A bearer token, such as an access token
+    Token(String),
+}
+
+impl Auth {
+    /// Add the `Authorization` header to a request
+    pub fn apply<B>(&self, request: ureq::RequestBuilder<B>) -> ureq::RequestBuilder<B> {
+        match self {
+            Auth::None => request,
+            Auth::Basic { user, password } => {
+                let encoded = BASE64_STANDARD.encode(format!("{}:{}", user, password));
+                request.header("Authorization", format!("Basic {}", encoded))
+            }
+            Auth::Token(token) => request.header("Authorization", format!("Bearer {}", token)),
+        }
+    }
+}
+
+/// Quote a string for a POSIX shell
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Run a command, returning its stdout
+fn run(command: &mut Command) -> Result<Vec<u8>> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .with_context(|| format!("unable to run {}", program))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} failed ({}): {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Upload a package to a URL (as given to `--publish`), checking that the uploaded file has the
+/// same checksum if `verify` is set
+pub fn upload(path: &Path, url: &str, verify: bool) -> Result<()> {
+    let destination = Destination::parse(url, &file_name(path)?)?;
+    tracing::info!(path = %path.display(), %destination, "publishing package");
+    destination
+        .upload(path)
+        .with_context(|| format!("unable to publish {:?} to {}", path, destination))?;
+
+    if verify {
+        let content = fs::read(path).with_context(|| format!("unable to read {:?}", path))?;
+        let expected = format!("{:x}", Sha256::digest(content));
+        let checksum = destination.checksum().with_context(|| {
+            format!("unable to verify the package published to {}", destination)
+        })?;
+        if checksum != expected {
+            anyhow::bail!(
+                "the package published to {} has the checksum {}, but {:?} has {}",
+                destination,
+                checksum,
+                path,
+                expected
+            );
+        }
+        tracing::info!(%checksum, "verified published package");
+    }
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+use std::fs;
+use std::path::Path;
+
+use super::{Publisher, Server, file_name};
+
+/// Deploy packages to an rpm repository of JFrog Artifactory, which verifies them against their
+/// checksum
+pub struct ArtifactoryPublisher;
+
+impl Publisher for ArtifactoryPublisher {
+    fn name(&self) -> &'static str {
+        "artifactory"
+    }
+
+    fn publish(&self, server: &Server, path: &Path) -> Result<()> {
+        let content = fs::read(path).with_context(|| format!("unable to read {:?}", path))?;
+        let url = server.file_url(&server.repository, &file_name(path)?);
+        server
+            .auth
+            .apply(ureq::put(&url))
+            .header("Content-Type", "application/x-rpm")
+            .header(
+                "X-Checksum-Sha256",
+                format!("{:x}", Sha256::digest(&content)),
+            )
+            .send(&content[..])
+            .with_context(|| format!("unable to upload to {}", url))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+use anyhow::{Context, Result};
+
+use std::fs;
+use std::path::Path;
+
+use super::{Publisher, Server, file_name};
+
+/// Upload packages to a yum hosted repository of Sonatype Nexus, which generates the repository
+/// metadata itself
+pub struct NexusPublisher;
+
+impl Publisher for NexusPublisher {
+    fn name(&self) -> &'static str {
+        "nexus"
+    }
+
+    fn publish(&self, server: &Server, path: &Path) -> Result<()> {
+        let content = fs::read(path).with_context(|| format!("unable to read {:?}", path))?;
+        let url = server.file_url(
+            &format!("repository/{}", server.repository),
+            &file_name(path)?,
+        );
+        server
+            .auth
+            .apply(ureq::put(&url))
+            .header("Content-Type", "application/x-rpm")
+            .send(&content[..])
+            .with_context(|| format!("unable to upload to {}", url))?;
+        Ok(())
+    }
+}
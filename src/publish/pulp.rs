@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use super::{Publisher, Server, file_name};
+
+/// How long to wait for the upload task to finish
+const TASK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Upload packages with the Pulp 3 RPM API, adding them to a new version of the repository
+pub struct PulpPublisher;
+
+impl Publisher for PulpPublisher {
+    fn name(&self) -> &'static str {
+        "pulp"
+    }
+
+    fn publish(&self, server: &Server, path: &Path) -> Result<()> {
+        let repository = repository_href(server)?;
+        let content = fs::read(path).with_context(|| format!("unable to read {:?}", path))?;
+
+        // the package and the repository are sent as a multipart form
+        let boundary = format!("rpm-builder-{:x}", Sha256::digest(&content));
+        let repository_part = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"repository\"\r\n\r\n{}\r\n",
+            boundary, repository
+        );
+        let file_part = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: application/x-rpm\r\n\r\n",
+            boundary,
+            file_name(path)?.replace('"', "")
+        );
+        let mut body = Vec::with_capacity(content.len() + 512);
+        body.extend(repository_part.as_bytes());
+        body.extend(file_part.as_bytes());
+        body.extend(&content);
+        body.extend(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+        let url = format!("{}/pulp/api/v3/content/rpm/packages/upload/", server.url);
+        let response: Value = server
+            .auth
+            .apply(ureq::post(&url))
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .send(&body[..])
+            .and_then(|mut response| response.body_mut().read_json())
+            .with_context(|| format!("unable to upload to {}", url))?;
+        let task = response["task"]
+            .as_str()
+            .context("pulp didn't return the upload task")?;
+        wait_for_task(server, task)
+    }
+}
+
+/// Look up the href of the repository by its name
+fn repository_href(server: &Server) -> Result<String> {
+    let url = format!("{}/pulp/api/v3/repositories/rpm/rpm/", server.url);
+    let response: Value = server
+        .auth
+        .apply(ureq::get(&url))
+        .query("name", &server.repository)
+        .call()
+        .and_then(|mut response| response.body_mut().read_json())
+        .with_context(|| format!("unable to look up the repository at {}", url))?;
+    match response["results"][0]["pulp_href"].as_str() {
+        Some(href) => Ok(href.to_owned()),
+        None => anyhow::bail!("pulp has no rpm repository {}", server.repository),
+    }
+}
+
+fn wait_for_task(server: &Server, task: &str) -> Result<()> {
+    let url = format!("{}{}", server.url, task);
+    let start = std::time::Instant::now();
+    loop {
+        let task: Value = server
+            .auth
+            .apply(ureq::get(&url))
+            .call()
+            .and_then(|mut response| response.body_mut().read_json())
+            .with_context(|| format!("unable to check the upload task {}", url))?;
+        match task["state"].as_str().unwrap_or_default() {
+            "completed" => return Ok(()),
+            "failed" | "canceled" => anyhow::bail!(
+                "the upload task {} failed: {}",
+                url,
+                task["error"]["description"]
+                    .as_str()
+                    .unwrap_or("unknown error")
+            ),
+            _ if start.elapsed() > TASK_TIMEOUT => {
+                anyhow::bail!("the upload task {} didn't finish in time", url)
+            }
+            _ => thread::sleep(Duration::from_secs(1)),
+        }
+    }
+}
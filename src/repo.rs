@@ -0,0 +1,586 @@
+use anyhow::{Context, Result};
+use clap_derive::{Args, ValueEnum};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{Read as _, Write as _};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::inspect::DependencyInfo;
+
+const COMMON_NAMESPACE: &str = "http://linux.duke.edu/metadata/common";
+const RPM_NAMESPACE: &str = "http://linux.duke.edu/metadata/rpm";
+
+#[derive(Args, Debug)]
+pub struct RepoArgs {
+    #[arg(
+        value_name = "DIRECTORY",
+        help = "Directory with the rpms (searched recursively) to generate the repodata/ for"
+    )]
+    pub directory: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "COMPRESSION",
+        value_enum,
+        default_value = "gzip",
+        help = "Specify the compression of the metadata files"
+    )]
+    pub compression: RepoCompression,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum RepoCompression {
+    Gzip,
+    Zstd,
+}
+
+/// A package listed in the primary metadata of an existing repository
+#[derive(Debug)]
+pub struct PublishedPackage {
+    pub name: String,
+    pub arch: String,
+    pub epoch: String,
+    pub version: String,
+    pub release: String,
+}
+
+impl PublishedPackage {
+    pub fn evr(&self) -> rpm::Evr<'_> {
+        // 0 is left out, like rpm does when printing versions
+        let epoch = if self.epoch == "0" { "" } else { &self.epoch };
+        rpm::Evr::new(epoch, &self.version, &self.release)
+    }
+}
+
+/// A package of the repository, with what the metadata needs besides its header
+struct RepoPackage {
+    pkg: rpm::Package,
+    /// The path relative to the repository, with `/` separators
+    location: String,
+    checksum: String,
+    size: u64,
+    modified_at: u64,
+}
+
+/// Generate the `repodata/` of a directory of rpms like createrepo does, so that dnf can use it
+/// as a repository. Existing metadata is replaced.
+pub fn repo(args: RepoArgs) -> Result<()> {
+    let mut paths = Vec::new();
+    find_packages(&args.directory, &mut paths)?;
+    paths.sort();
+
+    let packages = paths
+        .iter()
+        .map(|path| load_package(&args.directory, path))
+        .collect::<Result<Vec<_>>>()?;
+
+    // the new metadata is put in place all at once, so the repository is never half updated
+    let new_repodata = args.directory.join(".repodata");
+    if new_repodata.exists() {
+        fs::remove_dir_all(&new_repodata)
+            .with_context(|| format!("unable to remove {:?}", new_repodata))?;
+    }
+    fs::create_dir(&new_repodata)
+        .with_context(|| format!("unable to create {:?}", new_repodata))?;
+
+    let timestamp = revision()?;
+    let mut repomd = String::new();
+    writeln!(repomd, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        repomd,
+        r#"<repomd xmlns="http://linux.duke.edu/metadata/repo" xmlns:rpm="{}">"#,
+        RPM_NAMESPACE
+    )?;
+    writeln!(repomd, "  <revision>{}</revision>", timestamp)?;
+    for (kind, content) in [
+        ("primary", primary(&packages)?),
+        ("filelists", filelists(&packages)?),
+        ("other", other(&packages)?),
+    ] {
+        let record = write_metadata(&new_repodata, kind, &content, &args.compression, timestamp)?;
+        repomd.push_str(&record);
+    }
+    writeln!(repomd, "</repomd>")?;
+    let repomd_path = new_repodata.join("repomd.xml");
+    fs::write(&repomd_path, repomd)
+        .with_context(|| format!("unable to write {:?}", repomd_path))?;
+
+    let repodata = args.directory.join("repodata");
+    if repodata.exists() {
+        fs::remove_dir_all(&repodata)
+            .with_context(|| format!("unable to remove the old metadata {:?}", repodata))?;
+    }
+    fs::rename(&new_repodata, &repodata)
+        .with_context(|| format!("unable to move the metadata to {:?}", repodata))?;
+    tracing::info!(path = %repodata.display(), packages = packages.len(), "wrote repository metadata");
+
+    Ok(())
+}
+
+fn find_packages(directory: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    let entries =
+        fs::read_dir(directory).with_context(|| format!("unable to read {:?}", directory))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let file_name = entry.file_name();
+        if entry.file_type()?.is_dir() {
+            if file_name != "repodata" && file_name != ".repodata" {
+                find_packages(&path, paths)?;
+            }
+        } else if path.extension().is_some_and(|extension| extension == "rpm") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn load_package(directory: &Path, path: &Path) -> Result<RepoPackage> {
+    let content = fs::read(path).with_context(|| format!("unable to read package {:?}", path))?;
+    let pkg = rpm::Package::parse(&mut content.as_slice())
+        .with_context(|| format!("unable to read package {:?}", path))?;
+    let modified_at = fs::metadata(path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let location = path
+        .strip_prefix(directory)?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    tracing::info!(path = %path.display(), "adding package");
+    Ok(RepoPackage {
+        pkg,
+        location,
+        checksum: format!("{:x}", Sha256::digest(&content)),
+        size: content.len() as u64,
+        modified_at,
+    })
+}
+
+/// The revision of the metadata, which honors SOURCE_DATE_EPOCH like builds do
+fn revision() -> Result<u64> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) => value.trim().parse().with_context(|| {
+            format!(
+                "invalid SOURCE_DATE_EPOCH {:?}, expected a Unix timestamp",
+                value
+            )
+        }),
+        Err(_) => Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs()),
+    }
+}
+
+/// Write a metadata file, named after its checksum, returning its record for `repomd.xml`
+fn write_metadata(
+    repodata: &Path,
+    kind: &str,
+    content: &str,
+    compression: &RepoCompression,
+    timestamp: u64,
+) -> Result<String> {
+    let (compressed, extension) = match compression {
+        RepoCompression::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(content.as_bytes())?;
+            (encoder.finish()?, "gz")
+        }
+        RepoCompression::Zstd => (zstd::encode_all(content.as_bytes(), 0)?, "zst"),
+    };
+    let checksum = format!("{:x}", Sha256::digest(&compressed));
+    let file_name = format!("{}-{}.xml.{}", checksum, kind, extension);
+    let path = repodata.join(&file_name);
+    fs::write(&path, &compressed).with_context(|| format!("unable to write {:?}", path))?;
+
+    let mut record = String::new();
+    writeln!(record, r#"  <data type="{}">"#, kind)?;
+    writeln!(
+        record,
+        r#"    <checksum type="sha256">{}</checksum>"#,
+        checksum
+    )?;
+    writeln!(
+        record,
+        r#"    <open-checksum type="sha256">{:x}</open-checksum>"#,
+        Sha256::digest(content.as_bytes())
+    )?;
+    writeln!(record, r#"    <location href="repodata/{}"/>"#, file_name)?;
+    writeln!(record, "    <timestamp>{}</timestamp>", timestamp)?;
+    writeln!(record, "    <size>{}</size>", compressed.len())?;
+    writeln!(record, "    <open-size>{}</open-size>", content.len())?;
+    writeln!(record, "  </data>")?;
+    Ok(record)
+}
+
+fn primary(packages: &[RepoPackage]) -> Result<String> {
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        xml,
+        r#"<metadata xmlns="{}" xmlns:rpm="{}" packages="{}">"#,
+        COMMON_NAMESPACE,
+        RPM_NAMESPACE,
+        packages.len()
+    )?;
+    for package in packages {
+        let metadata = &package.pkg.metadata;
+        let optional = |value: Result<&str, rpm::Error>| escape(value.unwrap_or_default());
+        writeln!(xml, r#"<package type="rpm">"#)?;
+        writeln!(xml, "  <name>{}</name>", escape(metadata.get_name()?))?;
+        writeln!(xml, "  <arch>{}</arch>", escape(metadata.get_arch()?))?;
+        writeln!(xml, "  {}", version(metadata)?)?;
+        writeln!(
+            xml,
+            r#"  <checksum type="sha256" pkgid="YES">{}</checksum>"#,
+            package.checksum
+        )?;
+        writeln!(
+            xml,
+            "  <summary>{}</summary>",
+            escape(metadata.get_summary()?)
+        )?;
+        writeln!(
+            xml,
+            "  <description>{}</description>",
+            optional(metadata.get_description())
+        )?;
+        writeln!(
+            xml,
+            "  <packager>{}</packager>",
+            optional(metadata.get_packager())
+        )?;
+        writeln!(xml, "  <url>{}</url>", optional(metadata.get_url()))?;
+        writeln!(
+            xml,
+            r#"  <time file="{}" build="{}"/>"#,
+            package.modified_at,
+            metadata.get_build_time().unwrap_or(0)
+        )?;
+        writeln!(
+            xml,
+            r#"  <size package="{}" installed="{}" archive="{}"/>"#,
+            package.size,
+            metadata.get_installed_size().unwrap_or(0),
+            archive_size(metadata)
+        )?;
+        writeln!(xml, r#"  <location href="{}"/>"#, escape(&package.location))?;
+
+        writeln!(xml, "  <format>")?;
+        writeln!(
+            xml,
+            "    <rpm:license>{}</rpm:license>",
+            optional(metadata.get_license())
+        )?;
+        writeln!(
+            xml,
+            "    <rpm:vendor>{}</rpm:vendor>",
+            optional(metadata.get_vendor())
+        )?;
+        writeln!(
+            xml,
+            "    <rpm:group>{}</rpm:group>",
+            optional(metadata.get_group())
+        )?;
+        writeln!(
+            xml,
+            "    <rpm:buildhost>{}</rpm:buildhost>",
+            optional(metadata.get_build_host())
+        )?;
+        writeln!(
+            xml,
+            "    <rpm:sourcerpm>{}</rpm:sourcerpm>",
+            optional(metadata.get_source_rpm())
+        )?;
+        let offsets = metadata.get_package_segment_offsets();
+        writeln!(
+            xml,
+            r#"    <rpm:header-range start="{}" end="{}"/>"#,
+            offsets.header, offsets.payload
+        )?;
+        for (kind, dependencies) in [
+            ("provides", metadata.get_provides()?),
+            ("requires", metadata.get_requires()?),
+            ("conflicts", metadata.get_conflicts()?),
+            ("obsoletes", metadata.get_obsoletes()?),
+            ("suggests", metadata.get_suggests()?),
+            ("enhances", metadata.get_enhances()?),
+            ("recommends", metadata.get_recommends()?),
+            ("supplements", metadata.get_supplements()?),
+        ] {
+            // the rpmlib() requirements are satisfied by rpm itself
+            let dependencies: Vec<_> = dependencies
+                .into_iter()
+                .filter(|dependency| !dependency.name.starts_with("rpmlib("))
+                .collect();
+            if dependencies.is_empty() {
+                continue;
+            }
+            writeln!(xml, "    <rpm:{}>", kind)?;
+            for dependency in &dependencies {
+                writeln!(
+                    xml,
+                    "      {}",
+                    dependency_entry(dependency, kind == "requires")
+                )?;
+            }
+            writeln!(xml, "    </rpm:{}>", kind)?;
+        }
+        // like createrepo, only the files which are commonly depended on are listed
+        for file in metadata.get_file_entries()? {
+            let path = file.path.to_string_lossy();
+            if path.starts_with("/etc/") || path.contains("bin/") || path == "/usr/lib/sendmail" {
+                writeln!(xml, "    {}", file_entry(&file))?;
+            }
+        }
+        writeln!(xml, "  </format>")?;
+        writeln!(xml, "</package>")?;
+    }
+    writeln!(xml, "</metadata>")?;
+    Ok(xml)
+}
+
+fn filelists(packages: &[RepoPackage]) -> Result<String> {
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        xml,
+        r#"<filelists xmlns="http://linux.duke.edu/metadata/filelists" packages="{}">"#,
+        packages.len()
+    )?;
+    for package in packages {
+        let metadata = &package.pkg.metadata;
+        writeln!(xml, "{}", package_element(package)?)?;
+        writeln!(xml, "  {}", version(metadata)?)?;
+        for file in metadata.get_file_entries()? {
+            writeln!(xml, "  {}", file_entry(&file))?;
+        }
+        writeln!(xml, "</package>")?;
+    }
+    writeln!(xml, "</filelists>")?;
+    Ok(xml)
+}
+
+fn other(packages: &[RepoPackage]) -> Result<String> {
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        xml,
+        r#"<otherdata xmlns="http://linux.duke.edu/metadata/other" packages="{}">"#,
+        packages.len()
+    )?;
+    for package in packages {
+        let metadata = &package.pkg.metadata;
+        writeln!(xml, "{}", package_element(package)?)?;
+        writeln!(xml, "  {}", version(metadata)?)?;
+        for entry in metadata.get_changelog_entries()? {
+            writeln!(
+                xml,
+                r#"  <changelog author="{}" date="{}">{}</changelog>"#,
+                escape(&entry.name),
+                entry.timestamp,
+                escape(&entry.description)
+            )?;
+        }
+        writeln!(xml, "</package>")?;
+    }
+    writeln!(xml, "</otherdata>")?;
+    Ok(xml)
+}
+
+/// The opening tag of a package in `filelists.xml` and `other.xml`
+fn package_element(package: &RepoPackage) -> Result<String> {
+    let metadata = &package.pkg.metadata;
+    Ok(format!(
+        r#"<package pkgid="{}" name="{}" arch="{}">"#,
+        package.checksum,
+        escape(metadata.get_name()?),
+        escape(metadata.get_arch()?)
+    ))
+}
+
+fn version(metadata: &rpm::PackageMetadata) -> Result<String> {
+    Ok(format!(
+        r#"<version epoch="{}" ver="{}" rel="{}"/>"#,
+        metadata.get_epoch().unwrap_or(0),
+        escape(metadata.get_version()?),
+        escape(metadata.get_release()?)
+    ))
+}
+
+/// The size of the uncompressed payload, which rpm records in the signature header
+fn archive_size(metadata: &rpm::PackageMetadata) -> u64 {
+    let signature = &metadata.signature;
+    signature
+        .get_entry_data_as_u64(rpm::IndexSignatureTag::RPMSIGTAG_LONGARCHIVESIZE)
+        .or_else(|_| {
+            signature
+                .get_entry_data_as_u32(rpm::IndexSignatureTag::RPMSIGTAG_PAYLOADSIZE)
+                .map(u64::from)
+        })
+        .or_else(|_| {
+            metadata
+                .header
+                .get_entry_data_as_u64(rpm::IndexTag::RPMTAG_PAYLOADSIZE)
+        })
+        .unwrap_or(0)
+}
+
+fn dependency_entry(dependency: &rpm::Dependency, is_requirement: bool) -> String {
+    let mut entry = format!(r#"<rpm:entry name="{}""#, escape(&dependency.name));
+    let info = DependencyInfo::from(dependency);
+    if let (Some(operator), Some(version)) = (info.operator, &info.version) {
+        let flags = match operator {
+            "<" => "LT",
+            "<=" => "LE",
+            ">=" => "GE",
+            ">" => "GT",
+            _ => "EQ",
+        };
+        // the version is `[epoch:]version[-release]`
+        let (epoch, rest) = match version.split_once(':') {
+            Some((epoch, rest)) if epoch.chars().all(|c| c.is_ascii_digit()) => (epoch, rest),
+            _ => ("0", version.as_str()),
+        };
+        let _ = write!(
+            entry,
+            r#" flags="{}" epoch="{}" ver="{}""#,
+            flags,
+            epoch,
+            escape(rest.split_once('-').map_or(rest, |(version, _)| version))
+        );
+        if let Some((_, release)) = rest.split_once('-') {
+            let _ = write!(entry, r#" rel="{}""#, escape(release));
+        }
+    }
+    let pre = rpm::DependencyFlags::PREREQ
+        | rpm::DependencyFlags::SCRIPT_PRE
+        | rpm::DependencyFlags::SCRIPT_POST;
+    if is_requirement && dependency.flags.intersects(pre) {
+        entry.push_str(r#" pre="1""#);
+    }
+    entry.push_str("/>");
+    entry
+}
+
+fn file_entry(file: &rpm::FileEntry) -> String {
+    let kind = if file.flags.contains(rpm::FileFlags::GHOST) {
+        r#" type="ghost""#
+    } else if matches!(file.mode, rpm::FileMode::Dir { .. }) {
+        r#" type="dir""#
+    } else {
+        ""
+    };
+    format!(
+        "<file{}>{}</file>",
+        kind,
+        escape(&file.path.to_string_lossy())
+    )
+}
+
+/// Read the packages of an existing repository from its primary metadata. The repository is
+/// either a directory containing `repodata/` or an http(s) URL of one.
+pub fn published_packages(repository: &str) -> Result<Vec<PublishedPackage>> {
+    let read = |location: &str| -> Result<Vec<u8>> {
+        if repository.starts_with("http://") || repository.starts_with("https://") {
+            let url = format!("{}/{}", repository.trim_end_matches('/'), location);
+            ureq::get(&url)
+                .call()
+                .and_then(|mut response| {
+                    response
+                        .body_mut()
+                        .with_config()
+                        .limit(u64::MAX)
+                        .read_to_vec()
+                })
+                .with_context(|| format!("unable to download {}", url))
+        } else {
+            let directory = repository.strip_prefix("file://").unwrap_or(repository);
+            let path = Path::new(directory).join(location);
+            fs::read(&path).with_context(|| format!("unable to read {:?}", path))
+        }
+    };
+
+    let repomd =
+        String::from_utf8(read("repodata/repomd.xml")?).context("repomd.xml is not valid UTF-8")?;
+    let primary_location = Regex::new(r#"(?s)<data type="primary">.*?<location href="([^"]+)""#)
+        .unwrap()
+        .captures(&repomd)
+        .map(|captures| captures[1].to_owned())
+        .context("repomd.xml doesn't list the primary metadata")?;
+    let compressed = read(&primary_location)?;
+    let mut primary = String::new();
+    if primary_location.ends_with(".gz") {
+        flate2::read::GzDecoder::new(&compressed[..]).read_to_string(&mut primary)?;
+    } else if primary_location.ends_with(".zst") {
+        primary = String::from_utf8(zstd::decode_all(&compressed[..])?)?;
+    } else if primary_location.ends_with(".xml") {
+        primary = String::from_utf8(compressed)?;
+    } else {
+        anyhow::bail!(
+            "unsupported compression of the primary metadata {}, only gzip and zstd can be read",
+            primary_location
+        );
+    }
+
+    let package_re = Regex::new(r#"(?s)<package type="rpm">(.*?)</package>"#).unwrap();
+    let name_re = Regex::new(r"<name>([^<]*)</name>").unwrap();
+    let arch_re = Regex::new(r"<arch>([^<]*)</arch>").unwrap();
+    let version_re =
+        Regex::new(r#"<version epoch="([^"]*)" ver="([^"]*)" rel="([^"]*)"/>"#).unwrap();
+    package_re
+        .captures_iter(&primary)
+        .map(|package| {
+            let package = &package[1];
+            let (Some(name), Some(arch), Some(version)) = (
+                name_re.captures(package),
+                arch_re.captures(package),
+                version_re.captures(package),
+            ) else {
+                anyhow::bail!(
+                    "invalid package in {}: {}",
+                    primary_location,
+                    package.trim()
+                );
+            };
+            Ok(PublishedPackage {
+                name: unescape(&name[1]),
+                arch: unescape(&arch[1]),
+                epoch: unescape(&version[1]),
+                version: unescape(&version[2]),
+                release: unescape(&version[3]),
+            })
+        })
+        .collect()
+}
+
+/// Undo the escaping of XML text
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Escape text for XML, dropping the control characters XML can't contain
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,154 @@
+use anyhow::Result;
+
+use std::path::Path;
+
+/// The directory policy modules are installed into
+pub const MODULE_DIR: &str = "/usr/share/selinux/packages";
+
+/// A file context for a path of the package, e.g. `system_u:object_r:httpd_sys_content_t:s0`
+#[derive(Debug)]
+pub struct FileContext {
+    pub path: String,
+    pub user: Option<String>,
+    pub role: Option<String>,
+    pub context_type: String,
+    pub range: Option<String>,
+}
+
+impl FileContext {
+    /// Parse a context given as `<dest-path>:<context>`, where the context is either a full
+    /// `user:role:type[:range]` context or just a type
+    pub fn parse(raw: &str) -> Result<FileContext> {
+        let Some((path, context)) = raw.split_once(':') else {
+            anyhow::bail!(
+                "invalid SELinux context {}: it needs to be of the form <dest-path>:<context>",
+                raw
+            );
+        };
+        if !path.starts_with('/') || path.contains('\'') {
+            anyhow::bail!(
+                "invalid path {}: it needs to be absolute and can't contain quotes",
+                path
+            );
+        }
+
+        let is_identifier =
+            |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        let parts: Vec<&str> = context.splitn(4, ':').collect();
+        let file_context = match parts.as_slice() {
+            [context_type] => FileContext {
+                path: path.to_owned(),
+                user: None,
+                role: None,
+                context_type: context_type.to_string(),
+                range: None,
+            },
+            [user, role, context_type, range @ ..] => FileContext {
+                path: path.to_owned(),
+                user: Some(user.to_string()),
+                role: Some(role.to_string()),
+                context_type: context_type.to_string(),
+                range: range.first().map(|range| range.to_string()),
+            },
+            _ => anyhow::bail!(
+                "invalid SELinux context {}: it needs to be a type or of the form user:role:type[:range]",
+                context
+            ),
+        };
+
+        let identifiers_are_valid = [&file_context.user, &file_context.role]
+            .into_iter()
+            .flatten()
+            .chain([&file_context.context_type])
+            .all(|s| is_identifier(s));
+        let range_is_valid = file_context.range.as_deref().is_none_or(|range| {
+            range
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || ":.,-".contains(c))
+        });
+        if !identifiers_are_valid || !range_is_valid {
+            anyhow::bail!("invalid SELinux context {}", context);
+        }
+        Ok(file_context)
+    }
+
+    /// The file context specification matching the path and everything below it
+    fn spec(&self) -> String {
+        let mut spec = String::new();
+        for c in self.path.chars() {
+            if "\\.^$*+?()[]{}|".contains(c) {
+                spec.push('\\');
+            }
+            spec.push(c);
+        }
+        spec.push_str("(/.*)?");
+        spec
+    }
+
+    /// Register the context with the local policy and relabel the path. An existing rule for the
+    /// path, e.g. from an earlier version of the package, is modified instead.
+    pub fn post_install(&self) -> String {
+        let mut options = format!("-t {}", self.context_type);
+        if let Some(user) = &self.user {
+            options.push_str(&format!(" -s {}", user));
+        }
+        if let Some(range) = &self.range {
+            options.push_str(&format!(" -r {}", range));
+        }
+        format!(
+            "if command -v semanage >/dev/null 2>&1 ; then\n    semanage fcontext -a {options} '{spec}' 2>/dev/null || semanage fcontext -m {options} '{spec}' || :\n    restorecon -R '{path}' || :\nfi\n",
+            spec = self.spec(),
+            path = self.path,
+        )
+    }
+
+    /// Remove the context from the local policy when the package is removed
+    pub fn post_uninstall(&self) -> String {
+        format!(
+            "if [ $1 -eq 0 ] && command -v semanage >/dev/null 2>&1 ; then\n    semanage fcontext -d '{}' 2>/dev/null || :\nfi\n",
+            self.spec()
+        )
+    }
+}
+
+/// The name of a policy module, which is the file name without the `.pp`, `.pp.bz2` or `.cil`
+/// extension
+pub fn module_name(path: &Path) -> Result<String> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    let name = [".pp.bz2", ".pp", ".cil"]
+        .into_iter()
+        .find_map(|extension| file_name.strip_suffix(extension))
+        .filter(|name| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        });
+    match name {
+        Some(name) => Ok(name.to_owned()),
+        None => anyhow::bail!(
+            "invalid policy module {:?}: it needs to be a .pp, .pp.bz2 or .cil file",
+            path
+        ),
+    }
+}
+
+/// The equivalent of `%selinux_modules_install`: install the module into the targeted policy and
+/// reload the policy
+pub fn module_post_install(dest: &str) -> String {
+    format!(
+        "if command -v semodule >/dev/null 2>&1 ; then\n    semodule -n -s targeted -X 200 -i '{}' || :\n    selinuxenabled && load_policy || :\nfi\n",
+        dest
+    )
+}
+
+/// The equivalent of `%selinux_modules_uninstall`: remove the module when the package is removed
+pub fn module_post_uninstall(name: &str) -> String {
+    format!(
+        "if [ $1 -eq 0 ] && command -v semodule >/dev/null 2>&1 ; then\n    semodule -n -s targeted -X 200 -r {} || :\n    selinuxenabled && load_policy || :\nfi\n",
+        name
+    )
+}
@@ -0,0 +1,172 @@
+use anyhow::{Context, Result};
+use clap_derive::{Args, ValueEnum};
+use rpm::signature::pgp::Signer;
+use rpm::signature::{AlgorithmType, Signing};
+use sha2::{Digest, Sha256};
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::header;
+
+#[derive(Args, Debug)]
+pub struct SignArgs {
+    #[arg(value_name = "PACKAGE", help = "Path to the rpm to sign")]
+    pub package: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "SIGN_WITH_PGP_ASC",
+        required = true,
+        help = "Sign the package with the specified PGP secret key, can be given multiple times to add a signature per key"
+    )]
+    pub sign_with_pgp_asc: Vec<PathBuf>,
+
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "OUT",
+        help = "Write the signed package to this path instead of replacing the original"
+    )]
+    pub out: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "MODE",
+        default_value = "header-only",
+        help = "Which signatures to add: only over the header, or also (legacy) over the header and payload"
+    )]
+    pub signature_mode: SignatureMode,
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+pub enum SignatureMode {
+    /// Only sign the header, which covers the payload through its digests
+    HeaderOnly,
+    /// Also add a signature over the header and the payload, like older rpm versions did
+    HeaderAndPayload,
+}
+
+pub fn sign(args: SignArgs) -> Result<()> {
+    let mut pkg = rpm::Package::open(&args.package)
+        .with_context(|| format!("unable to read package {:?}", &args.package))?;
+
+    // any existing signatures are replaced rather than added to
+    pkg.clear_signatures()
+        .with_context(|| format!("unable to remove signatures from {:?}", &args.package))?;
+    let signers = load_signers(&args.sign_with_pgp_asc)?;
+    let pkg = sign_package(pkg, &signers, rpm::Timestamp::now(), &args.signature_mode)
+        .with_context(|| format!("unable to sign package {:?}", &args.package))?;
+
+    let output_path = match args.out {
+        Some(path) if path.is_dir() => path.join(args.package.file_name().unwrap_or_default()),
+        Some(path) => path,
+        None => args.package,
+    };
+    pkg.write_file(&output_path)
+        .with_context(|| format!("unable to write package to path {:?}", &output_path))?;
+    tracing::info!(path = %output_path.display(), "wrote package");
+
+    Ok(())
+}
+
+/// Sign a package with each of the signers (replacing any existing signatures), adding the
+/// signatures selected by the mode
+pub fn sign_package(
+    pkg: rpm::Package,
+    signers: &[Signer],
+    time: rpm::Timestamp,
+    mode: &SignatureMode,
+) -> Result<rpm::Package> {
+    let header = header::header_bytes(&pkg)?;
+    let mut builder = rpm::SignatureHeaderBuilder::new()
+        .set_sha256_digest(&format!("{:x}", Sha256::digest(&header)))
+        .set_content_length((header.len() + pkg.content.len()) as u64);
+    for signer in signers {
+        builder = builder.add_openpgp_signature(signer.sign(header.as_slice(), time)?);
+    }
+    let mut pkg = pkg;
+    pkg.metadata.signature = builder.build()?;
+
+    if *mode == SignatureMode::HeaderAndPayload {
+        // rpm keeps one RSA and one DSA (or EdDSA) signature over the header and payload, so
+        // with several signers of the same kind the last one is used, like for the legacy
+        // header signatures
+        let mut tags = Vec::new();
+        for signer in signers {
+            let tag = match signer.algorithm() {
+                AlgorithmType::RSA => rpm::IndexSignatureTag::RPMSIGTAG_PGP,
+                AlgorithmType::ECDSA | AlgorithmType::EdDSA => {
+                    rpm::IndexSignatureTag::RPMSIGTAG_GPG
+                }
+            };
+            // chained instead of copied, the payload can be large
+            let signed = header.as_slice().chain(pkg.content.as_slice());
+            let signature = signer.sign(signed, time)?;
+            tags.retain(|(t, _)| *t != tag);
+            tags.push((tag, header::Value::Bin(signature)));
+        }
+        pkg = header::set_signature_tags(pkg, &tags)?;
+    }
+
+    for tag in SIGNATURE_TAGS {
+        let count = match pkg.metadata.signature.get_entry_data_as_string_array(tag) {
+            Ok(signatures) => signatures.len(),
+            Err(_) => pkg
+                .metadata
+                .signature
+                .get_entry_data_as_binary(tag)
+                .map_or(0, |_| 1),
+        };
+        if count > 0 {
+            tracing::info!(%tag, count, "filled signature slot");
+        }
+    }
+    Ok(pkg)
+}
+
+/// The tags of the signature header which hold signatures
+const SIGNATURE_TAGS: [rpm::IndexSignatureTag; 5] = [
+    rpm::IndexSignatureTag::RPMSIGTAG_OPENPGP,
+    rpm::IndexSignatureTag::RPMSIGTAG_RSA,
+    rpm::IndexSignatureTag::RPMSIGTAG_DSA,
+    rpm::IndexSignatureTag::RPMSIGTAG_PGP,
+    rpm::IndexSignatureTag::RPMSIGTAG_GPG,
+];
+
+/// Load the ASCII-armored PGP secret keys to sign packages with
+pub fn load_signers(signing_key_paths: &[PathBuf]) -> Result<Vec<Signer>> {
+    signing_key_paths
+        .iter()
+        .map(|path| {
+            let signer = load_signer(path)?;
+            tracing::info!(key = %path.display(), algorithm = ?signer.algorithm(), "signing package");
+            Ok(signer)
+        })
+        .collect()
+}
+
+/// Load an ASCII-armored PGP secret key to sign packages with
+pub fn load_signer(signing_key_path: &Path) -> Result<Signer> {
+    let raw_key = fs::read(signing_key_path).with_context(|| {
+        format!(
+            "unable to load private key file from path {:?}",
+            signing_key_path
+        )
+    })?;
+
+    match Signer::load_from_asc_bytes(&raw_key) {
+        Err(rpm::Error::UnsupportedPGPKeyType(algorithm)) => anyhow::bail!(
+            "unable to create signer from private key {:?}: {:?} keys aren't supported, only RSA, ECDSA and EdDSA (e.g. Ed25519) ones",
+            signing_key_path,
+            algorithm
+        ),
+        signer => signer.with_context(|| {
+            format!(
+                "unable to create signer from private key {:?}",
+                signing_key_path
+            )
+        }),
+    }
+}
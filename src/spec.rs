@@ -0,0 +1,1184 @@
+//! Reading rpmbuild spec files, for `build --from-spec`, and writing them for `build --emit-spec`.
+//!
+//! The preamble, the descriptions and the file lists are translated into the equivalent command
+//! line options, like a manifest is. The scriptlets and the changelog are kept as they are, since
+//! the options for them take files rather than their content. The build sections (`%prep`,
+//! `%build`, `%install`, ...) aren't run: the files are taken from a buildroot which was
+//! populated beforehand.
+//!
+//! The spec files which are written describe the packages which were built, so they list the
+//! files as they are in the packages, but have no build sections either.
+
+use anyhow::{Context, Result};
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::changelog;
+use crate::inspect::{DependencyInfo, PackageInfo};
+
+/// The directory macros rpm defines, which `%files` sections are usually written with
+const DIRECTORY_MACROS: &[(&str, &str)] = &[
+    ("_prefix", "/usr"),
+    ("_exec_prefix", "%{_prefix}"),
+    ("_bindir", "%{_exec_prefix}/bin"),
+    ("_sbindir", "%{_exec_prefix}/sbin"),
+    ("_libexecdir", "%{_exec_prefix}/libexec"),
+    ("_libdir", "%{_exec_prefix}/%{_lib}"),
+    ("_includedir", "%{_prefix}/include"),
+    ("_datadir", "%{_prefix}/share"),
+    ("_datarootdir", "%{_prefix}/share"),
+    ("_docdir", "%{_datadir}/doc"),
+    ("_defaultdocdir", "%{_datadir}/doc"),
+    ("_licensedir", "%{_datadir}/licenses"),
+    ("_infodir", "%{_datadir}/info"),
+    ("_mandir", "%{_datadir}/man"),
+    ("_sysconfdir", "/etc"),
+    ("_initddir", "%{_sysconfdir}/rc.d/init.d"),
+    ("_localstatedir", "/var"),
+    ("_sharedstatedir", "/var/lib"),
+    ("_rundir", "/run"),
+    ("_unitdir", "/usr/lib/systemd/system"),
+    ("_userunitdir", "/usr/lib/systemd/user"),
+    ("_presetdir", "/usr/lib/systemd/system-preset"),
+    ("_tmpfilesdir", "/usr/lib/tmpfiles.d"),
+    ("_sysusersdir", "/usr/lib/sysusers.d"),
+    ("_udevrulesdir", "/usr/lib/udev/rules.d"),
+];
+
+/// The sections which build the package, and aren't needed when the buildroot already exists
+const BUILD_SECTIONS: &[&str] = &[
+    "prep",
+    "generate_buildrequires",
+    "conf",
+    "build",
+    "install",
+    "check",
+    "clean",
+];
+
+/// The scriptlet sections, by the name of the options which set them on the command line
+const SCRIPTLET_SECTIONS: &[(&str, &str)] = &[
+    ("pre", "pre-install-script"),
+    ("post", "post-install-script"),
+    ("preun", "pre-uninstall-script"),
+    ("postun", "post-uninstall-script"),
+    ("pretrans", "pre-trans-script"),
+    ("posttrans", "post-trans-script"),
+    ("verifyscript", "verify-script"),
+];
+
+/// A spec file, with its main package and the ones declared with `%package`
+#[derive(Debug)]
+pub struct SpecFile {
+    pub package: Package,
+    pub subpackages: Vec<Package>,
+    /// The `%changelog`, which rpm adds to every package
+    pub changelog: Vec<changelog::Entry>,
+}
+
+/// A package of a spec file
+#[derive(Debug)]
+pub struct Package {
+    pub name: String,
+    pub scriptlets: Vec<Scriptlet>,
+    args: Vec<OsString>,
+}
+
+/// A scriptlet section, e.g. `%post -p /sbin/ldconfig`
+#[derive(Clone, Debug)]
+pub struct Scriptlet {
+    /// The name of the option setting the same scriptlet, e.g. `post-install-script`
+    pub kind: String,
+    pub interpreter: Option<String>,
+    pub content: String,
+}
+
+impl Package {
+    /// The preamble, description and files of the package, as command line options (without the
+    /// name)
+    pub fn to_args(&self) -> Vec<OsString> {
+        self.args.clone()
+    }
+
+    /// Whether the package sets the option with the given long name, e.g. `exec_file`
+    pub fn sets(&self, option: &str) -> bool {
+        self.args.iter().any(|arg| {
+            let arg = arg.to_string_lossy();
+            let flag = arg.trim_start_matches("--").split('=').next();
+            flag.is_some_and(|flag| flag.replace('-', "_") == option)
+        })
+    }
+
+    /// The scriptlet of the spec file for the option with the given long name, e.g.
+    /// `post-install-script`
+    pub fn scriptlet(scriptlets: &[Scriptlet], kind: &str) -> Option<Scriptlet> {
+        scriptlets.iter().find(|s| s.kind == kind).cloned()
+    }
+}
+
+/// The section of the spec file which is being read
+enum Section {
+    Preamble,
+    Description,
+    Files,
+    Scriptlet(Scriptlet),
+    Changelog,
+    /// A build section, which is skipped
+    Build,
+}
+
+/// A package while the spec file is read
+#[derive(Default)]
+struct PackageDefinition {
+    name: String,
+    args: Vec<OsString>,
+    description: Vec<String>,
+    files: Vec<String>,
+    scriptlets: Vec<Scriptlet>,
+}
+
+impl SpecFile {
+    /// Load a spec file. The absolute paths of the `%files` sections are looked up in the
+    /// buildroot, and `arch` decides whether `%{_libdir}` is `/usr/lib64` unless the spec file
+    /// has a `BuildArch`.
+    pub fn load(path: &Path, buildroot: Option<&Path>, arch: &str) -> Result<SpecFile> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("error reading spec file {:?}", path))?;
+        let mut reader = Reader::new(buildroot, arch);
+        reader
+            .read(&content)
+            .with_context(|| format!("invalid spec file {:?}", path))?;
+        reader
+            .finish()
+            .with_context(|| format!("invalid spec file {:?}", path))
+    }
+}
+
+struct Reader<'a> {
+    buildroot: Option<&'a Path>,
+    macros: BTreeMap<String, String>,
+    packages: Vec<PackageDefinition>,
+    /// The package the current section belongs to
+    current: usize,
+    section: Section,
+    section_lines: Vec<String>,
+    changelog: Vec<String>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buildroot: Option<&'a Path>, arch: &str) -> Reader<'a> {
+        let mut macros: BTreeMap<String, String> = DIRECTORY_MACROS
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        macros.insert("_lib".to_owned(), lib_dir_name(arch).to_owned());
+        if let Some(buildroot) = buildroot {
+            macros.insert("buildroot".to_owned(), buildroot.display().to_string());
+        }
+        Reader {
+            buildroot,
+            macros,
+            packages: vec![PackageDefinition::default()],
+            current: 0,
+            section: Section::Preamble,
+            section_lines: Vec::new(),
+            changelog: Vec::new(),
+        }
+    }
+
+    fn read(&mut self, content: &str) -> Result<()> {
+        for (index, line) in content.lines().enumerate() {
+            self.read_line(line)
+                .with_context(|| format!("line {}", index + 1))?;
+        }
+        self.end_section()
+    }
+
+    fn read_line(&mut self, line: &str) -> Result<()> {
+        let trimmed = line.trim();
+        let keyword = trimmed
+            .strip_prefix('%')
+            .map(|rest| rest.split_whitespace().next().unwrap_or_default());
+        let is_build_section = matches!(self.section, Section::Build);
+
+        match keyword {
+            Some(keyword) if is_section(keyword) => {
+                self.end_section()?;
+                let line = self.expand(trimmed)?;
+                return self.start_section(&line);
+            }
+            // the build sections are only skipped, so their macros don't matter
+            _ if is_build_section => return Ok(()),
+            Some("define") | Some("global") => return self.define(trimmed),
+            Some("undefine") => {
+                let name = trimmed["%undefine".len()..].trim();
+                self.macros.remove(name);
+                return Ok(());
+            }
+            Some(keyword) if keyword.starts_with("if") || keyword.starts_with("el") => {
+                anyhow::bail!("conditionals (%{}) aren't supported", keyword);
+            }
+            Some("endif") => anyhow::bail!("conditionals (%endif) aren't supported"),
+            _ => {}
+        }
+
+        if let Section::Preamble = self.section {
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                return Ok(());
+            }
+            let line = self.expand(trimmed)?;
+            return self.read_tag(&line);
+        }
+        // the directives of %files lines look like macros, so the rest of the line is only
+        // expanded once they are parsed
+        let line = match self.section {
+            Section::Files => line.to_owned(),
+            _ => self.expand(line)?,
+        };
+        self.section_lines.push(line);
+        Ok(())
+    }
+
+    /// `%define <name> <value>` or `%global <name> <value>`, where the value of `%global` is
+    /// expanded right away
+    fn define(&mut self, line: &str) -> Result<()> {
+        let definition = line
+            .trim_start_matches(|c: char| !c.is_whitespace())
+            .trim_start();
+        let (name, value) = definition
+            .split_once(char::is_whitespace)
+            .unwrap_or((definition, ""));
+        let value = value.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            anyhow::bail!("invalid macro definition {:?}", line);
+        }
+        let value = if line.starts_with("%global") {
+            self.expand(value)?
+        } else {
+            value.to_owned()
+        };
+        self.macros.insert(name.to_owned(), value);
+        Ok(())
+    }
+
+    /// Parse a `Tag: value` line of a preamble
+    fn read_tag(&mut self, line: &str) -> Result<()> {
+        let (tag, value) = line
+            .split_once(':')
+            .with_context(|| format!("expected a 'Tag: value' line, found {:?}", line))?;
+        let value = value.trim();
+        // e.g. Requires(post)
+        let (tag, qualifiers) = match tag.split_once('(') {
+            Some((tag, qualifiers)) => (tag, qualifiers.trim_end().strip_suffix(')')),
+            None => (tag, None),
+        };
+        let tag = tag.trim().to_ascii_lowercase();
+        let is_main_package = self.current == 0;
+
+        let mut options = Vec::new();
+        match tag.as_str() {
+            "name" if is_main_package => {
+                self.packages[0].name = value.to_owned();
+                self.macros.insert("name".to_owned(), value.to_owned());
+            }
+            // like rpm, the tags of the main package can be referred to as macros
+            "version" | "release" | "epoch" | "license" | "summary" | "url" | "vendor" => {
+                options.push((tag.clone(), value.to_owned()));
+                if is_main_package {
+                    self.macros.insert(tag.clone(), value.to_owned());
+                }
+            }
+            "prefix" | "packager" | "group" | "distribution" => {
+                options.push((tag.clone(), value.to_owned()))
+            }
+            "buildarch" | "buildarchitectures" => {
+                options.push(("arch".to_owned(), value.to_owned()));
+                self.macros
+                    .insert("_lib".to_owned(), lib_dir_name(value).to_owned());
+            }
+            "requires" => {
+                for dependency in split_dependencies(value)? {
+                    let dependency = match qualifiers {
+                        Some(qualifiers) => format!("{} [{}]", dependency, qualifiers),
+                        None => dependency,
+                    };
+                    options.push((tag.clone(), dependency));
+                }
+            }
+            "orderwithrequires" => {
+                for dependency in split_dependencies(value)? {
+                    options.push(("order-with-requires".to_owned(), dependency));
+                }
+            }
+            "provides" | "obsoletes" | "conflicts" | "suggests" | "recommends" | "enhances"
+            | "supplements" => {
+                for dependency in split_dependencies(value)? {
+                    options.push((tag.clone(), dependency));
+                }
+            }
+            // only needed to build the package, or not recorded by rpm-builder
+            "buildrequires" | "buildconflicts" | "buildroot" | "exclusivearch" | "excludearch"
+            | "exclusiveos" | "excludeos" | "autoreq" | "autoprov" | "autoreqprov" | "nosource"
+            | "nopatch" => {}
+            _ if tag.starts_with("source") || tag.starts_with("patch") => {}
+            _ => anyhow::bail!("unsupported tag {:?}", tag),
+        }
+
+        let package = &mut self.packages[self.current];
+        for (option, value) in options {
+            package.args.push(format!("--{}={}", option, value).into());
+        }
+        Ok(())
+    }
+
+    /// Start a section from its header line, e.g. `%files -n foo` or `%post -p /sbin/ldconfig`
+    fn start_section(&mut self, line: &str) -> Result<()> {
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap_or_default().trim_start_matches('%');
+
+        let mut name = None;
+        let mut interpreter = None;
+        while let Some(word) = words.next() {
+            match word {
+                "-n" => name = Some(words.next().context("-n needs a package name")?.to_owned()),
+                "-p" if scriptlet_kind(keyword).is_some() => {
+                    interpreter = Some(words.collect::<Vec<_>>().join(" "));
+                    break;
+                }
+                "-f" => anyhow::bail!("file lists (%{} -f) aren't supported", keyword),
+                "-l" => anyhow::bail!("translated sections (%{} -l) aren't supported", keyword),
+                _ if word.starts_with('-') => {
+                    anyhow::bail!("unsupported option {} of %{}", word, keyword)
+                }
+                suffix if name.is_none() => {
+                    name = Some(format!("{}-{}", self.packages[0].name, suffix))
+                }
+                _ => anyhow::bail!("unexpected {:?} after %{}", word, keyword),
+            }
+        }
+
+        if BUILD_SECTIONS.contains(&keyword) {
+            self.section = Section::Build;
+            return Ok(());
+        }
+        if self.packages[0].name.is_empty() {
+            anyhow::bail!("the preamble has no Name");
+        }
+        match name {
+            Some(name) if keyword == "package" => {
+                if self.packages.iter().any(|p| p.name == name) {
+                    anyhow::bail!("package {} is declared twice", name);
+                }
+                self.packages.push(PackageDefinition {
+                    name,
+                    ..Default::default()
+                });
+                self.current = self.packages.len() - 1;
+            }
+            Some(name) => {
+                self.current = self
+                    .packages
+                    .iter()
+                    .position(|p| p.name == name)
+                    .with_context(|| {
+                        format!("%{} of {}, which has no %package section", keyword, name)
+                    })?;
+            }
+            None if keyword == "package" => anyhow::bail!("%package needs a package name"),
+            None => self.current = 0,
+        }
+
+        self.section = match keyword {
+            "package" => Section::Preamble,
+            "description" => Section::Description,
+            "files" => Section::Files,
+            "changelog" => Section::Changelog,
+            _ => match scriptlet_kind(keyword) {
+                Some(kind) => Section::Scriptlet(Scriptlet {
+                    kind: kind.to_owned(),
+                    interpreter,
+                    content: String::new(),
+                }),
+                None => anyhow::bail!("the %{} section isn't supported", keyword),
+            },
+        };
+        Ok(())
+    }
+
+    fn end_section(&mut self) -> Result<()> {
+        let lines = std::mem::take(&mut self.section_lines);
+        let section = std::mem::replace(&mut self.section, Section::Build);
+        let package = &mut self.packages[self.current];
+        match section {
+            Section::Description => package.description = lines,
+            Section::Files => package.files.extend(lines),
+            Section::Scriptlet(mut scriptlet) => {
+                scriptlet.content = join_lines(&lines);
+                package.scriptlets.retain(|s| s.kind != scriptlet.kind);
+                package.scriptlets.push(scriptlet);
+            }
+            Section::Changelog => self.changelog.extend(lines),
+            Section::Preamble | Section::Build => {}
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<SpecFile> {
+        if self.packages[0].name.is_empty() {
+            anyhow::bail!("the preamble has no Name");
+        }
+        let changelog =
+            changelog::parse(&self.changelog.join("\n")).context("invalid %changelog section")?;
+
+        let doc_dir = self.expand("%{_docdir}")?;
+        let license_dir = self.expand("%{_licensedir}")?;
+        let mut packages = Vec::with_capacity(self.packages.len());
+        for definition in std::mem::take(&mut self.packages) {
+            let mut args = definition.args;
+            let description = join_lines(&definition.description);
+            if !description.is_empty() {
+                args.push(format!("--description={}", description).into());
+            }
+            let mut files = FileList::new(
+                self.buildroot,
+                format!("{}/{}", doc_dir, definition.name),
+                format!("{}/{}", license_dir, definition.name),
+            );
+            for line in &definition.files {
+                files
+                    .add(line, &|text| self.expand(text))
+                    .with_context(|| format!("invalid %files line {:?}", line))?;
+            }
+            args.extend(files.args);
+            packages.push(Package {
+                name: definition.name,
+                scriptlets: definition.scriptlets,
+                args,
+            });
+        }
+        let package = packages.remove(0);
+        Ok(SpecFile {
+            package,
+            subpackages: packages,
+            changelog,
+        })
+    }
+
+    /// Expand the macros of a line, leaving unknown `%name` macros alone like rpm does, but
+    /// rejecting unknown `%{name}` ones
+    fn expand(&self, text: &str) -> Result<String> {
+        self.expand_with_depth(text, 0)
+    }
+
+    fn expand_with_depth(&self, text: &str, depth: usize) -> Result<String> {
+        if depth > 64 {
+            anyhow::bail!("too many levels of macro expansion in {:?}", text);
+        }
+        let mut expanded = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(position) = rest.find('%') {
+            expanded.push_str(&rest[..position]);
+            rest = &rest[position + 1..];
+            if let Some(after) = rest.strip_prefix('%') {
+                expanded.push('%');
+                rest = after;
+            } else if rest.starts_with('{') {
+                let end = closing_brace(rest)
+                    .with_context(|| format!("unterminated macro in {:?}", text))?;
+                let inner = &rest[1..end];
+                rest = &rest[end + 1..];
+                expanded.push_str(&self.expand_braced(inner, depth)?);
+            } else if rest.starts_with('(') {
+                anyhow::bail!("shell expansions (%(...)) aren't supported");
+            } else {
+                let length = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                let name = &rest[..length];
+                match self.macros.get(name) {
+                    Some(value) if !name.is_empty() => {
+                        expanded.push_str(&self.expand_with_depth(value, depth + 1)?)
+                    }
+                    _ => {
+                        expanded.push('%');
+                        expanded.push_str(name);
+                    }
+                }
+                rest = &rest[length..];
+            }
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    /// Expand `%{name}`, `%{?name}`, `%{?name:value}` or `%{!?name:value}`
+    fn expand_braced(&self, inner: &str, depth: usize) -> Result<String> {
+        let (negated, conditional, inner) = if let Some(inner) = inner.strip_prefix("!?") {
+            (true, true, inner)
+        } else if let Some(inner) = inner.strip_prefix('?') {
+            (false, true, inner)
+        } else {
+            (false, false, inner)
+        };
+        let (name, alternative) = match inner.split_once(':') {
+            Some((name, alternative)) => (name, Some(alternative)),
+            None => (inner, None),
+        };
+        let value = self.macros.get(name);
+
+        if !conditional {
+            return match (value, alternative) {
+                (Some(value), None) => self.expand_with_depth(value, depth + 1),
+                (None, None) => anyhow::bail!("undefined macro %{{{}}}", name),
+                (_, Some(_)) => anyhow::bail!("unsupported macro %{{{}}}", inner),
+            };
+        }
+        match (value.is_some() != negated, alternative, value) {
+            (true, Some(alternative), _) => self.expand_with_depth(alternative, depth + 1),
+            (true, None, Some(value)) => self.expand_with_depth(value, depth + 1),
+            _ => Ok(String::new()),
+        }
+    }
+}
+
+/// The position of the brace closing the one at the start of `text`
+fn closing_brace(text: &str) -> Option<usize> {
+    let mut level = 0;
+    for (position, c) in text.char_indices() {
+        match c {
+            '{' => level += 1,
+            '}' => {
+                level -= 1;
+                if level == 0 {
+                    return Some(position);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_section(keyword: &str) -> bool {
+    matches!(keyword, "package" | "description" | "files" | "changelog")
+        || BUILD_SECTIONS.contains(&keyword)
+        || scriptlet_kind(keyword).is_some()
+        || keyword.starts_with("trigger")
+        || keyword.starts_with("filetrigger")
+        || keyword.starts_with("transfiletrigger")
+        || matches!(keyword, "preuntrans" | "postuntrans")
+}
+
+fn scriptlet_kind(keyword: &str) -> Option<&'static str> {
+    SCRIPTLET_SECTIONS
+        .iter()
+        .find(|(section, _)| *section == keyword)
+        .map(|(_, kind)| *kind)
+}
+
+/// The name of the library directory for an architecture, `lib64` for 64-bit ones
+fn lib_dir_name(arch: &str) -> &'static str {
+    match arch {
+        "x86_64" | "aarch64" | "ppc64" | "ppc64le" | "s390x" | "riscv64" | "sparc64"
+        | "loongarch64" | "mips64el" => "lib64",
+        _ => "lib",
+    }
+}
+
+/// Join the lines of a section, without the blank lines around them
+fn join_lines(lines: &[String]) -> String {
+    lines.join("\n").trim_matches('\n').trim_end().to_owned()
+}
+
+/// Split the value of a dependency tag, which may list several dependencies separated by commas
+/// or whitespace, e.g. `foo >= 1.0, bar (baz or qux)`
+fn split_dependencies(value: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut rest = value.trim_start_matches([' ', '\t', ',']);
+    while !rest.is_empty() {
+        let length = if rest.starts_with('(') {
+            closing_parenthesis(rest).with_context(|| {
+                format!("unbalanced parentheses in the dependencies {:?}", value)
+            })? + 1
+        } else {
+            rest.find([' ', '\t', ',']).unwrap_or(rest.len())
+        };
+        tokens.push(&rest[..length]);
+        rest = rest[length..].trim_start_matches([' ', '\t', ',']);
+    }
+
+    let mut dependencies = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(name) = tokens.next() {
+        match tokens.peek() {
+            Some(&operator) if matches!(operator, "<" | "<=" | "=" | "==" | ">=" | ">") => {
+                tokens.next();
+                let version = tokens
+                    .next()
+                    .with_context(|| format!("missing the version of {} {}", name, operator))?;
+                let operator = if operator == "==" { "=" } else { operator };
+                dependencies.push(format!("{} {} {}", name, operator, version));
+            }
+            _ => dependencies.push(name.to_owned()),
+        }
+    }
+    Ok(dependencies)
+}
+
+fn closing_parenthesis(text: &str) -> Option<usize> {
+    let mut level = 0;
+    for (position, c) in text.char_indices() {
+        match c {
+            '(' => level += 1,
+            ')' => {
+                level -= 1;
+                if level == 0 {
+                    return Some(position);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+const FILE_DIRECTIVES: &[&str] = &[
+    "doc", "license", "readme", "artifact", "config", "dir", "attr", "defattr", "caps", "lang",
+    "verify", "ghost", "exclude",
+];
+
+/// The kind of a `%files` entry, given by its directives
+#[derive(Clone, Copy, PartialEq)]
+enum FileKind {
+    Regular,
+    Doc,
+    License,
+    Readme,
+    Artifact,
+    Config,
+}
+
+/// The translation of `%files` sections into file options
+struct FileList<'a> {
+    buildroot: Option<&'a Path>,
+    /// The directory relative `%doc` files go into, and the one for `%license` files
+    doc_dir: String,
+    license_dir: String,
+    /// The defaults of `%defattr`: the file mode, the user, the group and the directory mode
+    defaults: [Option<String>; 4],
+    args: Vec<OsString>,
+}
+
+impl<'a> FileList<'a> {
+    fn new(buildroot: Option<&'a Path>, doc_dir: String, license_dir: String) -> FileList<'a> {
+        FileList {
+            buildroot,
+            doc_dir,
+            license_dir,
+            defaults: Default::default(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Add the paths of a line of a `%files` section, e.g. `%attr(0755, root, root) /usr/bin/foo`
+    fn add(&mut self, line: &str, expand: &dyn Fn(&str) -> Result<String>) -> Result<()> {
+        let mut rest = line.trim();
+        if rest.is_empty() || rest.starts_with('#') {
+            return Ok(());
+        }
+
+        let mut kind = FileKind::Regular;
+        let mut noreplace = false;
+        let mut missingok = false;
+        let mut ghost = false;
+        let mut is_dir = false;
+        let mut attributes: [Option<String>; 3] = Default::default();
+        let mut caps = None;
+        let mut verify = None;
+        while let Some(directive) = rest.strip_prefix('%') {
+            let length = directive
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(directive.len());
+            let name = &directive[..length];
+            if !FILE_DIRECTIVES.contains(&name) {
+                break;
+            }
+            let mut after = &directive[length..];
+            let argument = match after.strip_prefix('(') {
+                Some(argument) => {
+                    let end = argument
+                        .find(')')
+                        .with_context(|| format!("unterminated %{}(", name))?;
+                    after = &argument[end + 1..];
+                    Some(expand(&argument[..end])?)
+                }
+                None => None,
+            };
+            let argument = argument.as_deref();
+            rest = after.trim_start();
+
+            match (name, argument) {
+                ("doc", None) => kind = FileKind::Doc,
+                ("license", None) => kind = FileKind::License,
+                ("readme", None) => kind = FileKind::Readme,
+                ("artifact", None) => kind = FileKind::Artifact,
+                ("config", argument) => {
+                    kind = FileKind::Config;
+                    noreplace = argument.is_some_and(|a| a.contains("noreplace"));
+                    missingok = argument.is_some_and(|a| a.contains("missingok"));
+                }
+                ("dir", None) => is_dir = true,
+                ("attr", Some(argument)) => attributes = permissions(argument)?,
+                ("defattr", Some(argument)) => {
+                    let [mode, user, group] = permissions(argument)?;
+                    let dir_mode = argument.split(',').nth(3).and_then(permission);
+                    self.defaults = [mode, user, group, dir_mode];
+                    return Ok(());
+                }
+                ("caps", Some(argument)) => caps = Some(argument.trim().to_owned()),
+                ("verify", Some(argument)) => verify = Some(verify_attribute(argument)),
+                // not recorded by rpm-builder
+                ("lang", Some(_)) => {}
+                ("ghost", None) => ghost = true,
+                ("exclude", None) => anyhow::bail!("%exclude isn't supported"),
+                _ => anyhow::bail!("unsupported directive %{}", name),
+            }
+        }
+
+        // the file attributes, of which the capabilities, noreplace and missingok don't apply to
+        // directories
+        let attribute_list = |default_mode: &Option<String>, is_dir: bool| {
+            let mut list = Vec::new();
+            if let Some(mode) = attributes[0].as_ref().or(default_mode.as_ref()) {
+                list.push(format!("mode={}", mode));
+            }
+            if let Some(user) = attributes[1].as_ref().or(self.defaults[1].as_ref()) {
+                list.push(format!("user={}", user));
+            }
+            if let Some(group) = attributes[2].as_ref().or(self.defaults[2].as_ref()) {
+                list.push(format!("group={}", group));
+            }
+            if let Some(caps) = caps.as_ref().filter(|_| !is_dir) {
+                list.push(format!("caps={}", caps));
+            }
+            if let Some(verify) = &verify {
+                list.push(format!("verify={}", verify));
+            }
+            if noreplace && !is_dir {
+                list.push("noreplace".to_owned());
+            }
+            if missingok && !is_dir {
+                list.push("missingok".to_owned());
+            }
+            if ghost {
+                list.push("flags=ghost".to_owned());
+            }
+            list.join(",")
+        };
+        let file_attributes = attribute_list(&self.defaults[0], false);
+        let dir_attributes = attribute_list(&self.defaults[3], true);
+
+        let rest = expand(rest)?;
+        for path in split_paths(&rest)? {
+            let path = path.as_str();
+            if path.contains(':') {
+                anyhow::bail!("paths containing ':' aren't supported: {}", path);
+            }
+
+            if !path.starts_with('/') {
+                // like rpmbuild copies them from the build directory, relative documentation and
+                // license files are taken from the current directory
+                let dest_dir = match kind {
+                    FileKind::Doc => &self.doc_dir,
+                    FileKind::License => &self.license_dir,
+                    _ => anyhow::bail!("{} isn't an absolute path", path),
+                };
+                let source = Path::new(path);
+                if source.is_dir() {
+                    let file_name = source.file_name().context("path does not have filename")?;
+                    let dest = format!("{}/{}", dest_dir, file_name.to_string_lossy());
+                    self.push("doc-dir", &format!("{}:{}", path, dest), "");
+                } else {
+                    let option = match kind {
+                        FileKind::License => "license-file",
+                        _ => "doc-file",
+                    };
+                    let value = format!("{}:{}/", path, dest_dir);
+                    self.push(option, &value, &file_attributes);
+                }
+                continue;
+            }
+
+            let buildroot = self.buildroot.with_context(|| {
+                format!(
+                    "{} can only be found with the --buildroot it was installed into",
+                    path
+                )
+            })?;
+            let sources = if crate::build::is_glob(path) {
+                let pattern = buildroot.join(path.trim_start_matches('/'));
+                let matches =
+                    glob::glob(&pattern.to_string_lossy())?.collect::<Result<Vec<_>, _>>()?;
+                if matches.is_empty() {
+                    anyhow::bail!("{} doesn't match anything in {:?}", path, buildroot);
+                }
+                matches
+            } else {
+                vec![buildroot.join(path.trim_start_matches('/'))]
+            };
+
+            for source in sources {
+                let dest = Path::new("/").join(source.strip_prefix(buildroot)?);
+                let dest = dest.to_string_lossy();
+                let metadata = fs::symlink_metadata(&source)
+                    .with_context(|| format!("{} doesn't exist in {:?}", dest, buildroot))?;
+                let source = source.to_string_lossy();
+
+                if is_dir {
+                    if !metadata.is_dir() {
+                        anyhow::bail!("{} is listed with %dir, but isn't a directory", dest);
+                    }
+                    self.push("create-dir", &dest, &dir_attributes);
+                } else if metadata.is_symlink() {
+                    let target = fs::read_link(source.as_ref())?;
+                    self.push(
+                        "symlink",
+                        &format!("{}:{}", dest, target.to_string_lossy()),
+                        "",
+                    );
+                } else if metadata.is_dir() {
+                    // like rpmbuild, the directory is packaged along with everything below it
+                    self.push("create-dir", &dest, &dir_attributes);
+                    let option = match kind {
+                        FileKind::Doc | FileKind::License | FileKind::Readme => "doc-dir",
+                        FileKind::Config => "config-dir",
+                        FileKind::Regular | FileKind::Artifact => "dir",
+                    };
+                    self.push(option, &format!("{}:{}", source, dest), "");
+                } else {
+                    let option = match kind {
+                        FileKind::Regular => "file",
+                        FileKind::Doc => "doc-file",
+                        FileKind::License => "license-file",
+                        FileKind::Readme => "readme-file",
+                        FileKind::Artifact => "artifact-file",
+                        FileKind::Config => "config-file",
+                    };
+                    self.push(option, &format!("{}:{}", source, dest), &file_attributes);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, option: &str, value: &str, attributes: &str) {
+        let argument = match attributes {
+            "" => format!("--{}={}", option, value),
+            _ => format!("--{}={}:{}", option, value, attributes),
+        };
+        self.args.push(argument.into());
+    }
+}
+
+/// The verify attribute for the argument of `%verify`, e.g. `not(md5,size,mtime)` for
+/// `%verify(not md5 size mtime)`
+fn verify_attribute(argument: &str) -> String {
+    let mut checks: Vec<&str> = argument
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|check| !check.is_empty())
+        .collect();
+    let negated = checks.first() == Some(&"not");
+    if negated {
+        checks.remove(0);
+    }
+    format!("{}({})", if negated { "not" } else { "" }, checks.join(","))
+}
+
+/// Split the paths of a `%files` line on whitespace, where a path containing whitespace is
+/// quoted, e.g. `"/usr/share/my app"`
+fn split_paths(text: &str) -> Result<Vec<String>> {
+    let mut paths = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let (path, after) = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted
+                    .find('"')
+                    .with_context(|| format!("unterminated quote in {}", text))?;
+                (&quoted[..end], &quoted[end + 1..])
+            }
+            None => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+        };
+        paths.push(path.to_owned());
+        rest = after.trim_start();
+    }
+    Ok(paths)
+}
+
+/// Parse the `<mode>, <user>, <group>` of `%attr` and `%defattr`, where `-` keeps the default
+fn permissions(argument: &str) -> Result<[Option<String>; 3]> {
+    let fields: Vec<&str> = argument.split(',').collect();
+    if fields.len() < 3 {
+        anyhow::bail!("expected (<mode>, <user>, <group>), found ({})", argument);
+    }
+    Ok([
+        permission(fields[0]),
+        permission(fields[1]),
+        permission(fields[2]),
+    ])
+}
+
+fn permission(field: &str) -> Option<String> {
+    match field.trim() {
+        "-" | "" => None,
+        value => Some(value.to_owned()),
+    }
+}
+
+/// The header of the scriptlet sections, by the kind `inspect` reports them as
+const SCRIPTLET_KINDS: &[(&str, &str)] = &[
+    ("pre-install", "pre"),
+    ("post-install", "post"),
+    ("pre-uninstall", "preun"),
+    ("post-uninstall", "postun"),
+    ("pre-trans", "pretrans"),
+    ("post-trans", "posttrans"),
+    ("pre-untrans", "preuntrans"),
+    ("post-untrans", "postuntrans"),
+    ("verify", "verifyscript"),
+];
+
+/// Write a spec file describing built packages, the first of which is the main package and the
+/// others its subpackages. If `path` is a directory, the spec file is written into it as
+/// `<name>.spec`.
+pub fn write(path: &Path, packages: &[rpm::Package]) -> Result<PathBuf> {
+    let content = to_spec(packages)?;
+    let path = if path.is_dir() {
+        path.join(format!("{}.spec", packages[0].metadata.get_name()?))
+    } else {
+        path.to_path_buf()
+    };
+    fs::write(&path, content).with_context(|| format!("unable to write spec file {:?}", path))?;
+    Ok(path)
+}
+
+/// Describe built packages as a spec file, as far as spec files can: the `%files` sections list
+/// what the packages contain, but there are no build sections to put the files into the
+/// buildroot
+pub fn to_spec(packages: &[rpm::Package]) -> Result<String> {
+    let main = PackageInfo::from_package(&packages[0])?;
+    let mut spec = format!(
+        "# Generated by rpm-builder from {}-{}-{}.{}.rpm, the files are expected in the buildroot\n\n",
+        main.name, main.version, main.release, main.arch
+    );
+    write_preamble(&mut spec, &packages[0], &main, None)?;
+
+    let mut sections = String::new();
+    write_sections(&mut sections, &main, None);
+    for package in &packages[1..] {
+        let info = PackageInfo::from_package(package)?;
+        spec.push_str(&format!("\n%package -n {}\n", info.name));
+        write_preamble(&mut spec, package, &info, Some(&main))?;
+        write_sections(&mut sections, &info, Some(&info.name));
+    }
+    spec.push_str(&sections);
+
+    if !main.changelog.is_empty() {
+        spec.push_str("\n%changelog\n");
+        for entry in &main.changelog {
+            let date = chrono::DateTime::from_timestamp(entry.timestamp as i64, 0)
+                .context("invalid changelog timestamp")?;
+            spec.push_str(&format!(
+                "* {} {}\n{}\n\n",
+                date.format("%a %b %d %Y"),
+                escape(&entry.name),
+                escape(&entry.description)
+            ));
+        }
+        spec.truncate(spec.trim_end().len() + 1);
+    }
+    Ok(spec)
+}
+
+/// Write the tags of a package, leaving out the ones a subpackage shares with the main package
+fn write_preamble(
+    spec: &mut String,
+    pkg: &rpm::Package,
+    info: &PackageInfo,
+    main: Option<&PackageInfo>,
+) -> Result<()> {
+    let mut tag = |name: &str, value: &str, main_value: Option<&str>| {
+        if !value.is_empty() && main_value != Some(value) {
+            spec.push_str(&format!("{:<16}{}\n", format!("{}:", name), escape(value)));
+        }
+    };
+    if main.is_none() {
+        tag("Name", &info.name, None);
+    }
+    if info.epoch != 0 {
+        tag("Epoch", &info.epoch.to_string(), main.map(|_| "0"));
+    }
+    tag("Version", &info.version, main.map(|m| m.version.as_str()));
+    tag("Release", &info.release, main.map(|m| m.release.as_str()));
+    tag("Summary", &info.summary, None);
+    tag("License", &info.license, main.map(|m| m.license.as_str()));
+    let url = info.url.as_deref().unwrap_or_default();
+    tag(
+        "URL",
+        url,
+        main.map(|m| m.url.as_deref().unwrap_or_default()),
+    );
+    let vendor = info.vendor.as_deref().unwrap_or_default();
+    tag(
+        "Vendor",
+        vendor,
+        main.map(|m| m.vendor.as_deref().unwrap_or_default()),
+    );
+    tag("BuildArch", &info.arch, main.map(|m| m.arch.as_str()));
+    let prefixes = pkg
+        .metadata
+        .header
+        .get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_PREFIXES)
+        .unwrap_or_default();
+    for prefix in prefixes {
+        tag("Prefix", prefix, None);
+    }
+
+    let metadata = &pkg.metadata;
+    let self_provides = [info.name.clone(), format!("{}({})", info.name, info.arch)];
+    let dependencies = [
+        ("Requires", metadata.get_requires()?),
+        ("Provides", metadata.get_provides()?),
+        ("Obsoletes", metadata.get_obsoletes()?),
+        ("Conflicts", metadata.get_conflicts()?),
+        ("Recommends", metadata.get_recommends()?),
+        ("Suggests", metadata.get_suggests()?),
+        ("Supplements", metadata.get_supplements()?),
+        ("Enhances", metadata.get_enhances()?),
+    ];
+    for (name, dependencies) in dependencies {
+        for dependency in dependencies {
+            // the dependencies rpm adds by itself
+            let is_automatic = match name {
+                "Requires" => dependency.flags.contains(rpm::DependencyFlags::RPMLIB),
+                "Provides" => self_provides.contains(&dependency.name),
+                "Recommends" => {
+                    dependency.name.starts_with("user(") || dependency.name.starts_with("group(")
+                }
+                _ => false,
+            };
+            if is_automatic {
+                continue;
+            }
+            let qualifiers: Vec<&str> = [
+                (rpm::DependencyFlags::PRETRANS, "pretrans"),
+                (rpm::DependencyFlags::SCRIPT_PRE, "pre"),
+                (rpm::DependencyFlags::SCRIPT_POST, "post"),
+                (rpm::DependencyFlags::SCRIPT_PREUN, "preun"),
+                (rpm::DependencyFlags::SCRIPT_POSTUN, "postun"),
+                (rpm::DependencyFlags::POSTTRANS, "posttrans"),
+            ]
+            .into_iter()
+            .filter(|(flag, _)| dependency.flags.contains(*flag))
+            .map(|(_, qualifier)| qualifier)
+            .collect();
+            let name = if qualifiers.is_empty() {
+                name.to_owned()
+            } else {
+                format!("{}({})", name, qualifiers.join(","))
+            };
+            let dependency = DependencyInfo::from(&dependency);
+            let value = match (dependency.operator, &dependency.version) {
+                (Some(operator), Some(version)) => {
+                    format!("{} {} {}", dependency.name, operator, version)
+                }
+                _ => dependency.name,
+            };
+            spec.push_str(&format!("{:<16}{}\n", format!("{}:", name), escape(&value)));
+        }
+    }
+    Ok(())
+}
+
+/// Write the description, scriptlets and files of a package, whose sections are named after it
+/// unless it is the main package
+fn write_sections(spec: &mut String, info: &PackageInfo, name: Option<&str>) {
+    let suffix = name.map(|name| format!(" -n {}", name)).unwrap_or_default();
+
+    spec.push_str(&format!(
+        "\n%description{}\n{}\n",
+        suffix,
+        escape(&info.description)
+    ));
+
+    for scriptlet in &info.scriptlets {
+        let Some((_, section)) = SCRIPTLET_KINDS.iter().find(|(k, _)| *k == scriptlet.kind) else {
+            continue;
+        };
+        let interpreter = scriptlet
+            .interpreter
+            .as_ref()
+            .filter(|program| program.as_slice() != ["/bin/sh"])
+            .map(|program| format!(" -p {}", program.join(" ")))
+            .unwrap_or_default();
+        spec.push_str(&format!("\n%{}{}{}\n", section, suffix, interpreter));
+        if !scriptlet.script.is_empty() {
+            spec.push_str(&escape(scriptlet.script.trim_end()));
+            spec.push('\n');
+        }
+    }
+
+    spec.push_str(&format!("\n%files{}\n", suffix));
+    for file in &info.files {
+        let mut directives = Vec::new();
+        let flag = |name: &str| file.flags.iter().any(|f| f == name);
+        if file.linkto.is_none() {
+            let permissions = file.mode & 0o7777;
+            directives.push(format!(
+                "%attr({:04o}, {}, {})",
+                permissions, file.user, file.group
+            ));
+        }
+        if file.mode & 0o170000 == 0o040000 {
+            directives.push("%dir".to_owned());
+        }
+        if flag("config") {
+            let arguments: Vec<&str> = ["missingok", "noreplace"]
+                .into_iter()
+                .filter(|name| flag(name))
+                .collect();
+            if arguments.is_empty() {
+                directives.push("%config".to_owned());
+            } else {
+                directives.push(format!("%config({})", arguments.join(",")));
+            }
+        }
+        for name in ["doc", "license", "readme", "artifact", "ghost"] {
+            if flag(name) {
+                directives.push(format!("%{}", name));
+            }
+        }
+        if let Some(caps) = &file.caps {
+            directives.push(format!("%caps({})", caps));
+        }
+        if let Some(verify) = &file.verify {
+            directives.push(format!("%verify({})", verify));
+        }
+        let path = escape(&file.path.to_string_lossy());
+        if path.contains(char::is_whitespace) {
+            directives.push(format!("\"{}\"", path));
+        } else {
+            directives.push(path);
+        }
+        spec.push_str(&directives.join(" "));
+        spec.push('\n');
+    }
+}
+
+/// Escape the `%` of literal text, which would otherwise start a macro
+fn escape(text: &str) -> String {
+    text.replace('%', "%%")
+}
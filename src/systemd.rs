@@ -0,0 +1,85 @@
+use anyhow::Result;
+
+/// The directory system units are installed into
+pub const UNIT_DIR: &str = "/usr/lib/systemd/system";
+
+const UNIT_TYPES: &[&str] = &[
+    "service",
+    "socket",
+    "device",
+    "mount",
+    "automount",
+    "swap",
+    "target",
+    "path",
+    "timer",
+    "slice",
+    "scope",
+];
+
+/// Parse a unit given as `<src>:<unit-name>`
+pub fn parse_unit(raw: &str, separator: &str) -> Result<(String, String)> {
+    let Some((source, unit)) = crate::build::split_last(raw, separator) else {
+        anyhow::bail!(
+            "invalid systemd unit {}: it needs to be of the form <src>:<unit-name>",
+            raw
+        );
+    };
+    let is_valid = unit.rsplit_once('.').is_some_and(|(name, unit_type)| {
+        !name.is_empty() && !name.starts_with('-') && UNIT_TYPES.contains(&unit_type)
+    });
+    if !is_valid {
+        anyhow::bail!(
+            "invalid systemd unit name {}: it needs to be a file name ending in the unit type, e.g. foo.service",
+            unit
+        );
+    }
+    // the unit names end up in the scriptlets, so only the characters systemd allows in them
+    // are accepted, which the shell doesn't interpret apart from the backslash
+    if let Some(c) = unit
+        .chars()
+        .find(|&c| !c.is_ascii_alphanumeric() && !":_.\\@-".contains(c))
+    {
+        anyhow::bail!(
+            "invalid systemd unit name {}: {:?} isn't allowed, only letters, digits and : _ . \\ @ -",
+            unit,
+            c
+        );
+    }
+    Ok((source, unit))
+}
+
+/// The equivalent of `%systemd_post`: apply the presets on the initial installation
+pub fn post_install(units: &[String]) -> String {
+    format!(
+        "if [ $1 -eq 1 ] ; then\n    # Initial installation\n    systemctl --no-reload preset {} >/dev/null 2>&1 || :\nfi\n",
+        shell_words(units)
+    )
+}
+
+/// The equivalent of `%systemd_preun`: stop and disable the units when the package is removed
+pub fn pre_uninstall(units: &[String]) -> String {
+    format!(
+        "if [ $1 -eq 0 ] ; then\n    # Package removal, not upgrade\n    systemctl --no-reload disable --now {} >/dev/null 2>&1 || :\nfi\n",
+        shell_words(units)
+    )
+}
+
+/// The equivalent of `%systemd_postun_with_restart`: reload the unit files, and restart the
+/// running units when the package is upgraded
+pub fn post_uninstall(units: &[String]) -> String {
+    format!(
+        "systemctl daemon-reload >/dev/null 2>&1 || :\nif [ $1 -ge 1 ] ; then\n    # Package upgrade, not uninstall\n    systemctl try-restart {} >/dev/null 2>&1 || :\nfi\n",
+        shell_words(units)
+    )
+}
+
+/// Join the unit names for a scriptlet, escaping the backslashes of escaped names such as
+/// `foo\x2dbar.service`
+fn shell_words(units: &[String]) -> String {
+    units
+        .iter()
+        .map(|unit| unit.replace('\\', "\\\\"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,109 @@
+use anyhow::Result;
+
+/// The directory sysusers.d files are installed into
+pub const SYSUSERS_DIR: &str = "/usr/lib/sysusers.d";
+
+const DEFAULT_HOME: &str = "/";
+const DEFAULT_SHELL: &str = "/usr/sbin/nologin";
+
+/// A system user, along with the group of the same name
+#[derive(Debug)]
+pub struct SysUser {
+    pub name: String,
+    pub uid: Option<u32>,
+    pub home: Option<String>,
+    pub shell: Option<String>,
+}
+
+impl SysUser {
+    /// Parse a user given as `<name>[:uid=...,home=...,shell=...]`
+    pub fn parse(raw: &str) -> Result<SysUser> {
+        let (name, attributes) = raw.split_once(':').unwrap_or((raw, ""));
+        let is_valid_name = name.len() <= 31
+            && name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+        if !is_valid_name {
+            anyhow::bail!(
+                "invalid user name {}: it needs to start with a lowercase letter or an underscore, followed by lowercase letters, digits, underscores or dashes",
+                name
+            );
+        }
+
+        let mut user = SysUser {
+            name: name.to_owned(),
+            uid: None,
+            home: None,
+            shell: None,
+        };
+        for attribute in attributes.split(',').filter(|a| !a.is_empty()) {
+            let Some((key, value)) = attribute.split_once('=') else {
+                anyhow::bail!("user attribute '{}' requires a value", attribute);
+            };
+            // the paths end up in the pre-install scriptlet, so only characters the shell
+            // doesn't interpret are allowed
+            let is_valid_path = value.starts_with('/')
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "._/+-".contains(c));
+            match key {
+                "uid" => {
+                    user.uid =
+                        Some(value.parse().map_err(|_| {
+                            anyhow::anyhow!("invalid uid {} for user {}", value, name)
+                        })?)
+                }
+                "home" | "shell" if !is_valid_path => anyhow::bail!(
+                    "invalid {} {} for user {}: it needs to be an absolute path of letters, digits and . _ / + -",
+                    key,
+                    value,
+                    name
+                ),
+                "home" => user.home = Some(value.to_owned()),
+                "shell" => user.shell = Some(value.to_owned()),
+                _ => anyhow::bail!("unknown user attribute '{}'", key),
+            }
+        }
+        Ok(user)
+    }
+
+    /// The path of the sysusers.d file of the user
+    pub fn config_path(&self) -> String {
+        format!("{}/{}.conf", SYSUSERS_DIR, self.name)
+    }
+
+    /// The content of the sysusers.d file, which creates the user and its group
+    pub fn config(&self) -> String {
+        let uid = self.uid.map(|uid| uid.to_string());
+        format!(
+            "u {} {} - {} {}\n",
+            self.name,
+            uid.as_deref().unwrap_or("-"),
+            self.home.as_deref().unwrap_or("-"),
+            self.shell.as_deref().unwrap_or("-"),
+        )
+    }
+
+    /// The equivalent of `%sysusers_create_compat`: create the user with systemd-sysusers, and
+    /// fall back to useradd on systems without it
+    pub fn pre_install(&self) -> String {
+        let mut useradd = format!(
+            "useradd -r -g {} -d {} -s {}",
+            self.name,
+            self.home.as_deref().unwrap_or(DEFAULT_HOME),
+            self.shell.as_deref().unwrap_or(DEFAULT_SHELL),
+        );
+        let mut groupadd = "groupadd -r".to_owned();
+        if let Some(uid) = self.uid {
+            useradd.push_str(&format!(" -u {}", uid));
+            groupadd.push_str(&format!(" -g {}", uid));
+        }
+        format!(
+            "if command -v systemd-sysusers >/dev/null 2>&1 ; then\n    systemd-sysusers --replace={path} - <<'SYSUSERS'\n{config}SYSUSERS\nelse\n    getent group {name} >/dev/null || {groupadd} {name}\n    getent passwd {name} >/dev/null || {useradd} {name}\nfi\n",
+            path = self.config_path(),
+            config = self.config(),
+            name = self.name,
+        )
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+pub type Variables = BTreeMap<String, String>;
+
+/// Parse a variable definition of the form `<key>=<value>`
+pub fn parse_define(raw: &str) -> Result<(String, String)> {
+    match raw.split_once('=') {
+        Some((key, value))
+            if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+        {
+            Ok((key.to_owned(), value.to_owned()))
+        }
+        _ => anyhow::bail!(
+            "invalid define {}: it needs to be of the form <key>=<value>, where the key only contains letters, digits and underscores",
+            raw
+        ),
+    }
+}
+
+/// Replace every `${key}` and `%{key}` with the value of the variable. Like rpm does with
+/// undefined macros, references to unknown variables are left as they are.
+pub fn expand(input: &str, variables: &Variables) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find(['$', '%']) {
+        output.push_str(&rest[..start]);
+        let reference = &rest[start..];
+        let value = reference
+            .strip_prefix(['$', '%'])
+            .and_then(|r| r.strip_prefix('{'))
+            .and_then(|r| r.split_once('}'))
+            .and_then(|(key, remainder)| Some((variables.get(key)?, remainder)));
+        match value {
+            Some((value, remainder)) => {
+                output.push_str(value);
+                rest = remainder;
+            }
+            None => {
+                output.push_str(&reference[..1]);
+                rest = &reference[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Replace every `$NAME` and `${NAME}` with the value of the environment variable, and `$$` with
+/// `$`. Unlike with `expand`, it's an error to refer to a variable that isn't set.
+pub fn expand_env(input: &str) -> Result<String> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        let reference = &rest[start + 1..];
+        let (name, remainder) = if let Some(remainder) = reference.strip_prefix('$') {
+            output.push('$');
+            rest = remainder;
+            continue;
+        } else if let Some((name, remainder)) =
+            reference.strip_prefix('{').and_then(|r| r.split_once('}'))
+        {
+            (name, remainder)
+        } else {
+            let end = reference
+                .find(|c| !is_name_char(c))
+                .unwrap_or(reference.len());
+            reference.split_at(end)
+        };
+
+        // e.g. "$5", which can't be a reference to a variable
+        if name.is_empty()
+            || name.starts_with(|c: char| c.is_ascii_digit())
+            || !name.chars().all(is_name_char)
+        {
+            output.push('$');
+            rest = reference;
+            continue;
+        }
+        let value = std::env::var(name)
+            .map_err(|_| anyhow::anyhow!("environment variable {} is not set", name))?;
+        output.push_str(&value);
+        rest = remainder;
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Values which can contain references to variables
+pub trait Expand {
+    /// Replace the value with the result of `expander`
+    fn expand(&mut self, expander: &dyn Fn(&str) -> Result<String>) -> Result<()>;
+}
+
+impl Expand for String {
+    fn expand(&mut self, expander: &dyn Fn(&str) -> Result<String>) -> Result<()> {
+        *self = expander(self)?;
+        Ok(())
+    }
+}
+
+impl Expand for PathBuf {
+    fn expand(&mut self, expander: &dyn Fn(&str) -> Result<String>) -> Result<()> {
+        // paths which aren't valid UTF-8 can't contain a (valid) reference
+        if let Some(path) = self.to_str() {
+            *self = PathBuf::from(expander(path)?);
+        }
+        Ok(())
+    }
+}
+
+impl<T: Expand> Expand for Option<T> {
+    fn expand(&mut self, expander: &dyn Fn(&str) -> Result<String>) -> Result<()> {
+        match self {
+            Some(value) => value.expand(expander),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: Expand> Expand for Vec<T> {
+    fn expand(&mut self, expander: &dyn Fn(&str) -> Result<String>) -> Result<()> {
+        self.iter_mut().try_for_each(|value| value.expand(expander))
+    }
+}
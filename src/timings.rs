@@ -0,0 +1,68 @@
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+/// The wall time spent in each phase of the builds, in the order the phases first ran
+#[derive(Default)]
+struct Timings {
+    enabled: bool,
+    depth: usize,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+thread_local! {
+    static TIMINGS: RefCell<Timings> = RefCell::new(Timings::default());
+}
+
+/// Start recording the time spent in the phases, for `--timings`
+pub fn enable() {
+    TIMINGS.with(|timings| timings.borrow_mut().enabled = true);
+}
+
+/// A running phase, which adds its time to the phase of its name when it's dropped. Phases
+/// started while another one is running are part of it and aren't recorded separately, e.g. the
+/// phases of the build which scans the files for dependencies.
+pub struct Phase {
+    name: &'static str,
+    start: Instant,
+}
+
+pub fn phase(name: &'static str) -> Phase {
+    TIMINGS.with(|timings| timings.borrow_mut().depth += 1);
+    Phase {
+        name,
+        start: Instant::now(),
+    }
+}
+
+impl Drop for Phase {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        TIMINGS.with(|timings| {
+            let mut timings = timings.borrow_mut();
+            timings.depth -= 1;
+            if !timings.enabled || timings.depth > 0 {
+                return;
+            }
+            match timings
+                .phases
+                .iter_mut()
+                .find(|(name, _)| *name == self.name)
+            {
+                Some((_, time)) => *time += elapsed,
+                None => timings.phases.push((self.name, elapsed)),
+            }
+        });
+    }
+}
+
+/// Print the time spent in each phase to stderr, along with the rest of the total time
+pub fn report(total: Duration) {
+    let phases = TIMINGS.with(|timings| std::mem::take(&mut timings.borrow_mut().phases));
+    let measured: Duration = phases.iter().map(|(_, time)| *time).sum();
+    eprintln!("Timings:");
+    for (name, time) in &phases {
+        eprintln!("  {:<26}{:>12.1?}", name, time);
+    }
+    eprintln!("  {:<26}{:>12.1?}", "other", total.saturating_sub(measured));
+    eprintln!("  {:<26}{:>12.1?}", "total", total);
+}
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use base64::prelude::*;
+use clap_derive::Args;
+use rpm::signature::Verifying;
+use rpm::signature::pgp::Verifier;
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::header;
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    #[arg(value_name = "PACKAGE", help = "Path to the rpm to verify")]
+    pub package: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "PUBLIC_KEY",
+        help = "Also verify the package signature using the specified PGP public key"
+    )]
+    pub key: Option<PathBuf>,
+}
+
+pub fn verify(args: VerifyArgs) -> Result<()> {
+    let pkg = rpm::Package::open(&args.package)
+        .with_context(|| format!("unable to read package {:?}", &args.package))?;
+
+    match args.key {
+        Some(key_path) => {
+            let raw_key = fs::read(&key_path).with_context(|| {
+                format!("unable to load public key file from path {:?}", key_path)
+            })?;
+            let verifier = Verifier::load_from_asc_bytes(&raw_key).with_context(|| {
+                format!("unable to create verifier from public key {:?}", key_path)
+            })?;
+            // verifying the signature includes verifying the digests
+            let skipped = verify_signatures(&pkg, &verifier).with_context(|| {
+                format!("signature verification failed for {:?}", &args.package)
+            })?;
+            match skipped {
+                0 => println!("{}: digests signatures OK", args.package.display()),
+                skipped => println!(
+                    "{}: digests signatures OK ({} by other keys not checked)",
+                    args.package.display(),
+                    skipped
+                ),
+            }
+        }
+        None => {
+            pkg.verify_digests()
+                .with_context(|| format!("digest verification failed for {:?}", &args.package))?;
+            println!("{}: digests OK", args.package.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify the signatures of a package which were made by the key of the verifier, returning how
+/// many were made by other keys. At least one signature needs to be made by the key.
+fn verify_signatures(pkg: &rpm::Package, verifier: &Verifier) -> Result<usize> {
+    let signatures = pkg
+        .metadata
+        .signature
+        .get_entry_data_as_string_array(rpm::IndexSignatureTag::RPMSIGTAG_OPENPGP);
+    // rpm checks every signature against the key, which fails for the ones made by other keys
+    let signatures = match signatures {
+        Ok(signatures) if signatures.len() > 1 => signatures,
+        _ => {
+            pkg.verify_signature(verifier)?;
+            return Ok(0);
+        }
+    };
+
+    pkg.verify_digests()?;
+    let header = header::header_bytes(pkg)?;
+    let mut skipped = 0;
+    for signature in signatures {
+        let signature = BASE64_STANDARD
+            .decode(signature)
+            .context("invalid OpenPGP signature")?;
+        match verifier.verify(header.as_slice(), &signature) {
+            Ok(()) => {}
+            Err(rpm::Error::KeyNotFoundError { .. }) => skipped += 1,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if skipped == signatures.len() {
+        anyhow::bail!("none of the {} signatures were made by the key", skipped);
+    }
+    Ok(skipped)
+}
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use clap_derive::{Args, Subcommand, ValueEnum};
+use serde::Serialize;
+
+use std::cmp::Ordering;
+
+use crate::build::validate_evr;
+use crate::inspect::OutputFormat;
+
+#[derive(Args, Debug)]
+pub struct VersionArgs {
+    #[command(subcommand)]
+    pub command: VersionCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VersionCommand {
+    #[command(
+        about = "Compare two versions the way rpm does, with '~' sorting before and '^' after the version without them",
+        args_override_self = true
+    )]
+    Compare(CompareArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct CompareArgs {
+    #[arg(
+        value_name = "EVR",
+        help = "The version to compare, of the form [epoch:]version[-release]"
+    )]
+    pub evr: String,
+
+    #[arg(value_name = "OTHER_EVR", help = "The version to compare it to")]
+    pub other: String,
+
+    #[arg(
+        long,
+        value_name = "RELATION",
+        value_enum,
+        help = "Fail unless the first version is older than, equal to or newer than the second one, e.g. --expect newer to check that a new build upgrades the previous one"
+    )]
+    pub expect: Option<Relation>,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        default_value = "text",
+        help = "Specify the output format"
+    )]
+    pub format: OutputFormat,
+}
+
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Relation {
+    Older,
+    Equal,
+    Newer,
+}
+
+impl Relation {
+    fn describe(self) -> &'static str {
+        match self {
+            Relation::Older => "older than",
+            Relation::Equal => "equal to",
+            Relation::Newer => "newer than",
+        }
+    }
+}
+
+/// The result of comparing two versions
+#[derive(Serialize, Debug)]
+pub struct Comparison {
+    pub evr: String,
+    pub other: String,
+    pub relation: Relation,
+    /// -1, 0 or 1, like rpmvercmp
+    pub result: i8,
+}
+
+pub fn version(args: VersionArgs) -> Result<()> {
+    match args.command {
+        VersionCommand::Compare(args) => compare_command(args),
+    }
+}
+
+fn compare_command(args: CompareArgs) -> Result<()> {
+    let comparison = compare(&args.evr, &args.other)?;
+    match args.format {
+        OutputFormat::Text => {
+            let operator = match comparison.relation {
+                Relation::Older => "<",
+                Relation::Equal => "==",
+                Relation::Newer => ">",
+            };
+            println!("{} {} {}", comparison.evr, operator, comparison.other);
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&comparison)?),
+    }
+
+    match args.expect {
+        Some(expected) if comparison.relation != expected => anyhow::bail!(
+            "{} is {} {}, not {} it",
+            comparison.evr,
+            comparison.relation.describe(),
+            comparison.other,
+            expected.describe()
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Compare two `[epoch:]version[-release]` strings like rpm: a missing epoch is 0, and a
+/// version without a release is older than the same version with one
+pub fn compare(evr: &str, other: &str) -> Result<Comparison> {
+    for value in [evr, other] {
+        validate_evr(value).with_context(|| format!("invalid version {:?}", value))?;
+    }
+    let (relation, result) = match rpm::Evr::parse(evr).cmp(&rpm::Evr::parse(other)) {
+        Ordering::Less => (Relation::Older, -1),
+        Ordering::Equal => (Relation::Equal, 0),
+        Ordering::Greater => (Relation::Newer, 1),
+    };
+    Ok(Comparison {
+        evr: evr.to_owned(),
+        other: other.to_owned(),
+        relation,
+        result,
+    })
+}
@@ -1,5 +1,6 @@
 use std::env;
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 
 use assert_cmd::{Command, cargo_bin};
@@ -15,6 +16,7 @@ fn test_basic_defaults() -> Result<(), Box<dyn std::error::Error>> {
 
     assert!(!fs::exists(&out_file).unwrap());
     Command::new(cargo_bin!())
+        .arg("build")
         .arg("test")
         .arg("-o")
         .arg(&out_file)
@@ -73,15 +75,17 @@ fn test_no_name_provided() -> Result<(), Box<dyn std::error::Error>> {
     let tmp_dir = TempDir::new("rpm-builder-no-name-provided")?;
 
     Command::new(cargo_bin!())
+        .arg("build")
         .arg("-o")
-        .arg(&tmp_dir.path())
+        .arg(tmp_dir.path())
         .assert()
         .failure();
 
     Ok(())
 }
 
-/// Test adding basic metadata (version, epoch, release, arch, license, summary) to the package
+/// Test adding basic metadata (version, epoch, release, arch, license, summary, url, vendor,
+/// packager, group, distribution) to the package
 #[test]
 fn test_set_basic_metadata() -> Result<(), Box<dyn std::error::Error>> {
     let tmp_dir = TempDir::new("rpm-builder-test-set-metadata")?;
@@ -91,6 +95,7 @@ fn test_set_basic_metadata() -> Result<(), Box<dyn std::error::Error>> {
 
     assert!(!fs::exists(&out_file).unwrap());
     Command::new(cargo_bin!())
+        .arg("build")
         .arg("test-set-metadata")
         .arg("--epoch")
         .arg("1")
@@ -104,6 +109,16 @@ fn test_set_basic_metadata() -> Result<(), Box<dyn std::error::Error>> {
         .arg("MPL-2.0")
         .arg("--summary")
         .arg("blah blah blah")
+        .arg("--url")
+        .arg("https://example.com/test-set-metadata")
+        .arg("--vendor")
+        .arg("Example Corp")
+        .arg("--packager")
+        .arg("Jane Doe <jane@example.com>")
+        .arg("--group")
+        .arg("Applications/System")
+        .arg("--distribution")
+        .arg("Example Linux 1")
         .arg("-o")
         .arg(&out_file)
         .assert()
@@ -119,6 +134,80 @@ fn test_set_basic_metadata() -> Result<(), Box<dyn std::error::Error>> {
     assert_eq!(pkg.metadata.get_license()?, "MPL-2.0");
     assert_eq!(pkg.metadata.get_summary()?, "blah blah blah");
     assert_eq!(pkg.metadata.get_description()?, "blah blah blah"); // should be a copy of the summary
+    assert_eq!(
+        pkg.metadata.get_url()?,
+        "https://example.com/test-set-metadata"
+    );
+    assert_eq!(pkg.metadata.get_vendor()?, "Example Corp");
+    assert_eq!(pkg.metadata.get_packager()?, "Jane Doe <jane@example.com>");
+    assert_eq!(pkg.metadata.get_group()?, "Applications/System");
+    assert_eq!(
+        pkg.metadata
+            .header
+            .get_entry_data_as_string(rpm::IndexTag::RPMTAG_DISTRIBUTION)?,
+        "Example Linux 1"
+    );
+
+    Ok(())
+}
+
+/// Test setting a description independently of the summary
+#[test]
+fn test_description() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-description")?;
+    let out_file = tmp_dir.path().join("test-description-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-description")
+        .arg("--summary")
+        .arg("A short summary")
+        .arg("--description")
+        .arg("A much longer description")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(pkg.metadata.get_summary()?, "A short summary");
+    assert_eq!(pkg.metadata.get_description()?, "A much longer description");
+
+    let description_file = tmp_dir.path().join("description.txt");
+    fs::write(
+        &description_file,
+        "The first paragraph.\n\nThe second paragraph.\n",
+    )?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-description")
+        .arg("--summary")
+        .arg("A short summary")
+        .arg("--description-file")
+        .arg(&description_file)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        pkg.metadata.get_description()?,
+        "The first paragraph.\n\nThe second paragraph."
+    );
+
+    // The two options are mutually exclusive
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-description")
+        .arg("--description")
+        .arg("A much longer description")
+        .arg("--description-file")
+        .arg(&description_file)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure();
 
     Ok(())
 }
@@ -130,39 +219,42 @@ fn test_output_option() -> Result<(), Box<dyn std::error::Error>> {
 
     // test using an explicit filename as output
     let explicit_filename = &tmp_dir.path().join("explicit-filename.rpm");
-    assert!(!fs::exists(&explicit_filename).unwrap());
+    assert!(!fs::exists(explicit_filename).unwrap());
     Command::new(cargo_bin!())
+        .arg("build")
         .arg("test-output")
         .arg("-o")
-        .arg(&explicit_filename)
+        .arg(explicit_filename)
         .assert()
         .success();
-    assert!(fs::exists(&explicit_filename).unwrap());
+    assert!(fs::exists(explicit_filename).unwrap());
 
     let filename = Path::new("test-output-1.0.0-1.noarch.rpm");
-    let file_in_tmp = &tmp_dir.path().join(&filename);
+    let file_in_tmp = &tmp_dir.path().join(filename);
 
     // test using a directory as output, no provided filename
-    assert!(!fs::exists(&file_in_tmp).unwrap());
+    assert!(!fs::exists(file_in_tmp).unwrap());
     Command::new(cargo_bin!())
+        .arg("build")
         .arg("test-output")
         .arg("-o")
-        .arg(&tmp_dir.path())
+        .arg(tmp_dir.path())
         .assert()
         .success();
-    assert!(fs::exists(&file_in_tmp).unwrap());
+    assert!(fs::exists(file_in_tmp).unwrap());
 
     // test no output option provided at all - no provided filename, current working directory
     let orig_cwd = env::current_dir()?;
-    env::set_current_dir(&tmp_dir.path())?;
+    env::set_current_dir(tmp_dir.path())?;
 
     let expected_filename = Path::new("test-no-output-1.0.0-1.noarch.rpm");
-    assert!(!fs::exists(&expected_filename).unwrap());
+    assert!(!fs::exists(expected_filename).unwrap());
     Command::new(cargo_bin!())
+        .arg("build")
         .arg("test-no-output")
         .assert()
         .success();
-    assert!(fs::exists(&expected_filename).unwrap());
+    assert!(fs::exists(expected_filename).unwrap());
 
     env::set_current_dir(orig_cwd)?;
 
@@ -175,21 +267,22 @@ fn test_package_compression() -> Result<(), Box<dyn std::error::Error>> {
     let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
     let tmp_dir = TempDir::new("rpm-builder-test-compression-option")?;
 
-    for compression in ["gzip", "zstd", "none"] {
+    for compression in ["gzip", "zstd", "xz", "bzip2", "none"] {
         let out_file = tmp_dir.path().join(format!(
             "test-compression-{}-1.0.0-1.noarch.rpm",
             &compression
         ));
         assert!(!fs::exists(&out_file).unwrap());
         Command::new(cargo_bin!())
-            .arg(&format!("test-compression-{}", &compression))
+            .arg("build")
+            .arg(format!("test-compression-{}", &compression))
             .arg("--exec-file")
-            .arg(&format!(
+            .arg(format!(
                 "{}/tests/assets/multiplication_tables.py:/usr/bin/multiplication_tables",
                 workspace_path.to_string_lossy()
             ))
             .arg("--compression")
-            .arg(&compression)
+            .arg(compression)
             .arg("-o")
             .arg(&out_file)
             .assert()
@@ -203,18 +296,60 @@ fn test_package_compression() -> Result<(), Box<dyn std::error::Error>> {
                 "none" => rpm::CompressionType::None,
                 "zstd" => rpm::CompressionType::Zstd,
                 "gzip" => rpm::CompressionType::Gzip,
+                "xz" => rpm::CompressionType::Xz,
+                "bzip2" => rpm::CompressionType::Bzip2,
                 _ => unreachable!(),
             }
         );
     }
 
+    // Test setting the compression level
+    let out_file = tmp_dir
+        .path()
+        .join("test-compression-level-1.0.0-1.noarch.rpm");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-compression-level")
+        .arg("--compression")
+        .arg("zstd")
+        .arg("--compression-level")
+        .arg("3")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        pkg.metadata
+            .header
+            .get_entry_data_as_string(rpm::IndexTag::RPMTAG_PAYLOADFLAGS)?,
+        "3"
+    );
+
+    // Test compression levels which are out of range, or used without compression
+    for (compression, level) in [("gzip", "10"), ("zstd", "23"), ("none", "1")] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-compression-level")
+            .arg("--compression")
+            .arg(compression)
+            .arg("--compression-level")
+            .arg(level)
+            .arg("-o")
+            .arg(tmp_dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("compression level"));
+    }
+
     // Test an invalid value for the compression option
     Command::new(cargo_bin!())
+        .arg("build")
         .arg("test-compression")
         .arg("--compression")
         .arg("invalid")
         .arg("-o")
-        .arg(&tmp_dir.path())
+        .arg(tmp_dir.path())
         .assert()
         .failure();
 
@@ -229,6 +364,7 @@ fn test_adding_changelogs() -> Result<(), Box<dyn std::error::Error>> {
 
     assert!(!fs::exists(&out_file).unwrap());
     Command::new(cargo_bin!())
+        .arg("build")
         .arg("test-changelogs")
         .arg("--changelog")
         .arg("Walter White <ww@breakingbad.com>:I am the danger:2018-01-02")
@@ -260,6 +396,74 @@ fn test_adding_changelogs() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Test adding changelog entries from a changelog file
+#[test]
+fn test_changelog_file() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-changelog-file")?;
+    let out_file = tmp_dir
+        .path()
+        .join("test-changelog-file-1.0.0-1.noarch.rpm");
+
+    let changelog_file = tmp_dir.path().join("changelog");
+    fs::write(
+        &changelog_file,
+        "* Sun Feb 03 2019 Jesse Pinkman <jpinkman@breakingbad.com> - 1.1-1
+- yeah, science!
+- times: 10:00, 11:00
+
+* Tue Jan 02 2018 Walter White <ww@breakingbad.com> - 1.0-1
+- I am the danger
+",
+    )?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-changelog-file")
+        .arg("--changelog-file")
+        .arg(&changelog_file)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        pkg.metadata.get_changelog_entries()?,
+        vec![
+            rpm::ChangelogEntry {
+                name: "Jesse Pinkman <jpinkman@breakingbad.com> - 1.1-1".to_owned(),
+                timestamp: 1549152000,
+                description: "- yeah, science!\n- times: 10:00, 11:00".to_owned()
+            },
+            rpm::ChangelogEntry {
+                name: "Walter White <ww@breakingbad.com> - 1.0-1".to_owned(),
+                timestamp: 1514851200,
+                description: "- I am the danger".to_owned()
+            }
+        ]
+    );
+
+    // Content before the first entry, and invalid dates, are rejected
+    for content in [
+        "- orphaned line\n* Tue Jan 02 2018 Walter White - 1.0-1\n- I am the danger\n",
+        "* Tue Jan 42 2018 Walter White - 1.0-1\n- I am the danger\n",
+    ] {
+        fs::write(&changelog_file, content)?;
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-changelog-file")
+            .arg("--changelog-file")
+            .arg(&changelog_file)
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid changelog file"));
+    }
+
+    Ok(())
+}
+
 /// Test adding dependencies / provides / conflicts / etc. to the package
 #[test]
 fn test_adding_dependencies() -> Result<(), Box<dyn std::error::Error>> {
@@ -268,6 +472,7 @@ fn test_adding_dependencies() -> Result<(), Box<dyn std::error::Error>> {
 
     assert!(!fs::exists(&out_file).unwrap());
     Command::new(cargo_bin!())
+        .arg("build")
         .args(vec![
             "test-dependencies",
             "--provides",
@@ -340,6 +545,125 @@ fn test_adding_dependencies() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Test dependencies on versions with an epoch and release
+#[test]
+fn test_dependency_evr() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-dependency-evr")?;
+    let out_file = tmp_dir
+        .path()
+        .join("test-dependency-evr-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .args(vec![
+            "test-dependency-evr",
+            "--requires",
+            "foo >= 2:1.5-3",
+            "--conflicts",
+            "bar<1:2.0",
+            "--obsoletes",
+            "baz <= 1.0-1.fc40",
+            "-o",
+            &out_file.to_string_lossy(),
+        ])
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert!(
+        pkg.metadata
+            .get_requires()?
+            .contains(&rpm::Dependency::greater_eq("foo", "2:1.5-3"))
+    );
+    assert_eq!(
+        pkg.metadata.get_conflicts()?,
+        vec![rpm::Dependency::less("bar", "1:2.0")]
+    );
+    assert_eq!(
+        pkg.metadata.get_obsoletes()?,
+        vec![rpm::Dependency::less_eq("baz", "1.0-1.fc40")]
+    );
+
+    for dependency in [
+        "foo >= a:1.0",
+        "foo >= :1.0",
+        "foo >= 1:",
+        "foo >= 1.0-",
+        "foo >= 1.0-1-2",
+        "foo >= 1.0 2.0",
+    ] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-dependency-evr")
+            .arg("--requires")
+            .arg(dependency)
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid version in dependency"));
+    }
+
+    Ok(())
+}
+
+/// Test adding rich (boolean) dependencies to the package
+#[test]
+fn test_rich_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-rich-dependencies")?;
+    let out_file = tmp_dir
+        .path()
+        .join("test-rich-dependencies-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .args(vec![
+            "test-rich-dependencies",
+            "--requires",
+            "(pkgA >= 1.0 or pkgB)",
+            "--recommends",
+            "(foo if (bar and baz))",
+            "-o",
+            &out_file.to_string_lossy(),
+        ])
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let requires = pkg.metadata.get_requires()?;
+    assert!(requires.contains(&rpm::Dependency::any("(pkgA >= 1.0 or pkgB)")));
+    assert!(
+        requires
+            .iter()
+            .any(|r| r.name == "rpmlib(RichDependencies)")
+    );
+    assert_eq!(
+        pkg.metadata.get_recommends()?,
+        vec![rpm::Dependency::any("(foo if (bar and baz))")]
+    );
+
+    // unbalanced parentheses, and rich provides and obsoletes are rejected
+    for (flag, dependency) in [
+        ("--requires", "(pkgA or pkgB"),
+        ("--requires", "(pkgA or pkgB)) or pkgC"),
+        ("--requires", "()"),
+        ("--provides", "(pkgA or pkgB)"),
+        ("--obsoletes", "(pkgA or pkgB)"),
+    ] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-rich-dependencies")
+            .arg(flag)
+            .arg(dependency)
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .failure();
+    }
+
+    Ok(())
+}
+
 /// Test adding files and directories to the RPM
 #[test]
 fn test_adding_files() -> Result<(), Box<dyn std::error::Error>> {
@@ -349,6 +673,7 @@ fn test_adding_files() -> Result<(), Box<dyn std::error::Error>> {
 
     assert!(!fs::exists(&out_file).unwrap());
     Command::new(cargo_bin!())
+        .arg("build")
         .args(vec![
             "test-adding-files",
             "--exec-file",
@@ -641,6 +966,7 @@ fn test_signature() -> Result<(), Box<dyn std::error::Error>> {
 
     assert!(!fs::exists(&out_file).unwrap());
     Command::new(cargo_bin!())
+        .arg("build")
         .arg("test-signature")
         .arg("--sign-with-pgp-asc")
         .arg(&private_key_path)
@@ -667,6 +993,7 @@ fn test_rpm_format() -> Result<(), Box<dyn std::error::Error>> {
     // Test with rpm-version 6 (should contain RPMFORMAT and PAYLOADSIZE tags, use LONG* size tags)
     let out_file_v6 = tmp_dir.path().join("test-rpm-format-6-1.0.0-1.noarch.rpm");
     Command::new(cargo_bin!())
+        .arg("build")
         .arg("test-rpm-format-6")
         .arg("--rpm-format")
         .arg("v6")
@@ -685,6 +1012,7 @@ fn test_rpm_format() -> Result<(), Box<dyn std::error::Error>> {
     // Test with rpm-version 4 (should not contain RPMFORMAT or PAYLOADSIZE)
     let out_file_v4 = tmp_dir.path().join("test-rpm-format-4-1.0.0-1.noarch.rpm");
     Command::new(cargo_bin!())
+        .arg("build")
         .arg("test-rpm-format-4")
         .arg("--rpm-format")
         .arg("v4")
@@ -702,13 +1030,7594 @@ fn test_rpm_format() -> Result<(), Box<dyn std::error::Error>> {
 
     // Test invalid rpm-version value
     Command::new(cargo_bin!())
+        .arg("build")
         .arg("test-rpm-format-invalid")
         .arg("--rpm-format")
         .arg("invalid")
         .arg("-o")
-        .arg(&tmp_dir.path())
+        .arg(tmp_dir.path())
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+/// Test reading the package definition from TOML and YAML manifests
+#[test]
+fn test_manifest() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-manifest")?;
+
+    let toml_manifest = tmp_dir.path().join("manifest.toml");
+    fs::write(
+        &toml_manifest,
+        format!(
+            r#"
+name = "test-manifest"
+version = "2.3.4"
+release = "5"
+summary = "built from a manifest"
+requires = ["wget >= 1.0.0", "bash"]
+exec_file = ["{}/tests/assets/multiplication_tables.py:/usr/bin/multiplication_tables"]
+changelog = ["jpinkman@breakingbad.com:yeah, science!:2019-02-03"]
+"#,
+            workspace_path.to_string_lossy()
+        ),
+    )?;
+
+    // options from the command line take precedence over (or are added to) the manifest
+    let out_file = tmp_dir.path().join("test-manifest-2.3.4-6.noarch.rpm");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--manifest")
+        .arg(&toml_manifest)
+        .arg("--release")
+        .arg("6")
+        .arg("--requires")
+        .arg("zsh")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(pkg.metadata.get_name()?, "test-manifest");
+    assert_eq!(pkg.metadata.get_version()?, "2.3.4");
+    assert_eq!(pkg.metadata.get_release()?, "6");
+    assert_eq!(pkg.metadata.get_summary()?, "built from a manifest");
+    assert_eq!(
+        pkg.metadata
+            .get_requires()?
+            .into_iter()
+            .filter(|r| !r.flags.contains(rpm::DependencyFlags::RPMLIB))
+            .collect::<Vec<rpm::Dependency>>(),
+        vec![
+            rpm::Dependency::greater_eq("wget", "1.0.0"),
+            rpm::Dependency::any("bash"),
+            rpm::Dependency::any("zsh"),
+        ]
+    );
+    assert_eq!(
+        pkg.metadata.get_file_paths()?,
+        vec![PathBuf::from("/usr/bin/multiplication_tables")]
+    );
+    assert_eq!(pkg.metadata.get_changelog_entries()?.len(), 1);
+
+    // the package name from the command line overrides the one in the manifest
+    let yaml_manifest = tmp_dir.path().join("manifest.yaml");
+    fs::write(
+        &yaml_manifest,
+        "name: test-manifest\nversion: 1.2.3\nepoch: 2\nconflicts:\n  - foobar123\n",
+    )?;
+    let out_file = tmp_dir.path().join("test-manifest-yaml-1.2.3-1.noarch.rpm");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-manifest-yaml")
+        .arg("--manifest")
+        .arg(&yaml_manifest)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(pkg.metadata.get_name()?, "test-manifest-yaml");
+    assert_eq!(pkg.metadata.get_epoch()?, 2);
+    assert_eq!(pkg.metadata.get_version()?, "1.2.3");
+    assert_eq!(
+        pkg.metadata.get_conflicts()?,
+        vec![rpm::Dependency::any("foobar123")]
+    );
+
+    // unknown keys are rejected
+    fs::write(&toml_manifest, "name = \"test-manifest\"\nbogus = 1\n")?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--manifest")
+        .arg(&toml_manifest)
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+/// Test displaying the metadata of an existing package
+#[test]
+fn test_inspect() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-inspect")?;
+    let out_file = tmp_dir.path().join("test-inspect-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-inspect")
+        .arg("--summary")
+        .arg("inspect me")
+        .arg("--requires")
+        .arg("wget >= 1.0.0")
+        .arg("--exec-file")
+        .arg(format!(
+            "{}/tests/assets/multiplication_tables.py:/usr/bin/multiplication_tables",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("--pre-install-script")
+        .arg(workspace_path.join("tests/assets/preinst.sh"))
+        .arg("--sign-with-pgp-asc")
+        .arg(workspace_path.join("tests/assets/package-manager.key"))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    Command::new(cargo_bin!())
+        .arg("inspect")
+        .arg(&out_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Name         : test-inspect\n"))
+        .stdout(predicate::str::contains(
+            "NEVRA        : test-inspect-0:1.0.0-1.noarch\n",
+        ))
+        .stdout(predicate::str::contains("Summary      : inspect me\n"))
+        .stdout(predicate::str::contains("Signature    : signed"))
+        .stdout(predicate::str::contains("Requires:\n"))
+        .stdout(predicate::str::contains("  wget >= 1.0.0\n"))
+        .stdout(predicate::str::contains(
+            "  100755 root:root /usr/bin/multiplication_tables\n",
+        ))
+        .stdout(predicate::str::contains(
+            "Scriptlets:\n  pre-install:\n    #!/bin/bash\n",
+        ));
+
+    // Test the JSON output
+    let output = Command::new(cargo_bin!())
+        .arg("inspect")
+        .arg("--format")
+        .arg("json")
+        .arg(&out_file)
+        .output()?;
+    assert!(output.status.success());
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(info["name"], "test-inspect");
+    assert_eq!(info["nevra"], "test-inspect-0:1.0.0-1.noarch");
+    assert_eq!(info["signature"]["signed"], true);
+    assert!(
+        info["requires"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!({"name": "wget", "operator": ">=", "version": "1.0.0"}))
+    );
+    let file = &info["files"][0];
+    assert_eq!(file["path"], "/usr/bin/multiplication_tables");
+    assert_eq!(file["mode"], 0o100755);
+    assert!(file["digest"].is_string());
+    assert_eq!(info["scriptlets"][0]["kind"], "pre-install");
+    assert_eq!(
+        info["scriptlets"][0]["script"],
+        fs::read_to_string(workspace_path.join("tests/assets/preinst.sh"))?
+    );
+
+    // Test a path which isn't an rpm
+    Command::new(cargo_bin!())
+        .arg("inspect")
+        .arg(workspace_path.join("tests/assets/example_config.toml"))
         .assert()
         .failure();
 
     Ok(())
 }
+
+/// Test signing an existing package
+#[test]
+fn test_sign_existing_package() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-sign-existing")?;
+    let out_file = tmp_dir.path().join("test-sign-1.0.0-1.noarch.rpm");
+
+    let private_key_path = workspace_path.join("tests/assets/package-manager.key");
+    let public_key_path = workspace_path.join("tests/assets/package-manager.key.pub");
+    let raw_public_key = fs::read(public_key_path)?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-sign")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let verifier = rpm::signature::pgp::Verifier::load_from_asc_bytes(&raw_public_key)?;
+    assert!(pkg.verify_signature(verifier).is_err());
+
+    Command::new(cargo_bin!())
+        .arg("sign")
+        .arg(&out_file)
+        .arg("--sign-with-pgp-asc")
+        .arg(&private_key_path)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let verifier = rpm::signature::pgp::Verifier::load_from_asc_bytes(&raw_public_key)?;
+    pkg.verify_signature(verifier)?;
+
+    // Re-signing replaces the existing signature, and can write to a different path
+    let resigned_dir = tmp_dir.path().join("resigned");
+    fs::create_dir(&resigned_dir)?;
+    Command::new(cargo_bin!())
+        .arg("sign")
+        .arg(&out_file)
+        .arg("--sign-with-pgp-asc")
+        .arg(&private_key_path)
+        .arg("-o")
+        .arg(&resigned_dir)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(resigned_dir.join("test-sign-1.0.0-1.noarch.rpm"))?;
+    let verifier = rpm::signature::pgp::Verifier::load_from_asc_bytes(&raw_public_key)?;
+    pkg.verify_signature(verifier)?;
+
+    Ok(())
+}
+
+/// Test verifying the digests and signatures of an existing package
+#[test]
+fn test_verify() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-verify")?;
+    let signed_file = tmp_dir.path().join("test-verify-1.0.0-1.noarch.rpm");
+    let unsigned_file = tmp_dir
+        .path()
+        .join("test-verify-unsigned-1.0.0-1.noarch.rpm");
+
+    let private_key_path = workspace_path.join("tests/assets/package-manager.key");
+    let public_key_path = workspace_path.join("tests/assets/package-manager.key.pub");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-verify")
+        .arg("--sign-with-pgp-asc")
+        .arg(&private_key_path)
+        .arg("-o")
+        .arg(&signed_file)
+        .assert()
+        .success();
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-verify-unsigned")
+        .arg("-o")
+        .arg(&unsigned_file)
+        .assert()
+        .success();
+
+    Command::new(cargo_bin!())
+        .arg("verify")
+        .arg(&signed_file)
+        .arg("--key")
+        .arg(&public_key_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("digests signatures OK\n"));
+
+    // an unsigned package has valid digests, but no signature
+    Command::new(cargo_bin!())
+        .arg("verify")
+        .arg(&unsigned_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("digests OK\n"));
+    Command::new(cargo_bin!())
+        .arg("verify")
+        .arg(&unsigned_file)
+        .arg("--key")
+        .arg(&public_key_path)
+        .assert()
+        .failure();
+
+    // corrupt the payload
+    let mut content = fs::read(&signed_file)?;
+    let last = content.len() - 1;
+    content[last] ^= 0xff;
+    fs::write(&signed_file, content)?;
+    Command::new(cargo_bin!())
+        .arg("verify")
+        .arg(&signed_file)
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+/// Test marking files as license and readme files
+#[test]
+fn test_license_and_readme_files() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-license-readme")?;
+    let out_file = tmp_dir
+        .path()
+        .join("test-license-readme-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-license-readme")
+        .arg("--license-file")
+        .arg(format!(
+            "{}/tests/assets/example_config.toml:/usr/share/licenses/test-license-readme/LICENSE",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("--readme-file")
+        .arg(format!(
+            "{}/tests/assets/example_data.xml:/usr/share/doc/test-license-readme/README:mode=0600",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(
+        entries[0].path,
+        PathBuf::from("/usr/share/doc/test-license-readme/README")
+    );
+    assert_eq!(entries[0].flags, rpm::FileFlags::README);
+    assert_eq!(entries[0].mode, rpm::FileMode::regular(0o600));
+    assert_eq!(
+        entries[1].path,
+        PathBuf::from("/usr/share/licenses/test-license-readme/LICENSE")
+    );
+    assert_eq!(entries[1].flags, rpm::FileFlags::LICENSE);
+
+    Ok(())
+}
+
+/// Test marking files as build artifacts, which is set in the header after the build
+#[test]
+fn test_artifact_file() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-artifact-file")?;
+    let out_file = tmp_dir.path().join("test-artifact-file-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-artifact-file")
+        .arg("--artifact-file")
+        .arg(format!(
+            "{}/tests/assets/example_data.xml:/usr/lib/.build-id/ab/cdef:mode=0600",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("--file")
+        .arg(format!(
+            "{}/tests/assets/example_config.toml:/etc/myapp/example_config.toml",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(
+        entries[0].path,
+        PathBuf::from("/etc/myapp/example_config.toml")
+    );
+    assert_eq!(entries[0].flags, rpm::FileFlags::empty());
+    assert_eq!(entries[1].path, PathBuf::from("/usr/lib/.build-id/ab/cdef"));
+    assert_eq!(entries[1].flags, rpm::FileFlags::ARTIFACT);
+    assert_eq!(entries[1].mode, rpm::FileMode::regular(0o600));
+
+    Ok(())
+}
+
+/// Test setting the mode and ownership of individual files
+#[test]
+fn test_file_attributes() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-file-attributes")?;
+    let out_file = tmp_dir
+        .path()
+        .join("test-file-attributes-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-attributes")
+        .arg("--config-file")
+        .arg(format!(
+            "{}/tests/assets/example_config.toml:/etc/nginx/example_config.toml:mode=0640,user=nginx,group=nginx",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("--exec-file")
+        .arg(format!(
+            "{}/tests/assets/multiplication_tables.py:/usr/bin/multiplication_tables:mode=0750,group=wheel",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(
+        entries[0].path,
+        PathBuf::from("/etc/nginx/example_config.toml")
+    );
+    assert_eq!(entries[0].mode, rpm::FileMode::regular(0o640));
+    assert_eq!(entries[0].flags, rpm::FileFlags::CONFIG);
+    assert_eq!(
+        entries[0].ownership,
+        rpm::FileOwnership {
+            user: "nginx".to_owned(),
+            group: "nginx".to_owned(),
+        }
+    );
+
+    assert_eq!(
+        entries[1].path,
+        PathBuf::from("/usr/bin/multiplication_tables")
+    );
+    assert_eq!(entries[1].mode, rpm::FileMode::regular(0o750));
+    assert_eq!(
+        entries[1].ownership,
+        rpm::FileOwnership {
+            user: "root".to_owned(),
+            group: "wheel".to_owned(),
+        }
+    );
+
+    // Test invalid attributes
+    for attributes in ["mode=999", "mode=", "owner=nginx"] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-file-attributes")
+            .arg("--file")
+            .arg(format!(
+                "{}/tests/assets/example_config.toml:/etc/example_config.toml:{}",
+                workspace_path.to_string_lossy(),
+                attributes
+            ))
+            .arg("-o")
+            .arg(tmp_dir.path())
+            .assert()
+            .failure();
+    }
+
+    Ok(())
+}
+
+/// Test marking config files as noreplace
+#[test]
+fn test_config_noreplace() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-config-noreplace")?;
+    let out_file = tmp_dir
+        .path()
+        .join("test-config-noreplace-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-config-noreplace")
+        .arg("--config-file")
+        .arg(format!(
+            "{}/tests/assets/example_config.toml:/etc/myapp/example_config.toml:noreplace,mode=0640",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("--config-file")
+        .arg(format!(
+            "{}/tests/assets/example_data.xml:/etc/myapp/example_data.xml",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(
+        entries[0].path,
+        PathBuf::from("/etc/myapp/example_config.toml")
+    );
+    assert_eq!(
+        entries[0].flags,
+        rpm::FileFlags::CONFIG | rpm::FileFlags::NOREPLACE
+    );
+    assert_eq!(entries[0].mode, rpm::FileMode::regular(0o640));
+
+    assert_eq!(
+        entries[1].path,
+        PathBuf::from("/etc/myapp/example_data.xml")
+    );
+    assert_eq!(entries[1].flags, rpm::FileFlags::CONFIG);
+
+    Ok(())
+}
+
+/// Test marking config files as missingok, which is set in the header after the build
+#[test]
+fn test_config_missingok() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-config-missingok")?;
+    let out_file = tmp_dir
+        .path()
+        .join("test-config-missingok-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-config-missingok")
+        .arg("--config-file")
+        .arg(format!(
+            "{}/tests/assets/example_config.toml:/etc/myapp/example_config.toml:missingok,noreplace",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("--file")
+        .arg(format!(
+            "{}/tests/assets/example_data.xml:/etc/myapp/example_data.xml:flags=config(missingok)",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("--file")
+        .arg(format!(
+            "{}/tests/assets/multiplication_tables.py:/usr/bin/multiplication_tables",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 3);
+
+    assert_eq!(
+        entries[0].path,
+        PathBuf::from("/etc/myapp/example_config.toml")
+    );
+    assert_eq!(
+        entries[0].flags,
+        rpm::FileFlags::CONFIG | rpm::FileFlags::NOREPLACE | rpm::FileFlags::MISSINGOK
+    );
+    assert_eq!(
+        entries[1].path,
+        PathBuf::from("/etc/myapp/example_data.xml")
+    );
+    assert_eq!(
+        entries[1].flags,
+        rpm::FileFlags::CONFIG | rpm::FileFlags::MISSINGOK
+    );
+    assert_eq!(
+        entries[2].path,
+        PathBuf::from("/usr/bin/multiplication_tables")
+    );
+    assert_eq!(entries[2].flags, rpm::FileFlags::empty());
+
+    Ok(())
+}
+
+/// Test using glob patterns as the source of file arguments
+#[test]
+fn test_file_globs() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-file-globs")?;
+    let out_file = tmp_dir.path().join("test-file-globs-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-globs")
+        .arg("--file")
+        .arg(format!(
+            "{}/tests/assets/foo/bar/*.txt:/usr/share/test-file-globs/",
+            workspace_path.to_string_lossy()
+        ))
+        // directories matched by the pattern are skipped
+        .arg("--doc-file")
+        .arg(format!(
+            "{}/tests/assets/foo/*:/usr/share/doc/test-file-globs",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        pkg.metadata.get_file_paths()?,
+        vec![
+            PathBuf::from("/usr/share/doc/test-file-globs/z.txt"),
+            PathBuf::from("/usr/share/test-file-globs/a.txt"),
+            PathBuf::from("/usr/share/test-file-globs/b.txt"),
+        ]
+    );
+
+    // A pattern without any matches is an error, unless explicitly allowed
+    let no_matches = format!(
+        "{}/tests/assets/*.nonexistent:/usr/share/test-file-globs/",
+        workspace_path.to_string_lossy()
+    );
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-globs")
+        .arg("--file")
+        .arg(&no_matches)
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("did not match any files"));
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-globs")
+        .arg("--file")
+        .arg(&no_matches)
+        .arg("--allow-empty-glob")
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success();
+
+    Ok(())
+}
+
+/// Test adding symlinks which don't exist on the build host
+#[test]
+fn test_symlinks() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-symlinks")?;
+    let out_file = tmp_dir.path().join("test-symlinks-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-symlinks")
+        .arg("--symlink")
+        .arg("/usr/bin/foo:/opt/foo/bin/foo")
+        .arg("--symlink")
+        .arg("/usr/lib64/libfoo.so:libfoo.so.1")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0].path, PathBuf::from("/usr/bin/foo"));
+    assert_eq!(entries[0].mode, rpm::FileMode::symbolic_link(0o777));
+    assert_eq!(entries[0].linkto, "/opt/foo/bin/foo");
+
+    assert_eq!(entries[1].path, PathBuf::from("/usr/lib64/libfoo.so"));
+    assert_eq!(entries[1].mode, rpm::FileMode::symbolic_link(0o777));
+    assert_eq!(entries[1].linkto, "libfoo.so.1");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-symlinks")
+        .arg("--symlink")
+        .arg("/usr/bin/foo")
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+/// Test that reproducible builds of the same inputs are byte-identical
+#[test]
+fn test_reproducible() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-reproducible")?;
+
+    let build = |out_file: &Path| {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-reproducible")
+            .arg("--exec-file")
+            .arg(format!(
+                "{}/tests/assets/multiplication_tables.py:/usr/bin/multiplication_tables",
+                workspace_path.to_string_lossy()
+            ))
+            .arg("--dir")
+            .arg(format!(
+                "{}/tests/assets/module:/usr/lib/python3.13/site-packages/module",
+                workspace_path.to_string_lossy()
+            ))
+            .arg("--symlink")
+            .arg("/usr/bin/tables:multiplication_tables")
+            .arg("--changelog")
+            .arg("me:older entry:2020-01-01")
+            .arg("--changelog")
+            .arg("me:newer entry:2020-06-01")
+            .arg("--reproducible")
+            .arg("-o")
+            .arg(out_file)
+            .env("SOURCE_DATE_EPOCH", "1600000000")
+            .assert()
+            .success();
+    };
+
+    let first = tmp_dir.path().join("first.rpm");
+    let second = tmp_dir.path().join("second.rpm");
+    build(&first);
+    // make sure the wall clock moves on between the two builds
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    build(&second);
+    assert_eq!(fs::read(&first)?, fs::read(&second)?);
+
+    let pkg = rpm::Package::open(&first)?;
+    assert_eq!(pkg.metadata.get_build_time()?, 1600000000);
+    for entry in pkg.metadata.get_file_entries()? {
+        assert_eq!(entry.modified_at, rpm::Timestamp::from(1600000000));
+    }
+    let changelog: Vec<String> = pkg
+        .metadata
+        .get_changelog_entries()?
+        .into_iter()
+        .map(|entry| entry.description)
+        .collect();
+    assert_eq!(changelog, vec!["newer entry", "older entry"]);
+
+    // Without SOURCE_DATE_EPOCH, everything is clamped to 0
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-reproducible")
+        .arg("--reproducible")
+        .arg("-o")
+        .arg(&first)
+        .env_remove("SOURCE_DATE_EPOCH")
+        .assert()
+        .success();
+    let pkg = rpm::Package::open(&first)?;
+    assert_eq!(pkg.metadata.get_build_time()?, 0);
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-reproducible")
+        .arg("-o")
+        .arg(&first)
+        .env("SOURCE_DATE_EPOCH", "yesterday")
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+/// Test adding install, uninstall and transaction scriptlets
+#[test]
+fn test_scriptlets() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-scriptlets")?;
+    let out_file = tmp_dir.path().join("test-scriptlets-1.0.0-1.noarch.rpm");
+    let script = workspace_path.join("tests/assets/preinst.sh");
+
+    let mut cmd = Command::new(cargo_bin!());
+    cmd.arg("build").arg("test-scriptlets");
+    for flag in [
+        "--pre-install-script",
+        "--post-install-script",
+        "--pre-uninstall-script",
+        "--post-uninstall-script",
+        "--pre-trans-script",
+        "--post-trans-script",
+    ] {
+        cmd.arg(flag).arg(&script);
+    }
+    cmd.arg("-o").arg(&out_file).assert().success();
+
+    let content = fs::read_to_string(&script)?;
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(pkg.metadata.get_pre_install_script()?.script, content);
+    assert_eq!(pkg.metadata.get_post_install_script()?.script, content);
+    assert_eq!(pkg.metadata.get_pre_uninstall_script()?.script, content);
+    assert_eq!(pkg.metadata.get_post_uninstall_script()?.script, content);
+    assert_eq!(pkg.metadata.get_pre_trans_script()?.script, content);
+    assert_eq!(pkg.metadata.get_post_trans_script()?.script, content);
+    assert!(pkg.metadata.get_pre_install_script()?.program.is_none());
+    assert!(pkg.metadata.get_pre_install_script()?.flags.is_none());
+
+    // Test custom interpreters and flags, including an interpreter without a script
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-scriptlets")
+        .arg("--pre-install-script")
+        .arg(&script)
+        .arg("--pre-install-interpreter")
+        .arg("/bin/bash -e")
+        .arg("--pre-install-flags")
+        .arg("expand,critical")
+        .arg("--post-install-interpreter")
+        .arg("/sbin/ldconfig")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let pre_install = pkg.metadata.get_pre_install_script()?;
+    assert_eq!(pre_install.script, content);
+    assert_eq!(
+        pre_install.program,
+        Some(vec!["/bin/bash".to_owned(), "-e".to_owned()])
+    );
+    assert_eq!(
+        pre_install.flags,
+        Some(rpm::ScriptletFlags::EXPAND | rpm::ScriptletFlags::CRITICAL)
+    );
+    let post_install = pkg.metadata.get_post_install_script()?;
+    assert_eq!(post_install.script, "");
+    assert_eq!(
+        post_install.program,
+        Some(vec!["/sbin/ldconfig".to_owned()])
+    );
+
+    // Flags without a script are rejected
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-scriptlets")
+        .arg("--post-trans-flags")
+        .arg("expand")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+/// Test preserving symlinks inside of directories instead of following them
+#[test]
+#[cfg(unix)]
+fn test_dir_preserve_symlinks() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-dir-preserve-symlinks")?;
+    let out_file = tmp_dir
+        .path()
+        .join("test-dir-preserve-symlinks-1.0.0-1.noarch.rpm");
+
+    let source_dir = tmp_dir.path().join("source");
+    fs::create_dir_all(source_dir.join("lib"))?;
+    fs::write(source_dir.join("lib/libfoo.so.1"), "not really a library")?;
+    std::os::unix::fs::symlink("libfoo.so.1", source_dir.join("lib/libfoo.so"))?;
+    std::os::unix::fs::symlink("/etc/hosts", source_dir.join("hosts"))?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-dir-preserve-symlinks")
+        .arg("--dir")
+        .arg(format!("{}:/opt/foo", source_dir.to_string_lossy()))
+        .arg("--dir-preserve-symlinks")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 3);
+
+    assert_eq!(entries[0].path, PathBuf::from("/opt/foo/hosts"));
+    assert_eq!(entries[0].mode, rpm::FileMode::symbolic_link(0o777));
+    assert_eq!(entries[0].linkto, "/etc/hosts");
+
+    assert_eq!(entries[1].path, PathBuf::from("/opt/foo/lib/libfoo.so"));
+    assert_eq!(entries[1].mode, rpm::FileMode::symbolic_link(0o777));
+    assert_eq!(entries[1].linkto, "libfoo.so.1");
+
+    assert_eq!(entries[2].path, PathBuf::from("/opt/foo/lib/libfoo.so.1"));
+    assert_eq!(entries[2].linkto, "");
+
+    Ok(())
+}
+
+/// Test adding empty directories owned by the package
+#[test]
+fn test_create_dir() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-create-dir")?;
+    let out_file = tmp_dir.path().join("test-create-dir-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-create-dir")
+        .arg("--create-dir")
+        .arg("/var/lib/myapp:mode=0750,user=myapp,group=myapp")
+        .arg("--create-dir")
+        .arg("/usr/share/myapp/")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0].path, PathBuf::from("/usr/share/myapp"));
+    assert_eq!(entries[0].mode, rpm::FileMode::dir(0o755));
+    assert_eq!(
+        entries[0].ownership,
+        rpm::FileOwnership {
+            user: "root".to_owned(),
+            group: "root".to_owned(),
+        }
+    );
+
+    assert_eq!(entries[1].path, PathBuf::from("/var/lib/myapp"));
+    assert_eq!(entries[1].mode, rpm::FileMode::dir(0o750));
+    assert_eq!(
+        entries[1].ownership,
+        rpm::FileOwnership {
+            user: "myapp".to_owned(),
+            group: "myapp".to_owned(),
+        }
+    );
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-create-dir")
+        .arg("--create-dir")
+        .arg("/var/lib/myapp:owner=myapp")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+/// Test creating changelog entries from the history of a git repository
+#[test]
+fn test_changelog_from_git() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-changelog-from-git")?;
+    let out_file = tmp_dir
+        .path()
+        .join("test-changelog-from-git-1.0.0-1.noarch.rpm");
+    let repo = tmp_dir.path().join("repo");
+    fs::create_dir(&repo)?;
+
+    let git = |args: &[&str], date: &str| {
+        std::process::Command::new("git")
+            .arg("-C")
+            .arg(&repo)
+            .args([
+                "-c",
+                "user.name=Walter White",
+                "-c",
+                "user.email=ww@breakingbad.com",
+            ])
+            .args(args)
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .output()
+            .map(|output| assert!(output.status.success()))
+    };
+    git(&["init", "--quiet"], "")?;
+    for (subject, date) in [
+        ("Initial commit", "1514851200 +0000"),
+        ("Add the formula", "1549152000 +0000"),
+        ("Fix the formula", "1580688000 +0000"),
+    ] {
+        git(&["commit", "--quiet", "--allow-empty", "-m", subject], date)?;
+    }
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-changelog-from-git")
+        .arg("--changelog-from-git")
+        .arg(&repo)
+        .arg("--changelog-limit")
+        .arg("2")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        pkg.metadata.get_changelog_entries()?,
+        vec![
+            rpm::ChangelogEntry {
+                name: "Walter White <ww@breakingbad.com>".to_owned(),
+                timestamp: 1580688000,
+                description: "- Fix the formula".to_owned()
+            },
+            rpm::ChangelogEntry {
+                name: "Walter White <ww@breakingbad.com>".to_owned(),
+                timestamp: 1549152000,
+                description: "- Add the formula".to_owned()
+            }
+        ]
+    );
+
+    // Not a git repository
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-changelog-from-git")
+        .arg("--changelog-from-git")
+        .arg(tmp_dir.path().join("missing"))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unable to read the git history"));
+
+    Ok(())
+}
+
+/// Test setting POSIX file capabilities on files
+#[test]
+fn test_file_capabilities() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-file-capabilities")?;
+    let out_file = tmp_dir
+        .path()
+        .join("test-file-capabilities-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-capabilities")
+        .arg("--exec-file")
+        .arg(format!(
+            "{}/tests/assets/multiplication_tables.py:/usr/bin/ping:caps=cap_net_raw+ep",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("--exec-file")
+        .arg(format!(
+            "{}/tests/assets/multiplication_tables.py:/usr/bin/tcpdump:mode=0750,caps=cap_net_raw,cap_net_admin=ep,group=wheel",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0].path, PathBuf::from("/usr/bin/ping"));
+    assert_eq!(entries[0].caps.as_deref(), Some("cap_net_raw+ep"));
+
+    assert_eq!(entries[1].path, PathBuf::from("/usr/bin/tcpdump"));
+    assert_eq!(
+        entries[1].caps.as_deref(),
+        Some("cap_net_raw,cap_net_admin=ep")
+    );
+    assert_eq!(entries[1].mode, rpm::FileMode::regular(0o750));
+    assert_eq!(entries[1].ownership.group, "wheel");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-capabilities")
+        .arg("--exec-file")
+        .arg(format!(
+            "{}/tests/assets/multiplication_tables.py:/usr/bin/ping:caps=cap_bogus+ep",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid file capabilities"));
+
+    Ok(())
+}
+
+/// Test adding a file with content read from stdin
+#[test]
+fn test_file_from_stdin() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-file-from-stdin")?;
+    let out_file = tmp_dir
+        .path()
+        .join("test-file-from-stdin-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-from-stdin")
+        .arg("--file-from-stdin")
+        .arg("/etc/myapp/generated.conf:mode=0600,user=myapp")
+        .arg("-o")
+        .arg(&out_file)
+        .write_stdin("rendered = true\n")
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].path, PathBuf::from("/etc/myapp/generated.conf"));
+    assert_eq!(entries[0].mode, rpm::FileMode::regular(0o600));
+    assert_eq!(entries[0].ownership.user, "myapp");
+    assert_eq!(entries[0].size, "rendered = true\n".len());
+
+    let extract_dir = tmp_dir.path().join("extracted");
+    pkg.extract(&extract_dir)?;
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("etc/myapp/generated.conf"))?,
+        "rendered = true\n"
+    );
+
+    Ok(())
+}
+
+/// Test extracting the files of a package into a directory
+#[test]
+#[cfg(unix)]
+fn test_extract() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-extract")?;
+    let out_file = tmp_dir.path().join("test-extract-1.0.0-1.noarch.rpm");
+    let extract_dir = tmp_dir.path().join("extracted");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-extract")
+        .arg("--exec-file")
+        .arg(format!(
+            "{}/tests/assets/multiplication_tables.py:/usr/bin/multiplication_tables",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("--file")
+        .arg(format!(
+            "{}/tests/assets/example_config.toml:/etc/foo/config.toml:mode=0640",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("--symlink")
+        .arg("/usr/bin/mt:multiplication_tables")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    Command::new(cargo_bin!())
+        .arg("extract")
+        .arg(&out_file)
+        .arg("--into")
+        .arg(&extract_dir)
+        .assert()
+        .success();
+
+    let executable = extract_dir.join("usr/bin/multiplication_tables");
+    assert_eq!(
+        fs::read(&executable)?,
+        fs::read(workspace_path.join("tests/assets/multiplication_tables.py"))?
+    );
+    assert_eq!(
+        fs::metadata(&executable)?.permissions().mode() & 0o777,
+        0o755
+    );
+
+    let config = extract_dir.join("etc/foo/config.toml");
+    assert_eq!(
+        fs::read(&config)?,
+        fs::read(workspace_path.join("tests/assets/example_config.toml"))?
+    );
+    assert_eq!(fs::metadata(&config)?.permissions().mode() & 0o777, 0o640);
+
+    assert_eq!(
+        fs::read_link(extract_dir.join("usr/bin/mt"))?,
+        PathBuf::from("multiplication_tables")
+    );
+
+    // Test that an existing directory isn't overwritten
+    Command::new(cargo_bin!())
+        .arg("extract")
+        .arg(&out_file)
+        .arg("--into")
+        .arg(&extract_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+
+    Ok(())
+}
+
+/// Test comparing two packages
+#[test]
+fn test_diff() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-diff")?;
+    let old_file = tmp_dir.path().join("old.rpm");
+    let new_file = tmp_dir.path().join("new.rpm");
+    let asset = |name: &str| workspace_path.join("tests/assets").join(name);
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-diff")
+        .arg("--version")
+        .arg("1.0.0")
+        .arg("--file")
+        .arg(format!(
+            "{}:/etc/foo/config",
+            asset("example_config.toml").display()
+        ))
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/share/foo/data.xml",
+            asset("example_data.xml").display()
+        ))
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/share/foo/removed",
+            asset("preinst.sh").display()
+        ))
+        .arg("--requires")
+        .arg("wget >= 1.0.0")
+        .arg("-o")
+        .arg(&old_file)
+        .assert()
+        .success();
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-diff")
+        .arg("--version")
+        .arg("1.1.0")
+        .arg("--file")
+        .arg(format!(
+            "{}:/etc/foo/config",
+            asset("update.patch").display()
+        ))
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/share/foo/data.xml",
+            asset("example_data.xml").display()
+        ))
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/share/foo/added",
+            asset("preinst.sh").display()
+        ))
+        .arg("--requires")
+        .arg("wget >= 2.0.0")
+        .arg("-o")
+        .arg(&new_file)
+        .assert()
+        .success();
+
+    Command::new(cargo_bin!())
+        .arg("diff")
+        .arg(&old_file)
+        .arg(&new_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  version: 1.0.0 -> 1.1.0\n"))
+        .stdout(predicate::str::contains("  + /usr/share/foo/added\n"))
+        .stdout(predicate::str::contains("  - /usr/share/foo/removed\n"))
+        .stdout(predicate::str::contains("  ~ /etc/foo/config\n"))
+        .stdout(predicate::str::contains("data.xml").not())
+        .stdout(predicate::str::contains(
+            "requires:\n  + wget >= 2.0.0\n  - wget >= 1.0.0\n",
+        ));
+
+    let output = Command::new(cargo_bin!())
+        .arg("diff")
+        .arg("--format")
+        .arg("json")
+        .arg(&old_file)
+        .arg(&new_file)
+        .output()?;
+    assert!(output.status.success());
+    let diff: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(
+        diff["files"],
+        serde_json::json!({
+            "added": ["/usr/share/foo/added"],
+            "removed": ["/usr/share/foo/removed"],
+            "changed": ["/etc/foo/config"],
+        })
+    );
+    assert_eq!(
+        diff["dependencies"]["requires"]["added"][0],
+        "wget >= 2.0.0"
+    );
+
+    // A package doesn't differ from itself
+    Command::new(cargo_bin!())
+        .arg("diff")
+        .arg(&old_file)
+        .arg(&old_file)
+        .assert()
+        .success()
+        .stdout("");
+
+    Ok(())
+}
+
+/// Test checking packages for common mistakes
+#[test]
+fn test_lint() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-lint")?;
+    let out_file = tmp_dir.path().join("test-lint-1.0.0-1.noarch.rpm");
+    let asset = |name: &str| workspace_path.join("tests/assets").join(name);
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-lint")
+        .arg("--summary")
+        .arg("a package that passes")
+        .arg("--exec-file")
+        .arg(format!(
+            "{}:/usr/bin/multiplication_tables",
+            asset("multiplication_tables.py").display()
+        ))
+        .arg("--symlink")
+        .arg("/usr/bin/mt:multiplication_tables")
+        .arg("--lint")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success()
+        .stderr("");
+
+    Command::new(cargo_bin!())
+        .arg("lint")
+        .arg(&out_file)
+        .assert()
+        .success()
+        .stderr("");
+
+    // Warnings are reported, but don't fail the build
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-lint")
+        .arg("--summary")
+        .arg("A summary.")
+        .arg("--exec-file")
+        .arg(format!("{}:/usr/bin/config", asset("example_config.toml").display()))
+        .arg("--lint")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "warning: the summary ends with a period",
+        ))
+        .stderr(predicate::str::contains(
+            "warning: executable /usr/bin/config is neither a script with a shebang nor an ELF binary",
+        ));
+
+    // Errors fail the build, and the package isn't written
+    let bad_file = tmp_dir.path().join("bad.rpm");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-lint")
+        .arg("--license")
+        .arg("")
+        .arg("--file")
+        .arg(format!("{}:./etc/foo.toml", asset("example_config.toml").display()))
+        .arg("--file")
+        .arg(format!("{}:/etc/bar.xml", asset("example_config.toml").display()))
+        .arg("--create-dir")
+        .arg("/etc/bar.xml")
+        .arg("--symlink")
+        .arg("/usr/lib64/libfoo.so:libfoo.so.1")
+        .arg("--lint")
+        .arg("-o")
+        .arg(&bad_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "error: destination path ./etc/foo.toml is not absolute",
+        ))
+        .stderr(predicate::str::contains(
+            "error: /etc/bar.xml was added more than once",
+        ))
+        .stderr(predicate::str::contains("error: the package has no license"))
+        .stderr(predicate::str::contains(
+            "error: symlink /usr/lib64/libfoo.so points to /usr/lib64/libfoo.so.1, which isn't in the package",
+        ))
+        .stderr(predicate::str::contains("4 lint error(s)"));
+    assert!(!bad_file.exists());
+
+    // The lint subcommand finds the mistakes that are visible in an existing package
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-lint")
+        .arg("--symlink")
+        .arg("/usr/lib64/libfoo.so:libfoo.so.1")
+        .arg("-o")
+        .arg(&bad_file)
+        .assert()
+        .success();
+    Command::new(cargo_bin!())
+        .arg("lint")
+        .arg(&bad_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "error: symlink /usr/lib64/libfoo.so points to /usr/lib64/libfoo.so.1",
+        ))
+        .stderr(predicate::str::contains(
+            "warning: the package has no summary",
+        ));
+
+    Ok(())
+}
+
+/// Test that a dry run prints the package without writing it
+#[test]
+fn test_dry_run() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-dry-run")?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-dry-run")
+        .arg("--version")
+        .arg("2.0.0")
+        .arg("--requires")
+        .arg("wget >= 1.0.0")
+        .arg("--exec-file")
+        .arg(format!(
+            "{}/tests/assets/multiplication_tables.py:/usr/bin/multiplication_tables",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("--pre-install-script")
+        .arg(workspace_path.join("tests/assets/preinst.sh"))
+        .arg("--dry-run")
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "NEVRA        : test-dry-run-0:2.0.0-1.noarch\n",
+        ))
+        .stdout(predicate::str::contains("  wget >= 1.0.0\n"))
+        .stdout(predicate::str::contains(
+            "  100755 root:root /usr/bin/multiplication_tables\n",
+        ))
+        .stdout(predicate::str::contains("  pre-install:\n"))
+        .stdout(predicate::str::contains(format!(
+            "Output       : {}\n",
+            tmp_dir
+                .path()
+                .join("test-dry-run-2.0.0-1.noarch.rpm")
+                .display()
+        )));
+
+    assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 0);
+
+    // Invalid arguments still fail
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-dry-run")
+        .arg("--file")
+        .arg("/does/not/exist:/usr/bin/foo")
+        .arg("--dry-run")
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+/// Test printing the absolute path of the package
+#[test]
+fn test_print_output_path() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-print-output-path")?;
+    let expected_path = tmp_dir
+        .path()
+        .join("test-print-output-path-1.0.0-1.noarch.rpm");
+
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-print-output-path")
+        .arg("--print-output-path")
+        .assert()
+        .success()
+        .stdout(format!("{}\n", expected_path.display()));
+    assert!(expected_path.exists());
+
+    // The path is the last line of a dry run
+    fs::remove_file(&expected_path)?;
+    let output = Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-print-output-path")
+        .arg("--dry-run")
+        .arg("--print-output-path")
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(
+        stdout.lines().last(),
+        Some(expected_path.to_string_lossy().as_ref())
+    );
+    assert!(!expected_path.exists());
+
+    Ok(())
+}
+
+/// Test building subpackages defined in a manifest
+#[test]
+fn test_subpackages() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-subpackages")?;
+    let manifest = tmp_dir.path().join("manifest.toml");
+    fs::write(
+        &manifest,
+        format!(
+            r#"
+name = "test-subpackages"
+version = "2.0.0"
+release = "3"
+summary = "the main package"
+exec_file = ["{assets}/multiplication_tables.py:/usr/bin/multiplication_tables"]
+requires = ["wget"]
+changelog = ["me:initial release:2024-01-02"]
+
+[[subpackage]]
+name = "test-subpackages-doc"
+summary = "the documentation"
+arch = "noarch"
+doc_file = ["{assets}/example_data.xml:/usr/share/doc/test-subpackages/data.xml"]
+requires = ["test-subpackages = 2.0.0-3"]
+
+[[subpackage]]
+name = "test-subpackages-devel"
+summary = "the headers"
+release = "4"
+file = ["{assets}/example_config.toml:/usr/include/test-subpackages.h"]
+"#,
+            assets = workspace_path.join("tests/assets").display()
+        ),
+    )?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--manifest")
+        .arg(&manifest)
+        .arg("--arch")
+        .arg("x86_64")
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(tmp_dir.path().join("test-subpackages-2.0.0-3.x86_64.rpm"))?;
+    assert_eq!(pkg.metadata.get_summary()?, "the main package");
+    assert_eq!(pkg.metadata.get_file_entries()?.len(), 1);
+
+    // the subpackage shares the version, release and changelog but not files and dependencies
+    let doc = rpm::Package::open(
+        tmp_dir
+            .path()
+            .join("test-subpackages-doc-2.0.0-3.noarch.rpm"),
+    )?;
+    assert_eq!(doc.metadata.get_summary()?, "the documentation");
+    let files = doc.metadata.get_file_entries()?;
+    assert_eq!(files.len(), 1);
+    assert_eq!(
+        files[0].path,
+        PathBuf::from("/usr/share/doc/test-subpackages/data.xml")
+    );
+    let requires = doc.metadata.get_requires()?;
+    assert!(requires.iter().any(|d| d.name == "test-subpackages"));
+    assert!(!requires.iter().any(|d| d.name == "wget"));
+    assert_eq!(doc.metadata.get_changelog_entries()?.len(), 1);
+
+    // options set by the subpackage take precedence
+    let devel = rpm::Package::open(
+        tmp_dir
+            .path()
+            .join("test-subpackages-devel-2.0.0-4.x86_64.rpm"),
+    )?;
+    assert_eq!(devel.metadata.get_summary()?, "the headers");
+
+    // the packages can't all be written to the same file
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--manifest")
+        .arg(&manifest)
+        .arg("-o")
+        .arg(tmp_dir.path().join("out.rpm"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("must be a directory"));
+
+    fs::write(
+        &manifest,
+        "name = \"test\"\n[[subpackage]]\nsummary = \"no name\"\n",
+    )?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--manifest")
+        .arg(&manifest)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("subpackage 1 has no 'name'"));
+
+    Ok(())
+}
+
+/// Test building a package through the library instead of the command line
+#[test]
+fn test_library() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    let mut args = rpm_builder::BuildArgs::new("test-library");
+    args.version = "3.2.1".to_owned();
+    args.exec_file.push(format!(
+        "{}/tests/assets/multiplication_tables.py:/usr/bin/multiplication_tables",
+        workspace_path.to_string_lossy()
+    ));
+    args.requires.push("wget >= 1.0.0".to_owned());
+
+    let pkg = rpm_builder::build::package(args)?;
+    assert_eq!(pkg.metadata.get_name()?, "test-library");
+    assert_eq!(pkg.metadata.get_version()?, "3.2.1");
+    assert_eq!(pkg.metadata.get_file_entries()?.len(), 1);
+    assert_eq!(
+        rpm_builder::build::output_path(&pkg, None, None)?,
+        PathBuf::from("test-library-3.2.1-1.noarch.rpm")
+    );
+
+    let dependency = rpm_builder::build::parse_dependency("foo >= 1:2.0-3")?;
+    assert_eq!(dependency.name, "foo");
+    assert_eq!(dependency.version, "1:2.0-3");
+    assert!(rpm_builder::build::parse_dependency("foo >= 1.0-").is_err());
+
+    Ok(())
+}
+
+/// Test taking the package metadata and binaries from a Cargo.toml
+#[test]
+fn test_from_cargo() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-from-cargo")?;
+    let project_dir = tmp_dir.path().join("project");
+    fs::create_dir_all(project_dir.join("target/release"))?;
+    fs::write(
+        project_dir.join("Cargo.toml"),
+        r#"
+[package]
+name = "test-from-cargo"
+version = "0.4.2"
+description = "A tool built with cargo"
+license = "MIT OR Apache-2.0"
+repository = "https://example.com/test-from-cargo"
+
+[[bin]]
+name = "tfc"
+path = "src/main.rs"
+"#,
+    )?;
+    fs::write(
+        project_dir.join("target/release/tfc"),
+        "#!/bin/sh\necho hi\n",
+    )?;
+
+    let out_file = tmp_dir.path().join("out.rpm");
+    Command::new(cargo_bin!())
+        .env_remove("CARGO_TARGET_DIR")
+        .arg("build")
+        .arg("--from-cargo")
+        .arg(project_dir.join("Cargo.toml"))
+        .arg("--cargo-bins")
+        .arg("--release")
+        .arg("2")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(pkg.metadata.get_name()?, "test-from-cargo");
+    assert_eq!(pkg.metadata.get_version()?, "0.4.2");
+    assert_eq!(pkg.metadata.get_release()?, "2");
+    assert_eq!(pkg.metadata.get_summary()?, "A tool built with cargo");
+    assert_eq!(pkg.metadata.get_license()?, "MIT OR Apache-2.0");
+    assert_eq!(
+        pkg.metadata.get_url()?,
+        "https://example.com/test-from-cargo"
+    );
+    assert_ne!(pkg.metadata.get_arch()?, "noarch");
+    let files = pkg.metadata.get_file_entries()?;
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, PathBuf::from("/usr/bin/tfc"));
+    assert_eq!(files[0].mode.permissions(), 0o755);
+
+    // The command line takes precedence, and the current directory is used by default
+    Command::new(cargo_bin!())
+        .current_dir(&project_dir)
+        .arg("build")
+        .arg("--version")
+        .arg("9.9.9")
+        .arg("--from-cargo")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(pkg.metadata.get_name()?, "test-from-cargo");
+    assert_eq!(pkg.metadata.get_version()?, "9.9.9");
+    assert_eq!(pkg.metadata.get_arch()?, "noarch");
+    assert!(pkg.metadata.get_file_entries()?.is_empty());
+
+    fs::write(
+        project_dir.join("Cargo.toml"),
+        "[package]\nname = \"test\"\nversion.workspace = true\n",
+    )?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--from-cargo")
+        .arg(&project_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("inherited from the workspace"));
+
+    Ok(())
+}
+
+/// Test substituting defined and built-in variables in arguments and manifests
+#[test]
+fn test_define() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-define")?;
+    let manifest = tmp_dir.path().join("manifest.toml");
+    fs::write(
+        &manifest,
+        "name = \"test-define\"\nsummary = \"%{name} for ${arch}\"\nrequires = [\"test-define-libs = %{version}-%{release}\"]\n",
+    )?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--manifest")
+        .arg(&manifest)
+        .arg("--define")
+        .arg(format!("assets={}/tests/assets", workspace_path.display()))
+        .arg("--define")
+        .arg("ver=2.5.0")
+        .arg("--version")
+        .arg("${ver}")
+        .arg("--exec-file")
+        .arg("${assets}/multiplication_tables.py:/opt/%{name}-%{version}/bin/mt")
+        .arg("--description")
+        .arg("100% of ${undefined} stays %{undefined}")
+        .arg("-o")
+        .arg(tmp_dir.path().join("%{name}.rpm"))
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(tmp_dir.path().join("test-define.rpm"))?;
+    assert_eq!(pkg.metadata.get_version()?, "2.5.0");
+    assert_eq!(pkg.metadata.get_summary()?, "test-define for noarch");
+    assert_eq!(
+        pkg.metadata.get_description()?,
+        "100% of ${undefined} stays %{undefined}"
+    );
+    assert_eq!(
+        pkg.metadata.get_file_entries()?[0].path,
+        PathBuf::from("/opt/test-define-2.5.0/bin/mt")
+    );
+    let requires = pkg.metadata.get_requires()?;
+    let dependency = requires
+        .iter()
+        .find(|d| d.name == "test-define-libs")
+        .unwrap();
+    assert_eq!(dependency.version, "2.5.0-1");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-define")
+        .arg("--define")
+        .arg("not a define")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid define"));
+
+    Ok(())
+}
+
+/// Test substituting environment variables with --expand-env
+#[test]
+fn test_expand_env() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-expand-env")?;
+    let out_file = tmp_dir.path().join("test-expand-env.rpm");
+    let args = |cmd: &mut Command| {
+        cmd.arg("build")
+            .arg("test-expand-env")
+            .arg("--version")
+            .arg("$CI_VERSION")
+            .arg("--summary")
+            .arg("costs $$5, or $5")
+            .arg("--exec-file")
+            .arg(format!(
+                "{}/tests/assets/multiplication_tables.py:/opt/${{PREFIX}}/bin/mt",
+                workspace_path.display()
+            ))
+            .arg("-o")
+            .arg(&out_file)
+            .env("CI_VERSION", "4.5.6")
+            .env("PREFIX", "expand");
+    };
+
+    // without --expand-env, nothing is substituted, and '$' isn't allowed in a version
+    let mut cmd = Command::new(cargo_bin!());
+    args(&mut cmd);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid version \"$CI_VERSION\""));
+
+    let mut cmd = Command::new(cargo_bin!());
+    args(&mut cmd);
+    cmd.arg("--expand-env").assert().success();
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(pkg.metadata.get_version()?, "4.5.6");
+    assert_eq!(pkg.metadata.get_summary()?, "costs $5, or $5");
+    assert_eq!(
+        pkg.metadata.get_file_entries()?[0].path,
+        PathBuf::from("/opt/expand/bin/mt")
+    );
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-expand-env")
+        .arg("--vendor")
+        .arg("$RPM_BUILDER_UNSET_VARIABLE")
+        .arg("--expand-env")
+        .env_remove("RPM_BUILDER_UNSET_VARIABLE")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "environment variable RPM_BUILDER_UNSET_VARIABLE is not set",
+        ));
+
+    Ok(())
+}
+
+/// Test adding the contents of a tarball
+#[test]
+fn test_from_tar() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-from-tar")?;
+    let archive_path = tmp_dir.path().join("payload.tar.gz");
+    let out_file = tmp_dir.path().join("test-from-tar.rpm");
+
+    let encoder = flate2::write::GzEncoder::new(
+        fs::File::create(&archive_path)?,
+        flate2::Compression::default(),
+    );
+    let mut archive = tar::Builder::new(encoder);
+    let header = |entry_type: tar::EntryType, mode: u32, size: u64| {
+        let mut header = tar::Header::new_ustar();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_size(size);
+        header.set_username("nginx").unwrap();
+        header.set_groupname("web").unwrap();
+        header
+    };
+    archive.append_data(
+        &mut header(tar::EntryType::Directory, 0o750, 0),
+        "./bin/",
+        io::empty(),
+    )?;
+    archive.append_data(
+        &mut header(tar::EntryType::Regular, 0o755, 12),
+        "./bin/tool",
+        "#!/bin/sh\nid".as_bytes(),
+    )?;
+    archive.append_link(
+        &mut header(tar::EntryType::Symlink, 0o777, 0),
+        "./bin/tool-link",
+        "tool",
+    )?;
+    archive.append_link(
+        &mut header(tar::EntryType::Link, 0o755, 0),
+        "./bin/tool-copy",
+        "./bin/tool",
+    )?;
+    archive.into_inner()?.finish()?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-from-tar")
+        .arg("--from-tar")
+        .arg(format!("{}:/opt/payload", archive_path.display()))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    let entry = |path: &str| {
+        entries
+            .iter()
+            .find(|e| e.path == Path::new(path))
+            .unwrap_or_else(|| panic!("{} is not in the package", path))
+    };
+
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entry("/opt/payload/bin").mode, rpm::FileMode::dir(0o750));
+    let tool = entry("/opt/payload/bin/tool");
+    assert_eq!(tool.mode, rpm::FileMode::regular(0o755));
+    assert_eq!(tool.ownership.user, "nginx");
+    assert_eq!(tool.ownership.group, "web");
+    assert_eq!(tool.size, 12);
+    let link = entry("/opt/payload/bin/tool-link");
+    assert_eq!(link.mode, rpm::FileMode::symbolic_link(0o777));
+    assert_eq!(link.linkto, "tool");
+    assert_eq!(entry("/opt/payload/bin/tool-copy").size, 12);
+
+    // entries escaping from the archive are refused
+    let bad_archive = tmp_dir.path().join("bad.tar");
+    let mut archive = tar::Builder::new(fs::File::create(&bad_archive)?);
+    let mut bad_header = header(tar::EntryType::Regular, 0o644, 0);
+    bad_header.as_old_mut().name[..12].copy_from_slice(b"../etc/hosts");
+    bad_header.set_cksum();
+    archive.append(&bad_header, io::empty())?;
+    archive.into_inner()?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-from-tar")
+        .arg("--from-tar")
+        .arg(&bad_archive)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("points outside of the archive"));
+
+    Ok(())
+}
+
+/// Test adding the filesystem of a container image with `--from-oci-layer`
+#[test]
+fn test_from_oci_layer() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-from-oci-layer")?;
+    let image_dir = tmp_dir.path().join("image");
+    let blobs_dir = image_dir.join("blobs").join("sha256");
+    let out_file = tmp_dir.path().join("test-from-oci-layer.rpm");
+    fs::create_dir_all(&blobs_dir)?;
+
+    // the digests aren't verified, so any hex string does
+    let digest = |n: u8| format!("{:0>64}", n);
+    let layer = |entries: &[(&str, &str)]| -> Result<Vec<u8>, io::Error> {
+        let mut archive = tar::Builder::new(Vec::new());
+        for (path, content) in entries {
+            let mut header = tar::Header::new_ustar();
+            if path.ends_with('/') {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(0o755);
+            } else {
+                header.set_mode(0o644);
+            }
+            header.set_size(content.len() as u64);
+            archive.append_data(&mut header, path, content.as_bytes())?;
+        }
+        archive.into_inner()
+    };
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    io::Write::write_all(
+        &mut encoder,
+        &layer(&[
+            ("etc/", ""),
+            ("etc/keep.conf", "keep"),
+            ("etc/removed.conf", "removed"),
+            ("usr/", ""),
+            ("usr/share/", ""),
+            ("usr/share/old/", ""),
+            ("usr/share/old/file", "old"),
+        ])?,
+    )?;
+    fs::write(blobs_dir.join(digest(1)), encoder.finish()?)?;
+    fs::write(
+        blobs_dir.join(digest(2)),
+        layer(&[
+            ("etc/.wh.removed.conf", ""),
+            ("usr/share/.wh..wh..opq", ""),
+            ("usr/share/new", "new"),
+        ])?,
+    )?;
+    fs::write(
+        blobs_dir.join(digest(3)),
+        format!(
+            r#"{{"schemaVersion": 2, "layers": [{{"digest": "sha256:{}", "size": 0}}, {{"digest": "sha256:{}", "size": 0}}]}}"#,
+            digest(1),
+            digest(2)
+        ),
+    )?;
+    fs::write(
+        image_dir.join("index.json"),
+        format!(
+            r#"{{"schemaVersion": 2, "manifests": [{{"digest": "sha256:{}", "size": 0}}]}}"#,
+            digest(3)
+        ),
+    )?;
+    fs::write(
+        image_dir.join("oci-layout"),
+        r#"{"imageLayoutVersion": "1.0.0"}"#,
+    )?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-from-oci-layer")
+        .arg("--from-oci-layer")
+        .arg(format!("{}:/opt/appliance", image_dir.display()))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let paths: Vec<PathBuf> = pkg
+        .metadata
+        .get_file_entries()?
+        .into_iter()
+        .map(|e| e.path)
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            "/opt/appliance/etc",
+            "/opt/appliance/etc/keep.conf",
+            "/opt/appliance/usr",
+            "/opt/appliance/usr/share",
+            "/opt/appliance/usr/share/new",
+        ]
+        .map(PathBuf::from)
+    );
+
+    // a single layer is packaged without its whiteouts
+    let layer_file = blobs_dir.join(digest(2));
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-from-oci-layer")
+        .arg("--from-oci-layer")
+        .arg(&layer_file)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let paths: Vec<PathBuf> = pkg
+        .metadata
+        .get_file_entries()?
+        .into_iter()
+        .map(|e| e.path)
+        .collect();
+    assert_eq!(paths, [PathBuf::from("/usr/share/new")]);
+
+    // registry references aren't resolved
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-from-oci-layer")
+        .arg("--from-oci-layer")
+        .arg("docker.io/library/alpine")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Images can't be pulled from a registry",
+        ));
+
+    Ok(())
+}
+
+/// Test installing a systemd unit with `--systemd-unit`
+#[test]
+fn test_systemd_unit() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-systemd-unit")?;
+    let unit_file = tmp_dir.path().join("app.service");
+    let script_file = tmp_dir.path().join("post.sh");
+    let out_file = tmp_dir.path().join("test-systemd-unit.rpm");
+    fs::write(&unit_file, "[Service]\nExecStart=/usr/bin/app\n")?;
+    fs::write(&script_file, "echo installed")?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-systemd-unit")
+        .arg("--systemd-unit")
+        .arg(format!("{}:app.service", unit_file.display()))
+        .arg("--post-install-script")
+        .arg(&script_file)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].path,
+        PathBuf::from("/usr/lib/systemd/system/app.service")
+    );
+    assert_eq!(entries[0].mode, rpm::FileMode::regular(0o644));
+
+    let post_install = pkg.metadata.get_post_install_script()?.script;
+    assert!(post_install.starts_with("echo installed\n"));
+    assert!(post_install.contains("systemctl --no-reload preset app.service"));
+    let pre_uninstall = pkg.metadata.get_pre_uninstall_script()?.script;
+    assert!(pre_uninstall.contains("systemctl --no-reload disable --now app.service"));
+    let post_uninstall = pkg.metadata.get_post_uninstall_script()?.script;
+    assert!(post_uninstall.contains("systemctl try-restart app.service"));
+
+    let requires = pkg.metadata.get_requires()?;
+    for flag in [
+        rpm::DependencyFlags::SCRIPT_POST,
+        rpm::DependencyFlags::SCRIPT_PREUN,
+        rpm::DependencyFlags::SCRIPT_POSTUN,
+    ] {
+        assert!(
+            requires
+                .iter()
+                .any(|r| r.name == "systemd" && r.flags.contains(flag))
+        );
+    }
+
+    // the snippets are shell scripts
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-systemd-unit")
+        .arg("--systemd-unit")
+        .arg(format!("{}:app.service", unit_file.display()))
+        .arg("--post-install-interpreter")
+        .arg("/usr/bin/lua")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the generated shell snippets can't be added",
+        ));
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-systemd-unit")
+        .arg("--systemd-unit")
+        .arg(format!("{}:app", unit_file.display()))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid systemd unit name app"));
+
+    // the unit names are used in the scriptlets, escaped names keep their backslashes
+    for unit in ["app;reboot.service", "$(id).service", "-app.service"] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-systemd-unit")
+            .arg("--systemd-unit")
+            .arg(format!("{}:{}", unit_file.display(), unit))
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid systemd unit name"));
+    }
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-systemd-unit")
+        .arg("--systemd-unit")
+        .arg(format!("{}:app\\x2dweb@.service", unit_file.display()))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        pkg.metadata.get_file_entries()?[0].path,
+        PathBuf::from("/usr/lib/systemd/system/app\\x2dweb@.service")
+    );
+    let post_install = pkg.metadata.get_post_install_script()?.script;
+    assert!(post_install.contains("systemctl --no-reload preset app\\\\x2dweb@.service"));
+
+    Ok(())
+}
+
+/// Test creating a system user with `--sysuser`
+#[test]
+fn test_sysuser() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-sysuser")?;
+    let out_file = tmp_dir.path().join("test-sysuser.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-sysuser")
+        .arg("--sysuser")
+        .arg("app:uid=345,home=/var/lib/app")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let files: Vec<_> = pkg.files()?.collect::<Result<_, _>>()?;
+    assert_eq!(files.len(), 1);
+    assert_eq!(
+        files[0].metadata.path,
+        PathBuf::from("/usr/lib/sysusers.d/app.conf")
+    );
+    assert_eq!(files[0].content, b"u app 345 - /var/lib/app -\n");
+
+    let pre_install = pkg.metadata.get_pre_install_script()?.script;
+    assert!(pre_install.contains("systemd-sysusers --replace=/usr/lib/sysusers.d/app.conf -"));
+    assert!(
+        pre_install.contains("useradd -r -g app -d /var/lib/app -s /usr/sbin/nologin -u 345 app")
+    );
+
+    let provides: Vec<String> = pkg
+        .metadata
+        .get_provides()?
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+    assert!(provides.contains(&"user(app)".to_owned()));
+    assert!(provides.contains(&"group(app)".to_owned()));
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-sysuser")
+        .arg("--sysuser")
+        .arg("app:shell=nologin")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid shell nologin for user app",
+        ));
+
+    // the pre-install scriptlet runs the paths through the shell
+    for (attribute, message) in [
+        ("home=/var/lib/$(id)", "invalid home /var/lib/$(id)"),
+        ("shell=/bin/sh;reboot", "invalid shell /bin/sh;reboot"),
+        ("home=/var/lib/'app'", "invalid home /var/lib/'app'"),
+    ] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-sysuser")
+            .arg("--sysuser")
+            .arg(format!("app:{}", attribute))
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(message));
+    }
+
+    Ok(())
+}
+
+/// Test labeling paths with `--selinux-context` and installing a policy module with
+/// `--selinux-module`
+#[test]
+fn test_selinux() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-selinux")?;
+    let module_file = tmp_dir.path().join("myapp.pp");
+    let out_file = tmp_dir.path().join("test-selinux.rpm");
+    fs::write(&module_file, "module")?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-selinux")
+        .arg("--selinux-module")
+        .arg(&module_file)
+        .arg("--selinux-context")
+        .arg("/srv/my.app:system_u:object_r:myapp_data_t:s0")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].path,
+        PathBuf::from("/usr/share/selinux/packages/myapp.pp")
+    );
+
+    let post_install = pkg.metadata.get_post_install_script()?.script;
+    let module_install = post_install
+        .find("semodule -n -s targeted -X 200 -i '/usr/share/selinux/packages/myapp.pp'")
+        .unwrap();
+    let add_context = post_install
+        .find(r"semanage fcontext -a -t myapp_data_t -s system_u -r s0 '/srv/my\.app(/.*)?'")
+        .unwrap();
+    assert!(module_install < add_context);
+    assert!(post_install.contains("restorecon -R '/srv/my.app'"));
+
+    let post_uninstall = pkg.metadata.get_post_uninstall_script()?.script;
+    assert!(post_uninstall.contains("semodule -n -s targeted -X 200 -r myapp"));
+    assert!(post_uninstall.contains(r"semanage fcontext -d '/srv/my\.app(/.*)?'"));
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-selinux")
+        .arg("--selinux-context")
+        .arg("/srv/app:object_r:myapp_data_t")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid SELinux context"));
+
+    Ok(())
+}
+
+/// Test generating requirements from shebangs with `--auto-interpreter-requires`
+#[test]
+fn test_auto_interpreter_requires() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-auto-interpreter-requires")?;
+    let python_script = tmp_dir.path().join("tool.py");
+    let env_script = tmp_dir.path().join("tool.sh");
+    let data_file = tmp_dir.path().join("data.pl");
+    let out_file = tmp_dir.path().join("test-auto-interpreter-requires.rpm");
+    fs::write(&python_script, "#!/usr/bin/python3 -s\nprint('hi')\n")?;
+    fs::write(&env_script, "#!/usr/bin/env bash\necho hi\n")?;
+    fs::write(&data_file, "#!/usr/bin/perl\n")?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-auto-interpreter-requires")
+        .arg("--auto-interpreter-requires")
+        .arg("--exec-file")
+        .arg(format!("{}:/usr/bin/tool", python_script.display()))
+        .arg("--exec-file")
+        .arg(format!("{}:/usr/bin/tool-sh", env_script.display()))
+        // not executable, so not a script
+        .arg("--file")
+        .arg(format!("{}:/usr/share/tool/data.pl", data_file.display()))
+        .arg("--requires")
+        .arg("/usr/bin/env")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let requires: Vec<String> = pkg
+        .metadata
+        .get_requires()?
+        .into_iter()
+        .map(|r| r.name)
+        .filter(|name| !name.starts_with("rpmlib("))
+        .collect();
+    assert_eq!(requires, ["/usr/bin/env", "/usr/bin/python3"]);
+
+    Ok(())
+}
+
+/// Test generating Python dependencies with `--python-auto-deps`
+#[test]
+fn test_python_auto_deps() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-python-auto-deps")?;
+    let site_packages = tmp_dir.path().join("site-packages");
+    let dist_info = site_packages.join("My_Tool-1.2rc1.dist-info");
+    let egg_info = site_packages.join("legacy.egg-info");
+    let out_file = tmp_dir.path().join("test-python-auto-deps.rpm");
+    fs::create_dir_all(&dist_info)?;
+    fs::create_dir_all(&egg_info)?;
+    fs::write(
+        dist_info.join("METADATA"),
+        "Metadata-Version: 2.1\n\
+         Name: My_Tool\n\
+         Version: 1.2rc1\n\
+         Requires-Dist: requests (>=2.0,<3)\n\
+         Requires-Dist: Typing.Extensions~=4.1\n\
+         Requires-Dist: tomli; python_version < \"3.11\"\n\
+         Requires-Dist: colorama; sys_platform == \"win32\"\n\
+         Requires-Dist: pytest; extra == \"test\"\n\
+         \n\
+         Requires-Dist: not-a-header\n",
+    )?;
+    fs::write(
+        egg_info.join("PKG-INFO"),
+        "Metadata-Version: 1.0\nName: legacy\nVersion: 0.9.post1\n",
+    )?;
+    fs::write(
+        egg_info.join("requires.txt"),
+        "six\n\n[:python_version >= \"3.8\"]\nattrs\n\n[docs]\nsphinx\n",
+    )?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-python-auto-deps")
+        .arg("--python-auto-deps")
+        .arg("--dir")
+        .arg(format!(
+            "{}:/usr/lib/python3.12/site-packages",
+            site_packages.display()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let format = |dependencies: Vec<rpm::Dependency>| -> Vec<String> {
+        dependencies
+            .into_iter()
+            .filter(|d| {
+                !d.name.starts_with("rpmlib(") && !d.name.starts_with("test-python-auto-deps")
+            })
+            .map(|d| match d.version.as_str() {
+                "" => d.name,
+                version => format!("{} {}", d.name, version),
+            })
+            .collect()
+    };
+    let pkg = rpm::Package::open(&out_file)?;
+    let mut provides = format(pkg.metadata.get_provides()?);
+    provides.sort();
+    assert_eq!(
+        provides,
+        [
+            "python3.12dist(legacy) 0.9^post1",
+            "python3.12dist(my-tool) 1.2~rc1",
+            "python3dist(legacy) 0.9^post1",
+            "python3dist(my-tool) 1.2~rc1",
+        ]
+    );
+    let mut requires = format(pkg.metadata.get_requires()?);
+    requires.sort();
+    assert_eq!(
+        requires,
+        [
+            "python(abi) 3.12",
+            "python3dist(attrs)",
+            "python3dist(requests) 2.0",
+            "python3dist(requests) 3",
+            "python3dist(six)",
+            "python3dist(typing-extensions) 4.1",
+            "python3dist(typing-extensions) 5",
+        ]
+    );
+
+    Ok(())
+}
+
+/// Test the pkgconfig, perl and node generators of `--dep-generator`
+#[test]
+fn test_dep_generator() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-dep-generator")?;
+    let pc_file = tmp_dir.path().join("foo.pc");
+    let pm_file = tmp_dir.path().join("Bar.pm");
+    let script_file = tmp_dir.path().join("foo-tool");
+    let package_json = tmp_dir.path().join("package.json");
+    let bundled_json = tmp_dir.path().join("bundled.json");
+    let out_file = tmp_dir.path().join("test-dep-generator.rpm");
+    fs::write(
+        &pc_file,
+        "prefix=/usr\nversion=1.4.2\n\nName: foo\nVersion: ${version}\nRequires: glib-2.0 >= 2.50, zlib\nRequires.private: libffi\n",
+    )?;
+    fs::write(
+        &pm_file,
+        "package Foo::Bar;\nuse strict;\nuse File::Temp 0.23;\nuse Foo::Bar::Util;\nour $VERSION = '1.002_003';\n\n=pod\n\nuse Not::Required;\n\n=cut\n\npackage Foo::Bar::Util;\n1;\n__END__\nuse After::End;\n",
+    )?;
+    fs::write(&script_file, "#!/usr/bin/perl\nrequire Getopt::Long;\n")?;
+    fs::write(
+        &package_json,
+        r#"{"name": "foo", "version": "2.0.0-beta.1", "dependencies": {"left-pad": "^1.3.0", "tiny": "~0.2", "any": "*", "exact": "4.0.1", "git": "github:user/repo"}}"#,
+    )?;
+    fs::write(&bundled_json, r#"{"name": "inner", "version": "0.1.0"}"#)?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-dep-generator")
+        .args(["--dep-generator", "pkgconfig"])
+        .args(["--dep-generator", "perl"])
+        .args(["--dep-generator", "node"])
+        .arg("--file")
+        .arg(format!("{}:/usr/lib64/pkgconfig/foo.pc", pc_file.display()))
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/share/perl5/vendor_perl/Foo/Bar.pm",
+            pm_file.display()
+        ))
+        .arg("--exec-file")
+        .arg(format!("{}:/usr/bin/foo-tool", script_file.display()))
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/lib/node_modules/foo/package.json",
+            package_json.display()
+        ))
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/lib/node_modules/foo/node_modules/inner/package.json",
+            bundled_json.display()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let format = |dependencies: Vec<rpm::Dependency>| -> Vec<String> {
+        let mut dependencies: Vec<String> = dependencies
+            .into_iter()
+            .filter(|d| !d.name.starts_with("rpmlib(") && !d.name.starts_with("test-"))
+            .map(|d| match d.version.as_str() {
+                "" => d.name,
+                version => format!("{} {}", d.name, version),
+            })
+            .collect();
+        dependencies.sort();
+        dependencies
+    };
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        format(pkg.metadata.get_provides()?),
+        [
+            "bundled(nodejs-inner) 0.1.0",
+            "npm(foo) 2.0.0~beta.1",
+            "perl(Foo::Bar) 1.002003",
+            "perl(Foo::Bar::Util) 1.002003",
+            "pkgconfig(foo) 1.4.2",
+        ]
+    );
+    assert_eq!(
+        format(pkg.metadata.get_requires()?),
+        [
+            "/usr/bin/pkg-config",
+            "npm(any)",
+            "npm(exact) 4.0.1",
+            "npm(git)",
+            "npm(left-pad) 1.3.0",
+            "npm(left-pad) 2",
+            "npm(tiny) 0.2",
+            "npm(tiny) 0.3",
+            "perl(File::Temp) 0.23",
+            "perl(Getopt::Long)",
+            "perl(strict)",
+            "pkgconfig(glib-2.0) 2.50",
+            "pkgconfig(libffi)",
+            "pkgconfig(zlib)",
+        ]
+    );
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-dep-generator")
+        .args(["--dep-generator", "cobol"])
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unknown dependency generator cobol, the available ones are: interpreter, node, perl, pkgconfig, python",
+        ));
+
+    Ok(())
+}
+
+/// Test dropping dependencies with `--filter-requires` and `--filter-provides`
+#[test]
+fn test_filter_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-filter-dependencies")?;
+    let script_file = tmp_dir.path().join("tool");
+    let out_file = tmp_dir.path().join("test-filter-dependencies.rpm");
+    fs::write(&script_file, "#!/usr/bin/perl\n")?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-filter-dependencies")
+        .arg("--auto-interpreter-requires")
+        .arg("--exec-file")
+        .arg(format!("{}:/usr/bin/tool", script_file.display()))
+        .args(["--requires", "bash"])
+        .args(["--requires", "libbundled.so.1"])
+        .args(["--provides", "libbundled.so.1"])
+        .args(["--provides", "tool"])
+        .args(["--filter-requires", "^/usr/bin/perl$"])
+        .args(["--filter-requires", "^libbundled"])
+        .args(["--filter-provides", "^libbundled"])
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let names = |dependencies: Vec<rpm::Dependency>| -> Vec<String> {
+        dependencies
+            .into_iter()
+            .map(|d| d.name)
+            .filter(|name| !name.starts_with("rpmlib(") && !name.starts_with("test-"))
+            .collect()
+    };
+    assert_eq!(names(pkg.metadata.get_requires()?), ["bash"]);
+    assert_eq!(names(pkg.metadata.get_provides()?), ["tool"]);
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-filter-dependencies")
+        .args(["--filter-requires", "(unclosed"])
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid dependency filter (unclosed",
+        ));
+
+    Ok(())
+}
+
+/// Test logging what goes into a package with `-v`, `-vv` and `--log-format json`
+#[test]
+fn test_verbose_logging() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-verbose-logging")?;
+    let config_file = format!(
+        "{}/tests/assets/example_config.toml:/etc/awesome/config.toml",
+        workspace_path.to_string_lossy()
+    );
+
+    // nothing is logged by default
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-verbose-logging")
+        .args(["--config-file", &config_file])
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-verbose-logging")
+        .arg("-v")
+        .args(["--config-file", &config_file])
+        .args(["--requires", "bash >= 4"])
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("adding config file"))
+        .stderr(predicate::str::contains(
+            "dest=\"/etc/awesome/config.toml\"",
+        ))
+        .stderr(predicate::str::contains("dependency=\"bash >= 4\""))
+        .stderr(predicate::str::contains("packaged file").not());
+
+    let output = Command::new(cargo_bin!())
+        .arg("-vv")
+        .args(["--log-format", "json"])
+        .arg("build")
+        .arg("test-verbose-logging")
+        .args(["--config-file", &config_file])
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .output()?;
+    assert!(output.status.success());
+    let messages = String::from_utf8(output.stderr)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    let packaged = messages
+        .iter()
+        .find(|message| message["fields"]["message"] == "packaged file")
+        .expect("the packaged files are logged with -vv");
+    assert_eq!(packaged["level"], "DEBUG");
+    assert_eq!(packaged["fields"]["path"], "/etc/awesome/config.toml");
+    assert_eq!(packaged["fields"]["mode"], "100644");
+    assert_eq!(packaged["fields"]["flags"], "FileFlags(CONFIG)");
+
+    Ok(())
+}
+
+/// Test setting the build time and build host with `--build-time` and `--build-host`
+#[test]
+fn test_build_time_and_host() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-build-time-and-host")?;
+    let out_file = tmp_dir.path().join("test-build-time.rpm");
+
+    let build = |build_time: &str| {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-build-time")
+            .args(["--build-time", build_time])
+            .args(["--build-host", "builder.example.com"])
+            .arg("--file")
+            .arg(format!(
+                "{}/tests/assets/example_data.xml:/usr/share/test/example_data.xml",
+                workspace_path.to_string_lossy()
+            ))
+            .arg("-o")
+            .arg(&out_file)
+            // the build time takes precedence
+            .env("SOURCE_DATE_EPOCH", "1600000000")
+            .assert()
+    };
+
+    for build_time in [
+        "1700000000",
+        "2023-11-14T22:13:20Z",
+        "2023-11-15T00:13:20+02:00",
+        "2023-11-14T22:13:20",
+    ] {
+        build(build_time).success();
+        let pkg = rpm::Package::open(&out_file)?;
+        assert_eq!(pkg.metadata.get_build_time()?, 1700000000, "{}", build_time);
+        assert_eq!(pkg.metadata.get_build_host()?, "builder.example.com");
+        for entry in pkg.metadata.get_file_entries()? {
+            assert_eq!(entry.modified_at, rpm::Timestamp::from(1700000000));
+        }
+    }
+
+    build("2023-11-14").success();
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(pkg.metadata.get_build_time()?, 1699920000);
+
+    build("last tuesday")
+        .failure()
+        .stderr(predicate::str::contains("invalid build time"));
+    build("2999-01-01")
+        .failure()
+        .stderr(predicate::str::contains("is in the future"));
+
+    Ok(())
+}
+
+/// Test overriding the operating system and setting the platform
+#[test]
+fn test_os_and_platform() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-os-and-platform")?;
+    let out_file = tmp_dir.path().join("test-os-and-platform.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-os-and-platform")
+        .args(["--arch", "ppc64"])
+        .args(["--os", "aix"])
+        .args(["--platform", "ppc64-ibm-aix7.2"])
+        .arg("--file")
+        .arg(format!(
+            "{}/tests/assets/example_data.xml:/opt/test/example_data.xml",
+            workspace_path.to_string_lossy()
+        ))
+        .arg("--sign-with-pgp-asc")
+        .arg(workspace_path.join("tests/assets/package-manager.key"))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let header = &pkg.metadata.header;
+    assert_eq!(
+        header.get_entry_data_as_string(rpm::IndexTag::RPMTAG_OS)?,
+        "aix"
+    );
+    assert_eq!(
+        header.get_entry_data_as_string(rpm::IndexTag::RPMTAG_PLATFORM)?,
+        "ppc64-ibm-aix7.2"
+    );
+    assert_eq!(pkg.metadata.get_name()?, "test-os-and-platform");
+    assert_eq!(pkg.files()?.count(), 1);
+
+    // the package is signed after the header was changed
+    let raw_public_key = fs::read(workspace_path.join("tests/assets/package-manager.key.pub"))?;
+    let verifier = rpm::signature::pgp::Verifier::load_from_asc_bytes(&raw_public_key)?;
+    pkg.verify_signature(verifier)?;
+    pkg.verify_digests()?;
+
+    // without the options, rpm's defaults are kept
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-os-and-platform")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        pkg.metadata
+            .header
+            .get_entry_data_as_string(rpm::IndexTag::RPMTAG_OS)?,
+        "linux"
+    );
+    assert!(
+        !pkg.metadata
+            .header
+            .entry_is_present(rpm::IndexTag::RPMTAG_PLATFORM)
+    );
+
+    Ok(())
+}
+
+/// Test making a package relocatable with `--prefix`
+#[test]
+fn test_prefix() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-prefix")?;
+    let out_file = tmp_dir.path().join("test-prefix.rpm");
+    let data_file = format!(
+        "{}/tests/assets/example_data.xml",
+        workspace_path.to_string_lossy()
+    );
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-prefix")
+        .args(["--prefix", "/opt/test/"])
+        .args(["--prefix", "/etc/test"])
+        .args(["--file", &format!("{}:/opt/test/share/data.xml", data_file)])
+        .args([
+            "--config-file",
+            &format!("{}:/etc/test/data.xml", data_file),
+        ])
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        pkg.metadata
+            .header
+            .get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_PREFIXES)?,
+        ["/opt/test", "/etc/test"]
+    );
+    assert_eq!(pkg.metadata.get_file_entries()?.len(), 2);
+
+    // files outside of the prefixes couldn't be relocated
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-prefix")
+        .args(["--prefix", "/opt/test"])
+        .args(["--file", &format!("{}:/opt/testing/data.xml", data_file)])
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "/opt/testing/data.xml is not below any of the prefixes /opt/test",
+        ));
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-prefix")
+        .args(["--prefix", "opt"])
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("it needs to be an absolute path"));
+
+    Ok(())
+}
+
+/// Test marking a package as part of a module with `--modularity-label` and `--modulemd`
+#[test]
+fn test_modularity() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-modularity")?;
+    let modulemd_file = tmp_dir.path().join("module.yaml");
+    fs::write(
+        &modulemd_file,
+        r#"---
+document: modulemd
+version: 2
+data:
+  name: awesome
+  stream: "2.0"
+  version: 20240501120000
+  context: c0ffee42
+  summary: An awesome module
+  description: An awesome module
+  license:
+    module: [MIT]
+  artifacts:
+    rpms:
+      - awesome-libs-0:2.0.0-1.x86_64
+...
+"#,
+    )?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-modularity")
+        .args(["--version", "2.0.0"])
+        .args(["--modularity-label", "awesome:2.0:20240501120000:c0ffee42"])
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success();
+    let pkg = rpm::Package::open(tmp_dir.path().join("test-modularity-2.0.0-1.noarch.rpm"))?;
+    assert_eq!(
+        pkg.metadata
+            .header
+            .get_entry_data_as_string(rpm::IndexTag::RPMTAG_MODULARITYLABEL)?,
+        "awesome:2.0:20240501120000:c0ffee42"
+    );
+
+    // the label is taken from the module, which lists the package as an artifact
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-modularity")
+        .args(["--version", "2.0.0"])
+        .arg("--modulemd")
+        .arg(&modulemd_file)
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success();
+    let pkg = rpm::Package::open(tmp_dir.path().join("test-modularity-2.0.0-1.noarch.rpm"))?;
+    assert_eq!(
+        pkg.metadata
+            .header
+            .get_entry_data_as_string(rpm::IndexTag::RPMTAG_MODULARITYLABEL)?,
+        "awesome:2.0:20240501120000:c0ffee42"
+    );
+    let module: serde_yaml::Value = serde_yaml::from_str(&fs::read_to_string(
+        tmp_dir
+            .path()
+            .join("test-modularity-2.0.0-1.noarch.modulemd.yaml"),
+    )?)?;
+    assert_eq!(module["data"]["name"], "awesome");
+    assert_eq!(
+        module["data"]["artifacts"]["rpms"],
+        serde_yaml::from_str::<serde_yaml::Value>(
+            "[awesome-libs-0:2.0.0-1.x86_64, test-modularity-0:2.0.0-1.noarch]"
+        )?
+    );
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-modularity")
+        .args(["--modularity-label", "awesome:2.0"])
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid modularity label awesome:2.0",
+        ));
+
+    Ok(())
+}
+
+/// Test adding translated summaries and descriptions
+#[test]
+fn test_translations() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-translations")?;
+    let out_file = tmp_dir.path().join("test-translations.rpm");
+    let description_de = tmp_dir.path().join("description.de");
+    fs::write(&description_de, "Ein wirklich tolles Paket.\n")?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-translations")
+        .args(["--summary", "An awesome package"])
+        .args(["--description", "A really awesome package."])
+        .args(["--summary-lang", "de:Ein tolles Paket"])
+        .args(["--summary-lang", "pt_BR:Um pacote incrível"])
+        .arg("--description-lang")
+        .arg(format!("de:{}", description_de.display()))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let header = &pkg.metadata.header;
+    assert_eq!(
+        header.get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_HEADERI18NTABLE)?,
+        ["C", "de", "pt_BR"]
+    );
+    assert_eq!(pkg.metadata.get_summary()?, "An awesome package");
+    assert_eq!(pkg.metadata.get_description()?, "A really awesome package.");
+
+    // rpm-rs only reads the first text of a translated tag, so the texts are checked in the
+    // encoded header, which stores them one after another
+    let raw_package = fs::read(&out_file)?;
+    let contains = |texts: &[&str]| {
+        let encoded: Vec<u8> = texts
+            .iter()
+            .flat_map(|t| [t.as_bytes(), b"\0"].concat())
+            .collect();
+        raw_package
+            .windows(encoded.len())
+            .any(|window| window == encoded)
+    };
+    assert!(contains(&[
+        "An awesome package",
+        "Ein tolles Paket",
+        "Um pacote incrível"
+    ]));
+    // locales without a translated description get the untranslated one
+    assert!(contains(&[
+        "A really awesome package.",
+        "Ein wirklich tolles Paket.",
+        "A really awesome package."
+    ]));
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-translations")
+        .args(["--summary-lang", "Ein tolles Paket"])
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid translation Ein tolles Paket",
+        ));
+
+    Ok(())
+}
+
+/// Test the --signature-mode flag
+#[test]
+fn test_signature_mode() -> Result<(), Box<dyn std::error::Error>> {
+    use rpm::signature::Verifying;
+
+    let workspace_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-signature-mode")?;
+    let private_key_path = workspace_path.join("tests/assets/package-manager.key");
+    let public_key_path = workspace_path.join("tests/assets/package-manager.key.pub");
+    let raw_public_key = fs::read(public_key_path)?;
+    let verifier = rpm::signature::pgp::Verifier::load_from_asc_bytes(&raw_public_key)?;
+
+    let payload_signature = |pkg: &rpm::Package| {
+        [
+            rpm::IndexSignatureTag::RPMSIGTAG_PGP,
+            rpm::IndexSignatureTag::RPMSIGTAG_GPG,
+        ]
+        .into_iter()
+        .find_map(|tag| pkg.metadata.signature.get_entry_data_as_binary(tag).ok())
+        .map(<[u8]>::to_vec)
+    };
+    // the signed data is everything after the lead and the (padded) signature header
+    let verify_payload_signature = |path: &Path| -> Result<(), Box<dyn std::error::Error>> {
+        let pkg = rpm::Package::open(path)?;
+        pkg.verify_signature(&verifier)?;
+        let signature = payload_signature(&pkg).ok_or("no header and payload signature")?;
+        let bytes = fs::read(path)?;
+        let field = |position: usize| {
+            u32::from_be_bytes(bytes[position..position + 4].try_into().unwrap()) as usize
+        };
+        let signature_size = 16 + field(96 + 8) * 16 + field(96 + 12);
+        let header_start = 96 + signature_size.next_multiple_of(8);
+        verifier.verify(&bytes[header_start..], &signature)?;
+        Ok(())
+    };
+
+    // by default only the header is signed
+    let header_only = tmp_dir.path().join("header-only.rpm");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-signature-mode")
+        .arg("--sign-with-pgp-asc")
+        .arg(&private_key_path)
+        .arg("-o")
+        .arg(&header_only)
+        .assert()
+        .success();
+    let pkg = rpm::Package::open(&header_only)?;
+    pkg.verify_signature(&verifier)?;
+    assert_eq!(payload_signature(&pkg), None);
+
+    let header_and_payload = tmp_dir.path().join("header-and-payload.rpm");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-signature-mode")
+        .arg("--sign-with-pgp-asc")
+        .arg(&private_key_path)
+        .arg("--signature-mode")
+        .arg("header-and-payload")
+        .arg("-o")
+        .arg(&header_and_payload)
+        .assert()
+        .success();
+    verify_payload_signature(&header_and_payload)?;
+
+    // the sign subcommand adds it as well
+    Command::new(cargo_bin!())
+        .arg("sign")
+        .arg(&header_only)
+        .arg("--sign-with-pgp-asc")
+        .arg(&private_key_path)
+        .arg("--signature-mode")
+        .arg("header-and-payload")
+        .assert()
+        .success();
+    verify_payload_signature(&header_only)?;
+
+    Ok(())
+}
+
+/// Test signing with several keys
+#[test]
+fn test_multiple_signatures() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-multiple-signatures")?;
+    let out_file = tmp_dir
+        .path()
+        .join("test-multiple-signatures-1.0.0-1.noarch.rpm");
+    let vendor_key_path = workspace_path.join("tests/assets/package-manager.key");
+    let project_key_path = workspace_path.join("tests/assets/project.key");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-multiple-signatures")
+        .arg("--sign-with-pgp-asc")
+        .arg(&vendor_key_path)
+        .arg("--sign-with-pgp-asc")
+        .arg(&project_key_path)
+        .arg("-o")
+        .arg(&out_file)
+        .arg("-v")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "filled signature slot tag=RPMSIGTAG_OPENPGP count=2",
+        ));
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let signatures = pkg
+        .metadata
+        .signature
+        .get_entry_data_as_string_array(rpm::IndexSignatureTag::RPMSIGTAG_OPENPGP)?;
+    assert_eq!(signatures.len(), 2);
+
+    // each key verifies its own signature
+    for public_key in ["package-manager.key.pub", "project.key.pub"] {
+        Command::new(cargo_bin!())
+            .arg("verify")
+            .arg(&out_file)
+            .arg("--key")
+            .arg(workspace_path.join("tests/assets").join(public_key))
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(
+                "digests signatures OK (1 by other keys not checked)",
+            ));
+    }
+
+    // the sign subcommand also takes several keys, and replaces the existing signatures
+    Command::new(cargo_bin!())
+        .arg("sign")
+        .arg(&out_file)
+        .arg("--sign-with-pgp-asc")
+        .arg(&project_key_path)
+        .assert()
+        .success();
+    Command::new(cargo_bin!())
+        .arg("verify")
+        .arg(&out_file)
+        .arg("--key")
+        .arg(workspace_path.join("tests/assets/package-manager.key.pub"))
+        .assert()
+        .failure();
+    Command::new(cargo_bin!())
+        .arg("verify")
+        .arg(&out_file)
+        .arg("--key")
+        .arg(workspace_path.join("tests/assets/project.key.pub"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("digests signatures OK\n"));
+
+    Ok(())
+}
+
+/// Test signing with EdDSA and ECDSA keys
+#[test]
+fn test_eddsa_and_ecdsa_signatures() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-eddsa-and-ecdsa-signatures")?;
+
+    for (key, algorithm) in [("ed25519", "EdDSA"), ("ecdsa", "ECDSA")] {
+        let out_file = tmp_dir.path().join(format!("{}.rpm", key));
+        let private_key_path = workspace_path.join(format!("tests/assets/{}.key", key));
+        let public_key_path = workspace_path.join(format!("tests/assets/{}.key.pub", key));
+
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-eddsa-and-ecdsa-signatures")
+            .arg("--sign-with-pgp-asc")
+            .arg(&private_key_path)
+            .arg("--signature-mode")
+            .arg("header-and-payload")
+            .arg("-o")
+            .arg(&out_file)
+            .arg("-v")
+            .assert()
+            .success()
+            .stderr(predicate::str::contains(format!(
+                "signing package key={} algorithm={}",
+                private_key_path.display(),
+                algorithm
+            )));
+
+        // the legacy signatures go in the DSA slots rather than the RSA ones
+        let pkg = rpm::Package::open(&out_file)?;
+        let signature = &pkg.metadata.signature;
+        for tag in [
+            rpm::IndexSignatureTag::RPMSIGTAG_OPENPGP,
+            rpm::IndexSignatureTag::RPMSIGTAG_DSA,
+            rpm::IndexSignatureTag::RPMSIGTAG_GPG,
+        ] {
+            assert!(signature.entry_is_present(tag), "{} is missing", tag);
+        }
+        for tag in [
+            rpm::IndexSignatureTag::RPMSIGTAG_RSA,
+            rpm::IndexSignatureTag::RPMSIGTAG_PGP,
+        ] {
+            assert!(!signature.entry_is_present(tag), "{} is present", tag);
+        }
+
+        Command::new(cargo_bin!())
+            .arg("verify")
+            .arg(&out_file)
+            .arg("--key")
+            .arg(&public_key_path)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("digests signatures OK"));
+    }
+
+    Ok(())
+}
+
+/// Test the --post-build-exec flag
+#[test]
+fn test_post_build_exec() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-post-build-exec")?;
+    // the path is quoted when it's substituted
+    let out_file = tmp_dir.path().join("it's a package.rpm");
+    let copy = tmp_dir.path().join("copy.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-post-build-exec")
+        .arg("-o")
+        .arg(&out_file)
+        .arg("--post-build-exec")
+        .arg(format!("cp {{}} {}", copy.display()))
+        .arg("--post-build-exec")
+        .arg("echo hook ran")
+        .arg("--print-output-path")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("hook ran"))
+        .stdout(predicate::str::contains("hook ran").not());
+    assert_eq!(fs::read(&out_file)?, fs::read(&copy)?);
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-post-build-exec")
+        .arg("-o")
+        .arg(&out_file)
+        .arg("--post-build-exec")
+        .arg("test ! -f {}")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("post-build hook"))
+        .stderr(predicate::str::contains("failed"));
+
+    // hooks don't run for dry runs
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-post-build-exec")
+        .arg("--dry-run")
+        .arg("--post-build-exec")
+        .arg("false")
+        .assert()
+        .success();
+
+    Ok(())
+}
+
+/// Test the repo subcommand
+#[test]
+fn test_repo() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Read;
+
+    let tmp_dir = TempDir::new("rpm-builder-test-repo")?;
+    let repo_dir = tmp_dir.path();
+    fs::create_dir(repo_dir.join("noarch"))?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-repo")
+        .arg("--requires")
+        .arg("bash >= 1:5.0-1")
+        .arg("--exec-file")
+        .arg("./tests/assets/preinst.sh:/usr/bin/test-repo")
+        .arg("-o")
+        .arg(repo_dir.join("noarch"))
+        .assert()
+        .success();
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-repo-extra")
+        .arg("--doc-file")
+        .arg("./tests/assets/preinst.sh:/usr/share/doc/test-repo-extra/README")
+        .arg("-o")
+        .arg(repo_dir)
+        .assert()
+        .success();
+
+    // the metadata files are listed in repomd.xml along with their checksums
+    let read_metadata = |kind: &str| -> Result<String, Box<dyn std::error::Error>> {
+        let repomd = fs::read_to_string(repo_dir.join("repodata/repomd.xml"))?;
+        let record = repomd
+            .split(r#"<data type=""#)
+            .find(|record| record.starts_with(&format!(r#"{}">"#, kind)))
+            .ok_or("no record for the metadata")?;
+        let location = record
+            .split(r#"<location href=""#)
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .ok_or("no location for the metadata")?;
+        let compressed = fs::read(repo_dir.join(location))?;
+        let checksum = format!("{:x}", <sha2::Sha256 as sha2::Digest>::digest(&compressed));
+        assert!(record.contains(&format!(r#"<checksum type="sha256">{}<"#, checksum)));
+        let mut content = String::new();
+        if location.ends_with(".gz") {
+            flate2::read::GzDecoder::new(compressed.as_slice()).read_to_string(&mut content)?;
+        } else {
+            zstd::Decoder::new(compressed.as_slice())?.read_to_string(&mut content)?;
+        }
+        Ok(content)
+    };
+
+    Command::new(cargo_bin!())
+        .arg("repo")
+        .arg(repo_dir)
+        .assert()
+        .success();
+    let primary = read_metadata("primary")?;
+    assert!(primary.contains(r#"packages="2""#));
+    assert!(primary.contains("<location href=\"noarch/test-repo-1.0.0-1.noarch.rpm\"/>"));
+    assert!(primary.contains("<location href=\"test-repo-extra-1.0.0-1.noarch.rpm\"/>"));
+    assert!(primary.contains(r#"<rpm:entry name="bash" flags="GE" epoch="1" ver="5.0" rel="1"/>"#));
+    let package_checksum = format!(
+        "{:x}",
+        <sha2::Sha256 as sha2::Digest>::digest(fs::read(
+            repo_dir.join("noarch/test-repo-1.0.0-1.noarch.rpm")
+        )?)
+    );
+    assert!(primary.contains(&format!(
+        r#"<checksum type="sha256" pkgid="YES">{}</checksum>"#,
+        package_checksum
+    )));
+    // only the commonly required files go in primary.xml, the rest are in filelists.xml
+    assert!(primary.contains("<file>/usr/bin/test-repo</file>"));
+    assert!(!primary.contains("<file>/usr/share/doc/test-repo-extra/README</file>"));
+    let filelists = read_metadata("filelists")?;
+    assert!(filelists.contains("<file>/usr/share/doc/test-repo-extra/README</file>"));
+    assert!(read_metadata("other")?.contains(r#"name="test-repo-extra""#));
+
+    // regenerating replaces the old metadata
+    Command::new(cargo_bin!())
+        .arg("repo")
+        .arg(repo_dir)
+        .arg("--compression")
+        .arg("zstd")
+        .assert()
+        .success();
+    let metadata_files: Vec<String> = fs::read_dir(repo_dir.join("repodata"))?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+        .collect::<Result<_, io::Error>>()?;
+    assert_eq!(metadata_files.len(), 4);
+    assert!(
+        metadata_files
+            .iter()
+            .all(|name| name == "repomd.xml" || name.ends_with(".xml.zst"))
+    );
+    assert!(read_metadata("primary")?.contains("<name>test-repo</name>"));
+
+    Ok(())
+}
+
+/// A request received by [`serve_http`]
+struct HttpRequest {
+    method: String,
+    /// The path, with the query
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+type HttpRequests = std::sync::Arc<std::sync::Mutex<Vec<HttpRequest>>>;
+
+/// Serve HTTP on a local port, answering every request with the status and body returned by the
+/// handler, which also gets the requests received before. Returns the base URL of the server and
+/// the requests it received.
+fn serve_http(
+    handler: impl Fn(&HttpRequest, &[HttpRequest]) -> (u16, Vec<u8>) + Send + 'static,
+) -> io::Result<(String, HttpRequests)> {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let address = format!("http://{}", listener.local_addr()?);
+    let requests = HttpRequests::default();
+    let received = requests.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap_or_default().to_owned();
+            let path = parts.next().unwrap_or_default().to_owned();
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                match line.trim_end().split_once(": ") {
+                    Some((name, value)) => headers.push((name.to_owned(), value.to_owned())),
+                    None => break,
+                }
+            }
+            let request = HttpRequest {
+                method,
+                path,
+                body: Vec::new(),
+                headers,
+            };
+            let length = request
+                .header("content-length")
+                .map_or(0, |l| l.parse().unwrap());
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let request = HttpRequest { body, ..request };
+
+            let mut requests = received.lock().unwrap();
+            let (status, response) = handler(&request, &requests);
+            write!(
+                stream,
+                "HTTP/1.1 {} Status\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                response.len()
+            )
+            .unwrap();
+            stream.write_all(&response).unwrap();
+            requests.push(request);
+        }
+    });
+    Ok((address, requests))
+}
+
+/// Test the --publish flag with an HTTP server
+#[test]
+fn test_publish_http() -> Result<(), Box<dyn std::error::Error>> {
+    // stores what is PUT, and serves it (with `suffix` appended) to GET requests
+    let serve = |suffix: &'static [u8]| {
+        serve_http(move |request, received| match request.method.as_str() {
+            "GET" => {
+                let put = received.iter().rev().find(|r| r.method == "PUT").unwrap();
+                (200, [put.body.as_slice(), suffix].concat())
+            }
+            _ => (201, Vec::new()),
+        })
+    };
+
+    let tmp_dir = TempDir::new("rpm-builder-test-publish-http")?;
+    let out_file = tmp_dir.path().join("test-publish-1.0.0-1.noarch.rpm");
+
+    let (address, requests) = serve(b"")?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-publish")
+        .arg("-o")
+        .arg(&out_file)
+        .arg("--publish")
+        .arg(address.replace("http://", "http://ci:secret@") + "/repo/")
+        .arg("--publish-verify")
+        .arg("-v")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("verified published package"))
+        .stderr(predicate::str::contains("secret").not());
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method, "PUT");
+    assert_eq!(requests[0].path, "/repo/test-publish-1.0.0-1.noarch.rpm");
+    // "ci:secret" in base64
+    assert_eq!(
+        requests[0].header("authorization"),
+        Some("Basic Y2k6c2VjcmV0")
+    );
+    assert_eq!(requests[0].body, fs::read(&out_file)?);
+    assert_eq!(requests[1].method, "GET");
+
+    // a package which doesn't come back the same fails the build
+    let (address, _) = serve(b"corrupted")?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-publish")
+        .arg("-o")
+        .arg(&out_file)
+        .arg("--publish")
+        .arg(format!("{}/repo/test-publish.rpm", address))
+        .arg("--publish-verify")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "the package published to {}/repo/test-publish.rpm has the checksum",
+            address
+        )));
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-publish")
+        .arg("-o")
+        .arg(&out_file)
+        .arg("--publish")
+        .arg("ftp://example.com/repo/")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unsupported publish URL"));
+
+    Ok(())
+}
+
+/// Test the publish subcommand with each of the publishers
+#[test]
+fn test_publish_subcommand() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-publish-subcommand")?;
+    let out_file = tmp_dir.path().join("test-publish-1.0.0-1.noarch.rpm");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-publish")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    let content = fs::read(&out_file)?;
+
+    // nexus, with a user and the password from the environment
+    let (address, requests) = serve_http(|_, _| (201, Vec::new()))?;
+    Command::new(cargo_bin!())
+        .arg("publish")
+        .arg(&out_file)
+        .arg("--to")
+        .arg("nexus")
+        .arg("--url")
+        .arg(format!("{}/", address))
+        .arg("--repository")
+        .arg("rpms")
+        .arg("--directory")
+        .arg("el9/")
+        .arg("--user")
+        .arg("ci")
+        .env("RPM_BUILDER_PUBLISH_PASSWORD", "secret")
+        .assert()
+        .success();
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "PUT");
+    assert_eq!(
+        requests[0].path,
+        "/repository/rpms/el9/test-publish-1.0.0-1.noarch.rpm"
+    );
+    assert_eq!(
+        requests[0].header("authorization"),
+        Some("Basic Y2k6c2VjcmV0")
+    );
+    assert_eq!(requests[0].body, content);
+
+    // artifactory, with a token and the checksum of the package
+    let (address, requests) = serve_http(|_, _| (201, Vec::new()))?;
+    Command::new(cargo_bin!())
+        .arg("publish")
+        .arg(&out_file)
+        .arg("--to")
+        .arg("artifactory")
+        .arg("--url")
+        .arg(format!("{}/artifactory", address))
+        .arg("--repository")
+        .arg("rpm-local")
+        .arg("--token")
+        .arg("abc123")
+        .assert()
+        .success();
+    let requests = requests.lock().unwrap();
+    assert_eq!(
+        requests[0].path,
+        "/artifactory/rpm-local/test-publish-1.0.0-1.noarch.rpm"
+    );
+    assert_eq!(requests[0].header("authorization"), Some("Bearer abc123"));
+    assert_eq!(
+        requests[0].header("x-checksum-sha256"),
+        Some(format!("{:x}", <sha2::Sha256 as sha2::Digest>::digest(&content)).as_str())
+    );
+
+    // pulp, which looks up the repository and waits for the upload task
+    let serve_pulp = |task_state: &'static str| {
+        serve_http(move |request, _| {
+            let body = match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/pulp/api/v3/repositories/rpm/rpm/?name=rpms") => {
+                    r#"{"results": [{"pulp_href": "/pulp/api/v3/repositories/rpm/rpm/1234/"}]}"#
+                        .to_owned()
+                }
+                ("POST", "/pulp/api/v3/content/rpm/packages/upload/") => {
+                    r#"{"task": "/pulp/api/v3/tasks/5678/"}"#.to_owned()
+                }
+                ("GET", "/pulp/api/v3/tasks/5678/") => format!(
+                    r#"{{"state": "{}", "error": {{"description": "invalid package"}}}}"#,
+                    task_state
+                ),
+                _ => return (404, Vec::new()),
+            };
+            (
+                if request.method == "POST" { 202 } else { 200 },
+                body.into_bytes(),
+            )
+        })
+    };
+    let (address, requests) = serve_pulp("completed")?;
+    Command::new(cargo_bin!())
+        .arg("publish")
+        .arg(&out_file)
+        .arg("--to")
+        .arg("pulp")
+        .arg("--url")
+        .arg(&address)
+        .arg("--repository")
+        .arg("rpms")
+        .assert()
+        .success();
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 3);
+    let upload = &requests[1];
+    let body = String::from_utf8_lossy(&upload.body);
+    assert!(
+        upload
+            .header("content-type")
+            .is_some_and(|t| t.starts_with("multipart/form-data; boundary="))
+    );
+    assert!(
+        body.contains("name=\"repository\"\r\n\r\n/pulp/api/v3/repositories/rpm/rpm/1234/\r\n")
+    );
+    assert!(body.contains("filename=\"test-publish-1.0.0-1.noarch.rpm\""));
+
+    let (address, _) = serve_pulp("failed")?;
+    Command::new(cargo_bin!())
+        .arg("publish")
+        .arg(&out_file)
+        .arg("--to")
+        .arg("pulp")
+        .arg("--url")
+        .arg(&address)
+        .arg("--repository")
+        .arg("rpms")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("failed: invalid package"));
+
+    Command::new(cargo_bin!())
+        .arg("publish")
+        .arg(&out_file)
+        .arg("--to")
+        .arg("quay")
+        .arg("--url")
+        .arg("https://example.com")
+        .arg("--repository")
+        .arg("rpms")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unknown publisher quay, the available ones are: artifactory, nexus, pulp",
+        ));
+
+    Ok(())
+}
+
+/// Test building the packages of a spec file from a buildroot
+#[test]
+fn test_from_spec() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-from-spec")?;
+    let buildroot = tmp_dir.path().join("buildroot");
+    fs::create_dir_all(buildroot.join("usr/bin"))?;
+    fs::create_dir_all(buildroot.join("usr/lib64"))?;
+    fs::create_dir_all(buildroot.join("usr/include"))?;
+    fs::create_dir_all(buildroot.join("usr/share/test-spec/data"))?;
+    fs::create_dir_all(buildroot.join("etc"))?;
+    fs::create_dir_all(buildroot.join("var/lib/test-spec"))?;
+    fs::write(
+        buildroot.join("usr/bin/test-spec"),
+        "#!/bin/sh\necho hello\n",
+    )?;
+    fs::set_permissions(
+        buildroot.join("usr/bin/test-spec"),
+        std::os::unix::fs::PermissionsExt::from_mode(0o755),
+    )?;
+    fs::write(buildroot.join("usr/lib64/libtest-spec.so.1"), "library")?;
+    std::os::unix::fs::symlink(
+        "libtest-spec.so.1",
+        buildroot.join("usr/lib64/libtest-spec.so"),
+    )?;
+    fs::write(buildroot.join("usr/share/test-spec/data/a.txt"), "a")?;
+    fs::write(buildroot.join("etc/test-spec.conf"), "debug = false\n")?;
+    fs::write(
+        buildroot.join("usr/include/test-spec.h"),
+        "int test(void);\n",
+    )?;
+    fs::write(tmp_dir.path().join("LICENSE"), "MIT License")?;
+    fs::write(tmp_dir.path().join("README.md"), "# test-spec")?;
+
+    let spec = tmp_dir.path().join("test-spec.spec");
+    fs::write(
+        &spec,
+        r#"%global upstream_version 2.1.0
+
+Name:           test-spec
+Version:        %{upstream_version}
+Release:        3%{?dist}
+Summary:        A package from a spec file
+License:        MIT
+URL:            https://example.com/%{name}
+Group:          Applications/System
+Packager:       Jane Doe <jane@example.com>
+Distribution:   Example Linux 1
+BuildArch:      x86_64
+Source0:        %{url}/archive/v%{version}.tar.gz
+BuildRequires:  gcc
+Requires:       bash >= 5.0, coreutils
+Requires(post): /sbin/ldconfig
+Recommends:     (less or more)
+
+%description
+A package which was built
+with rpmbuild before.
+
+%package devel
+Summary:        The headers of %{name}
+Requires:       %{name} = %{version}-%{release}
+
+%description devel
+The headers.
+
+%prep
+%autosetup -n %{name}-%{version}
+
+%build
+%cmake
+%cmake_build
+
+%install
+%cmake_install
+
+%pre
+getent group test-spec >/dev/null || groupadd -r test-spec
+
+%post -p /sbin/ldconfig
+
+%files
+%license LICENSE
+%doc README.md
+%{_bindir}/test-spec
+%{_libdir}/libtest-spec.so*
+%config(noreplace) %{_sysconfdir}/test-spec.conf
+%dir %attr(0750, root, test-spec) %{_sharedstatedir}/test-spec
+%{_datadir}/test-spec
+
+%files devel
+%{_includedir}/*.h
+
+%changelog
+* Tue Jan 02 2024 Jane Doe <jane@example.com> - 2.1.0-3
+- Build with rpm-builder
+"#,
+    )?;
+
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("--from-spec")
+        .arg(&spec)
+        .arg("--buildroot")
+        .arg(&buildroot)
+        .arg("--url")
+        .arg("https://example.org")
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(tmp_dir.path().join("test-spec-2.1.0-3.x86_64.rpm"))?;
+    assert_eq!(pkg.metadata.get_summary()?, "A package from a spec file");
+    assert_eq!(
+        pkg.metadata.get_description()?,
+        "A package which was built\nwith rpmbuild before."
+    );
+    assert_eq!(pkg.metadata.get_license()?, "MIT");
+    // the command line takes precedence over the spec file
+    assert_eq!(pkg.metadata.get_url()?, "https://example.org");
+    assert_eq!(pkg.metadata.get_group()?, "Applications/System");
+    assert_eq!(pkg.metadata.get_packager()?, "Jane Doe <jane@example.com>");
+    assert_eq!(
+        pkg.metadata
+            .header
+            .get_entry_data_as_string(rpm::IndexTag::RPMTAG_DISTRIBUTION)?,
+        "Example Linux 1"
+    );
+    let requires: Vec<String> = pkg
+        .metadata
+        .get_requires()?
+        .into_iter()
+        .filter(|r| !r.name.starts_with("rpmlib("))
+        .map(|r| format!("{} {}", r.name, r.version).trim().to_owned())
+        .collect();
+    assert_eq!(requires, vec!["bash 5.0", "coreutils", "/sbin/ldconfig"]);
+    assert!(
+        pkg.metadata
+            .get_requires()?
+            .iter()
+            .any(|r| r.name == "/sbin/ldconfig"
+                && r.flags.contains(rpm::DependencyFlags::SCRIPT_POST))
+    );
+    assert_eq!(pkg.metadata.get_recommends()?[0].name, "(less or more)");
+
+    let files: Vec<(String, rpm::FileMode, rpm::FileFlags, String)> = pkg
+        .metadata
+        .get_file_entries()?
+        .into_iter()
+        .map(|f| {
+            (
+                f.path.display().to_string(),
+                f.mode,
+                f.flags,
+                f.ownership.group,
+            )
+        })
+        .collect();
+    let expected = [
+        (
+            "/etc/test-spec.conf",
+            rpm::FileMode::regular(0o644),
+            rpm::FileFlags::CONFIG | rpm::FileFlags::NOREPLACE,
+        ),
+        (
+            "/usr/bin/test-spec",
+            rpm::FileMode::regular(0o755),
+            rpm::FileFlags::empty(),
+        ),
+        (
+            "/usr/lib64/libtest-spec.so",
+            rpm::FileMode::symbolic_link(0o777),
+            rpm::FileFlags::empty(),
+        ),
+        (
+            "/usr/lib64/libtest-spec.so.1",
+            rpm::FileMode::regular(0o644),
+            rpm::FileFlags::empty(),
+        ),
+        (
+            "/usr/share/doc/test-spec/README.md",
+            rpm::FileMode::regular(0o644),
+            rpm::FileFlags::DOC,
+        ),
+        (
+            "/usr/share/licenses/test-spec/LICENSE",
+            rpm::FileMode::regular(0o644),
+            rpm::FileFlags::LICENSE,
+        ),
+        (
+            "/usr/share/test-spec",
+            rpm::FileMode::dir(0o755),
+            rpm::FileFlags::empty(),
+        ),
+        (
+            "/usr/share/test-spec/data/a.txt",
+            rpm::FileMode::regular(0o644),
+            rpm::FileFlags::empty(),
+        ),
+        (
+            "/var/lib/test-spec",
+            rpm::FileMode::dir(0o750),
+            rpm::FileFlags::empty(),
+        ),
+    ];
+    assert_eq!(
+        files
+            .iter()
+            .map(|(path, mode, flags, _)| (path.as_str(), *mode, *flags))
+            .collect::<Vec<_>>(),
+        expected
+    );
+    assert_eq!(files[8].3, "test-spec");
+
+    let pre_install = pkg.metadata.get_pre_install_script()?;
+    assert_eq!(
+        pre_install.script,
+        "getent group test-spec >/dev/null || groupadd -r test-spec"
+    );
+    let post_install = pkg.metadata.get_post_install_script()?;
+    assert_eq!(post_install.script, "");
+    assert_eq!(
+        post_install.program,
+        Some(vec!["/sbin/ldconfig".to_owned()])
+    );
+    let changelog = pkg.metadata.get_changelog_entries()?;
+    assert_eq!(changelog.len(), 1);
+    assert_eq!(changelog[0].description, "- Build with rpm-builder");
+
+    // the subpackage shares the version, release and changelog, but not the scriptlets
+    let devel = rpm::Package::open(tmp_dir.path().join("test-spec-devel-2.1.0-3.x86_64.rpm"))?;
+    assert_eq!(devel.metadata.get_summary()?, "The headers of test-spec");
+    assert_eq!(devel.metadata.get_description()?, "The headers.");
+    let files = devel.metadata.get_file_entries()?;
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, PathBuf::from("/usr/include/test-spec.h"));
+    assert!(
+        devel
+            .metadata
+            .get_requires()?
+            .iter()
+            .any(|r| r.name == "test-spec" && r.version == "2.1.0-3")
+    );
+    assert_eq!(devel.metadata.get_changelog_entries()?.len(), 1);
+    assert!(devel.metadata.get_pre_install_script().is_err());
+
+    // files can only be found in a buildroot, and conditionals aren't supported
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("--from-spec")
+        .arg(&spec)
+        .arg("--dry-run")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--buildroot"));
+    fs::write(
+        &spec,
+        "Name: test-spec\n%if 0%{?fedora}\nRequires: dnf\n%endif\n",
+    )?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--from-spec")
+        .arg(&spec)
+        .arg("--dry-run")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "conditionals (%if) aren't supported",
+        ));
+
+    Ok(())
+}
+
+/// Test writing a spec file describing the packages, which can be built again with --from-spec
+#[test]
+fn test_emit_spec() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let assets = workspace_path.join("tests/assets");
+    let tmp_dir = TempDir::new("rpm-builder-test-emit-spec")?;
+    let manifest = tmp_dir.path().join("manifest.toml");
+    fs::write(
+        &manifest,
+        format!(
+            r#"
+name = "test-emit-spec"
+version = "1.2.0"
+release = "2"
+summary = "100% awesome"
+description = "A package\nwith a description"
+license = "MIT"
+url = "https://example.com"
+exec_file = ["{assets}/multiplication_tables.py:/usr/bin/multiplication_tables"]
+file = [
+    "{assets}/example_data.xml:/usr/share/test-emit-spec/my data.xml:verify=not(md5,size,mtime),mode=0644",
+    "{assets}/example_config.toml:/var/log/test-emit-spec.log:flags=ghost",
+]
+config_file = ["{assets}/example_config.toml:/etc/test-emit-spec/config.toml:noreplace,mode=0640,group=test"]
+doc_file = ["{assets}/example_data.xml:/usr/share/doc/test-emit-spec/data.xml:mode=0644"]
+create_dir = ["/var/lib/test-emit-spec:mode=0750"]
+symlink = ["/usr/bin/mt:multiplication_tables"]
+requires = ["wget >= 1.0", "bash"]
+provides = ["tables"]
+post_install_interpreter = "/sbin/ldconfig"
+changelog = ["Jane Doe <jane@example.com>:- first release:2024-01-02"]
+
+[[subpackage]]
+name = "test-emit-spec-devel"
+summary = "The headers"
+file = ["{assets}/example_config.toml:/usr/include/test-emit-spec.h"]
+requires = ["test-emit-spec = 1.2.0-2"]
+"#,
+            assets = assets.display()
+        ),
+    )?;
+
+    let built = tmp_dir.path().join("built");
+    fs::create_dir(&built)?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--manifest")
+        .arg(&manifest)
+        .arg("--arch")
+        .arg("x86_64")
+        .arg("--emit-spec")
+        .arg(tmp_dir.path())
+        .arg("-o")
+        .arg(&built)
+        .assert()
+        .success();
+
+    let spec = tmp_dir.path().join("test-emit-spec.spec");
+    let content = fs::read_to_string(&spec)?;
+    for expected in [
+        "Name:           test-emit-spec\n",
+        "Summary:        100%% awesome\n",
+        "BuildArch:      x86_64\n",
+        "Requires:       wget >= 1.0\n",
+        "Provides:       tables\n",
+        "%package -n test-emit-spec-devel\nSummary:        The headers\nRequires:       test-emit-spec = 1.2.0-2\n",
+        "%description\nA package\nwith a description\n",
+        "%post -p /sbin/ldconfig\n",
+        "%attr(0640, root, test) %config(noreplace) /etc/test-emit-spec/config.toml\n",
+        "%attr(0750, root, root) %dir /var/lib/test-emit-spec\n",
+        "%attr(0644, root, root) %verify(not md5 size mtime) \"/usr/share/test-emit-spec/my data.xml\"\n",
+        "%attr(0644, root, root) %ghost /var/log/test-emit-spec.log\n",
+        "%files -n test-emit-spec-devel\n%attr(0644, root, root) /usr/include/test-emit-spec.h\n",
+        "%changelog\n* Tue Jan 02 2024 Jane Doe <jane@example.com>\n- first release\n",
+    ] {
+        assert!(
+            content.contains(expected),
+            "{:?} not in:\n{}",
+            expected,
+            content
+        );
+    }
+    // the dependencies rpm adds by itself aren't listed
+    assert!(!content.contains("rpmlib("));
+    assert!(!content.contains("Provides:       test-emit-spec"));
+
+    // building the spec file from the contents of the packages gives the same packages
+    let buildroot = tmp_dir.path().join("buildroot");
+    Command::new(cargo_bin!())
+        .arg("extract")
+        .arg(built.join("test-emit-spec-1.2.0-2.x86_64.rpm"))
+        .arg("--into")
+        .arg(&buildroot)
+        .assert()
+        .success();
+    fs::create_dir_all(buildroot.join("usr/include"))?;
+    fs::copy(
+        assets.join("example_config.toml"),
+        buildroot.join("usr/include/test-emit-spec.h"),
+    )?;
+    let rebuilt = tmp_dir.path().join("rebuilt");
+    fs::create_dir(&rebuilt)?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--from-spec")
+        .arg(&spec)
+        .arg("--buildroot")
+        .arg(&buildroot)
+        .arg("-o")
+        .arg(&rebuilt)
+        .assert()
+        .success();
+
+    for file_name in [
+        "test-emit-spec-1.2.0-2.x86_64.rpm",
+        "test-emit-spec-devel-1.2.0-2.x86_64.rpm",
+    ] {
+        let original = rpm::Package::open(built.join(file_name))?;
+        let rebuilt = rpm::Package::open(rebuilt.join(file_name))?;
+        let files = |pkg: &rpm::Package| -> Result<Vec<_>, rpm::Error> {
+            Ok(pkg
+                .metadata
+                .get_file_entries()?
+                .into_iter()
+                .map(|f| (f.path, f.mode, f.flags, f.ownership, f.linkto))
+                .collect::<Vec<_>>())
+        };
+        assert_eq!(files(&rebuilt)?, files(&original)?);
+        let verify_flags = |pkg: &rpm::Package| {
+            pkg.metadata
+                .header
+                .get_entry_data_as_u32_array(rpm::IndexTag::RPMTAG_FILEVERIFYFLAGS)
+        };
+        assert_eq!(verify_flags(&rebuilt)?, verify_flags(&original)?);
+        assert_eq!(
+            rebuilt.metadata.get_requires()?,
+            original.metadata.get_requires()?
+        );
+        assert_eq!(
+            rebuilt.metadata.get_provides()?,
+            original.metadata.get_provides()?
+        );
+        assert_eq!(
+            rebuilt.metadata.get_summary()?,
+            original.metadata.get_summary()?
+        );
+        assert_eq!(
+            rebuilt.metadata.get_description()?,
+            original.metadata.get_description()?
+        );
+        assert_eq!(
+            rebuilt.metadata.get_changelog_entries()?,
+            original.metadata.get_changelog_entries()?
+        );
+    }
+
+    Ok(())
+}
+
+/// Test taking the default options of the subcommands from a config file
+#[test]
+fn test_config_file() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-config-file")?;
+
+    fs::write(
+        tmp_dir.path().join("rpm-builder.toml"),
+        format!(
+            r#"
+[build]
+name = "test-config"
+version = "1.2.3"
+summary = "built from the config"
+requires = ["bash"]
+exec_file = ["{}/tests/assets/multiplication_tables.py:/usr/bin/multiplication_tables"]
+
+[[build.subpackage]]
+name = "test-config-extra"
+summary = "a subpackage from the config"
+
+[inspect]
+format = "json"
+"#,
+            workspace_path.to_string_lossy()
+        ),
+    )?;
+
+    // the config in the current directory is used, the command line takes precedence over it
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("--version")
+        .arg("2.0.0")
+        .arg("--requires")
+        .arg("zsh")
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success();
+
+    let out_file = tmp_dir.path().join("test-config-2.0.0-1.noarch.rpm");
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(pkg.metadata.get_summary()?, "built from the config");
+    assert_eq!(
+        pkg.metadata
+            .get_requires()?
+            .into_iter()
+            .filter(|r| !r.flags.contains(rpm::DependencyFlags::RPMLIB))
+            .collect::<Vec<rpm::Dependency>>(),
+        vec![rpm::Dependency::any("bash"), rpm::Dependency::any("zsh")]
+    );
+    assert_eq!(
+        pkg.metadata.get_file_paths()?,
+        vec![PathBuf::from("/usr/bin/multiplication_tables")]
+    );
+    let subpackage =
+        rpm::Package::open(tmp_dir.path().join("test-config-extra-2.0.0-1.noarch.rpm"))?;
+    assert_eq!(
+        subpackage.metadata.get_summary()?,
+        "a subpackage from the config"
+    );
+
+    // the options of other subcommands come from their own section
+    let output = Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("inspect")
+        .arg(&out_file)
+        .output()?;
+    assert!(output.status.success());
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(info["name"], "test-config");
+
+    // the name from the command line overrides the one in the config
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-config-renamed")
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success();
+    assert!(
+        tmp_dir
+            .path()
+            .join("test-config-renamed-1.2.3-1.noarch.rpm")
+            .exists()
+    );
+
+    // --config selects another config file, before or after the subcommand
+    let other_config = tmp_dir.path().join("other.yaml");
+    fs::write(
+        &other_config,
+        "build:\n  name: test-other-config\n  version: 3.0.0\n",
+    )?;
+    for args in [
+        vec![
+            "--config".into(),
+            other_config.clone().into_os_string(),
+            "build".into(),
+        ],
+        vec![
+            "build".into(),
+            format!("--config={}", other_config.display()).into(),
+        ],
+    ] {
+        Command::new(cargo_bin!())
+            .current_dir(tmp_dir.path())
+            .args(args)
+            .arg("-o")
+            .arg(tmp_dir.path())
+            .assert()
+            .success();
+        let out_file = tmp_dir.path().join("test-other-config-3.0.0-1.noarch.rpm");
+        assert!(out_file.exists());
+        fs::remove_file(out_file)?;
+    }
+
+    // sections must be tables of options of an existing subcommand
+    for (config, error) in [
+        (
+            "[frobnicate]\nfoo = 1\n",
+            "there is no frobnicate subcommand",
+        ),
+        ("build = 1\n", "'build' must be a table"),
+        (
+            "[sign]\nname = \"foo\"\n",
+            "only the [build] section can have a 'name'",
+        ),
+    ] {
+        let bad_config = tmp_dir.path().join("bad.toml");
+        fs::write(&bad_config, config)?;
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("--config")
+            .arg(&bad_config)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(error));
+    }
+
+    Ok(())
+}
+
+/// Test rejecting mistakes in the destination paths with --strict-paths
+#[test]
+fn test_strict_paths() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let asset = |name: &str| workspace_path.join("tests/assets").join(name);
+    let tmp_dir = TempDir::new("rpm-builder-test-strict-paths")?;
+    let config = asset("example_config.toml");
+    let data = asset("example_data.xml");
+
+    // files in standard directories and in directories of the package are fine
+    let out_file = tmp_dir.path().join("test-strict-paths.rpm");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-strict-paths")
+        .arg("--file")
+        .arg(format!("{}:/etc/foo.toml", config.display()))
+        .arg("--create-dir")
+        .arg("/usr/share/test-strict-paths")
+        .arg("--create-dir")
+        .arg("/usr/share/test-strict-paths/data/")
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/share/test-strict-paths/data/data.xml",
+            data.display()
+        ))
+        .arg("--symlink")
+        .arg("/usr/share/test-strict-paths/data.xml:data/data.xml")
+        .arg("--strict-paths")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    assert!(out_file.exists());
+
+    for (args, error) in [
+        (
+            vec![
+                "--file".to_owned(),
+                format!("{}:./etc/foo.toml", config.display()),
+            ],
+            "--file: the destination path ./etc/foo.toml is not absolute",
+        ),
+        (
+            vec![
+                "--symlink".to_owned(),
+                "/usr/bin/../foo:/etc/foo".to_owned(),
+            ],
+            "--symlink: the destination path /usr/bin/../foo contains '..'",
+        ),
+        (
+            vec![
+                "--exec-file".to_owned(),
+                format!("{}:/opt/test/bin/foo", data.display()),
+                "--create-dir".to_owned(),
+                "/opt/test/bin".to_owned(),
+            ],
+            "--create-dir: /opt/test/bin is in /opt/test, which is neither in the package nor a standard directory",
+        ),
+    ] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-strict-paths")
+            .args(args)
+            .arg("--strict-paths")
+            .arg("-o")
+            .arg(tmp_dir.path().join("bad.rpm"))
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(error));
+    }
+    assert!(!tmp_dir.path().join("bad.rpm").exists());
+
+    // without it, the same mistakes are allowed
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-strict-paths")
+        .arg("--exec-file")
+        .arg(format!("{}:/opt/test/bin/foo", data.display()))
+        .arg("-o")
+        .arg(tmp_dir.path().join("lax.rpm"))
+        .assert()
+        .success();
+
+    Ok(())
+}
+
+/// Test the policies for files and directories with the same destination
+#[test]
+fn test_on_conflict() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-on-conflict")?;
+    let source_dir = tmp_dir.path().join("data");
+    fs::create_dir(&source_dir)?;
+    fs::write(source_dir.join("data.txt"), "from the directory\n")?;
+    fs::write(source_dir.join("other.txt"), "other\n")?;
+    let source_file = tmp_dir.path().join("data.txt");
+    fs::write(&source_file, "from the file\n")?;
+
+    let build = |policy: Option<&str>, out_file: &Path| {
+        let mut command = Command::new(cargo_bin!());
+        command
+            .arg("build")
+            .arg("test-on-conflict")
+            .arg("--file")
+            .arg(format!(
+                "{}:/usr/share/test/data.txt",
+                source_file.display()
+            ))
+            .arg("--dir")
+            .arg(format!("{}:/usr/share/test", source_dir.display()))
+            .arg("-o")
+            .arg(out_file);
+        if let Some(policy) = policy {
+            command.arg("--on-conflict").arg(policy);
+        }
+        command.assert()
+    };
+    let content = |out_file: &Path| -> Result<Vec<(PathBuf, Vec<u8>)>, rpm::Error> {
+        let pkg = rpm::Package::open(out_file)?;
+        let files = pkg.files()?.collect::<Result<Vec<_>, _>>()?;
+        Ok(files
+            .into_iter()
+            .map(|f| (f.metadata.path, f.content))
+            .collect())
+    };
+
+    // different sources with the same destination fail the build by default
+    let out_file = tmp_dir.path().join("error.rpm");
+    build(None, &out_file)
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "--file {} and --dir {} have the same destination /usr/share/test/data.txt",
+            source_file.display(),
+            source_dir.join("data.txt").display()
+        )));
+    assert!(!out_file.exists());
+
+    let out_file = tmp_dir.path().join("first-wins.rpm");
+    build(Some("first-wins"), &out_file).success();
+    assert_eq!(
+        content(&out_file)?,
+        vec![
+            (
+                PathBuf::from("/usr/share/test/data.txt"),
+                b"from the file\n".to_vec()
+            ),
+            (
+                PathBuf::from("/usr/share/test/other.txt"),
+                b"other\n".to_vec()
+            ),
+        ]
+    );
+
+    let out_file = tmp_dir.path().join("last-wins.rpm");
+    build(Some("last-wins"), &out_file).success();
+    assert_eq!(
+        content(&out_file)?,
+        vec![
+            (
+                PathBuf::from("/usr/share/test/data.txt"),
+                b"from the directory\n".to_vec()
+            ),
+            (
+                PathBuf::from("/usr/share/test/other.txt"),
+                b"other\n".to_vec()
+            ),
+        ]
+    );
+
+    // the same source added twice isn't a conflict
+    let out_file = tmp_dir.path().join("same-source.rpm");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-on-conflict")
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/share/test/data.txt",
+            source_file.display()
+        ))
+        .arg("--doc-file")
+        .arg(format!(
+            "{}:/usr/share/test/data.txt",
+            source_file.display()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    assert_eq!(content(&out_file)?.len(), 1);
+
+    // the other options can't add a destination which is already in the package, whatever the
+    // policy, as they don't go through it
+    for policy in [None, Some("first-wins"), Some("last-wins")] {
+        let out_file = tmp_dir.path().join("other-options.rpm");
+        let mut command = Command::new(cargo_bin!());
+        command
+            .arg("build")
+            .arg("test-on-conflict")
+            .arg("--file")
+            .arg(format!(
+                "{}:/usr/share/test/data.txt",
+                source_file.display()
+            ))
+            .arg("--symlink")
+            .arg("/usr/share/test/data.txt:/usr/share/test/other.txt")
+            .arg("-o")
+            .arg(&out_file);
+        if let Some(policy) = policy {
+            command.arg("--on-conflict").arg(policy);
+        }
+        command.assert().failure().stderr(predicate::str::contains(
+            "--symlink: /usr/share/test/data.txt was already added by --file",
+        ));
+        assert!(!out_file.exists());
+    }
+
+    Ok(())
+}
+
+/// Test file sources containing the separator, such as Windows paths, and --file-separator
+#[test]
+fn test_file_separator() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-file-separator")?;
+    // the same as C:\build\foo.exe in the current directory on Windows
+    fs::write(tmp_dir.path().join("C:\\build\\foo.exe"), "#!/bin/sh\n")?;
+    fs::create_dir_all(tmp_dir.path().join("C:/share"))?;
+    fs::write(tmp_dir.path().join("C:/share/data.txt"), "data\n")?;
+
+    let entries = |out_file: &Path| -> Result<Vec<(PathBuf, u16)>, rpm::Error> {
+        let pkg = rpm::Package::open(out_file)?;
+        Ok(pkg
+            .metadata
+            .get_file_entries()?
+            .into_iter()
+            .map(|f| (f.path, u16::from(f.mode)))
+            .collect())
+    };
+
+    let out_file = tmp_dir.path().join("default.rpm");
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-file-separator")
+        .arg("--exec-file")
+        .arg("C:\\build\\foo.exe:/usr/bin/foo")
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/share/test/data.txt:mode=0600",
+            tmp_dir.path().join("C:/share/data.txt").display()
+        ))
+        .arg("--file")
+        .arg("C:/share/data.txt:/usr/share/test/odd\\:name")
+        .arg("--dir")
+        .arg("C::/usr/share/c")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    assert_eq!(
+        entries(&out_file)?,
+        vec![
+            (PathBuf::from("/usr/bin/foo"), 0o100755),
+            (PathBuf::from("/usr/share/c/share/data.txt"), 0o100644),
+            (PathBuf::from("/usr/share/test/data.txt"), 0o100600),
+            (PathBuf::from("/usr/share/test/odd:name"), 0o100644),
+        ]
+    );
+
+    let out_file = tmp_dir.path().join("separator.rpm");
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-file-separator")
+        .arg("--file-separator")
+        .arg("|")
+        .arg("--file")
+        .arg("C:\\build\\foo.exe|/usr/bin/foo:bar|mode=0700")
+        .arg("--dir")
+        .arg("C:|/usr/share/c")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    assert_eq!(
+        entries(&out_file)?,
+        vec![
+            (PathBuf::from("/usr/bin/foo:bar"), 0o100700),
+            (PathBuf::from("/usr/share/c/share/data.txt"), 0o100644),
+        ]
+    );
+
+    // the attributes of a directory come after the destination as well
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-file-separator")
+        .arg("--dir")
+        .arg("C::/usr/share/c:mode=0700")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    assert_eq!(
+        entries(&out_file)?,
+        vec![(PathBuf::from("/usr/share/c/share/data.txt"), 0o100700)]
+    );
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-separator")
+        .arg("--file-separator")
+        .arg("")
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+/// Test changelog entries with ':' in their content or author
+#[test]
+fn test_changelog_separator() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-changelog-separator")?;
+    let out_file = tmp_dir.path().join("test-changelog-separator.rpm");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-changelog-separator")
+        .arg("--changelog")
+        .arg("Jane Doe <jane@example.com>:see https://example.com/issues/1, fixed at 12:30:2024-05-01")
+        .arg("--changelog")
+        .arg("ACME\\: Release Team:ratio 1\\:2:2024-04-01")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_changelog_entries()?;
+    let entries: Vec<(&str, &str)> = entries
+        .iter()
+        .map(|e| (e.name.as_str(), e.description.as_str()))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            (
+                "Jane Doe <jane@example.com>",
+                "see https://example.com/issues/1, fixed at 12:30"
+            ),
+            ("ACME: Release Team", "ratio 1:2"),
+        ]
+    );
+
+    for (entry, error) in [
+        (
+            "no separator",
+            "it needs to be of the form <author>:<content>:<yyyy-mm-dd>",
+        ),
+        (
+            "me:2024-05-01",
+            "it needs to be of the form <author>:<content>:<yyyy-mm-dd>",
+        ),
+        ("me:content:12:30", "invalid date \"30\""),
+    ] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-changelog-separator")
+            .arg("--changelog")
+            .arg(entry)
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(error));
+    }
+
+    Ok(())
+}
+
+/// Test reading dependencies from files with --requires-from and the like
+#[test]
+fn test_dependency_files() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-dependency-files")?;
+    let requires = tmp_dir.path().join("requires.txt");
+    fs::write(
+        &requires,
+        "# generated by another tool\nwget >= 1.0.0\n\n  zsh  # the shell\n(foo or bar)\n",
+    )?;
+    let provides = tmp_dir.path().join("provides.txt");
+    fs::write(&provides, "python3dist(test) = 1.2\n")?;
+    let conflicts = tmp_dir.path().join("conflicts.txt");
+    fs::write(&conflicts, "test-old < 2\n")?;
+
+    let out_file = tmp_dir.path().join("test-dependency-files.rpm");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-dependency-files")
+        .arg("--requires")
+        .arg("bash")
+        .arg("--requires-from")
+        .arg(&requires)
+        .arg("--provides-from")
+        .arg(&provides)
+        .arg("--conflicts-from")
+        .arg(&conflicts)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let require_names: Vec<String> = pkg
+        .metadata
+        .get_requires()?
+        .into_iter()
+        .filter(|r| !r.flags.contains(rpm::DependencyFlags::RPMLIB))
+        .map(|r| r.name)
+        .collect();
+    assert_eq!(require_names, vec!["bash", "wget", "zsh", "(foo or bar)"]);
+    assert!(
+        pkg.metadata
+            .get_provides()?
+            .contains(&rpm::Dependency::eq("python3dist(test)", "1.2"))
+    );
+    assert_eq!(
+        pkg.metadata.get_conflicts()?,
+        vec![rpm::Dependency::less("test-old", "2")]
+    );
+
+    fs::write(&requires, "wget\nnot a dependency\n")?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-dependency-files")
+        .arg("--requires-from")
+        .arg(&requires)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid dependency on line 2 of"));
+
+    Ok(())
+}
+
+/// Test reading the command line arguments from @<file>
+#[test]
+fn test_args_file() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-args-file")?;
+    let source = tmp_dir.path().join("data with spaces.txt");
+    fs::write(&source, "data\n")?;
+
+    // thousands of files, as generated by other tools
+    let mut args = String::from("build\r\ntest-args-file\r\n\r\n--version\r\n1.2.3\r\n");
+    for index in 0..2000 {
+        args.push_str(&format!(
+            "--file\n{}:/usr/share/test-args-file/{}.txt\n",
+            source.display(),
+            index
+        ));
+    }
+    let args_file = tmp_dir.path().join("args.txt");
+    fs::write(&args_file, args)?;
+
+    let out_file = tmp_dir.path().join("test-args-file.rpm");
+    Command::new(cargo_bin!())
+        .arg(format!("@{}", args_file.display()))
+        .arg("--release")
+        .arg("2")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(pkg.metadata.get_name()?, "test-args-file");
+    assert_eq!(pkg.metadata.get_version()?, "1.2.3");
+    assert_eq!(pkg.metadata.get_release()?, "2");
+    assert_eq!(pkg.metadata.get_file_entries()?.len(), 2000);
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg(format!("@{}", tmp_dir.path().join("missing.txt").display()))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error reading arguments file"));
+
+    Ok(())
+}
+
+/// Test adding the files of a NUL-separated list, e.g. from find -print0
+#[test]
+fn test_files_from() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-files-from")?;
+    let tree = tmp_dir.path().join("tree");
+    fs::create_dir_all(tree.join("share"))?;
+    let exotic = tree.join("share/with\nnewline: and 'quotes'.txt");
+    fs::write(&exotic, "exotic\n")?;
+    fs::write(tree.join("share/plain.txt"), "plain\n")?;
+    std::os::unix::fs::symlink("plain.txt", tree.join("share/link.txt"))?;
+
+    let mut list = Vec::new();
+    for (source, dest) in [
+        (tree.join("share"), "/usr/share/test"),
+        (
+            exotic.clone(),
+            "/usr/share/test/with\nnewline: and 'quotes'.txt",
+        ),
+        (tree.join("share/plain.txt"), "/usr/share/test/plain.txt"),
+        (tree.join("share/link.txt"), "/usr/share/test/link.txt"),
+    ] {
+        list.extend(source.to_string_lossy().as_bytes());
+        list.push(0);
+        list.extend(dest.as_bytes());
+        list.push(0);
+    }
+
+    let out_file = tmp_dir.path().join("test-files-from.rpm");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-files-from")
+        .arg("--files-from")
+        .arg("-")
+        .arg("-o")
+        .arg(&out_file)
+        .write_stdin(list.clone())
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let mut entries: Vec<(String, rpm::FileMode, String)> = pkg
+        .metadata
+        .get_file_entries()?
+        .into_iter()
+        .map(|f| (f.path.to_string_lossy().into_owned(), f.mode, f.linkto))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0].0, "/usr/share/test");
+    assert!(matches!(entries[0].1, rpm::FileMode::Dir { .. }));
+    assert_eq!(
+        (entries[1].0.as_str(), entries[1].2.as_str()),
+        ("/usr/share/test/link.txt", "plain.txt")
+    );
+    assert_eq!(entries[2].0, "/usr/share/test/plain.txt");
+    assert_eq!(
+        entries[3].0,
+        "/usr/share/test/with\nnewline: and 'quotes'.txt"
+    );
+
+    // the list can be read from a file as well, and must consist of pairs
+    let list_file = tmp_dir.path().join("files.list");
+    fs::write(&list_file, &list[..list.len() - 1])?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-files-from")
+        .arg("--files-from")
+        .arg(&list_file)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    fs::write(&list_file, b"/etc/hosts\0/etc/hosts\0/etc/passwd")?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-files-from")
+        .arg("--files-from")
+        .arg(&list_file)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the file list has no destination for /etc/passwd",
+        ));
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-files-from")
+        .arg("--files-from")
+        .arg("-")
+        .arg("--file-from-stdin")
+        .arg("/etc/foo")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("can't both read stdin"));
+
+    Ok(())
+}
+
+/// Test packaging everything in a buildroot
+#[test]
+fn test_buildroot() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-buildroot")?;
+    let buildroot = tmp_dir.path().join("buildroot");
+    fs::create_dir_all(buildroot.join("usr/bin"))?;
+    fs::create_dir_all(buildroot.join("usr/lib64"))?;
+    fs::create_dir_all(buildroot.join("usr/share/awesome"))?;
+    fs::create_dir_all(buildroot.join("etc/awesome"))?;
+    fs::write(buildroot.join("usr/bin/awesome"), "#!/bin/sh\n")?;
+    fs::write(buildroot.join("usr/lib64/libawesome.so.1"), "library")?;
+    fs::write(buildroot.join("usr/lib64/libawesome.la"), "libtool")?;
+    std::os::unix::fs::symlink("libawesome.so.1", buildroot.join("usr/lib64/libawesome.so"))?;
+    fs::write(buildroot.join("usr/share/awesome/data.txt"), "data")?;
+    fs::write(
+        buildroot.join("etc/awesome/awesome.conf"),
+        "debug = false\n",
+    )?;
+    let out_file = tmp_dir.path().join("test-buildroot.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-buildroot")
+        .arg("--buildroot")
+        .arg(&buildroot)
+        .arg("--buildroot-attr")
+        .arg("/etc/awesome/*:config,noreplace")
+        .arg("--buildroot-attr")
+        .arg("/usr/bin/*:mode=755")
+        .arg("--buildroot-attr")
+        .arg("/**/*.la:exclude")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let mut entries = pkg.metadata.get_file_entries()?;
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    let paths: Vec<_> = entries
+        .iter()
+        .map(|f| f.path.to_string_lossy().into_owned())
+        .collect();
+    // the standard directories such as /usr/bin aren't part of the package
+    assert_eq!(
+        paths,
+        [
+            "/etc/awesome",
+            "/etc/awesome/awesome.conf",
+            "/usr/bin/awesome",
+            "/usr/lib64/libawesome.so",
+            "/usr/lib64/libawesome.so.1",
+            "/usr/share/awesome",
+            "/usr/share/awesome/data.txt",
+        ]
+    );
+    assert!(matches!(entries[0].mode, rpm::FileMode::Dir { .. }));
+    assert!(entries[1].flags.contains(rpm::FileFlags::CONFIG));
+    assert!(entries[1].flags.contains(rpm::FileFlags::NOREPLACE));
+    assert_eq!(entries[2].mode.permissions(), 0o755);
+    assert_eq!(entries[3].linkto, "libawesome.so.1");
+    assert!(!entries[6].flags.contains(rpm::FileFlags::CONFIG));
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-buildroot")
+        .arg("--buildroot")
+        .arg(&buildroot)
+        .arg("--buildroot-attr")
+        .arg("/etc/awesome/*")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected <pattern>:<attributes>"));
+
+    Ok(())
+}
+
+/// Test adding the parent directories of the files to the package
+#[test]
+fn test_own_parent_dirs() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-own-parent-dirs")?;
+    let workspace_path = env::current_dir()?;
+    let asset = |name: &str| workspace_path.join("tests/assets").join(name);
+    let out_file = tmp_dir.path().join("test-own-parent-dirs.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-own-parent-dirs")
+        .arg("--exec-file")
+        .arg(format!(
+            "{}:/opt/myapp/bin/multiplication_tables.py",
+            asset("multiplication_tables.py").display()
+        ))
+        .arg("--file")
+        .arg(format!(
+            "{}:/opt/myapp/share/doc/example_data.xml",
+            asset("example_data.xml").display()
+        ))
+        .arg("--create-dir")
+        .arg("/opt/myapp/share:mode=750")
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/bin/example_data.xml",
+            asset("example_data.xml").display()
+        ))
+        .arg("--own-parent-dirs")
+        .arg("--strict-paths")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let mut entries = pkg.metadata.get_file_entries()?;
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    let paths: Vec<_> = entries
+        .iter()
+        .map(|f| f.path.to_string_lossy().into_owned())
+        .collect();
+    // /opt and /usr/bin are standard directories, and /opt/myapp/share is already packaged
+    assert_eq!(
+        paths,
+        [
+            "/opt/myapp",
+            "/opt/myapp/bin",
+            "/opt/myapp/bin/multiplication_tables.py",
+            "/opt/myapp/share",
+            "/opt/myapp/share/doc",
+            "/opt/myapp/share/doc/example_data.xml",
+            "/usr/bin/example_data.xml",
+        ]
+    );
+    assert!(matches!(entries[0].mode, rpm::FileMode::Dir { .. }));
+    assert_eq!(entries[3].mode.permissions(), 0o750);
+
+    Ok(())
+}
+
+/// Test setting the attributes of every file of a directory
+#[test]
+fn test_dir_attributes() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-dir-attributes")?;
+    let source = tmp_dir.path().join("conf");
+    fs::create_dir_all(source.join("conf.d"))?;
+    fs::write(source.join("app.conf"), "debug = false\n")?;
+    fs::write(source.join("conf.d/extra.conf"), "verbose = true\n")?;
+    let out_file = tmp_dir.path().join("test-dir-attributes.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-dir-attributes")
+        .arg("--dir")
+        .arg(format!(
+            "{}:/etc/app:user=app,group=app,mode=0640,flags=config(noreplace)",
+            source.display()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let mut entries = pkg.metadata.get_file_entries()?;
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].path, PathBuf::from("/etc/app/app.conf"));
+    assert_eq!(entries[1].path, PathBuf::from("/etc/app/conf.d/extra.conf"));
+    for entry in &entries {
+        assert_eq!(entry.mode.permissions(), 0o640);
+        assert_eq!(entry.ownership.user, "app");
+        assert_eq!(entry.ownership.group, "app");
+        assert!(entry.flags.contains(rpm::FileFlags::CONFIG));
+        assert!(entry.flags.contains(rpm::FileFlags::NOREPLACE));
+    }
+
+    // the flags work for single files too, and are checked
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-dir-attributes")
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/share/doc/app/app.conf:flags=doc",
+            source.join("app.conf").display()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    let pkg = rpm::Package::open(&out_file)?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert!(entries[0].flags.contains(rpm::FileFlags::DOC));
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-dir-attributes")
+        .arg("--config-dir")
+        .arg(format!("{}:/etc/app:flags=unknown", source.display()))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown file flag 'unknown'"));
+
+    Ok(())
+}
+
+/// Test limiting how deep directories are descended into, and following symlinks
+#[test]
+fn test_dir_max_depth() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-dir-max-depth")?;
+    let source = tmp_dir.path().join("tree");
+    fs::create_dir_all(source.join("a/b"))?;
+    fs::write(source.join("top.txt"), "top")?;
+    fs::write(source.join("a/middle.txt"), "middle")?;
+    fs::write(source.join("a/b/bottom.txt"), "bottom")?;
+    std::os::unix::fs::symlink("top.txt", source.join("link.txt"))?;
+    let out_file = tmp_dir.path().join("test-dir-max-depth.rpm");
+    let entries = |args: &[&str]| -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-dir-max-depth")
+            .arg("--dir")
+            .arg(format!("{}:/usr/share/tree", source.display()))
+            .args(args)
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .success();
+        let pkg = rpm::Package::open(&out_file)?;
+        let mut entries: Vec<_> = pkg
+            .metadata
+            .get_file_entries()?
+            .into_iter()
+            .map(|f| (f.path.to_string_lossy().into_owned(), f.linkto))
+            .collect();
+        entries.sort();
+        Ok(entries)
+    };
+    let entry = |path: &str, linkto: &str| (path.to_owned(), linkto.to_owned());
+
+    assert_eq!(
+        entries(&["--dir-max-depth", "2"])?,
+        [
+            entry("/usr/share/tree/a/middle.txt", ""),
+            entry("/usr/share/tree/link.txt", ""),
+            entry("/usr/share/tree/top.txt", ""),
+        ]
+    );
+    // the last of the symlink flags wins
+    assert_eq!(
+        entries(&[
+            "--dir-no-recurse",
+            "--dir-follow-symlinks",
+            "--dir-preserve-symlinks"
+        ])?,
+        [
+            entry("/usr/share/tree/link.txt", "top.txt"),
+            entry("/usr/share/tree/top.txt", ""),
+        ]
+    );
+    assert_eq!(
+        entries(&[
+            "--dir-no-recurse",
+            "--dir-preserve-symlinks",
+            "--dir-follow-symlinks"
+        ])?,
+        [
+            entry("/usr/share/tree/link.txt", ""),
+            entry("/usr/share/tree/top.txt", ""),
+        ]
+    );
+    assert_eq!(entries(&[])?.len(), 4);
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-dir-max-depth")
+        .arg("--dir-max-depth")
+        .arg("0")
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+/// Test choosing the modification times recorded for the files
+#[test]
+fn test_file_mtime() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-file-mtime")?;
+    let source = tmp_dir.path().join("data.txt");
+    fs::write(&source, "data")?;
+    // a file from the future, e.g. copied from a machine with a wrong clock
+    let future = std::time::SystemTime::now() + std::time::Duration::from_secs(365 * 24 * 3600);
+    fs::File::options()
+        .write(true)
+        .open(&source)?
+        .set_modified(future)?;
+    let out_file = tmp_dir.path().join("test-file-mtime.rpm");
+    let mtime = |args: &[&str]| -> Result<u32, Box<dyn std::error::Error>> {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-file-mtime")
+            .arg("--file")
+            .arg(format!("{}:/usr/share/test/data.txt", source.display()))
+            .args(args)
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .success();
+        let pkg = rpm::Package::open(&out_file)?;
+        Ok(pkg.metadata.get_file_entries()?[0].modified_at.0)
+    };
+    let future = future.duration_since(std::time::UNIX_EPOCH)?.as_secs() as u32;
+    let now = chrono::Utc::now().timestamp() as u32;
+
+    assert_eq!(mtime(&[])?, future);
+    assert_eq!(mtime(&["--file-mtime", "preserve"])?, future);
+    assert_eq!(mtime(&["--file-mtime", "clamp=1700000000"])?, 1700000000);
+    assert_eq!(
+        mtime(&["--file-mtime", "clamp=2023-11-14T22:13:20Z"])?,
+        1700000000
+    );
+    assert!((now..now + 60).contains(&mtime(&["--file-mtime", "now"])?));
+    // the source date still applies
+    assert_eq!(
+        mtime(&["--file-mtime", "now", "--build-time", "1600000000"])?,
+        1600000000
+    );
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-mtime")
+        .arg("--file-mtime")
+        .arg("latest")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid file-mtime latest, expected preserve, now or clamp=<time>",
+        ));
+
+    Ok(())
+}
+
+/// Test choosing the file name of the package
+#[test]
+fn test_filename_format() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-filename-format")?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-filename-format")
+        .arg("--version")
+        .arg("1.2.3")
+        .arg("--epoch")
+        .arg("2")
+        .arg("--filename-format")
+        .arg("%{name}-%{epoch}:%{version}-%{release}.%{arch}.rpm")
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success();
+    assert!(
+        tmp_dir
+            .path()
+            .join("test-filename-format-2:1.2.3-1.noarch.rpm")
+            .is_file()
+    );
+
+    // without an epoch, it is 0
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-filename-format")
+        .arg("--filename-format")
+        .arg("%{name}_%{epoch}_%{version}.rpm")
+        .assert()
+        .success();
+    assert!(
+        tmp_dir
+            .path()
+            .join("test-filename-format_0_1.0.0.rpm")
+            .is_file()
+    );
+
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-filename-format")
+        .arg("--filename-format")
+        .arg("%{name}-%{vendor}.rpm")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "only name, epoch, version, release and arch can be used",
+        ));
+
+    Ok(())
+}
+
+/// Test writing the package to a directory which doesn't exist yet
+#[test]
+fn test_create_out_dir() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-create-out-dir")?;
+
+    // a path ending with a slash is a directory, not the name of the package
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-create-out-dir")
+        .arg("-o")
+        .arg("out/")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the output directory \"out\" doesn't exist, use --create-out-dir to create it",
+        ));
+    assert!(!tmp_dir.path().join("out.rpm").exists());
+
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-create-out-dir")
+        .arg("-o")
+        .arg("out/x86_64/")
+        .arg("--create-out-dir")
+        .assert()
+        .success();
+    assert!(
+        tmp_dir
+            .path()
+            .join("out/x86_64/test-create-out-dir-1.0.0-1.noarch.rpm")
+            .is_file()
+    );
+
+    // the directory of a file name is created too
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-create-out-dir")
+        .arg("-o")
+        .arg("other/named.rpm")
+        .arg("--create-out-dir")
+        .assert()
+        .success();
+    assert!(tmp_dir.path().join("other/named.rpm").is_file());
+
+    Ok(())
+}
+
+/// Test the exit codes of the kinds of errors, and printing errors as JSON
+#[test]
+fn test_error_format() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-error-format")?;
+    let key = tmp_dir.path().join("broken.asc");
+    fs::write(&key, "not a key")?;
+    let out_file = tmp_dir.path().join("test-error-format.rpm");
+    let error = |args: &[&std::ffi::OsStr]| -> Result<(i32, serde_json::Value), Box<dyn std::error::Error>> {
+        let output = Command::new(cargo_bin!())
+            .arg("--error-format")
+            .arg("json")
+            .arg("build")
+            .arg("test-error-format")
+            .arg("-o")
+            .arg(&out_file)
+            .args(args)
+            .output()?;
+        let code = output.status.code().unwrap_or_default();
+        Ok((code, serde_json::from_slice(&output.stderr)?))
+    };
+
+    let (code, report) = error(&["--bogus".as_ref()])?;
+    assert_eq!(code, 2);
+    assert_eq!(report["kind"], "argument");
+    assert!(
+        report["message"]
+            .as_str()
+            .unwrap_or_default()
+            .starts_with("unexpected argument '--bogus' found")
+    );
+
+    let (code, report) = error(&["--file".as_ref(), "/nonexistent:/usr/share/x".as_ref()])?;
+    assert_eq!(code, 3);
+    assert_eq!(report["kind"], "io");
+    assert_eq!(report["exit_code"], 3);
+    assert_eq!(report["message"], "error adding regular file /nonexistent");
+    assert_eq!(
+        report["causes"][0],
+        "No such file or directory (os error 2)"
+    );
+
+    let (code, report) = error(&["--sign-with-pgp-asc".as_ref(), key.as_os_str()])?;
+    assert_eq!(code, 4);
+    assert_eq!(report["kind"], "signing");
+
+    let (code, report) = error(&["--requires".as_ref(), "not a dependency".as_ref()])?;
+    assert_eq!(code, 1);
+    assert_eq!(report["kind"], "build");
+
+    // the text format is the default, and uses the same exit codes
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-error-format")
+        .arg("--file")
+        .arg("/nonexistent:/usr/share/x")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .code(3)
+        .stderr(predicate::str::starts_with(
+            "Error: error adding regular file /nonexistent",
+        ));
+
+    Ok(())
+}
+
+/// Test building packages which the rpm of older distributions can install
+#[test]
+fn test_compat() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-compat")?;
+    let workspace_path = env::current_dir()?;
+    let asset = |name: &str| workspace_path.join("tests/assets").join(name);
+    let out_file = tmp_dir.path().join("test-compat.rpm");
+    let build = |args: &[&str]| {
+        let mut command = Command::new(cargo_bin!());
+        command
+            .arg("build")
+            .arg("test-compat")
+            .args(args)
+            .arg("-o")
+            .arg(&out_file);
+        command.assert()
+    };
+
+    // el7's rpm has no zstd, so the payload is compressed with xz instead
+    build(&["--compat", "el7"]).success();
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        pkg.metadata.get_payload_compressor()?,
+        rpm::CompressionType::Xz
+    );
+    build(&["--compat", "el7", "--compression", "gzip"]).success();
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        pkg.metadata.get_payload_compressor()?,
+        rpm::CompressionType::Gzip
+    );
+
+    build(&["--compat", "el7", "--compression", "zstd"])
+        .failure()
+        .stderr(predicate::str::contains(
+            "--compression zstd can't be used with --compat el7, its rpm doesn't support it",
+        ));
+    build(&["--compat", "el7", "--requires", "(foo or bar)"])
+        .failure()
+        .stderr(predicate::str::contains(
+            "the rich dependency (foo or bar) can't be used with --compat el7",
+        ));
+    build(&["--compat", "el8", "--requires", "(foo or bar)"]).success();
+    build(&["--compat", "fc41", "--rpm-format", "v6"])
+        .failure()
+        .stderr(predicate::str::contains(
+            "--rpm-format v6 can't be used with --compat fc41",
+        ));
+
+    let ed25519_key = asset("ed25519.key");
+    let ed25519_key = ed25519_key.to_string_lossy();
+    build(&["--compat", "el8", "--sign-with-pgp-asc", &ed25519_key])
+        .failure()
+        .stderr(predicate::str::contains(
+            "can't be used with --compat el8, its rpm can only check RSA signatures",
+        ));
+    build(&["--compat", "fc41", "--sign-with-pgp-asc", &ed25519_key]).success();
+
+    Ok(())
+}
+
+/// Test recording SHA-512 file digests
+#[test]
+fn test_file_digest_algo() -> Result<(), Box<dyn std::error::Error>> {
+    use sha2::Digest;
+
+    let tmp_dir = TempDir::new("rpm-builder-test-file-digest-algo")?;
+    let workspace_path = env::current_dir()?;
+    let asset = |name: &str| workspace_path.join("tests/assets").join(name);
+    let out_file = tmp_dir.path().join("test-file-digest-algo.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-digest-algo")
+        .arg("--file")
+        .arg(format!(
+            "{}:/usr/share/test/data.xml",
+            asset("example_data.xml").display()
+        ))
+        .arg("--create-dir")
+        .arg("/usr/share/test")
+        .arg("--symlink")
+        .arg("/usr/share/test/link.xml:data.xml")
+        .arg("--file-digest-algo")
+        .arg("sha512")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    pkg.verify_digests()?;
+    let entries = pkg.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 3);
+    let expected = format!(
+        "{:x}",
+        sha2::Sha512::digest(fs::read(asset("example_data.xml"))?)
+    );
+    for entry in entries {
+        let digest = entry.digest.ok_or("no digest")?;
+        assert_eq!(digest.algo, rpm::DigestAlgorithm::Sha2_512);
+        if entry.path == Path::new("/usr/share/test/data.xml") {
+            assert_eq!(digest.digest, expected);
+        }
+    }
+
+    Ok(())
+}
+
+/// Test choosing the digests of the header in the signature header
+#[test]
+fn test_header_digest() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-header-digest")?;
+    let workspace_path = env::current_dir()?;
+    let asset = |name: &str| workspace_path.join("tests/assets").join(name);
+    let out_file = tmp_dir.path().join("test-header-digest.rpm");
+    let digests =
+        |args: &[&std::ffi::OsStr]| -> Result<Vec<&'static str>, Box<dyn std::error::Error>> {
+            Command::new(cargo_bin!())
+                .arg("build")
+                .arg("test-header-digest")
+                .args(args)
+                .arg("-o")
+                .arg(&out_file)
+                .assert()
+                .success();
+            let pkg = rpm::Package::open(&out_file)?;
+            pkg.verify_digests()?;
+            let signature = &pkg.metadata.signature;
+            Ok([
+                ("sha1", rpm::IndexSignatureTag::RPMSIGTAG_SHA1),
+                ("sha256", rpm::IndexSignatureTag::RPMSIGTAG_SHA256),
+                ("sha3-256", rpm::IndexSignatureTag::RPMSIGTAG_SHA3_256),
+            ]
+            .into_iter()
+            .filter(|(_, tag)| signature.entry_is_present(*tag))
+            .map(|(name, _)| name)
+            .collect())
+        };
+
+    assert_eq!(digests(&[])?, ["sha256", "sha3-256"]);
+    assert_eq!(
+        digests(&["--header-digest".as_ref(), "sha256".as_ref()])?,
+        ["sha256"]
+    );
+    assert_eq!(
+        digests(&["--compat".as_ref(), "el7".as_ref()])?,
+        ["sha1", "sha256"]
+    );
+    let key = asset("package-manager.key");
+    assert_eq!(
+        digests(&[
+            "--header-digest".as_ref(),
+            "sha1".as_ref(),
+            "--header-digest".as_ref(),
+            "sha3-256".as_ref(),
+            "--sign-with-pgp-asc".as_ref(),
+            key.as_os_str(),
+        ])?,
+        ["sha1", "sha3-256"]
+    );
+    let pkg = rpm::Package::open(&out_file)?;
+    let verifier = rpm::signature::pgp::Verifier::load_from_asc_bytes(&fs::read(asset(
+        "package-manager.key.pub",
+    ))?)?;
+    pkg.verify_signature(verifier)?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-header-digest")
+        .arg("--compat")
+        .arg("el7")
+        .arg("--header-digest")
+        .arg("sha256")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--header-digest without sha1 can't be used with --compat el7",
+        ));
+
+    Ok(())
+}
+
+/// Test adding the obsoletes and provides of package renames
+#[test]
+fn test_renames_from() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-renames-from")?;
+    let out_file = tmp_dir.path().join("test-renames-from.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-renames-from")
+        .arg("--version")
+        .arg("2.0")
+        .arg("--release")
+        .arg("3")
+        .arg("--renames-from")
+        .arg("old-name")
+        .arg("--renames-from")
+        .arg("older-name=1.5-1")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        pkg.metadata.get_obsoletes()?,
+        vec![
+            rpm::Dependency::less("old-name", "2.0-3"),
+            rpm::Dependency::less("older-name", "1.5-1"),
+        ]
+    );
+    let provides = pkg.metadata.get_provides()?;
+    assert!(provides.contains(&rpm::Dependency::eq("old-name", "2.0-3")));
+    assert!(provides.contains(&rpm::Dependency::eq("older-name", "1.5-1")));
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-renames-from")
+        .arg("--epoch")
+        .arg("1")
+        .arg("--renames-from")
+        .arg("old-name")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    let pkg = rpm::Package::open(&out_file)?;
+    assert_eq!(
+        pkg.metadata.get_obsoletes()?,
+        vec![rpm::Dependency::less("old-name", "1:1.0.0-1")]
+    );
+
+    for rename in ["old name", "=1.0", "test-renames-from"] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-renames-from")
+            .arg("--renames-from")
+            .arg(rename)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid rename"));
+    }
+
+    Ok(())
+}
+
+/// Test the qualifiers of requirements and the OrderWithRequires dependencies
+#[test]
+fn test_requires_qualifiers() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-requires-qualifiers")?;
+    let out_file = tmp_dir.path().join("test-requires-qualifiers.rpm");
+    let requires_file = tmp_dir.path().join("requires.txt");
+    fs::write(&requires_file, "shadow-utils [pre]\n")?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-requires-qualifiers")
+        .arg("--requires")
+        .arg("systemd [post, preun]")
+        .arg("--requires")
+        .arg("bash >= 5.0 [pretrans]")
+        .arg("--requires")
+        .arg("(coreutils or busybox) [post]")
+        .arg("--requires-from")
+        .arg(&requires_file)
+        .arg("--order-with-requires")
+        .arg("firewalld")
+        .arg("--order-with-requires")
+        .arg("selinux-policy >= 3.14")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let requires: Vec<rpm::Dependency> = pkg
+        .metadata
+        .get_requires()?
+        .into_iter()
+        .filter(|r| !r.flags.contains(rpm::DependencyFlags::RPMLIB))
+        .collect();
+    assert_eq!(
+        requires,
+        vec![
+            rpm::Dependency {
+                name: "systemd".to_owned(),
+                flags: rpm::DependencyFlags::SCRIPT_POST | rpm::DependencyFlags::SCRIPT_PREUN,
+                version: "".to_owned(),
+            },
+            rpm::Dependency {
+                name: "bash".to_owned(),
+                flags: rpm::DependencyFlags::GE | rpm::DependencyFlags::PRETRANS,
+                version: "5.0".to_owned(),
+            },
+            rpm::Dependency {
+                name: "(coreutils or busybox)".to_owned(),
+                flags: rpm::DependencyFlags::SCRIPT_POST,
+                version: "".to_owned(),
+            },
+            rpm::Dependency::script_pre("shadow-utils"),
+        ]
+    );
+    let header = &pkg.metadata.header;
+    assert_eq!(
+        header.get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_ORDERNAME)?,
+        ["firewalld", "selinux-policy"]
+    );
+    assert_eq!(
+        header.get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_ORDERVERSION)?,
+        ["", "3.14"]
+    );
+    assert_eq!(
+        header.get_entry_data_as_u32_array(rpm::IndexTag::RPMTAG_ORDERFLAGS)?,
+        [0, rpm::DependencyFlags::GE.bits()]
+    );
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-requires-qualifiers")
+        .arg("--requires")
+        .arg("systemd [install]")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unknown qualifier \"install\" in requirement systemd [install]",
+        ));
+
+    Ok(())
+}
+
+/// Test dependencies with qualifiers in parentheses and file names with spaces
+#[test]
+fn test_dependency_names() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-dependency-names")?;
+    let out_file = tmp_dir.path().join("test-dependency-names.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-dependency-names")
+        .args(["--requires", "perl(Foo::Bar) >= 1.0"])
+        .args(["--requires", "libc.so.6()(64bit)"])
+        .args(["--requires", "bundled(golang(github.com/foo/bar))=1.2"])
+        .args(["--requires", "libstdc++"])
+        .args(["--requires", "font(:lang=de)"])
+        .args(["--requires", "python3dist(foo[bar]) < 2"])
+        .args(["--requires", "config(foo bar) = 1.0-1"])
+        .args(["--conflicts", "/opt/some dir/bin/tool"])
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let requires: Vec<rpm::Dependency> = pkg
+        .metadata
+        .get_requires()?
+        .into_iter()
+        .filter(|r| !r.flags.contains(rpm::DependencyFlags::RPMLIB))
+        .collect();
+    assert_eq!(
+        requires,
+        vec![
+            rpm::Dependency::greater_eq("perl(Foo::Bar)", "1.0"),
+            rpm::Dependency::any("libc.so.6()(64bit)"),
+            rpm::Dependency::eq("bundled(golang(github.com/foo/bar))", "1.2"),
+            rpm::Dependency::any("libstdc++"),
+            rpm::Dependency::any("font(:lang=de)"),
+            rpm::Dependency::less("python3dist(foo[bar])", "2"),
+            rpm::Dependency::eq("config(foo bar)", "1.0-1"),
+        ]
+    );
+    assert_eq!(
+        pkg.metadata.get_conflicts()?,
+        vec![rpm::Dependency::any("/opt/some dir/bin/tool")]
+    );
+
+    for (dependency, error) in [
+        ("foo bar", "only file names can contain spaces"),
+        ("-foo", "the name has to start with"),
+        ("= 1.0", "the name has to start with"),
+        ("perl(Foo", "unbalanced parentheses"),
+        ("perl(Foo)) >= 1", "unbalanced parentheses"),
+    ] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-dependency-names")
+            .arg(format!("--requires={}", dependency))
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(error));
+    }
+
+    Ok(())
+}
+
+/// Test deriving the release from the build time or the git history
+#[test]
+fn test_auto_release() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-auto-release")?;
+    let out_file = tmp_dir.path().join("test-auto-release.rpm");
+    let repo = tmp_dir.path().join("repo");
+    fs::create_dir(&repo)?;
+
+    let release = |args: &[&str]| -> Result<String, Box<dyn std::error::Error>> {
+        Command::new(cargo_bin!())
+            .current_dir(&repo)
+            .arg("build")
+            .arg("test-auto-release")
+            .args(args)
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .success();
+        let pkg = rpm::Package::open(&out_file)?;
+        Ok(pkg.metadata.get_release()?.to_owned())
+    };
+    assert_eq!(
+        release(&[
+            "--auto-release",
+            "timestamp",
+            "--build-time",
+            "2024-06-01T12:30:00Z"
+        ])?,
+        "1.20240601123000"
+    );
+
+    let git = |args: &[&str], date: &str| {
+        std::process::Command::new("git")
+            .arg("-C")
+            .arg(&repo)
+            .args([
+                "-c",
+                "user.name=Walter White",
+                "-c",
+                "user.email=ww@breakingbad.com",
+            ])
+            .args(args)
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .output()
+            .map(|output| {
+                assert!(output.status.success());
+                String::from_utf8_lossy(&output.stdout).trim().to_owned()
+            })
+    };
+    git(&["init", "--quiet"], "")?;
+    git(
+        &["commit", "--quiet", "--allow-empty", "-m", "Initial commit"],
+        "1514851200 +0000",
+    )?;
+    git(&["tag", "v1.0-beta"], "")?;
+    for date in ["1549152000 +0000", "1717243200 +0000"] {
+        git(
+            &["commit", "--quiet", "--allow-empty", "-m", "Change"],
+            date,
+        )?;
+    }
+    let hash = git(&["rev-parse", "--short", "HEAD"], "")?;
+
+    assert_eq!(
+        release(&["--auto-release", "git-count"])?,
+        format!("1.3.20240601git{}", hash)
+    );
+    assert_eq!(
+        release(&["--auto-release", "git-describe", "--release", "0.1"])?,
+        format!("0.1.2.20240601git{}", hash)
+    );
+
+    // the subpackages get the same release
+    let manifest = tmp_dir.path().join("rpm.toml");
+    fs::write(
+        &manifest,
+        "name = \"test-auto-release\"\nauto_release = \"git-count\"\n\n\
+         [[subpackage]]\nname = \"test-auto-release-devel\"\n",
+    )?;
+    Command::new(cargo_bin!())
+        .current_dir(&repo)
+        .arg("build")
+        .arg("--manifest")
+        .arg(&manifest)
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success();
+    for name in ["test-auto-release", "test-auto-release-devel"] {
+        let path = tmp_dir
+            .path()
+            .join(format!("{}-1.0.0-1.3.20240601git{}.noarch.rpm", name, hash));
+        assert!(path.exists(), "{:?} is missing", path);
+    }
+
+    Ok(())
+}
+
+/// Test checking the name, version and release against the rules of rpm, and sanitizing versions
+#[test]
+fn test_sanitize_version() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-sanitize-version")?;
+    let out_file = tmp_dir.path().join("test-sanitize-version.rpm");
+
+    for (version, sanitized) in [
+        ("1.0.0-beta.1", "1.0.0~beta.1"),
+        ("v2.1", "2.1"),
+        ("3.0.0-rc-2+build.5", "3.0.0~rc.2+build.5"),
+        ("1.0^20240601git1a2b3c4", "1.0^20240601git1a2b3c4"),
+    ] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-sanitize-version")
+            .arg("--version")
+            .arg(version)
+            .arg("--sanitize-version")
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .success();
+        let pkg = rpm::Package::open(&out_file)?;
+        assert_eq!(pkg.metadata.get_version()?, sanitized);
+    }
+
+    for (option, value, error) in [
+        (
+            "--version",
+            "1.0.0-beta.1",
+            "invalid version \"1.0.0-beta.1\": '-' at position 6 isn't allowed, use '~' for a pre-release (or --sanitize-version)",
+        ),
+        ("--version", "1.0 beta", "' ' at position 4 isn't allowed"),
+        ("--version", "1..0", "it contains \"..\""),
+        ("--version", "~1", "it has to start with"),
+        (
+            "--release",
+            "1-2",
+            "invalid release \"1-2\": '-' at position 2",
+        ),
+        ("--release", "", "the release of the package is empty"),
+    ] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-sanitize-version")
+            .arg(format!("{}={}", option, value))
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(error));
+    }
+    for name in ["foo bar", "-foo", "foo/bar", "foo:bar"] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("--")
+            .arg(name)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!("invalid name {:?}", name)));
+    }
+
+    Ok(())
+}
+
+/// Test comparing versions the way rpm does
+#[test]
+fn test_version_compare() -> Result<(), Box<dyn std::error::Error>> {
+    for (evr, other, output) in [
+        ("1.0~rc1", "1.0", "1.0~rc1 < 1.0"),
+        (
+            "1.0^20240601git1a2b3c4",
+            "1.0",
+            "1.0^20240601git1a2b3c4 > 1.0",
+        ),
+        ("1.0~rc1", "1.0~beta2", "1.0~rc1 > 1.0~beta2"),
+        ("1.10", "1.9", "1.10 > 1.9"),
+        ("1:0.9", "2.0", "1:0.9 > 2.0"),
+        ("0:1.0-1", "1.0-1", "0:1.0-1 == 1.0-1"),
+        ("1.0", "1.0-1", "1.0 < 1.0-1"),
+    ] {
+        Command::new(cargo_bin!())
+            .args(["version", "compare", evr, other])
+            .assert()
+            .success()
+            .stdout(format!("{}\n", output));
+    }
+
+    let output = Command::new(cargo_bin!())
+        .args(["version", "compare", "2.0-1", "2.0-2", "--format", "json"])
+        .output()?;
+    let comparison: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(comparison["relation"], "older");
+    assert_eq!(comparison["result"], -1);
+
+    Command::new(cargo_bin!())
+        .args(["version", "compare", "1.1", "1.0", "--expect", "newer"])
+        .assert()
+        .success();
+    Command::new(cargo_bin!())
+        .args(["version", "compare", "1.0~rc1", "1.0", "--expect", "newer"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "1.0~rc1 is older than 1.0, not newer than it",
+        ));
+    Command::new(cargo_bin!())
+        .args(["version", "compare", "1.0-1-2", "1.0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid version \"1.0-1-2\""));
+
+    Ok(())
+}
+
+/// Test checking that a package upgrades the ones already in a repository
+#[test]
+fn test_check_upgrades_from() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-check-upgrades-from")?;
+    let repo_dir = tmp_dir.path().join("repo");
+    fs::create_dir(&repo_dir)?;
+    for (name, release) in [("test-upgrade", "1"), ("test-upgrade", "2"), ("other", "9")] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg(name)
+            .arg("--release")
+            .arg(release)
+            .arg("-o")
+            .arg(&repo_dir)
+            .assert()
+            .success();
+    }
+
+    let build = |release: &str, epoch: &str| {
+        let mut cmd = Command::new(cargo_bin!());
+        cmd.arg("build")
+            .arg("test-upgrade")
+            .arg("--release")
+            .arg(release)
+            .arg("--epoch")
+            .arg(epoch)
+            .arg("--check-upgrades-from")
+            .arg(&repo_dir)
+            .arg("-o")
+            .arg(tmp_dir.path().join("test-upgrade.rpm"));
+        cmd
+    };
+    for compression in ["gzip", "zstd"] {
+        Command::new(cargo_bin!())
+            .arg("repo")
+            .arg(&repo_dir)
+            .arg("--compression")
+            .arg(compression)
+            .assert()
+            .success();
+
+        build("3", "0").assert().success();
+        build("1", "1").assert().success();
+        build("2", "0").assert().failure().stderr(predicate::str::contains(
+            "test-upgrade 1.0.0-2 doesn't upgrade test-upgrade 1.0.0-2.noarch of the repository",
+        ));
+        build("1.9", "0").assert().failure();
+    }
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-upgrade")
+        .arg("--check-upgrades-from")
+        .arg(tmp_dir.path().join("missing"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unable to read the packages of the repository",
+        ));
+
+    Ok(())
+}
+
+/// Test editing the metadata of an existing package without rebuilding its payload
+#[test]
+fn test_edit() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-edit")?;
+    let original = tmp_dir.path().join("test-edit.rpm");
+    let changelog = tmp_dir.path().join("changelog");
+    fs::write(
+        &changelog,
+        "* Mon May 06 2024 Walter White <ww@breakingbad.com> - 1.0.0-2.fc40\n- Fix the summary\n",
+    )?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-edit")
+        .arg("--summary")
+        .arg("A tpyo")
+        .arg("--release")
+        .arg("1.fc40")
+        .arg("--provides")
+        .arg("test-edit-compat = 1.0.0-1.fc40")
+        .arg("--obsoletes")
+        .arg("test-old < 1")
+        .arg("--changelog")
+        .arg("Walter White:- Initial release:2024-05-01")
+        .arg("--exec-file")
+        .arg(format!(
+            "{}/tests/assets/multiplication_tables.py:/usr/bin/mt",
+            workspace_path.display()
+        ))
+        .arg("--sign-with-pgp-asc")
+        .arg(workspace_path.join("tests/assets/package-manager.key"))
+        .arg("-o")
+        .arg(&original)
+        .assert()
+        .success();
+
+    Command::new(cargo_bin!())
+        .arg("edit")
+        .arg(&original)
+        .arg("--summary")
+        .arg("A typo")
+        .arg("--bump-release")
+        .arg("--obsoletes")
+        .arg("test-older < 2")
+        .arg("--changelog-file")
+        .arg(&changelog)
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success();
+
+    let before = rpm::Package::open(&original)?;
+    let pkg = rpm::Package::open(tmp_dir.path().join("test-edit-1.0.0-2.fc40.noarch.rpm"))?;
+    pkg.verify_digests()?;
+    assert_eq!(pkg.content, before.content);
+    assert_eq!(pkg.metadata.get_summary()?, "A typo");
+    assert_eq!(pkg.metadata.get_release()?, "2.fc40");
+    assert!(
+        pkg.metadata
+            .get_provides()?
+            .contains(&rpm::Dependency::eq("test-edit-compat", "1.0.0-2.fc40"))
+    );
+    assert_eq!(
+        pkg.metadata.get_obsoletes()?,
+        vec![
+            rpm::Dependency::less("test-old", "1"),
+            rpm::Dependency::less("test-older", "2"),
+        ]
+    );
+    let changelog = pkg.metadata.get_changelog_entries()?;
+    assert_eq!(changelog.len(), 1);
+    assert_eq!(changelog[0].description, "- Fix the summary");
+    assert_eq!(
+        pkg.metadata.get_file_entries()?[0].path,
+        PathBuf::from("/usr/bin/mt")
+    );
+    // the old signature doesn't match anymore, so it's gone
+    assert!(
+        !pkg.metadata
+            .signature
+            .entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_OPENPGP)
+    );
+
+    // in place, signing it again
+    Command::new(cargo_bin!())
+        .arg("edit")
+        .arg(&original)
+        .arg("--release")
+        .arg("5")
+        .arg("--sign-with-pgp-asc")
+        .arg(workspace_path.join("tests/assets/package-manager.key"))
+        .assert()
+        .success();
+    let pkg = rpm::Package::open(&original)?;
+    assert_eq!(pkg.metadata.get_release()?, "5");
+    let verifier = rpm::signature::pgp::Verifier::load_from_asc_bytes(&fs::read(
+        workspace_path.join("tests/assets/package-manager.key.pub"),
+    )?)?;
+    pkg.verify_signature(verifier)?;
+
+    for (args, error) in [
+        (vec![], "nothing to edit"),
+        (vec!["--release", "1-2"], "invalid release \"1-2\""),
+        (
+            vec!["--obsoletes", "(a or b)"],
+            "rich dependencies can't be used",
+        ),
+    ] {
+        Command::new(cargo_bin!())
+            .arg("edit")
+            .arg(&original)
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(error));
+    }
+
+    Ok(())
+}
+
+/// Test the --payload-format flag
+#[test]
+fn test_payload_format() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-payload-format")?;
+    let file = format!(
+        "{}/tests/assets/multiplication_tables.py:/usr/bin/multiplication_tables",
+        workspace_path.to_string_lossy()
+    );
+
+    for (format, magic, rpm_format) in [
+        ("cpio", &b"070701"[..], false),
+        ("newc", &b"070701"[..], false),
+        ("stripped", &b"07070X"[..], true),
+    ] {
+        let out_file = tmp_dir.path().join(format!("{}.rpm", format));
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-payload-format")
+            .arg("--exec-file")
+            .arg(&file)
+            .arg("--compression")
+            .arg("none")
+            .arg("--payload-format")
+            .arg(format)
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .success();
+        let content = fs::read(&out_file)?;
+        assert!(content.windows(magic.len()).any(|window| window == magic));
+        let pkg = rpm::PackageMetadata::open(&out_file)?;
+        assert_eq!(
+            pkg.header.entry_is_present(rpm::IndexTag::RPMTAG_RPMFORMAT),
+            rpm_format
+        );
+    }
+
+    // the formats which the rpm format doesn't write fail
+    for (format, rpm_format) in [("newc", "v6"), ("stripped", "v4")] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-payload-format")
+            .arg("--payload-format")
+            .arg(format)
+            .arg("--rpm-format")
+            .arg(rpm_format)
+            .arg("-o")
+            .arg(tmp_dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!(
+                "--payload-format {} can't be used with --rpm-format {}",
+                format, rpm_format
+            )));
+    }
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-payload-format")
+        .arg("--payload-format")
+        .arg("stripped")
+        .arg("--compat")
+        .arg("el8")
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+/// Test the --skip-compression-if-incompressible flag
+#[test]
+fn test_skip_compression_if_incompressible() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-skip-compression")?;
+
+    // pseudo-random bytes, which don't compress
+    let mut state = 0x2545f4914f6cdd1d_u64;
+    let noise: Vec<u8> = (0..512 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    let noise_file = tmp_dir.path().join("image.tar.xz");
+    fs::write(&noise_file, noise)?;
+    let text_file = workspace_path.join("tests/assets/multiplication_tables.py");
+
+    for (source, compression) in [
+        (&noise_file, rpm::CompressionType::None),
+        (&text_file, rpm::CompressionType::Gzip),
+    ] {
+        let out_file = tmp_dir.path().join("test-skip-compression.rpm");
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-skip-compression")
+            .arg("--file")
+            .arg(format!("{}:/usr/share/test/file", source.display()))
+            .arg("--compression")
+            .arg("gzip")
+            .arg("--skip-compression-if-incompressible")
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .success();
+        let pkg = rpm::Package::open(&out_file)?;
+        assert_eq!(pkg.metadata.get_payload_compressor()?, compression);
+        assert_eq!(pkg.metadata.get_file_entries()?.len(), 1);
+    }
+
+    Ok(())
+}
+
+/// Test the --max-memory flag
+#[test]
+fn test_max_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-max-memory")?;
+    let out_file = tmp_dir.path().join("test-max-memory.rpm");
+
+    // the compression levels are lowered until the compressor fits in half of the memory
+    for (compression, max_memory, level) in [
+        ("xz", "100", "4"),
+        ("xz", "2000", "9"),
+        ("zstd", "150", "18"),
+        ("bzip2", "4", "2"),
+    ] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-max-memory")
+            .arg("--compression")
+            .arg(compression)
+            .arg("--max-memory")
+            .arg(max_memory)
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .success();
+        let pkg = rpm::PackageMetadata::open(&out_file)?;
+        assert_eq!(
+            pkg.header
+                .get_entry_data_as_string(rpm::IndexTag::RPMTAG_PAYLOADFLAGS)?,
+            level
+        );
+    }
+
+    // content from stdin beyond a quarter of the memory is spilled to a temporary file
+    let content: Vec<u8> = (0..400 * 1024).map(|i| (i % 251) as u8).collect();
+    let assert = Command::new(cargo_bin!())
+        .arg("-v")
+        .arg("build")
+        .arg("test-max-memory")
+        .arg("--file-from-stdin")
+        .arg("/usr/share/test/data")
+        .arg("--max-memory")
+        .arg("1")
+        .arg("-o")
+        .arg(&out_file)
+        .write_stdin(content.clone())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "spilled the content read from stdin",
+        ));
+    let pkg = rpm::Package::open(&out_file)?;
+    let files = pkg.files()?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].content, content);
+    // the temporary file has a random name and is removed after the build
+    let stderr = String::from_utf8(assert.get_output().stderr.clone())?;
+    let spilled = stderr
+        .split("path=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .ok_or("no path of the temporary file")?;
+    assert!(!spilled.ends_with(&format!("-{}", std::process::id())));
+    assert!(!Path::new(spilled).exists());
+
+    // megabytes beyond the addressable memory are an error in the arguments
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-max-memory")
+        .arg("--max-memory")
+        .arg(u64::MAX.to_string())
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .code(2);
+
+    Ok(())
+}
+
+/// Test reporting the time spent in the phases of a build with --timings
+#[test]
+fn test_timings() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-timings")?;
+    let file = format!(
+        "{}/tests/assets/multiplication_tables.py:/usr/bin/multiplication_tables",
+        workspace_path.to_string_lossy()
+    );
+
+    let assert = Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-timings")
+        .arg("--exec-file")
+        .arg(&file)
+        .arg("--timings")
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success();
+    let stderr = String::from_utf8(assert.get_output().stderr.clone())?;
+    for phase in [
+        "Timings:",
+        "file walking",
+        "digests and compression",
+        "header assembly",
+        "signing",
+        "writing",
+        "total",
+    ] {
+        assert!(stderr.contains(phase), "{} missing in {}", phase, stderr);
+    }
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-timings")
+        .arg("--exec-file")
+        .arg(&file)
+        .arg("-o")
+        .arg(tmp_dir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Timings:").not());
+
+    Ok(())
+}
+
+/// Test that the files are in the same order whatever the order of the flags and of the directory
+/// listings
+#[test]
+fn test_deterministic_file_order() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-deterministic-file-order")?;
+
+    // the same trees, with the files created in opposite orders
+    let names = ["zeta", "alpha", "mu", "beta"];
+    for (tree, order) in [("one", false), ("two", true)] {
+        let root = tmp_dir.path().join(tree);
+        fs::create_dir_all(root.join("sub"))?;
+        let mut names = names.to_vec();
+        if order {
+            names.reverse();
+        }
+        for name in names {
+            fs::write(root.join(name), name)?;
+            fs::write(root.join("sub").join(name), name)?;
+        }
+    }
+    let extra = tmp_dir.path().join("extra");
+    fs::write(&extra, "extra")?;
+
+    let mut packages = Vec::new();
+    for (tree, files) in [
+        ("one", ["/usr/bin/b-extra", "/usr/bin/a-extra"]),
+        ("two", ["/usr/bin/a-extra", "/usr/bin/b-extra"]),
+    ] {
+        let out_file = tmp_dir.path().join(format!("{}.rpm", tree));
+        let mut command = Command::new(cargo_bin!());
+        command
+            .env("SOURCE_DATE_EPOCH", "1600000000")
+            .arg("build")
+            .arg("test-deterministic-file-order")
+            .arg("--dir")
+            .arg(format!(
+                "{}:/usr/share/test",
+                tmp_dir.path().join(tree).display()
+            ));
+        for dest in files {
+            command
+                .arg("--file")
+                .arg(format!("{}:{}", extra.display(), dest));
+        }
+        command.arg("-o").arg(&out_file).assert().success();
+        packages.push(fs::read(&out_file)?);
+    }
+    assert_eq!(packages[0], packages[1]);
+
+    let pkg = rpm::Package::open(tmp_dir.path().join("one.rpm"))?;
+    let paths: Vec<_> = pkg
+        .metadata
+        .get_file_paths()?
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    let mut sorted = paths.clone();
+    sorted.sort();
+    assert_eq!(paths, sorted);
+    assert_eq!(paths.len(), 10);
+
+    Ok(())
+}
+
+/// Test --default-file-mode, --default-dir-mode and --default-exec-mode
+#[test]
+fn test_default_modes() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp_dir = TempDir::new("rpm-builder-test-default-modes")?;
+    let tree = tmp_dir.path().join("tree");
+    fs::create_dir(&tree)?;
+    fs::write(tree.join("app.conf"), "key = value\n")?;
+    fs::set_permissions(tree.join("app.conf"), fs::Permissions::from_mode(0o664))?;
+    fs::write(tree.join("run"), "#!/bin/sh\n")?;
+    fs::set_permissions(tree.join("run"), fs::Permissions::from_mode(0o775))?;
+    let out_file = tmp_dir.path().join("test-default-modes.rpm");
+
+    let build = |modes: &[&str]| {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-default-modes")
+            .arg("--dir")
+            .arg(format!("{}:/etc/test", tree.display()))
+            .arg("--file")
+            .arg(format!(
+                "{}:/etc/other.conf:mode=0640",
+                tree.join("app.conf").display()
+            ))
+            .arg("--exec-file")
+            .arg(format!("{}:/usr/bin/test", tree.join("run").display()))
+            .arg("--create-dir")
+            .arg("/var/lib/test")
+            .args(modes)
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+    };
+    let modes = || -> Result<Vec<(String, rpm::FileMode)>, Box<dyn std::error::Error>> {
+        let pkg = rpm::PackageMetadata::open(&out_file)?;
+        Ok(pkg
+            .get_file_entries()?
+            .into_iter()
+            .map(|entry| (entry.path.to_string_lossy().into_owned(), entry.mode))
+            .collect())
+    };
+
+    // by default the files keep the permissions of their source
+    build(&[]).success();
+    assert_eq!(
+        modes()?,
+        vec![
+            ("/etc/other.conf".to_owned(), rpm::FileMode::regular(0o640)),
+            (
+                "/etc/test/app.conf".to_owned(),
+                rpm::FileMode::regular(0o664)
+            ),
+            ("/etc/test/run".to_owned(), rpm::FileMode::regular(0o775)),
+            ("/usr/bin/test".to_owned(), rpm::FileMode::regular(0o755)),
+            ("/var/lib/test".to_owned(), rpm::FileMode::dir(0o755)),
+        ]
+    );
+
+    // the mode attributes still win over the defaults
+    build(&[
+        "--default-file-mode",
+        "0600",
+        "--default-exec-mode",
+        "0750",
+        "--default-dir-mode",
+        "0700",
+    ])
+    .success();
+    assert_eq!(
+        modes()?,
+        vec![
+            ("/etc/other.conf".to_owned(), rpm::FileMode::regular(0o640)),
+            (
+                "/etc/test/app.conf".to_owned(),
+                rpm::FileMode::regular(0o600)
+            ),
+            ("/etc/test/run".to_owned(), rpm::FileMode::regular(0o750)),
+            ("/usr/bin/test".to_owned(), rpm::FileMode::regular(0o750)),
+            ("/var/lib/test".to_owned(), rpm::FileMode::dir(0o700)),
+        ]
+    );
+
+    build(&["--default-file-mode", "0999"])
+        .failure()
+        .stderr(predicate::str::contains("invalid --default-file-mode"));
+
+    Ok(())
+}
+
+/// Test --default-user and --default-group, and numeric owners
+#[test]
+fn test_default_owners() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-default-owners")?;
+    let source = workspace_path.join("tests/assets/multiplication_tables.py");
+    let out_file = tmp_dir.path().join("test-default-owners.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-default-owners")
+        .arg("--file")
+        .arg(format!("{}:/opt/app/plain", source.display()))
+        .arg("--file")
+        .arg(format!(
+            "{}:/opt/app/nginx:user=nginx,group=0",
+            source.display()
+        ))
+        .arg("--symlink")
+        .arg("/opt/app/link:plain")
+        .arg("--create-dir")
+        .arg("/opt/app/data")
+        .arg("--default-user")
+        .arg("app")
+        .arg("--default-group")
+        .arg("1000")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("the numeric owner only resolves"));
+
+    let pkg = rpm::PackageMetadata::open(&out_file)?;
+    let owners: Vec<_> = pkg
+        .get_file_entries()?
+        .into_iter()
+        .map(|entry| {
+            (
+                entry.path.to_string_lossy().into_owned(),
+                entry.ownership.user,
+                entry.ownership.group,
+            )
+        })
+        .collect();
+    let owner =
+        |path: &str, user: &str, group: &str| (path.to_owned(), user.to_owned(), group.to_owned());
+    assert_eq!(
+        owners,
+        vec![
+            owner("/opt/app/data", "app", "1000"),
+            owner("/opt/app/link", "app", "1000"),
+            owner("/opt/app/nginx", "nginx", "root"),
+            owner("/opt/app/plain", "app", "1000"),
+        ]
+    );
+
+    for (flag, value) in [
+        ("--default-user", "bad user"),
+        ("--default-group", "-wheel"),
+    ] {
+        Command::new(cargo_bin!())
+            .arg("build")
+            .arg("test-default-owners")
+            .arg(format!("{}={}", flag, value))
+            .arg("-o")
+            .arg(&out_file)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!("invalid {}", flag)));
+    }
+
+    Ok(())
+}
+
+/// Test selecting the checks of `rpm -V` per file, with the verify attribute and `%verify`
+#[test]
+fn test_verify_flags() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-verify-flags")?;
+    let source = workspace_path.join("tests/assets/multiplication_tables.py");
+    let out_file = tmp_dir.path().join("test-verify-flags.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-verify-flags")
+        .arg("--file")
+        .arg(format!("{}:/opt/app/plain", source.display()))
+        .arg("--config-file")
+        .arg(format!(
+            "{}:/etc/app.conf:noreplace,verify=not(md5,size,mtime),mode=0640",
+            source.display()
+        ))
+        .arg("--file")
+        .arg(format!(
+            "{}:/var/log/app.log:verify=(user,group)",
+            source.display()
+        ))
+        .arg("--file")
+        .arg(format!("{}:/opt/app/mode:verify=mode", source.display()))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::PackageMetadata::open(&out_file)?;
+    let paths: Vec<_> = pkg
+        .get_file_paths()?
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    let flags = pkg
+        .header
+        .get_entry_data_as_u32_array(rpm::IndexTag::RPMTAG_FILEVERIFYFLAGS)?;
+    let all = rpm::FileVerifyFlags::all();
+    let expected = [
+        (
+            "/etc/app.conf",
+            all - rpm::FileVerifyFlags::MD5
+                - rpm::FileVerifyFlags::FILESIZE
+                - rpm::FileVerifyFlags::MTIME,
+        ),
+        ("/opt/app/mode", rpm::FileVerifyFlags::MODE),
+        ("/opt/app/plain", all),
+        (
+            "/var/log/app.log",
+            rpm::FileVerifyFlags::USER | rpm::FileVerifyFlags::GROUP,
+        ),
+    ];
+    assert_eq!(paths.len(), expected.len());
+    for (path, flag) in expected {
+        let index = paths.iter().position(|p| p == path).unwrap();
+        assert_eq!(flags[index], flag.bits(), "{}", path);
+    }
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-verify-flags")
+        .arg("--file")
+        .arg(format!(
+            "{}:/opt/app/plain:verify=not(md5,inode)",
+            source.display()
+        ))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown verify check 'inode'"));
+
+    // %verify of a spec file
+    let buildroot = tmp_dir.path().join("buildroot");
+    fs::create_dir_all(buildroot.join("etc"))?;
+    fs::write(buildroot.join("etc/app.conf"), "debug = false\n")?;
+    let spec = tmp_dir.path().join("test-verify-flags.spec");
+    fs::write(
+        &spec,
+        "Name: test-verify-flags\nVersion: 1.0\nRelease: 1\nSummary: Verify flags\nLicense: MIT\n\n\
+         %description\nVerify flags.\n\n\
+         %files\n%config %verify(not md5 size mtime) /etc/app.conf\n",
+    )?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--from-spec")
+        .arg(&spec)
+        .arg("--buildroot")
+        .arg(&buildroot)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    let pkg = rpm::PackageMetadata::open(&out_file)?;
+    assert_eq!(
+        pkg.header
+            .get_entry_data_as_u32_array(rpm::IndexTag::RPMTAG_FILEVERIFYFLAGS)?,
+        vec![
+            (all - rpm::FileVerifyFlags::MD5
+                - rpm::FileVerifyFlags::FILESIZE
+                - rpm::FileVerifyFlags::MTIME)
+                .bits()
+        ]
+    );
+
+    Ok(())
+}
+
+/// Test adding the license files found in directories as license files
+#[test]
+fn test_auto_license_files() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-auto-license-files")?;
+    let app = tmp_dir.path().join("app");
+    fs::create_dir_all(app.join("vendor/lib"))?;
+    fs::write(app.join("LICENSE"), "MIT License")?;
+    fs::write(app.join("license.py"), "print('not a license')\n")?;
+    fs::write(app.join("main.py"), "print('hello')\n")?;
+    fs::write(app.join("vendor/lib/COPYING.LIB"), "LGPL")?;
+    let docs = tmp_dir.path().join("docs");
+    fs::create_dir_all(&docs)?;
+    fs::write(docs.join("README.md"), "# app")?;
+    fs::write(docs.join("LICENSE-APACHE.txt"), "Apache License")?;
+    let out_file = tmp_dir.path().join("test-auto-license-files.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-auto-license-files")
+        .arg("--dir")
+        .arg(format!("{}:/opt/app:user=app", app.display()))
+        .arg("--doc-dir")
+        .arg(format!(
+            "{}:/usr/share/doc/test-auto-license-files",
+            docs.display()
+        ))
+        .arg("--auto-license-files")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::PackageMetadata::open(&out_file)?;
+    let files: Vec<_> = pkg
+        .get_file_entries()?
+        .into_iter()
+        .map(|entry| {
+            (
+                entry.path.to_string_lossy().into_owned(),
+                entry.flags,
+                entry.ownership.user,
+            )
+        })
+        .collect();
+    let license = |path: &str| (path.to_owned(), rpm::FileFlags::LICENSE, "root".to_owned());
+    assert_eq!(
+        files,
+        vec![
+            (
+                "/opt/app/license.py".to_owned(),
+                rpm::FileFlags::empty(),
+                "app".to_owned()
+            ),
+            (
+                "/opt/app/main.py".to_owned(),
+                rpm::FileFlags::empty(),
+                "app".to_owned()
+            ),
+            (
+                "/usr/share/doc/test-auto-license-files/README.md".to_owned(),
+                rpm::FileFlags::DOC,
+                "root".to_owned()
+            ),
+            license("/usr/share/licenses/test-auto-license-files/LICENSE"),
+            license("/usr/share/licenses/test-auto-license-files/LICENSE-APACHE.txt"),
+            license("/usr/share/licenses/test-auto-license-files/vendor/lib/COPYING.LIB"),
+        ]
+    );
+
+    Ok(())
+}
+
+/// Test installing man pages gzipped into the directory of their section
+#[test]
+fn test_man_page() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::{Read, Write};
+
+    let tmp_dir = TempDir::new("rpm-builder-test-man-page")?;
+    let page = ".TH FOO 1\n.SH NAME\nfoo \\- do things\n";
+    fs::write(tmp_dir.path().join("foo.1"), page)?;
+    fs::write(tmp_dir.path().join("food.man"), page)?;
+    fs::write(tmp_dir.path().join("libfoo"), page)?;
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(page.as_bytes())?;
+    fs::write(tmp_dir.path().join("foo.conf.5.gz"), encoder.finish()?)?;
+    let out_file = tmp_dir.path().join("test-man-page.rpm");
+
+    let build = || {
+        let mut command = Command::new(cargo_bin!());
+        command
+            .current_dir(tmp_dir.path())
+            .arg("build")
+            .arg("test-man-page")
+            .arg("--man-page")
+            .arg("foo.1:1")
+            .arg("--man-page")
+            .arg("food.man:8")
+            .arg("--man-page")
+            .arg("libfoo:3p")
+            .arg("--man-page")
+            .arg("foo.conf.5.gz:5")
+            .arg("-o")
+            .arg(&out_file);
+        command
+    };
+    build().assert().success();
+
+    let pkg = rpm::Package::open(&out_file)?;
+    let mut files: Vec<_> = pkg.files()?.collect::<Result<_, _>>()?;
+    files.sort_by(|a, b| a.metadata.path.cmp(&b.metadata.path));
+    let paths: Vec<_> = files
+        .iter()
+        .map(|file| file.metadata.path.to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            "/usr/share/man/man1/foo.1.gz",
+            "/usr/share/man/man3/libfoo.3p.gz",
+            "/usr/share/man/man5/foo.conf.5.gz",
+            "/usr/share/man/man8/food.8.gz",
+        ]
+    );
+    for file in &files {
+        assert_eq!(file.metadata.flags, rpm::FileFlags::DOC);
+        assert_eq!(file.metadata.mode, rpm::FileMode::regular(0o644));
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(file.content.as_slice()).read_to_string(&mut content)?;
+        assert_eq!(content, page);
+    }
+
+    // gzip -n: the same man page compresses to the same bytes
+    let first = fs::read(&out_file)?;
+    build().arg("--reproducible").assert().success();
+    let pkg = rpm::Package::open(&out_file)?;
+    let files: Vec<_> = pkg.files()?.collect::<Result<_, _>>()?;
+    let original = rpm::Package::parse(&mut first.as_slice())?;
+    let original: Vec<_> = original.files()?.collect::<Result<_, _>>()?;
+    for (file, original) in files.iter().zip(&original) {
+        assert_eq!(file.content, original.content);
+    }
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-man-page")
+        .arg("--man-page")
+        .arg("foo.1:man")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid man page section man"));
+
+    Ok(())
+}
+
+/// Test generating the shell completions of rpm-builder
+#[test]
+fn test_completions() -> Result<(), Box<dyn std::error::Error>> {
+    Command::new(cargo_bin!())
+        .arg("completions")
+        .arg("bash")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("_rpm__builder()"))
+        .stdout(predicate::str::contains("--exec-file"));
+    Command::new(cargo_bin!())
+        .arg("completions")
+        .arg("zsh")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("#compdef rpm-builder"));
+    for shell in ["fish", "powershell"] {
+        Command::new(cargo_bin!())
+            .arg("completions")
+            .arg(shell)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("sign-with-pgp-asc"));
+    }
+    Command::new(cargo_bin!())
+        .arg("completions")
+        .arg("tcsh")
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+/// Test generating the man pages of rpm-builder and its subcommands
+#[test]
+fn test_manpage() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-manpage")?;
+    let out_dir = tmp_dir.path().join("man1");
+
+    Command::new(cargo_bin!())
+        .arg("manpage")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            ".TH rpm-builder 1  \"rpm-builder {}\"",
+            env!("CARGO_PKG_VERSION")
+        )))
+        .stdout(predicate::str::contains("Build RPMs with ease"));
+
+    Command::new(cargo_bin!())
+        .arg("manpage")
+        .arg("-o")
+        .arg(&out_dir)
+        .assert()
+        .success();
+    for page in [
+        "rpm-builder.1",
+        "rpm-builder-build.1",
+        "rpm-builder-version-compare.1",
+    ] {
+        assert!(out_dir.join(page).is_file(), "{}", page);
+    }
+    let build = fs::read_to_string(out_dir.join("rpm-builder-build.1"))?;
+    assert!(build.contains("Build a new rpm"));
+    assert!(build.contains("\\-\\-exec\\-file"));
+
+    Ok(())
+}
+
+/// Test that extracting doesn't write through the symlinks of a package, and fails on special files
+#[test]
+#[cfg(unix)]
+fn test_extract_untrusted() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = TempDir::new("rpm-builder-test-extract-untrusted")?;
+    let source = workspace_path.join("tests/assets/multiplication_tables.py");
+    let outside = tmp_dir.path().join("outside");
+    fs::create_dir(&outside)?;
+    let out_file = tmp_dir.path().join("test-extract-untrusted.rpm");
+
+    // a symlink to a directory outside, followed by a file inside of it
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-extract-untrusted")
+        .arg("--symlink")
+        .arg(format!("/a:{}", outside.display()))
+        .arg("--file")
+        .arg(format!("{}:/a/passwd", source.display()))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    Command::new(cargo_bin!())
+        .arg("extract")
+        .arg(&out_file)
+        .arg("--into")
+        .arg(tmp_dir.path().join("escaped"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the path \"/a/passwd\" is inside of the symlink \"/a\"",
+        ));
+    assert!(!outside.join("passwd").exists());
+    assert!(!tmp_dir.path().join("escaped").exists());
+
+    // a character device, made by changing the mode recorded in the header
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-extract-untrusted")
+        .arg("--file")
+        .arg(format!("{}:/dev/thing:mode=7531", source.display()))
+        .arg("--compression")
+        .arg("none")
+        .arg("--reproducible")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    let mut bytes = fs::read(&out_file)?;
+    let regular = (0o107531u16).to_be_bytes();
+    let positions: Vec<_> = bytes
+        .windows(2)
+        .enumerate()
+        .filter(|(_, window)| *window == regular)
+        .map(|(position, _)| position)
+        .collect();
+    assert_eq!(positions.len(), 1);
+    bytes[positions[0]..positions[0] + 2].copy_from_slice(&(0o027531u16).to_be_bytes());
+    fs::write(&out_file, bytes)?;
+    Command::new(cargo_bin!())
+        .arg("extract")
+        .arg(&out_file)
+        .arg("--into")
+        .arg(tmp_dir.path().join("device"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "\"/dev/thing\" can't be extracted, its mode 27531 is neither",
+        ));
+
+    Ok(())
+}
+
+/// Test adding a verify script, from a file and from the `%verifyscript` of a spec file
+#[test]
+fn test_verify_script() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-verify-script")?;
+    let script = tmp_dir.path().join("verify.sh");
+    fs::write(&script, "test -d /var/lib/app\n")?;
+    let out_file = tmp_dir.path().join("test-verify-script.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-verify-script")
+        .arg("--verify-script")
+        .arg(&script)
+        .arg("--verify-interpreter")
+        .arg("/bin/sh -e")
+        .arg("--verify-flags")
+        .arg("expand")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::PackageMetadata::open(&out_file)?;
+    assert_eq!(
+        pkg.header
+            .get_entry_data_as_string(rpm::IndexTag::RPMTAG_VERIFYSCRIPT)?,
+        "test -d /var/lib/app\n"
+    );
+    assert_eq!(
+        pkg.header
+            .get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_VERIFYSCRIPTPROG)?,
+        ["/bin/sh", "-e"]
+    );
+    assert_eq!(
+        pkg.header
+            .get_entry_data_as_u32(rpm::IndexTag::RPMTAG_VERIFYSCRIPTFLAGS)?,
+        rpm::ScriptletFlags::EXPAND.bits()
+    );
+    Command::new(cargo_bin!())
+        .arg("inspect")
+        .arg(&out_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("verify (/bin/sh -e):"));
+
+    let spec = tmp_dir.path().join("test-verify-script.spec");
+    fs::write(
+        &spec,
+        "Name: test-verify-script\nVersion: 1.0\nRelease: 1\nSummary: Verify script\nLicense: MIT\n\n\
+         %description\nVerify script.\n\n\
+         %verifyscript\ntest -f /etc/app.conf\n\n\
+         %files\n",
+    )?;
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--from-spec")
+        .arg(&spec)
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    let pkg = rpm::PackageMetadata::open(&out_file)?;
+    assert_eq!(
+        pkg.header
+            .get_entry_data_as_string(rpm::IndexTag::RPMTAG_VERIFYSCRIPT)?,
+        "test -f /etc/app.conf"
+    );
+
+    Ok(())
+}
+
+/// Test adding file triggers, which are written to the header after the build
+#[test]
+fn test_file_triggers() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-file-triggers")?;
+    let fonts = tmp_dir.path().join("fonts.sh");
+    fs::write(&fonts, "fc-cache -s\n")?;
+    let icons = tmp_dir.path().join("icons.sh");
+    fs::write(&icons, "gtk-update-icon-cache /usr/share/icons\n")?;
+    let out_file = tmp_dir.path().join("test-file-triggers.rpm");
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-triggers")
+        .arg("--file-trigger-in")
+        .arg(format!("/usr/share/fonts:{}", fonts.display()))
+        .arg("--file-trigger-postun")
+        .arg(format!("/usr/share/fonts:{}", fonts.display()))
+        .arg("--trans-file-trigger-in")
+        .arg(format!("/usr/share/icons:{}", icons.display()))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let pkg = rpm::PackageMetadata::open(&out_file)?;
+    let header = &pkg.header;
+    assert_eq!(
+        header.get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_FILETRIGGERSCRIPTS)?,
+        ["fc-cache -s\n", "fc-cache -s\n"]
+    );
+    assert_eq!(
+        header.get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_FILETRIGGERSCRIPTPROG)?,
+        ["/bin/sh", "/bin/sh"]
+    );
+    assert_eq!(
+        header.get_entry_data_as_u32_array(rpm::IndexTag::RPMTAG_FILETRIGGERSCRIPTFLAGS)?,
+        [0, 0]
+    );
+    assert_eq!(
+        header.get_entry_data_as_u32_array(rpm::IndexTag::RPMTAG_FILETRIGGERPRIORITIES)?,
+        [1_000_000, 1_000_000]
+    );
+    assert_eq!(
+        header.get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_FILETRIGGERNAME)?,
+        ["/usr/share/fonts", "/usr/share/fonts"]
+    );
+    assert_eq!(
+        header.get_entry_data_as_u32_array(rpm::IndexTag::RPMTAG_FILETRIGGERINDEX)?,
+        [0, 1]
+    );
+    assert_eq!(
+        header.get_entry_data_as_u32_array(rpm::IndexTag::RPMTAG_FILETRIGGERFLAGS)?,
+        [
+            rpm::DependencyFlags::TRIGGERIN.bits(),
+            rpm::DependencyFlags::TRIGGERPOSTUN.bits()
+        ]
+    );
+    assert_eq!(
+        header.get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_FILETRIGGERVERSION)?,
+        ["", ""]
+    );
+    assert_eq!(
+        header.get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_TRANSFILETRIGGERSCRIPTS)?,
+        ["gtk-update-icon-cache /usr/share/icons\n"]
+    );
+    assert_eq!(
+        header.get_entry_data_as_string_array(rpm::IndexTag::RPMTAG_TRANSFILETRIGGERNAME)?,
+        ["/usr/share/icons"]
+    );
+    assert_eq!(
+        header.get_entry_data_as_u32_array(rpm::IndexTag::RPMTAG_TRANSFILETRIGGERFLAGS)?,
+        [rpm::DependencyFlags::TRIGGERIN.bits()]
+    );
+    assert!(pkg.get_requires()?.contains(&rpm::Dependency {
+        name: "rpmlib(FileTriggers)".to_owned(),
+        flags: rpm::DependencyFlags::RPMLIB | rpm::DependencyFlags::LE,
+        version: "4.12.0-1".to_owned(),
+    }));
+
+    // rpm matches the files by a path prefix, not by a pattern
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-triggers")
+        .arg("--file-trigger-in")
+        .arg(format!("/usr/share/fonts/*.ttf:{}", fonts.display()))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("it can't be a glob pattern"));
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-file-triggers")
+        .arg("--file-trigger-in")
+        .arg(format!("/usr/share/fonts:{}", fonts.display()))
+        .arg("--compat")
+        .arg("el7")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--file-trigger-in can't be used with --compat el7",
+        ));
+
+    Ok(())
+}
+
+/// Test that the output directory of subpackages is created with --create-out-dir
+#[test]
+fn test_subpackages_create_out_dir() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-subpackages-out-dir")?;
+    let manifest = tmp_dir.path().join("manifest.toml");
+    fs::write(
+        &manifest,
+        "name = \"test-out-dir\"\nversion = \"1.0.0\"\narch = \"noarch\"\n\n[[subpackage]]\nname = \"test-out-dir-doc\"\n",
+    )?;
+
+    // a trailing separator makes it a directory, which still needs to be created
+    let out_dir = tmp_dir.path().join("out/");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--manifest")
+        .arg(&manifest)
+        .arg("-o")
+        .arg(&out_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("use --create-out-dir"));
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--manifest")
+        .arg(&manifest)
+        .arg("--create-out-dir")
+        .arg("-o")
+        .arg(&out_dir)
+        .assert()
+        .success();
+    assert!(out_dir.join("test-out-dir-1.0.0-1.noarch.rpm").is_file());
+    assert!(
+        out_dir
+            .join("test-out-dir-doc-1.0.0-1.noarch.rpm")
+            .is_file()
+    );
+
+    // without one, --create-out-dir creates it as the directory of all the packages
+    let nested_dir = tmp_dir.path().join("nested/dir");
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("--manifest")
+        .arg(&manifest)
+        .arg("--create-out-dir")
+        .arg("-o")
+        .arg(&nested_dir)
+        .assert()
+        .success();
+    assert!(nested_dir.join("test-out-dir-1.0.0-1.noarch.rpm").is_file());
+    assert!(
+        nested_dir
+            .join("test-out-dir-doc-1.0.0-1.noarch.rpm")
+            .is_file()
+    );
+
+    Ok(())
+}
+
+/// Test that the options which aren't file or directory options split on the separator the same way
+#[test]
+fn test_separator_options() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-separator-options")?;
+    // the same as C:\out\x.tar in the current directory on Windows
+    let mut archive = tar::Builder::new(fs::File::create(tmp_dir.path().join("C:\\out\\x.tar"))?);
+    let mut header = tar::Header::new_ustar();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_mode(0o755);
+    header.set_size(12);
+    archive.append_data(&mut header, "./tool", "#!/bin/sh\nid".as_bytes())?;
+    archive.into_inner()?;
+
+    let entries = |out_file: &Path| -> Result<Vec<(PathBuf, u16, String)>, rpm::Error> {
+        let pkg = rpm::Package::open(out_file)?;
+        Ok(pkg
+            .metadata
+            .get_file_entries()?
+            .into_iter()
+            .map(|f| (f.path, u16::from(f.mode), f.linkto))
+            .collect())
+    };
+
+    let out_file = tmp_dir.path().join("default.rpm");
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-separator-options")
+        .arg("--from-tar")
+        .arg("C:\\out\\x.tar:/opt")
+        .arg("--from-oci-layer")
+        .arg("C:\\out\\x.tar:/srv")
+        .arg("--symlink")
+        .arg("/usr/bin/a\\:b:../../opt/tool")
+        .arg("--create-dir")
+        .arg("/var/lib/test:mode=0700")
+        .arg("--file-from-stdin")
+        .arg("/etc/test.conf:mode=0600")
+        .write_stdin("key = value\n")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    assert_eq!(
+        entries(&out_file)?,
+        vec![
+            (PathBuf::from("/etc/test.conf"), 0o100600, String::new()),
+            (PathBuf::from("/opt/tool"), 0o100755, String::new()),
+            (PathBuf::from("/srv/tool"), 0o100755, String::new()),
+            (
+                PathBuf::from("/usr/bin/a:b"),
+                0o120777,
+                "../../opt/tool".to_owned()
+            ),
+            (PathBuf::from("/var/lib/test"), 0o040700, String::new()),
+        ]
+    );
+
+    // an archive without a prefix is added below /
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-separator-options")
+        .arg("--from-tar")
+        .arg("C:\\out\\x.tar")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    assert_eq!(
+        entries(&out_file)?,
+        vec![(PathBuf::from("/tool"), 0o100755, String::new())]
+    );
+
+    let out_file = tmp_dir.path().join("separator.rpm");
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-separator-options")
+        .arg("--file-separator")
+        .arg("|")
+        .arg("--from-tar")
+        .arg("C:\\out\\x.tar|/opt:1")
+        .arg("--symlink")
+        .arg("/usr/lib/libtest.so|libtest:1.so")
+        .arg("--create-dir")
+        .arg("/var/lib/test:1|mode=0700")
+        .arg("--file-from-stdin")
+        .arg("/etc/test:1.conf|mode=0600")
+        .write_stdin("key = value\n")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    assert_eq!(
+        entries(&out_file)?,
+        vec![
+            (PathBuf::from("/etc/test:1.conf"), 0o100600, String::new()),
+            (PathBuf::from("/opt:1/tool"), 0o100755, String::new()),
+            (
+                PathBuf::from("/usr/lib/libtest.so"),
+                0o120777,
+                "libtest:1.so".to_owned()
+            ),
+            (PathBuf::from("/var/lib/test:1"), 0o040700, String::new()),
+        ]
+    );
+
+    // and so do the options naming a source and what it's installed as
+    fs::write(tmp_dir.path().join("app:1.service"), "[Service]\n")?;
+    fs::write(tmp_dir.path().join("tool:1.man"), ".TH TOOL 1\n")?;
+    let buildroot = tmp_dir.path().join("buildroot");
+    fs::create_dir_all(buildroot.join("etc"))?;
+    fs::write(buildroot.join("etc/app:1.conf"), "key = value\n")?;
+    Command::new(cargo_bin!())
+        .current_dir(tmp_dir.path())
+        .arg("build")
+        .arg("test-separator-options")
+        .arg("--file-separator")
+        .arg("|")
+        .arg("--systemd-unit")
+        .arg("app:1.service|app.service")
+        .arg("--man-page")
+        .arg("tool:1.man|1")
+        .arg("--buildroot")
+        .arg(&buildroot)
+        .arg("--buildroot-attr")
+        .arg("/etc/app:1.conf|mode=0600")
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success();
+    let paths: Vec<_> = entries(&out_file)?
+        .into_iter()
+        .map(|(path, mode, _)| (path, mode))
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            (PathBuf::from("/etc/app:1.conf"), 0o100600),
+            (
+                PathBuf::from("/usr/lib/systemd/system/app.service"),
+                0o100644
+            ),
+            (PathBuf::from("/usr/share/man/man1/tool:1.1.gz"), 0o100644),
+        ]
+    );
+
+    Ok(())
+}
+
+/// Test that the --publish flag passes scp:// URLs to scp and ssh safely
+#[test]
+fn test_publish_scp() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-publish-scp")?;
+    let out_file = tmp_dir.path().join("test-publish-scp.rpm");
+
+    // scp and ssh record their arguments, and ssh prints the checksum of the package. Only the
+    // arguments are checked: the quoting of the remote path relies on scp -O passing it through
+    // the remote shell, as ssh does with its command.
+    let bin_dir = tmp_dir.path().join("bin");
+    fs::create_dir(&bin_dir)?;
+    for (program, extra) in [
+        ("scp", String::new()),
+        ("ssh", format!("sha256sum '{}'\n", out_file.display())),
+    ] {
+        let script = bin_dir.join(program);
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\nprintf '%s\\n' \"$@\" > '{}'\n{}",
+                tmp_dir.path().join(format!("{}.args", program)).display(),
+                extra
+            ),
+        )?;
+        fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755))?;
+    }
+    let path = format!("{}:{}", bin_dir.display(), env::var("PATH")?);
+
+    Command::new(cargo_bin!())
+        .env("PATH", &path)
+        .arg("build")
+        .arg("test-publish-scp")
+        .arg("-o")
+        .arg(&out_file)
+        .arg("--publish")
+        .arg("scp://ci@repo.example.com:2222/srv/it's here.rpm")
+        .arg("--publish-verify")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(tmp_dir.path().join("scp.args"))?,
+        format!(
+            "-O\n-P\n2222\n--\n{}\nci@repo.example.com:'/srv/it'\\''s here.rpm'\n",
+            out_file.display()
+        )
+    );
+    assert_eq!(
+        fs::read_to_string(tmp_dir.path().join("ssh.args"))?,
+        "-p\n2222\n--\nci@repo.example.com\nsha256sum -- '/srv/it'\\''s here.rpm'\n"
+    );
+
+    // hosts which ssh would take as options are rejected
+    for url in [
+        "scp://-oProxyCommand=touch${IFS}pwned/srv/",
+        "ssh://repo example.com/srv/",
+    ] {
+        Command::new(cargo_bin!())
+            .env("PATH", &path)
+            .arg("build")
+            .arg("test-publish-scp")
+            .arg("-o")
+            .arg(&out_file)
+            .arg("--publish")
+            .arg(url)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid host"));
+    }
+
+    Ok(())
+}
+
+/// Test that archive entries which only record numeric owners are treated like numeric owners
+/// of the file options
+#[test]
+fn test_from_tar_numeric_owners() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("rpm-builder-test-from-tar-numeric-owners")?;
+    let archive_path = tmp_dir.path().join("payload.tar");
+    let out_file = tmp_dir.path().join("test-from-tar-numeric-owners.rpm");
+
+    let mut archive = tar::Builder::new(fs::File::create(&archive_path)?);
+    for (path, uid, gid) in [("./app", 1000, 0), ("./root-owned", 0, 1000)] {
+        let mut header = tar::Header::new_ustar();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_mode(0o644);
+        header.set_size(4);
+        header.set_uid(uid);
+        header.set_gid(gid);
+        archive.append_data(&mut header, path, "data".as_bytes())?;
+    }
+    archive.into_inner()?;
+
+    Command::new(cargo_bin!())
+        .arg("build")
+        .arg("test-from-tar-numeric-owners")
+        .arg("--from-tar")
+        .arg(format!("{}:/opt", archive_path.display()))
+        .arg("-o")
+        .arg(&out_file)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("the numeric owner only resolves"));
+
+    let pkg = rpm::PackageMetadata::open(&out_file)?;
+    let owners: Vec<_> = pkg
+        .get_file_entries()?
+        .into_iter()
+        .map(|entry| (entry.path, entry.ownership.user, entry.ownership.group))
+        .collect();
+    assert_eq!(
+        owners,
+        vec![
+            (
+                PathBuf::from("/opt/app"),
+                "1000".to_owned(),
+                "root".to_owned()
+            ),
+            (
+                PathBuf::from("/opt/root-owned"),
+                "root".to_owned(),
+                "1000".to_owned()
+            ),
+        ]
+    );
+
+    Ok(())
+}